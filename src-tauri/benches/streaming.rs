@@ -0,0 +1,152 @@
+//! Throughput of the streaming paths on generated fixtures.
+//!
+//! Run `cargo bench --bench streaming -- --save-baseline main` before a refactor and
+//! `cargo bench --bench streaming -- --baseline main` after it to see regressions.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nmeditor_lib::{bench, CsvDialect, CsvMacroSpec, FindReplaceSpec};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+const SIZES: &[usize] = &[10_000, 100_000];
+const COLUMNS: usize = 12;
+
+struct Fixture {
+    name: &'static str,
+    delimiter: char,
+    eol: &'static str,
+    /// Quote every other text cell and embed delimiters and line breaks in some of them.
+    quoted: bool,
+}
+
+const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "comma",
+        delimiter: ',',
+        eol: "\n",
+        quoted: false,
+    },
+    Fixture {
+        name: "semicolon-quoted",
+        delimiter: ';',
+        eol: "\r\n",
+        quoted: true,
+    },
+    Fixture {
+        name: "tab",
+        delimiter: '\t',
+        eol: "\r\n",
+        quoted: false,
+    },
+];
+
+impl Fixture {
+    fn dialect(&self) -> CsvDialect {
+        CsvDialect {
+            delimiter: Some(if self.delimiter == '\t' {
+                "\\t".to_string()
+            } else {
+                self.delimiter.to_string()
+            }),
+            eol: Some(if self.eol == "\n" { "LF" } else { "CRLF" }.to_string()),
+            ..CsvDialect::default()
+        }
+    }
+
+    /// Write the fixture once per size into the temp dir and return its path.
+    fn path(&self, rows: usize) -> PathBuf {
+        let dir = std::env::temp_dir().join("nmeditor-bench");
+        std::fs::create_dir_all(&dir).expect("create fixture dir");
+        let path = dir.join(format!("{}-{}.csv", self.name, rows));
+        if path.exists() {
+            return path;
+        }
+        let sep = self.delimiter.to_string();
+        let mut text = (0..COLUMNS)
+            .map(|col| format!("col_{}", col))
+            .collect::<Vec<_>>()
+            .join(&sep);
+        text.push_str(self.eol);
+        for row in 0..rows {
+            for col in 0..COLUMNS {
+                if col > 0 {
+                    text.push(self.delimiter);
+                }
+                match col % 4 {
+                    0 => write!(text, "{}", row),
+                    1 => write!(text, "{:.2}", row as f64 * 1.37),
+                    2 => write!(text, "2024-{:02}-{:02}", row % 12 + 1, row % 28 + 1),
+                    _ if self.quoted && row % 2 == 0 => {
+                        write!(
+                            text,
+                            "\"name {}{} with \"\"quotes\"\"\nand a break\"",
+                            row, sep
+                        )
+                    }
+                    _ => write!(text, "name {} value {}", row % 997, col),
+                }
+                .expect("write fixture cell");
+            }
+            text.push_str(self.eol);
+        }
+        std::fs::write(&path, text).expect("write fixture");
+        path
+    }
+}
+
+/// Run `f` for every fixture and size in a group named `group`.
+fn each_fixture(c: &mut Criterion, group: &str, mut f: impl FnMut(&str, &str, &CsvDialect, usize)) {
+    let mut group = c.benchmark_group(group);
+    group.sample_size(10);
+    for fixture in FIXTURES {
+        for &rows in SIZES {
+            let path = fixture.path(rows);
+            let path = path.to_str().expect("utf-8 temp path");
+            let target = format!("{}.out", path);
+            let dialect = fixture.dialect();
+            group.throughput(Throughput::Elements(rows as u64));
+            group.bench_function(BenchmarkId::new(fixture.name, rows), |b| {
+                b.iter(|| f(path, &target, &dialect, rows))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn streaming(c: &mut Criterion) {
+    each_fixture(c, "preview", |path, _, dialect, _| {
+        black_box(bench::preview(path, dialect).expect("preview"));
+    });
+    each_fixture(c, "window", |path, _, dialect, rows| {
+        black_box(bench::window(path, dialect, rows / 2, 200).expect("window"));
+    });
+    each_fixture(c, "count", |path, _, dialect, _| {
+        black_box(bench::count(path, dialect).expect("count"));
+    });
+    each_fixture(c, "stats", |path, _, dialect, _| {
+        black_box(bench::stats(path, dialect).expect("stats"));
+    });
+
+    let uppercase = CsvMacroSpec {
+        op: "uppercase".to_string(),
+        column: 3,
+        ..CsvMacroSpec::default()
+    };
+    each_fixture(c, "macro", |path, target, dialect, _| {
+        black_box(bench::macro_pass(path, target, dialect, &uppercase).expect("macro"));
+    });
+
+    let replace = FindReplaceSpec {
+        find: "value 1".to_string(),
+        replace: "v1".to_string(),
+        column: None,
+        regex: false,
+        match_case: true,
+    };
+    each_fixture(c, "find_replace", |path, target, dialect, _| {
+        black_box(bench::find_replace(path, target, dialect, &replace).expect("find/replace"));
+    });
+}
+
+criterion_group!(benches, streaming);
+criterion_main!(benches);
@@ -9,7 +9,6 @@ use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::sync::OnceLock;
 #[cfg(desktop)]
 use tauri::menu::{Menu, MenuItemBuilder, SubmenuBuilder};
-#[cfg(desktop)]
 use tauri::Manager;
 use tauri::Emitter;
 
@@ -81,102 +80,599 @@ fn rewrite_as_utf16le(path: &str, bom: bool) -> Result<(), String> {
     Ok(())
 }
 
-#[cfg(desktop)]
-fn is_zh(locale: &str) -> bool {
-    locale.to_lowercase().starts_with("zh")
+fn delimiter_label(delimiter: u8) -> String {
+    match delimiter {
+        b'\t' => "\\t".to_string(),
+        other => String::from_utf8_lossy(&[other]).to_string(),
+    }
+}
+
+/// CSV format options, accepted as one `dialect` argument by the read and write commands
+/// and kept per session. Unset fields mean: comma (or detected) delimiter, CRLF, no BOM,
+/// UTF-8, `"` for quote and escape, quoting only where necessary, every column, and no
+/// spreadsheet adjustments. `quoting`, `null_token` and `columns` only affect output.
+/// Sessions opened with another `encoding`, or without a header row, read a UTF-8 copy.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CsvDialect {
+    pub delimiter: Option<String>,
+    pub eol: Option<String>,
+    pub bom: Option<bool>,
+    pub encoding: Option<String>,
+    pub quote: Option<String>,
+    pub escape: Option<String>,
+    /// Start output with a `sep=<delimiter>` line, which Excel reads as the delimiter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sep_hint: Option<bool>,
+    /// Neutralize output cells a spreadsheet would evaluate as formulas: `quote` prefixes
+    /// them with `'`, `space` with a space.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formula_guard: Option<String>,
+    /// `necessary`, `always`, `non_numeric` or `never`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quoting: Option<String>,
+    /// Written in place of empty cells.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub null_token: Option<String>,
+    /// Output only these header columns, in this order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+    /// `false` for a file without a header row; opening one names the columns `column_1`,
+    /// `column_2`, ...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_header: Option<bool>,
+}
+
+impl CsvDialect {
+    fn delimiter_byte(&self) -> u8 {
+        parse_delimiter(self.delimiter.as_deref().unwrap_or(","))
+    }
+
+    /// The configured delimiter, or one detected from the first 64 KiB of `path`.
+    fn resolve_delimiter(&self, path: &str) -> Result<u8, String> {
+        if let Some(value) = self.delimiter.as_deref() {
+            return Ok(parse_delimiter(value));
+        }
+        let mut sample = String::new();
+        BufReader::new(File::open(path).map_err(|e| e.to_string())?)
+            .take(64 * 1024)
+            .read_to_string(&mut sample)
+            .map_err(|e| e.to_string())?;
+        Ok(detect_delimiter(&sample))
+    }
+
+    /// This dialect with every option `profile` sets taking precedence.
+    fn with_profile(&self, profile: &CsvDialect) -> Self {
+        fn pick<T: Clone>(profile: &Option<T>, own: &Option<T>) -> Option<T> {
+            profile.clone().or_else(|| own.clone())
+        }
+        Self {
+            delimiter: pick(&profile.delimiter, &self.delimiter),
+            eol: pick(&profile.eol, &self.eol),
+            bom: pick(&profile.bom, &self.bom),
+            encoding: pick(&profile.encoding, &self.encoding),
+            quote: pick(&profile.quote, &self.quote),
+            escape: pick(&profile.escape, &self.escape),
+            sep_hint: pick(&profile.sep_hint, &self.sep_hint),
+            formula_guard: pick(&profile.formula_guard, &self.formula_guard),
+            quoting: pick(&profile.quoting, &self.quoting),
+            null_token: pick(&profile.null_token, &self.null_token),
+            columns: pick(&profile.columns, &self.columns),
+            has_header: pick(&profile.has_header, &self.has_header),
+        }
+    }
+
+    fn with_delimiter(&self, delimiter: u8) -> Self {
+        Self {
+            delimiter: Some(delimiter_label(delimiter)),
+            ..self.clone()
+        }
+    }
+
+    fn quote_byte(&self) -> u8 {
+        self.quote
+            .as_deref()
+            .and_then(|q| q.as_bytes().first().copied())
+            .unwrap_or(b'"')
+    }
+
+    fn escape_byte(&self) -> u8 {
+        self.escape
+            .as_deref()
+            .and_then(|q| q.as_bytes().first().copied())
+            .unwrap_or(b'"')
+    }
+
+    fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .has_headers(true)
+            .delimiter(self.delimiter_byte())
+            .quote(self.quote_byte());
+        if self.escape_byte() != self.quote_byte() {
+            builder.escape(Some(self.escape_byte()));
+        }
+        builder
+    }
+
+    fn reader(&self, path: &str) -> Result<csv::Reader<BufReader<File>>, String> {
+        Ok(self
+            .reader_builder()
+            .from_reader(BufReader::new(File::open(path).map_err(|e| e.to_string())?)))
+    }
+
+    /// A reader for the records from byte `start` on, without a header row.
+    fn reader_at(&self, path: &str, start: u64) -> Result<csv::Reader<BufReader<File>>, String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start(start))
+            .map_err(|e| e.to_string())?;
+        Ok(self
+            .reader_builder()
+            .has_headers(false)
+            .from_reader(BufReader::new(file)))
+    }
+
+    fn writer(&self, path: &str) -> Result<csv::Writer<File>, String> {
+        Ok(self.writer_to(File::create(path).map_err(|e| e.to_string())?))
+    }
+
+    /// `writer`, or for a dry run one that leaves `path` alone and only counts bytes.
+    fn output_writer(
+        &self,
+        path: &str,
+        dry_run: bool,
+    ) -> Result<csv::Writer<CommandOutput>, String> {
+        let output = if dry_run {
+            CommandOutput::DryRun(0)
+        } else {
+            CommandOutput::File(
+                File::create(path).map_err(|e| e.to_string())?,
+                Box::default(),
+            )
+        };
+        Ok(self.writer_to(output))
+    }
+
+    fn writer_to<W: Write>(&self, output: W) -> csv::Writer<W> {
+        csv::WriterBuilder::new()
+            .has_headers(true)
+            .delimiter(self.delimiter_byte())
+            .terminator(normalize_terminator(self.eol.clone()))
+            .quote(self.quote_byte())
+            .escape(self.escape_byte())
+            .quote_style(self.quote_style())
+            .from_writer(output)
+    }
+
+    fn quote_style(&self) -> csv::QuoteStyle {
+        match self.quoting.as_deref() {
+            Some("always") => csv::QuoteStyle::Always,
+            Some("non_numeric") => csv::QuoteStyle::NonNumeric,
+            Some("never") => csv::QuoteStyle::Never,
+            _ => csv::QuoteStyle::Necessary,
+        }
+    }
+
+    /// The input `encoding` when it is not UTF-8.
+    fn foreign_encoding(&self) -> Option<&str> {
+        self.encoding
+            .as_deref()
+            .filter(|encoding| !matches!(encoding.to_ascii_uppercase().as_str(), "UTF-8" | "UTF8"))
+    }
+
+    fn is_utf16(&self) -> bool {
+        self.encoding
+            .as_deref()
+            .is_some_and(|encoding| encoding.eq_ignore_ascii_case("UTF-16LE"))
+    }
+
+    /// Whether [`Self::finish_output`] changes the file at all.
+    fn rewrites_output(&self) -> bool {
+        self.rewrites_records() || self.bom.unwrap_or(false) || self.is_utf16()
+    }
+
+    /// Whether [`Self::finish_output`] rewrites records, not just the encoding.
+    fn rewrites_records(&self) -> bool {
+        self.sep_hint.unwrap_or(false)
+            || self.formula_guard.is_some()
+            || self.null_token.is_some()
+            || self.columns.is_some()
+    }
+
+    fn formula_prefix(&self) -> Result<Option<&'static str>, String> {
+        match self.formula_guard.as_deref() {
+            None => Ok(None),
+            Some("quote") => Ok(Some("'")),
+            Some("space") => Ok(Some(" ")),
+            Some(other) => Err(format!("unknown formula guard '{}'", other)),
+        }
+    }
+
+    /// Rewrite a flushed output file with the column selection, null token, `sep=` line and
+    /// formula guard, if requested.
+    fn apply_record_options(&self, path: &str) -> Result<(), String> {
+        if !self.rewrites_records() {
+            return Ok(());
+        }
+        let prefix = self.formula_prefix()?;
+        let staged = format!("{}.sheet", path);
+        let mut output = std::io::BufWriter::new(File::create(&staged).map_err(|e| e.to_string())?);
+        if self.sep_hint.unwrap_or(false) {
+            let eol = if self.eol.as_deref() == Some("LF") {
+                "\n"
+            } else {
+                "\r\n"
+            };
+            write!(output, "sep={}{}", self.delimiter_byte() as char, eol)
+                .map_err(|e| e.to_string())?;
+        }
+        let mut reader = self
+            .reader_builder()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+            .map_err(|e| e.to_string())?;
+        let mut writer = self.writer_to(output);
+        let mut selection: Option<Vec<usize>> = None;
+        for (index, record) in reader.records().enumerate() {
+            let record = record.map_err(|e| e.to_string())?;
+            if index == 0 {
+                if let Some(columns) = &self.columns {
+                    selection = Some(
+                        columns
+                            .iter()
+                            .map(|name| {
+                                record
+                                    .iter()
+                                    .position(|header| header == name)
+                                    .ok_or_else(|| format!("export column '{}' not found", name))
+                            })
+                            .collect::<Result<_, _>>()?,
+                    );
+                }
+            }
+            let cells: Vec<&str> = match &selection {
+                Some(selection) => selection
+                    .iter()
+                    .map(|&col| record.get(col).unwrap_or(""))
+                    .collect(),
+                None => record.iter().collect(),
+            };
+            writer
+                .write_record(cells.into_iter().map(|cell| {
+                    let cell = match &self.null_token {
+                        Some(token) if index > 0 && cell.is_empty() => token.as_str(),
+                        _ => cell,
+                    };
+                    match prefix {
+                        Some(prefix) => guard_formula(cell, prefix).into_owned(),
+                        None => cell.to_string(),
+                    }
+                }))
+                .map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())?;
+        drop(writer);
+        fs::rename(&staged, path).map_err(|e| e.to_string())
+    }
+
+    /// Flush `writer` and [finish](Self::finish_output) its file, returning the output's
+    /// hashes: taken while writing when finishing leaves the bytes alone, read back
+    /// otherwise. A dry run only reports the bytes it would have written.
+    fn finish_writer(
+        &self,
+        writer: csv::Writer<CommandOutput>,
+        path: &str,
+    ) -> Result<FileHashes, String> {
+        let output = writer.into_inner().map_err(|e| e.to_string())?;
+        let dry_run = matches!(output, CommandOutput::DryRun(_));
+        let hashes = output.into_hashes();
+        if dry_run {
+            return Ok(hashes);
+        }
+        self.finish_output(path)?;
+        if self.rewrites_output() {
+            output_hashes(path)
+        } else {
+            Ok(hashes)
+        }
+    }
+
+    /// Apply the record options, encoding and BOM to a flushed output file.
+    fn finish_output(&self, path: &str) -> Result<(), String> {
+        self.apply_record_options(path)?;
+        let bom = self.bom.unwrap_or(false);
+        if self.is_utf16() {
+            rewrite_as_utf16le(path, bom)
+        } else {
+            rewrite_with_utf8_bom(path, bom)
+        }
+    }
+}
+/// Where a file-writing command sends its records: the target file, hashed on the way, or
+/// nowhere for a dry run, which still performs the full pass.
+enum CommandOutput {
+    File(File, Box<OutputDigest>),
+    DryRun(u64),
+}
+
+impl CommandOutput {
+    /// Bytes written so far.
+    fn bytes(&self) -> u64 {
+        match self {
+            Self::File(_, digest) => digest.bytes,
+            Self::DryRun(bytes) => *bytes,
+        }
+    }
+
+    /// Close the file and return the hashes of what was written; a dry run has none.
+    fn into_hashes(self) -> FileHashes {
+        match self {
+            Self::File(_, digest) => digest.finish(),
+            Self::DryRun(bytes) => FileHashes {
+                bytes,
+                md5: None,
+                sha256: None,
+            },
+        }
+    }
+}
+
+impl Write for CommandOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::File(file, digest) => {
+                let written = file.write(buf)?;
+                digest.update(&buf[..written]);
+                Ok(written)
+            }
+            Self::DryRun(bytes) => {
+                *bytes += buf.len() as u64;
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::File(file, _) => file.flush(),
+            Self::DryRun(_) => Ok(()),
+        }
+    }
+}
+
+/// MD5 and SHA-256 of the bytes written so far, so outputs need no second read to hash.
+#[derive(Default)]
+struct OutputDigest {
+    bytes: u64,
+    md5: md5::Md5,
+    sha256: sha2::Sha256,
+}
+
+impl OutputDigest {
+    fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        self.bytes += data.len() as u64;
+        self.md5.update(data);
+        self.sha256.update(data);
+    }
+
+    fn finish(self) -> FileHashes {
+        use sha2::Digest;
+        FileHashes {
+            bytes: self.bytes,
+            md5: Some(hex_digest(&self.md5.finalize())),
+            sha256: Some(hex_digest(&self.sha256.finalize())),
+        }
+    }
+}
+
+impl Write for OutputDigest {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Whether a spreadsheet could evaluate the cell: it starts with `=`, `+`, `-`, `@`, tab or
+/// CR and isn't a plain number such as `-12.5`.
+fn starts_like_formula(cell: &str) -> bool {
+    cell.starts_with(['=', '+', '-', '@', '\t', '\r']) && cell.parse::<f64>().is_err()
+}
+
+/// Prefix a cell that [starts like a formula](starts_like_formula) so spreadsheets show it
+/// as text.
+fn guard_formula<'a>(cell: &'a str, prefix: &str) -> std::borrow::Cow<'a, str> {
+    if starts_like_formula(cell) {
+        std::borrow::Cow::Owned(format!("{}{}", prefix, cell))
+    } else {
+        std::borrow::Cow::Borrowed(cell)
+    }
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Stream a file through the requested digests in one pass. `progress` receives
+/// `(bytes_read, total_bytes)` after every chunk.
+fn compute_file_hashes(
+    path: &str,
+    md5: bool,
+    sha256: bool,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<FileHashes, String> {
+    use sha2::Digest;
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut md5_hasher = md5.then(md5::Md5::new);
+    let mut sha256_hasher = sha256.then(sha2::Sha256::new);
+    let mut buffer = vec![0u8; 1024 * 1024];
+    let mut bytes = 0u64;
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        if let Some(hasher) = md5_hasher.as_mut() {
+            hasher.update(&buffer[..read]);
+        }
+        if let Some(hasher) = sha256_hasher.as_mut() {
+            hasher.update(&buffer[..read]);
+        }
+        bytes += read as u64;
+        progress(bytes, total);
+    }
+    Ok(FileHashes {
+        bytes,
+        md5: md5_hasher.map(|hasher| hex_digest(&hasher.finalize())),
+        sha256: sha256_hasher.map(|hasher| hex_digest(&hasher.finalize())),
+    })
+}
+
+/// Checksums attached to every written output, for publishing alongside delivered files.
+/// Outputs written through [`CommandOutput`] are hashed while writing instead; this reads
+/// back the ones a later pass rewrote or that were written some other way.
+fn output_hashes(path: &str) -> Result<FileHashes, String> {
+    compute_file_hashes(path, true, true, |_, _| {})
+}
+
+/// Bundled translation tables, one JSON object of `key -> text` per locale.
+const LOCALE_TABLES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("zh", include_str!("../locales/zh.json")),
+    ("ja", include_str!("../locales/ja.json")),
+    ("ko", include_str!("../locales/ko.json")),
+    ("de", include_str!("../locales/de.json")),
+    ("fr", include_str!("../locales/fr.json")),
+    ("es", include_str!("../locales/es.json")),
+    ("pt-BR", include_str!("../locales/pt-BR.json")),
+];
+
+static TRANSLATIONS: OnceLock<HashMap<String, HashMap<String, String>>> = OnceLock::new();
+
+fn translations() -> &'static HashMap<String, HashMap<String, String>> {
+    TRANSLATIONS.get_or_init(|| {
+        LOCALE_TABLES
+            .iter()
+            .filter_map(|(locale, json)| {
+                let table = serde_json::from_str(json)
+                    .map_err(|err| tracing::warn!(locale, error = %err, "bad locale table"))
+                    .ok()?;
+                Some((locale.to_lowercase(), table))
+            })
+            .collect()
+    })
+}
+
+/// Translate `key` for `locale`, falling back from `pt-BR` to `pt`, then to English, then
+/// to the key itself.
+fn tr(locale: &str, key: &str) -> String {
+    let tables = translations();
+    let locale = locale.replace('_', "-").to_lowercase();
+    let language = locale.split('-').next().unwrap_or_default();
+    [locale.as_str(), language, "en"]
+        .iter()
+        .find_map(|candidate| tables.get(*candidate)?.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// `tr` in the locale the frontend last set for the menu, with `{name}` filled in.
+fn tr_app(app: &tauri::AppHandle, key: &str, name: &str) -> String {
+    tr_app_with(app, key, &[("name", &name)])
+}
+
+/// `tr_app` with any number of `{placeholder}` values filled in.
+fn tr_app_with(
+    app: &tauri::AppHandle,
+    key: &str,
+    args: &[(&str, &dyn std::fmt::Display)],
+) -> String {
+    let locale = app
+        .state::<AppState>()
+        .menu_locale
+        .lock()
+        .map(|locale| locale.clone())
+        .unwrap_or_default();
+    args.iter().fold(tr(&locale, key), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), &value.to_string())
+    })
+}
+
+/// Locale codes with a bundled translation table.
+#[tauri::command]
+fn list_locales() -> Vec<String> {
+    LOCALE_TABLES
+        .iter()
+        .map(|(locale, _)| locale.to_string())
+        .collect()
 }
 
 #[cfg(desktop)]
 fn build_app_menu<R: tauri::Runtime, M: Manager<R>>(
     manager: &M,
     locale: &str,
+    recent: &[String],
+    keys: &HashMap<String, String>,
 ) -> tauri::Result<Menu<R>> {
-    let zh = is_zh(locale);
-    let file_open = MenuItemBuilder::with_id("file_open", if zh { "打开..." } else { "Open..." })
-        .accelerator("CmdOrCtrl+O")
-        .build(manager)?;
-    let file_save = MenuItemBuilder::with_id("file_save", if zh { "保存" } else { "Save" })
-        .accelerator("CmdOrCtrl+S")
-        .build(manager)?;
-    let file_save_as = MenuItemBuilder::with_id("file_save_as", if zh { "另存为..." } else { "Save As..." })
-        .accelerator("CmdOrCtrl+Shift+S")
-        .build(manager)?;
-    let file_macro = MenuItemBuilder::with_id("file_macro", if zh { "运行宏(文件)" } else { "Run Macro (file)" })
-        .accelerator("CmdOrCtrl+Shift+M")
-        .build(manager)?;
-    let file_find_replace = MenuItemBuilder::with_id(
-        "file_find_replace",
-        if zh { "查找/替换(文件)" } else { "Find/Replace (file)" },
-    )
-        .accelerator("CmdOrCtrl+Shift+F")
-        .build(manager)?;
-    let app_quit = MenuItemBuilder::with_id("app_quit", if zh { "退出" } else { "Quit" })
-        .accelerator("CmdOrCtrl+Q")
-        .build(manager)?;
-
-    let edit_undo = MenuItemBuilder::with_id("edit_undo", if zh { "撤销" } else { "Undo" })
-        .accelerator("CmdOrCtrl+Z")
-        .build(manager)?;
-    let edit_redo = MenuItemBuilder::with_id("edit_redo", if zh { "重做" } else { "Redo" })
-        .accelerator("CmdOrCtrl+Shift+Z")
-        .build(manager)?;
-    let edit_clear = MenuItemBuilder::with_id("edit_clear", if zh { "清除编辑" } else { "Clear Edits" })
-        .accelerator("CmdOrCtrl+Shift+X")
-        .build(manager)?;
-
-    let view_load_more = MenuItemBuilder::with_id("view_load_more", if zh { "加载更多行" } else { "Load more rows" })
-        .accelerator("CmdOrCtrl+L")
-        .build(manager)?;
-    let view_stats = MenuItemBuilder::with_id(
-        "view_stats",
-        if zh { "列统计(全量)" } else { "Column stats (full)" },
-    )
-        .accelerator("CmdOrCtrl+Shift+T")
-        .build(manager)?;
-    let view_toggle_quickbar =
-        MenuItemBuilder::with_id("view_toggle_quickbar", if zh { "切换快捷栏" } else { "Toggle quickbar" })
-            .accelerator("CmdOrCtrl+1")
-            .build(manager)?;
-    let view_toggle_findbar =
-        MenuItemBuilder::with_id("view_toggle_findbar", if zh { "切换查找栏" } else { "Toggle find bar" })
-            .accelerator("CmdOrCtrl+2")
-            .build(manager)?;
-    let view_toggle_macro =
-        MenuItemBuilder::with_id("view_toggle_macro", if zh { "切换宏面板" } else { "Toggle macro panel" })
-            .accelerator("CmdOrCtrl+3")
-            .build(manager)?;
-    let view_toggle_ops = MenuItemBuilder::with_id(
-        "view_toggle_ops",
-        if zh { "切换列/排序/筛选面板" } else { "Toggle column/sort/filter panel" },
-    )
-    .accelerator("CmdOrCtrl+4")
-    .build(manager)?;
-    let view_toggle_export =
-        MenuItemBuilder::with_id("view_toggle_export", if zh { "切换导出选项" } else { "Toggle export options" })
-            .accelerator("CmdOrCtrl+5")
-            .build(manager)?;
-    let view_toggle_find_panel =
-        MenuItemBuilder::with_id("view_toggle_find_panel", if zh { "切换查找/替换面板" } else { "Toggle find/replace panel" })
-            .accelerator("CmdOrCtrl+6")
-            .build(manager)?;
-    let view_toggle_stats_panel =
-        MenuItemBuilder::with_id("view_toggle_stats_panel", if zh { "切换统计面板" } else { "Toggle stats panel" })
-            .accelerator("CmdOrCtrl+7")
-            .build(manager)?;
+    let item = |id: &str| {
+        let mut builder = MenuItemBuilder::with_id(id, tr(locale, id));
+        if let Some(accelerator) = keys.get(id).filter(|accelerator| !accelerator.is_empty()) {
+            builder = builder.accelerator(accelerator);
+        }
+        builder.build(manager)
+    };
+    let file_open = item("file_open")?;
+    let file_new_window = item("file_new_window")?;
+    let file_save = item("file_save")?;
+    let file_save_as = item("file_save_as")?;
+    let file_macro = item("file_macro")?;
+    let file_find_replace = item("file_find_replace")?;
+    let app_quit = item("app_quit")?;
 
-    let tools_find_loaded =
-        MenuItemBuilder::with_id("tools_find_loaded", if zh { "查找/替换(已加载)" } else { "Find/Replace (loaded)" })
-            .accelerator("CmdOrCtrl+F")
-            .build(manager)?;
-    let tools_macro_loaded = MenuItemBuilder::with_id("tools_macro_loaded", if zh { "宏(已加载)" } else { "Macro (loaded)" })
-        .accelerator("CmdOrCtrl+M")
-        .build(manager)?;
+    let edit_undo = item("edit_undo")?;
+    let edit_redo = item("edit_redo")?;
+    let edit_clear = item("edit_clear")?;
+
+    let view_load_more = item("view_load_more")?;
+    let view_stats = item("view_stats")?;
+    let view_toggle_quickbar = item("view_toggle_quickbar")?;
+    let view_toggle_findbar = item("view_toggle_findbar")?;
+    let view_toggle_macro = item("view_toggle_macro")?;
+    let view_toggle_ops = item("view_toggle_ops")?;
+    let view_toggle_export = item("view_toggle_export")?;
+    let view_toggle_find_panel = item("view_toggle_find_panel")?;
+    let view_toggle_stats_panel = item("view_toggle_stats_panel")?;
+
+    let tools_find_loaded = item("tools_find_loaded")?;
+    let tools_macro_loaded = item("tools_macro_loaded")?;
+
+    let help_about = item("help_about")?;
 
-    let help_about = MenuItemBuilder::with_id("help_about", if zh { "关于 nmeditor" } else { "About nmeditor" })
-        .build(manager)?;
+    // Item ids carry the path so the frontend can reopen it directly.
+    let mut recent_menu = SubmenuBuilder::new(manager, tr(locale, "file_recent"));
+    if recent.is_empty() {
+        let empty = MenuItemBuilder::with_id("recent_empty", tr(locale, "recent_empty"))
+            .enabled(false)
+            .build(manager)?;
+        recent_menu = recent_menu.item(&empty);
+    }
+    for path in recent {
+        recent_menu = recent_menu.text(format!("recent_file:{}", path), path);
+    }
+    let recent_menu = recent_menu
+        .separator()
+        .text("recent_clear", tr(locale, "recent_clear"))
+        .build()?;
 
-    let file_menu = SubmenuBuilder::new(manager, if zh { "文件" } else { "File" })
+    let file_menu = SubmenuBuilder::new(manager, tr(locale, "menu_file"))
         .item(&file_open)
+        .item(&recent_menu)
+        .item(&file_new_window)
         .item(&file_save)
         .item(&file_save_as)
         .separator()
@@ -186,14 +682,14 @@ fn build_app_menu<R: tauri::Runtime, M: Manager<R>>(
         .item(&app_quit)
         .build()?;
 
-    let edit_menu = SubmenuBuilder::new(manager, if zh { "编辑" } else { "Edit" })
+    let edit_menu = SubmenuBuilder::new(manager, tr(locale, "menu_edit"))
         .item(&edit_undo)
         .item(&edit_redo)
         .separator()
         .item(&edit_clear)
         .build()?;
 
-    let view_menu = SubmenuBuilder::new(manager, if zh { "视图" } else { "View" })
+    let view_menu = SubmenuBuilder::new(manager, tr(locale, "menu_view"))
         .item(&view_load_more)
         .item(&view_stats)
         .separator()
@@ -206,12 +702,12 @@ fn build_app_menu<R: tauri::Runtime, M: Manager<R>>(
         .item(&view_toggle_stats_panel)
         .build()?;
 
-    let tools_menu = SubmenuBuilder::new(manager, if zh { "工具" } else { "Tools" })
+    let tools_menu = SubmenuBuilder::new(manager, tr(locale, "menu_tools"))
         .item(&tools_find_loaded)
         .item(&tools_macro_loaded)
         .build()?;
 
-    let help_menu = SubmenuBuilder::new(manager, if zh { "帮助" } else { "Help" })
+    let help_menu = SubmenuBuilder::new(manager, tr(locale, "menu_help"))
         .item(&help_about)
         .build()?;
 
@@ -224,127 +720,672 @@ fn build_app_menu<R: tauri::Runtime, M: Manager<R>>(
     Ok(menu)
 }
 
-#[tauri::command]
-fn set_menu_locale(app: tauri::AppHandle, locale: String) -> Result<(), String> {
+/// Rebuild the menu bar with the current locale and recent files.
+fn refresh_app_menu(app: &tauri::AppHandle) -> Result<(), String> {
+    set_app_menu(app, &load_keybindings(app)?)
+}
+
+/// Rebuild the menu with `keys` as the accelerators.
+fn set_app_menu(app: &tauri::AppHandle, keys: &HashMap<String, String>) -> Result<(), String> {
     #[cfg(desktop)]
     {
-        let menu = build_app_menu(&app, &locale).map_err(|e| e.to_string())?;
+        let state = app.state::<AppState>();
+        let locale = state
+            .menu_locale
+            .lock()
+            .map_err(|_| "lock poisoned")?
+            .clone();
+        let recent = load_recent_files(app)?;
+        let menu = build_app_menu(app, &locale, &recent, keys).map_err(|e| e.to_string())?;
         app.set_menu(menu).map_err(|e| e.to_string())?;
     }
     Ok(())
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct CsvPreview {
-    pub headers: Vec<String>,
-    pub rows: Vec<Vec<String>>,
-    pub delimiter: String,
-    pub path: String,
+#[tauri::command]
+fn set_menu_locale(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    locale: String,
+) -> Result<(), String> {
+    *state.menu_locale.lock().map_err(|_| "lock poisoned")? = locale;
+    refresh_app_menu(&app)
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct CsvSlice {
-    pub rows: Vec<Vec<String>>,
-    pub start: usize,
-    pub end: usize,
-    pub eof: bool,
-}
+const RECENT_FILES_FILE: &str = "recent_files.json";
 
-#[derive(Serialize, Deserialize)]
-pub struct CsvSessionInfo {
-    pub session_id: u64,
-    pub headers: Vec<String>,
-    pub delimiter: String,
-    pub path: String,
-}
+const RECENT_FILES_LIMIT: usize = 10;
 
-#[derive(Serialize, Deserialize)]
-pub struct CsvPatch {
-    pub row: usize,
-    pub col: usize,
-    pub value: String,
+fn load_recent_files(app: &tauri::AppHandle) -> Result<Vec<String>, String> {
+    read_json_config(&app_config_file(app, RECENT_FILES_FILE)?)
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-#[serde(tag = "type")]
-pub enum RowOp {
-    #[serde(rename = "insert")]
-    Insert { index: usize, values: Vec<String> },
-    #[serde(rename = "delete")]
-    Delete { index: usize },
+fn store_recent_files(app: &tauri::AppHandle, recent: &Vec<String>) -> Result<(), String> {
+    write_json_config(&app_config_file(app, RECENT_FILES_FILE)?, recent)?;
+    refresh_app_menu(app)
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-#[serde(tag = "type")]
-pub enum ColumnOp {
-    #[serde(rename = "insert")]
-    Insert { index: usize, name: String },
-    #[serde(rename = "delete")]
-    Delete { index: usize },
-    #[serde(rename = "rename")]
-    Rename { index: usize, name: String },
+/// Most recently opened first.
+#[tauri::command]
+fn list_recent_files(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    load_recent_files(&app)
 }
 
-#[derive(Clone)]
-struct NormalizedRowOp {
-    input_index: isize,
-    op: RowOp,
+/// Move `path` to the top of the recent list and refresh the menu.
+#[tauri::command]
+fn add_recent_file(app: tauri::AppHandle, path: UserPath) -> Result<Vec<String>, String> {
+    let mut recent = load_recent_files(&app)?;
+    recent.retain(|existing| existing != &path);
+    recent.insert(0, path.to_string());
+    recent.truncate(RECENT_FILES_LIMIT);
+    store_recent_files(&app, &recent)?;
+    Ok(recent)
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct CsvMacroSpec {
-    pub op: String,
-    pub column: usize,
-    pub find: Option<String>,
-    pub replace: Option<String>,
-    pub text: Option<String>,
+#[tauri::command]
+fn clear_recent_files(app: tauri::AppHandle) -> Result<(), String> {
+    store_recent_files(&app, &Vec::new())
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct CsvMacroResult {
-    pub output_path: String,
-    pub applied: usize,
-}
+const KEYBINDINGS_FILE: &str = "keybindings.json";
 
-#[derive(Serialize, Deserialize)]
-pub struct ColumnStat {
-    pub name: String,
-    pub non_empty: usize,
-    pub distinct: usize,
-    pub distinct_truncated: bool,
-    pub inferred: String,
-}
+/// Menu actions and their default accelerators; an empty accelerator means unbound.
+const DEFAULT_KEYBINDINGS: &[(&str, &str)] = &[
+    ("file_open", "CmdOrCtrl+O"),
+    ("file_new_window", "CmdOrCtrl+Shift+N"),
+    ("file_save", "CmdOrCtrl+S"),
+    ("file_save_as", "CmdOrCtrl+Shift+S"),
+    ("file_macro", "CmdOrCtrl+Shift+M"),
+    ("file_find_replace", "CmdOrCtrl+Shift+F"),
+    ("app_quit", "CmdOrCtrl+Q"),
+    ("edit_undo", "CmdOrCtrl+Z"),
+    ("edit_redo", "CmdOrCtrl+Shift+Z"),
+    ("edit_clear", "CmdOrCtrl+Shift+X"),
+    ("view_load_more", "CmdOrCtrl+L"),
+    ("view_stats", "CmdOrCtrl+Shift+T"),
+    ("view_toggle_quickbar", "CmdOrCtrl+1"),
+    ("view_toggle_findbar", "CmdOrCtrl+2"),
+    ("view_toggle_macro", "CmdOrCtrl+3"),
+    ("view_toggle_ops", "CmdOrCtrl+4"),
+    ("view_toggle_export", "CmdOrCtrl+5"),
+    ("view_toggle_find_panel", "CmdOrCtrl+6"),
+    ("view_toggle_stats_panel", "CmdOrCtrl+7"),
+    ("tools_find_loaded", "CmdOrCtrl+F"),
+    ("tools_macro_loaded", "CmdOrCtrl+M"),
+    ("help_about", ""),
+];
 
 #[derive(Serialize, Deserialize)]
-pub struct FindReplaceSpec {
-    pub find: String,
-    pub replace: String,
-    pub column: Option<usize>,
-    pub regex: bool,
-    pub match_case: bool,
+pub struct Keybinding {
+    pub action: String,
+    pub accelerator: String,
+    pub default_accelerator: String,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct FindReplaceResult {
-    pub output_path: String,
-    pub applied: usize,
+/// Named keys an accelerator may end in, besides letters, digits, F1-F24 and the number
+/// pad digits. The first name of each group is the canonical one.
+const ACCELERATOR_KEYS: &[&[&str]] = &[
+    &["Space"],
+    &["Tab"],
+    &["Enter"],
+    &["Escape", "Esc"],
+    &["Backspace"],
+    &["Delete"],
+    &["Insert"],
+    &["Home"],
+    &["End"],
+    &["PageUp"],
+    &["PageDown"],
+    &["ArrowUp", "Up"],
+    &["ArrowDown", "Down"],
+    &["ArrowLeft", "Left"],
+    &["ArrowRight", "Right"],
+    &["Backquote", "`"],
+    &["Backslash", "\\"],
+    &["BracketLeft", "["],
+    &["BracketRight", "]"],
+    &["Comma", ","],
+    &["Minus", "-"],
+    &["Period", "."],
+    &["Quote", "'"],
+    &["Semicolon", ";"],
+    &["Slash", "/"],
+    &["Equal", "="],
+];
+
+/// Canonical name of an accelerator's key, or `None` for a key the menu would drop.
+fn accelerator_key(key: &str) -> Option<String> {
+    let upper = key.to_ascii_uppercase();
+    let single = upper
+        .strip_prefix("KEY")
+        .filter(|c| c.len() == 1 && c.as_bytes()[0].is_ascii_uppercase())
+        .or_else(|| {
+            upper
+                .strip_prefix("DIGIT")
+                .filter(|c| c.len() == 1 && c.as_bytes()[0].is_ascii_digit())
+        })
+        .unwrap_or(&upper);
+    if single.len() == 1 && single.as_bytes()[0].is_ascii_alphanumeric() {
+        return Some(single.to_string());
+    }
+    let number = |prefix: &str| {
+        upper
+            .strip_prefix(prefix)
+            .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            .filter(|n| n.len() == 1 || !n.starts_with('0'))
+            .and_then(|n| n.parse::<u8>().ok())
+    };
+    if let Some(n) = number("F").filter(|n| (1..=24).contains(n)) {
+        return Some(format!("F{}", n));
+    }
+    let numpad = number("NUMPAD").or_else(|| number("NUM"));
+    if let Some(n) = numpad.filter(|n| *n <= 9) {
+        return Some(format!("NUMPAD{}", n));
+    }
+    ACCELERATOR_KEYS
+        .iter()
+        .find(|names| names.iter().any(|name| name.eq_ignore_ascii_case(key)))
+        .map(|names| names[0].to_ascii_uppercase())
+}
+
+/// Canonical form of an accelerator for conflict checks: known modifiers in a fixed order,
+/// `CmdOrCtrl` resolved for this platform, key upper-cased. Fails on a key the menu
+/// wouldn't accept, which it would otherwise drop without a word.
+fn normalize_accelerator(accelerator: &str) -> Result<String, String> {
+    let parts: Vec<&str> = accelerator.split('+').map(str::trim).collect();
+    let Some((key, modifiers)) = parts.split_last().filter(|(key, _)| !key.is_empty()) else {
+        return Err(format!("accelerator has no key: {}", accelerator));
+    };
+    let primary = if cfg!(target_os = "macos") {
+        "Cmd"
+    } else {
+        "Ctrl"
+    };
+    let mut canonical = Vec::new();
+    for modifier in modifiers {
+        let name = match modifier.to_ascii_lowercase().as_str() {
+            "cmdorctrl" | "cmdorcontrol" | "commandorctrl" | "commandorcontrol" => primary,
+            "ctrl" | "control" => "Ctrl",
+            "cmd" | "command" | "super" | "meta" => "Cmd",
+            "alt" | "option" => "Alt",
+            "shift" => "Shift",
+            _ => return Err(format!("unknown modifier in {}: {}", accelerator, modifier)),
+        };
+        if !canonical.contains(&name) {
+            canonical.push(name);
+        }
+    }
+    canonical.sort_by_key(|name| {
+        ["Cmd", "Ctrl", "Alt", "Shift"]
+            .iter()
+            .position(|m| m == name)
+    });
+    let Some(key) = accelerator_key(key) else {
+        return Err(format!("unknown key in {}: {}", accelerator, key));
+    };
+    Ok(canonical
+        .into_iter()
+        .chain(std::iter::once(key.as_str()))
+        .collect::<Vec<_>>()
+        .join("+"))
+}
+
+/// Defaults overlaid with the user's overrides, by action.
+fn load_keybindings(app: &tauri::AppHandle) -> Result<HashMap<String, String>, String> {
+    let overrides: HashMap<String, String> =
+        read_json_config(&app_config_file(app, KEYBINDINGS_FILE)?)?;
+    Ok(DEFAULT_KEYBINDINGS
+        .iter()
+        .map(|(action, default)| {
+            let accelerator = overrides
+                .get(*action)
+                .cloned()
+                .unwrap_or_else(|| default.to_string());
+            (action.to_string(), accelerator)
+        })
+        .collect())
+}
+
+#[tauri::command]
+fn get_keybindings(app: tauri::AppHandle) -> Result<Vec<Keybinding>, String> {
+    let keys = load_keybindings(&app)?;
+    Ok(DEFAULT_KEYBINDINGS
+        .iter()
+        .map(|(action, default)| Keybinding {
+            action: action.to_string(),
+            accelerator: keys.get(*action).cloned().unwrap_or_default(),
+            default_accelerator: default.to_string(),
+        })
+        .collect())
+}
+
+/// Replace the user's overrides (action -> accelerator, empty to unbind) and rebuild the
+/// menu. Rejects unknown actions, malformed accelerators and two actions sharing a key.
+/// The overrides are saved only once the menu has been built with them.
+#[tauri::command]
+fn set_keybindings(
+    app: tauri::AppHandle,
+    bindings: HashMap<String, String>,
+) -> Result<Vec<Keybinding>, String> {
+    if let Some(unknown) = bindings
+        .keys()
+        .find(|action| !DEFAULT_KEYBINDINGS.iter().any(|(known, _)| known == action))
+    {
+        return Err(format!("unknown action: {}", unknown));
+    }
+
+    let mut taken: HashMap<String, &str> = HashMap::new();
+    let mut conflicts = Vec::new();
+    for (action, default) in DEFAULT_KEYBINDINGS {
+        let accelerator = bindings.get(*action).map(String::as_str).unwrap_or(default);
+        if accelerator.is_empty() {
+            continue;
+        }
+        let canonical = normalize_accelerator(accelerator)?;
+        if let Some(other) = taken.get(&canonical) {
+            conflicts.push(format!("{} ({} and {})", accelerator, other, action));
+        } else {
+            taken.insert(canonical, action);
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(structured_error(
+            "keybinding_conflict",
+            &format!("shortcuts assigned twice: {}", conflicts.join(", ")),
+            serde_json::json!({ "conflicts": conflicts }),
+        ));
+    }
+
+    let overrides: HashMap<&String, &String> = bindings
+        .iter()
+        .filter(|(action, accelerator)| {
+            DEFAULT_KEYBINDINGS
+                .iter()
+                .any(|(known, default)| known == action && default != accelerator)
+        })
+        .collect();
+    let keys: HashMap<String, String> = DEFAULT_KEYBINDINGS
+        .iter()
+        .map(|(action, default)| {
+            let accelerator = bindings.get(*action).map(String::as_str).unwrap_or(default);
+            (action.to_string(), accelerator.to_string())
+        })
+        .collect();
+    set_app_menu(&app, &keys)?;
+    if let Err(err) = write_json_config(&app_config_file(&app, KEYBINDINGS_FILE)?, &overrides) {
+        // Put back the menu of the bindings still on disk.
+        let _ = refresh_app_menu(&app);
+        return Err(err);
+    }
+    get_keybindings(app)
+}
+
+/// Grid context-menu actions, in menu order; `None` marks a separator.
+const CELL_MENU_ACTIONS: &[Option<&str>] = &[
+    Some("copy_cell"),
+    Some("copy_row"),
+    None,
+    Some("insert_above"),
+    Some("insert_below"),
+    Some("delete_row"),
+    None,
+    Some("filter_value"),
+];
+
+/// A context-menu choice sent to the frontend as `cell-context-menu`.
+#[derive(Serialize, Clone)]
+struct CellMenuEvent {
+    action: String,
+    row: usize,
+    col: usize,
+}
+
+/// Parse a context-menu item id (`cell:<action>:<row>:<col>`).
+fn parse_cell_menu_id(id: &str) -> Option<CellMenuEvent> {
+    let mut parts = id.strip_prefix("cell:")?.split(':');
+    let action = parts.next()?.to_string();
+    let row = parts.next()?.parse().ok()?;
+    let col = parts.next()?.parse().ok()?;
+    Some(CellMenuEvent { action, row, col })
+}
+
+/// Pop up the native context menu for a grid cell at the given window coordinates (logical
+/// pixels). Item ids encode the cell, so the choice comes back with its coordinates.
+#[tauri::command]
+fn show_cell_context_menu(
+    window: tauri::Window,
+    state: tauri::State<AppState>,
+    row: usize,
+    col: usize,
+    x: f64,
+    y: f64,
+    read_only: Option<bool>,
+) -> Result<(), String> {
+    #[cfg(desktop)]
+    {
+        use tauri::menu::PredefinedMenuItem;
+        let locale = state
+            .menu_locale
+            .lock()
+            .map_err(|_| "lock poisoned")?
+            .clone();
+        let menu = Menu::new(&window).map_err(|e| e.to_string())?;
+        for action in CELL_MENU_ACTIONS {
+            match action {
+                Some(action) => {
+                    let edits = matches!(*action, "insert_above" | "insert_below" | "delete_row");
+                    let item = MenuItemBuilder::with_id(
+                        format!("cell:{}:{}:{}", action, row, col),
+                        tr(&locale, &format!("cell_{}", action)),
+                    )
+                    .enabled(!(edits && read_only.unwrap_or(false)))
+                    .build(&window)
+                    .map_err(|e| e.to_string())?;
+                    menu.append(&item).map_err(|e| e.to_string())?;
+                }
+                None => {
+                    let separator =
+                        PredefinedMenuItem::separator(&window).map_err(|e| e.to_string())?;
+                    menu.append(&separator).map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        window
+            .popup_menu_at(&menu, tauri::LogicalPosition::new(x, y))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CsvPreview {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub delimiter: String,
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CsvSlice {
+    pub rows: Vec<Vec<String>>,
+    pub start: usize,
+    pub end: usize,
+    pub eof: bool,
+    /// Where each row starts in the source file, in step with `rows`.
+    #[serde(default)]
+    pub positions: Vec<RowPosition>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RowPosition {
+    pub byte: u64,
+    /// 1-based physical line; a multi-line row reports its first line.
+    pub line: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CsvSessionInfo {
+    pub session_id: u64,
+    /// Display names; repeated names are suffixed when disambiguation was requested.
+    pub headers: Vec<String>,
+    /// Names as written in the file. Saves keep these unless a column is renamed.
+    pub original_headers: Vec<String>,
+    pub duplicate_headers: Vec<DuplicateHeader>,
+    pub delimiter: String,
+    /// The session's dialect with the delimiter resolved.
+    pub dialect: CsvDialect,
+    pub path: String,
+    pub read_only: bool,
+    pub read_only_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CsvPatch {
+    pub row: usize,
+    pub col: usize,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum RowOp {
+    #[serde(rename = "insert")]
+    Insert { index: usize, values: Vec<String> },
+    #[serde(rename = "delete")]
+    Delete { index: usize },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum ColumnOp {
+    #[serde(rename = "insert")]
+    Insert { index: usize, name: String },
+    #[serde(rename = "delete")]
+    Delete { index: usize },
+    #[serde(rename = "rename")]
+    Rename { index: usize, name: String },
+}
+
+/// A run of output rows. Row ops address rows by their position at the time of the edit,
+/// so they are replayed over these runs rather than over the file.
+enum RowSegment {
+    /// Input rows `start..end`; the last segment is open-ended.
+    Input {
+        start: usize,
+        end: usize,
+    },
+    Inserted(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CsvMacroSpec {
+    pub op: String,
+    pub column: usize,
+    pub find: Option<String>,
+    pub replace: Option<String>,
+    pub text: Option<String>,
+    pub salt: Option<String>,
+    pub keep: Option<usize>,
+    pub mask_char: Option<String>,
+    pub start: Option<i64>,
+    pub step: Option<i64>,
+    pub width: Option<usize>,
+    pub new_column: Option<String>,
+    pub pad_char: Option<String>,
+    pub length: Option<usize>,
+    pub ellipsis: Option<String>,
+    pub decimals: Option<usize>,
+    pub factor: Option<f64>,
+    pub separator: Option<String>,
+    pub locale: Option<String>,
+    pub json_path: Option<String>,
+    pub source_column: Option<usize>,
+    pub condition: Option<MacroCondition>,
+    pub then_value: Option<String>,
+    pub else_value: Option<String>,
+    pub script: Option<String>,
+    pub max_rows: Option<usize>,
+    /// Time one row's script run may take (default 30 s).
+    pub time_limit_ms: Option<u64>,
+    pub plugin: Option<String>,
+    pub date_format: Option<String>,
+    pub output_format: Option<String>,
+    pub source_timezone: Option<String>,
+    pub target_timezone: Option<String>,
+    /// Conversion rates by code, e.g. `{"EUR": 1.08}`; merged over `rates_path`.
+    pub rates: Option<HashMap<String, f64>>,
+    /// Two-column CSV (code, rate) with a header row.
+    pub rates_path: Option<String>,
+    /// Column holding each row's code; `rate_key` is used when absent.
+    pub rate_column: Option<usize>,
+    pub rate_key: Option<String>,
+    /// Divide by the rate instead of multiplying.
+    pub divide: Option<bool>,
+    /// `latitude` or `longitude` for `normalize_coordinate`; inferred from N/S/E/W otherwise.
+    pub axis: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MacroCondition {
+    pub column: Option<usize>,
+    pub operator: String,
+    pub value: Option<String>,
+    pub value_column: Option<usize>,
+    /// Upper bound for `between`.
+    pub value_end: Option<String>,
+    /// chrono format for the date operators; common ISO-like formats are tried otherwise.
+    pub date_format: Option<String>,
+    /// IANA zone used for values without an offset (UTC by default).
+    pub timezone: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FileHashes {
+    pub bytes: u64,
+    pub md5: Option<String>,
+    pub sha256: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveResult {
+    pub output_path: String,
+    pub hashes: FileHashes,
+    /// Set by commands that rewrite rows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counts: Option<RowCounts>,
+    /// Nothing was written; `hashes` only has the byte count.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<SaveVerification>,
+}
+
+/// Result of re-reading a written file with its output dialect.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SaveVerification {
+    /// Row and column counts match and every sampled cell reads back as written.
+    pub ok: bool,
+    pub rows: usize,
+    pub expected_rows: usize,
+    pub columns: usize,
+    pub expected_columns: usize,
+    pub cells_checked: usize,
+    /// Sampled cells that read back differently, as (row, column, expected, found).
+    pub mismatches: Vec<(usize, usize, String, String)>,
+    /// Why the written file could not be read back, if it could not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CsvMacroResult {
+    pub output_path: String,
+    pub applied: usize,
+    pub failed: usize,
+    pub hashes: FileHashes,
+    pub counts: RowCounts,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+/// What a file-rewriting command did, for callers to check against what they expected.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RowCounts {
+    pub rows_read: usize,
+    pub rows_written: usize,
+    pub rows_inserted: usize,
+    pub rows_deleted: usize,
+    pub cells_modified: usize,
+}
+
+/// Cells that differ between a row as read and as written. Columns a macro inserted are
+/// added to `before` empty first, and cells missing on either side count as empty.
+fn count_changed_cells(
+    mut before: Vec<String>,
+    inserted: impl IntoIterator<Item = usize>,
+    after: &[String],
+) -> usize {
+    for idx in inserted {
+        before.insert(idx.min(before.len()), String::new());
+    }
+    (0..before.len().max(after.len()))
+        .filter(|&i| {
+            before.get(i).map_or("", String::as_str) != after.get(i).map_or("", String::as_str)
+        })
+        .count()
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnStat {
+    pub name: String,
+    pub rows: usize,
+    pub non_empty: usize,
+    pub distinct: usize,
+    pub distinct_truncated: bool,
+    /// HyperLogLog estimate; equals `distinct` when the exact set was not truncated.
+    pub distinct_estimate: usize,
+    pub inferred: String,
+    /// Numeric min/max for number columns, lexicographic otherwise.
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub examples: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct FindReplaceSpec {
+    pub find: String,
+    pub replace: String,
+    pub column: Option<usize>,
+    pub regex: bool,
+    pub match_case: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FindReplaceResult {
+    pub output_path: String,
+    pub applied: usize,
+    pub hashes: FileHashes,
+    pub counts: RowCounts,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
 }
 
 struct CsvSession {
     reader: csv::Reader<BufReader<File>>,
     row_index: usize,
     eof: bool,
+    path: String,
+    dialect: CsvDialect,
+    read_only: bool,
+    /// Cells that differ from the file on disk, keyed by (row, col).
+    changes: std::collections::BTreeMap<(usize, usize), CellChange>,
+    /// Label of the window that opened the session.
+    owner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CellChange {
+    pub row: usize,
+    pub col: usize,
+    pub original: String,
+    pub value: String,
+    /// What produced the edit, e.g. `patch` or `macro`.
+    pub source: String,
 }
 
 #[derive(Clone)]
 struct CsvIndexEntry {
     row: usize,
     byte: u64,
+    line: u64,
+    /// `group_hash` of the raw bytes up to the next entry, or to the end of the file.
+    hash: u64,
 }
 
 #[derive(Clone)]
 struct CsvIndex {
-    delimiter: u8,
+    /// The dialect the index was read with, delimiter resolved.
+    dialect: CsvDialect,
     stride: usize,
     data_start: u64,
     offsets: Vec<CsvIndexEntry>,
@@ -359,6 +1400,24 @@ struct AppState {
     indexes: Arc<Mutex<HashMap<String, CsvIndex>>>,
     index_jobs: Arc<Mutex<HashMap<u64, IndexJob>>>,
     next_index_job: AtomicU64,
+    /// Cancel flags of running typed exports, by job id.
+    export_cancels: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+    /// Step-through replaces in progress, by id.
+    replace_sessions: Mutex<HashMap<u64, ReplaceSession>>,
+    /// Value indexes of filtered columns, by path, delimiter and column.
+    column_indexes: Mutex<HashMap<String, ColumnIndex>>,
+    next_window: AtomicU64,
+    http_server: Mutex<Option<HttpServer>>,
+    startup_stream: Mutex<Option<String>>,
+    /// Files to open that no window has collected yet.
+    pending_opens: Mutex<Vec<String>>,
+    /// Stop flags of running watch-folder threads, by rule name.
+    watch_folders: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    job_history: Mutex<Vec<JobRun>>,
+    menu_locale: Mutex<String>,
+    /// OAuth access tokens by cloud provider, never written to disk.
+    #[cfg(feature = "cloud-export")]
+    cloud_tokens: Mutex<HashMap<String, String>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -389,8 +1448,67 @@ static MENU_EVENT_GUARD: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::n
 
 const INDEX_STRIDE: usize = 1000;
 
-fn index_key(path: &str, delimiter: u8) -> String {
-    format!("{}::{}", path, delimiter)
+/// Cache key for `path` read with `dialect` (delimiter resolved). Record boundaries depend
+/// on the quote and escape as well as the delimiter, so all three are part of it.
+fn index_key(path: &str, dialect: &CsvDialect) -> String {
+    format!(
+        "{}::{}:{}:{}",
+        path,
+        dialect.delimiter_byte(),
+        dialect.quote_byte(),
+        dialect.escape_byte()
+    )
+}
+
+fn group_hash(bytes: &[u8]) -> u64 {
+    use std::hash::Hasher;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Passes reads through and keeps what was read until `hash_up_to` takes it, so the
+/// indexer can hash the raw bytes behind the records it parses.
+struct HashingReader<R> {
+    inner: R,
+    kept: Vec<u8>,
+    /// Offset of `kept[0]` in the file.
+    start: u64,
+    hasher: std::collections::hash_map::DefaultHasher,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            kept: Vec::new(),
+            start: 0,
+            hasher: Default::default(),
+        }
+    }
+
+    /// Hash the bytes kept before file offset `end`, or all of them for `u64::MAX`.
+    fn hash_up_to(&mut self, end: u64) {
+        use std::hash::Hasher;
+        let taken = (end.saturating_sub(self.start) as usize).min(self.kept.len());
+        self.hasher.write(&self.kept[..taken]);
+        self.kept.drain(..taken);
+        self.start += taken as u64;
+    }
+
+    /// The hash of everything taken since the last call.
+    fn take_hash(&mut self) -> u64 {
+        use std::hash::Hasher;
+        std::mem::take(&mut self.hasher).finish()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.kept.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
 }
 
 fn file_signature(path: &PathBuf) -> Result<(u64, u64), String> {
@@ -423,26 +1541,17 @@ fn is_job_canceled(jobs: &Arc<Mutex<HashMap<u64, IndexJob>>>, job_id: u64) -> bo
 
 #[tauri::command]
 fn start_prepare_csv_index(
+    app: tauri::AppHandle,
     state: tauri::State<AppState>,
-    path: String,
-    delimiter: Option<String>,
+    path: UserPath,
+    dialect: Option<CsvDialect>,
 ) -> Result<StartIndexResponse, String> {
     let path_buf = PathBuf::from(&path);
-
-    let delimiter_byte = if let Some(value) = delimiter.as_deref() {
-        parse_delimiter(value)
-    } else {
-        let mut sample = String::new();
-        let sample_reader = BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?);
-        sample_reader
-            .take(64 * 1024)
-            .read_to_string(&mut sample)
-            .map_err(|e| e.to_string())?;
-        detect_delimiter(&sample)
-    };
+    let dialect = dialect.unwrap_or_default();
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
 
     let signature = file_signature(&path_buf)?;
-    let key = index_key(&path, delimiter_byte);
+    let key = index_key(&path, &dialect);
     if let Ok(indexes) = state.indexes.lock() {
         if let Some(existing) = indexes.get(&key) {
             if existing.file_len == signature.0 && existing.modified == signature.1 {
@@ -475,20 +1584,24 @@ fn start_prepare_csv_index(
     let jobs = state.index_jobs.clone();
     let indexes = state.indexes.clone();
     tauri::async_runtime::spawn_blocking(move || {
+        let taskbar = TaskbarProgress::start(&app);
         let result = (|| -> Result<CsvIndex, String> {
             let (file_len, modified) = file_signature(&path_buf)?;
-            let mut reader = csv::ReaderBuilder::new()
-                .has_headers(true)
-                .delimiter(delimiter_byte)
-                .from_reader(BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?));
+            let file = File::open(&path).map_err(|e| e.to_string())?;
+            let mut reader = dialect
+                .reader_builder()
+                .from_reader(HashingReader::new(file));
 
             let _ = reader.headers().map_err(|e| e.to_string())?;
-            let mut offsets = Vec::new();
+            let mut offsets: Vec<CsvIndexEntry> = Vec::new();
             let mut record = csv::StringRecord::new();
             let mut row_index = 0usize;
             let mut last_pos = reader.position().byte();
+            let mut last_line = reader.position().line();
             let data_start = last_pos;
             let mut last_progress = 0.0f32;
+            reader.get_mut().hash_up_to(data_start);
+            reader.get_mut().take_hash();
 
             loop {
                 if is_job_canceled(&jobs, job_id) {
@@ -499,13 +1612,21 @@ fn start_prepare_csv_index(
                 }
                 let record_start = last_pos;
                 if row_index % INDEX_STRIDE == 0 {
+                    let group_hash = reader.get_mut().take_hash();
+                    if let Some(previous) = offsets.last_mut() {
+                        previous.hash = group_hash;
+                    }
                     offsets.push(CsvIndexEntry {
                         row: row_index,
                         byte: record_start,
+                        line: last_line,
+                        hash: 0,
                     });
                 }
                 row_index += 1;
                 last_pos = reader.position().byte();
+                last_line = reader.position().line();
+                reader.get_mut().hash_up_to(last_pos);
 
                 if row_index % INDEX_STRIDE == 0 {
                     let progress = if file_len > 0 {
@@ -515,15 +1636,21 @@ fn start_prepare_csv_index(
                     };
                     if progress - last_progress >= 0.01 {
                         last_progress = progress;
+                        taskbar.set((progress * 100.0) as u64);
                         update_index_job(&jobs, job_id, |job| {
                             job.progress = progress;
                         });
                     }
                 }
             }
+            reader.get_mut().hash_up_to(u64::MAX);
+            let group_hash = reader.get_mut().take_hash();
+            if let Some(last) = offsets.last_mut() {
+                last.hash = group_hash;
+            }
 
             Ok(CsvIndex {
-                delimiter: delimiter_byte,
+                dialect: dialect.clone(),
                 stride: INDEX_STRIDE,
                 data_start,
                 offsets,
@@ -535,8 +1662,8 @@ fn start_prepare_csv_index(
 
         match result {
             Ok(index) => {
+                tracing::info!(path = %path, rows = index.total_rows, "csv index ready");
                 if let Ok(mut map) = indexes.lock() {
-                    let key = index_key(&path, delimiter_byte);
                     map.insert(key, index.clone());
                 }
                 update_index_job(&jobs, job_id, |job| {
@@ -546,6 +1673,7 @@ fn start_prepare_csv_index(
                 });
             }
             Err(err) => {
+                tracing::warn!(path = %path, error = %err, "csv index job stopped");
                 if err == "canceled" {
                     update_index_job(&jobs, job_id, |job| {
                         job.done = true;
@@ -598,42 +1726,151 @@ fn cancel_prepare_csv_index(
     Ok(false)
 }
 
-fn find_index_base(index: &CsvIndex, start: usize) -> (usize, u64) {
-    let mut base_row = 0usize;
-    let mut base_offset = index.data_start;
+/// The last index entry at or before row `start`, as (row, byte, line).
+fn find_index_base(index: &CsvIndex, start: usize) -> (usize, u64, u64) {
+    let mut base = (0usize, index.data_start, 1);
     for entry in &index.offsets {
         if entry.row > start {
             break;
         }
-        base_row = entry.row;
-        base_offset = entry.byte;
+        base = (entry.row, entry.byte, entry.line);
     }
-    (base_row, base_offset)
+    base
 }
 
-/// Load the first chunk of a CSV for preview, using a detected or provided delimiter.
+/// Raw bytes shown for one record at most; longer records are cut.
+const RAW_RECORD_LIMIT: u64 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+pub struct RawRecord {
+    pub row: usize,
+    /// Byte offset of the record in the file.
+    pub byte: u64,
+    /// Bytes the record spans, line terminator included.
+    pub length: u64,
+    pub truncated: bool,
+    /// The bytes as UTF-8, invalid sequences replaced.
+    pub text: String,
+    /// The bytes with control, zero-width and invalid bytes written as `\xNN`.
+    pub escaped: String,
+}
+
+/// Printable text with everything else as `\xNN`, so `a\tb\r\n` shows its tab and CRLF.
+fn escape_raw_bytes(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    let hex = |escaped: &mut String, bytes: &[u8]| {
+        for b in bytes {
+            escaped.push_str(&format!("\\x{:02X}", b));
+        }
+    };
+    for chunk in bytes.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            if ch.is_control() || is_invisible_char(ch) {
+                hex(&mut escaped, ch.encode_utf8(&mut [0; 4]).as_bytes());
+            } else {
+                escaped.push(ch);
+            }
+        }
+        hex(&mut escaped, chunk.invalid());
+    }
+    escaped
+}
+
+/// The untouched bytes of data row `row_index` (0-based), found from the positional index
+/// when one is built, or by scanning from the top.
 #[tauri::command]
-fn preview_csv(path: String, delimiter: Option<String>) -> Result<CsvPreview, String> {
-    let path_buf = PathBuf::from(&path);
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn get_raw_record(
+    state: tauri::State<AppState>,
+    path: UserPath,
+    row_index: usize,
+    dialect: Option<CsvDialect>,
+) -> Result<RawRecord, String> {
+    let dialect = dialect.unwrap_or_default();
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+    let index = fresh_index(&state.indexes, &path, &dialect)?;
+    read_raw_record(&path, row_index, &dialect, index.as_ref())
+}
 
-    // Sample a small slice to guess the delimiter if not provided.
-    let mut sample = String::new();
-    let sample_reader = BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?);
-    sample_reader
-        .take(64 * 1024)
-        .read_to_string(&mut sample)
-        .map_err(|e| e.to_string())?;
+/// `get_raw_record` with the delimiter resolved, starting from `index` when there is one.
+fn read_raw_record(
+    path: &str,
+    row_index: usize,
+    dialect: &CsvDialect,
+    index: Option<&CsvIndex>,
+) -> Result<RawRecord, String> {
+    let file_len = fs::metadata(path).map_err(|e| e.to_string())?.len();
 
-    let delimiter_byte = delimiter
-        .as_deref()
-        .map(parse_delimiter)
-        .unwrap_or_else(|| detect_delimiter(&sample));
+    // Start the reader at the nearest index entry; record positions are relative to it.
+    let (mut row, base_offset, mut reader) = match index {
+        Some(index) => {
+            let (base_row, base_offset, _) = find_index_base(index, row_index);
+            (base_row, base_offset, dialect.reader_at(path, base_offset)?)
+        }
+        None => (0, 0, dialect.reader(path)?),
+    };
+    let mut record = csv::ByteRecord::new();
+    let start = loop {
+        if !reader
+            .read_byte_record(&mut record)
+            .map_err(|e| e.to_string())?
+        {
+            return Err(format!("row {} is past the end of the file", row_index));
+        }
+        if row == row_index {
+            break base_offset + record.position().map_or(0, |p| p.byte());
+        }
+        row += 1;
+    };
+    let end = match reader.read_byte_record(&mut record) {
+        Ok(true) => base_offset + record.position().map_or(0, |p| p.byte()),
+        _ => file_len,
+    };
+
+    // After a CRLF the reader places the next record on its LF; give the LF back to the
+    // record it ends.
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut read_at = |offset: u64| -> Result<Option<u8>, String> {
+        let mut byte = [0u8];
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| e.to_string())?;
+        Ok(file.read_exact(&mut byte).ok().map(|_| byte[0]))
+    };
+    let start = if read_at(start)? == Some(b'\n') && start > 0 {
+        start + 1
+    } else {
+        start
+    };
+    let end = if end > start && read_at(end - 1)? == Some(b'\r') && read_at(end)? == Some(b'\n') {
+        end + 1
+    } else {
+        end
+    };
+    let length = end.saturating_sub(start);
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(start))
+        .map_err(|e| e.to_string())?;
+    (&mut file)
+        .take(length.min(RAW_RECORD_LIMIT))
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    Ok(RawRecord {
+        row: row_index,
+        byte: start,
+        length,
+        truncated: length > RAW_RECORD_LIMIT,
+        text: String::from_utf8_lossy(&bytes).into_owned(),
+        escaped: escape_raw_bytes(&bytes),
+    })
+}
 
-    // Re-open for actual CSV read to avoid consuming the sample handle.
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .from_reader(File::open(&path_buf).map_err(|e| e.to_string())?);
+/// Load the first chunk of a CSV for preview, using a detected or provided delimiter.
+#[tauri::command]
+fn preview_csv(path: UserPath, dialect: Option<CsvDialect>) -> Result<CsvPreview, String> {
+    // Detect the delimiter from a sample if not provided.
+    let dialect = dialect.unwrap_or_default();
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+    let mut reader = dialect.reader(&path)?;
 
     let headers = reader
         .headers()
@@ -646,767 +1883,14502 @@ fn preview_csv(path: String, delimiter: Option<String>) -> Result<CsvPreview, St
         rows.push(record.iter().map(|s| s.to_string()).collect());
     }
 
-    let delimiter_str = match delimiter_byte {
-        b'\t' => "\\t".to_string(),
-        other => String::from_utf8_lossy(&[other]).to_string(),
-    };
-
     Ok(CsvPreview {
         headers,
         rows,
-        delimiter: delimiter_str,
-        path,
+        delimiter: dialect.delimiter.unwrap_or_default(),
+        path: path.to_string(),
     })
 }
 
+/// Encodings [`preview_with_encodings`] tries when none are given.
+const ENCODING_CANDIDATES: [&str; 5] = [
+    "UTF-8",
+    "UTF-16LE",
+    "UTF-16BE",
+    "windows-1252",
+    "ISO-8859-1",
+];
+
+/// Decode `bytes` as `encoding`, dropping a leading byte order mark and replacing what does
+/// not decode with U+FFFD.
+fn decode_sample(bytes: &[u8], encoding: &str) -> Result<String, String> {
+    decode_bytes(strip_bom(bytes, encoding), encoding)
+}
+
+fn strip_bom<'a>(bytes: &'a [u8], encoding: &str) -> &'a [u8] {
+    let bom: &[u8] = match encoding.to_ascii_uppercase().as_str() {
+        "UTF-8" | "UTF8" => &[0xEF, 0xBB, 0xBF],
+        "UTF-16LE" => &[0xFF, 0xFE],
+        "UTF-16BE" => &[0xFE, 0xFF],
+        _ => &[],
+    };
+    bytes.strip_prefix(bom).unwrap_or(bytes)
+}
+
+fn decode_bytes(bytes: &[u8], encoding: &str) -> Result<String, String> {
+    let utf16 = |bytes: &[u8], unit: fn([u8; 2]) -> u16| {
+        let units = bytes.chunks(2).map(|pair| match pair {
+            [a, b] => unit([*a, *b]),
+            _ => 0xFFFD,
+        });
+        char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>()
+    };
+    Ok(match encoding.to_ascii_uppercase().as_str() {
+        "UTF-8" | "UTF8" => String::from_utf8_lossy(bytes).into_owned(),
+        "UTF-16LE" => utf16(bytes, u16::from_le_bytes),
+        "UTF-16BE" => utf16(bytes, u16::from_be_bytes),
+        "WINDOWS-1252" | "CP1252" => bytes
+            .iter()
+            .map(|&b| match b {
+                0x80..=0x9F => CP1252_HIGH[usize::from(b - 0x80)],
+                _ => char::from(b),
+            })
+            .collect(),
+        "ISO-8859-1" | "LATIN1" => bytes.iter().map(|&b| char::from(b)).collect(),
+        _ => return Err(format!("unsupported encoding: {encoding}")),
+    })
+}
+
+/// Stream `source` decoded as `encoding` into `out` as UTF-8, without the byte order mark.
+/// UTF-16 code units and surrogate pairs split across reads are carried to the next one.
+fn transcode_to_utf8(
+    source: &mut impl Read,
+    encoding: &str,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let upper = encoding.to_ascii_uppercase();
+    let wide = match upper.as_str() {
+        "UTF-16LE" => Some(u16::from_le_bytes as fn([u8; 2]) -> u16),
+        "UTF-16BE" => Some(u16::from_be_bytes as fn([u8; 2]) -> u16),
+        _ => None,
+    };
+    let utf8 = matches!(upper.as_str(), "UTF-8" | "UTF8");
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut chunk: Vec<u8> = Vec::new();
+    let mut first = true;
+    loop {
+        let read = source.read(&mut buffer).map_err(|e| e.to_string())?;
+        chunk.extend_from_slice(&buffer[..read]);
+        let mut end = chunk.len();
+        if let (Some(unit), true) = (wide, read > 0) {
+            end -= end % 2;
+            if end >= 2 && (0xD800..0xDC00).contains(&unit([chunk[end - 2], chunk[end - 1]])) {
+                end -= 2;
+            }
+        }
+        let carry = chunk.split_off(end);
+        let bytes = if first {
+            strip_bom(&chunk, encoding)
+        } else {
+            &chunk[..]
+        };
+        if utf8 {
+            out.write_all(bytes)
+        } else {
+            out.write_all(decode_bytes(bytes, encoding)?.as_bytes())
+        }
+        .map_err(|e| e.to_string())?;
+        first = false;
+        chunk = carry;
+        if read == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// The file to read `path` from and the dialect to read it with, delimiter resolved: like a
+/// session, a file in another encoding or without a header row is read from `import_copy`.
+fn readable_source(path: &str, dialect: &CsvDialect) -> Result<(String, CsvDialect), String> {
+    if dialect.foreign_encoding().is_some() || dialect.has_header == Some(false) {
+        return import_copy(path, dialect);
+    }
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(path)?);
+    Ok((path.to_string(), dialect))
+}
+
+/// A UTF-8 copy of `path` with a header row, in the temp dir, for opening a file in another
+/// encoding or without a header: sessions, indexes and saves all read UTF-8 with the header
+/// first. Returns the copy and the dialect to read it with.
+fn import_copy(path: &str, dialect: &CsvDialect) -> Result<(String, CsvDialect), String> {
+    let decoded = temp_path("import", "csv")?;
+    let mut output = std::io::BufWriter::new(File::create(&decoded).map_err(|e| e.to_string())?);
+    let mut source = File::open(path).map_err(|e| e.to_string())?;
+    transcode_to_utf8(
+        &mut source,
+        dialect.foreign_encoding().unwrap_or("UTF-8"),
+        &mut output,
+    )?;
+    output.flush().map_err(|e| e.to_string())?;
+    drop(output);
+    let decoded = decoded.to_string_lossy().to_string();
+    let copy_dialect = CsvDialect {
+        encoding: None,
+        bom: None,
+        has_header: None,
+        ..dialect.clone()
+    };
+    let copy_dialect = copy_dialect.with_delimiter(copy_dialect.resolve_delimiter(&decoded)?);
+    if dialect.has_header != Some(false) {
+        return Ok((decoded, copy_dialect));
+    }
+
+    let width = copy_dialect
+        .reader_builder()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(&decoded)
+        .map_err(|e| e.to_string())?
+        .records()
+        .next()
+        .transpose()
+        .map_err(|e| e.to_string())?
+        .map_or(0, |record| record.len());
+    let named = temp_path("import", "csv")?;
+    let mut output = std::io::BufWriter::new(File::create(&named).map_err(|e| e.to_string())?);
+    let eol = if ends_lines_with_crlf(&decoded)? {
+        "CRLF"
+    } else {
+        "LF"
+    };
+    let mut header = CsvDialect {
+        eol: Some(eol.to_string()),
+        ..copy_dialect.clone()
+    }
+    .writer_to(&mut output);
+    header
+        .write_record((1..=width).map(|n| format!("column_{}", n)))
+        .map_err(|e| e.to_string())?;
+    header.flush().map_err(|e| e.to_string())?;
+    drop(header);
+    std::io::copy(
+        &mut File::open(&decoded).map_err(|e| e.to_string())?,
+        &mut output,
+    )
+    .map_err(|e| e.to_string())?;
+    output.flush().map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(&decoded);
+    Ok((named.to_string_lossy().to_string(), copy_dialect))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EncodingPreview {
+    pub encoding: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub delimiter: String,
+    /// U+FFFD characters in the decoded sample; zero for a plausible encoding.
+    pub replacements: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EncodingComparison {
+    pub path: String,
+    /// The encoding a byte order mark at the start of the file names, if any.
+    pub bom: Option<String>,
+    pub previews: Vec<EncodingPreview>,
+}
+
+/// Decode the start of the file with each of `encodings` (default: the common candidates)
+/// and parse it, so the user can pick the one whose rows read correctly.
+#[tauri::command]
+fn preview_with_encodings(
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+    encodings: Option<Vec<String>>,
+    limit: Option<usize>,
+) -> Result<EncodingComparison, String> {
+    let dialect = dialect.unwrap_or_default();
+    let limit = limit.unwrap_or(20);
+    let mut sample = Vec::new();
+    File::open(&path)
+        .map_err(|e| e.to_string())?
+        .take(64 * 1024)
+        .read_to_end(&mut sample)
+        .map_err(|e| e.to_string())?;
+    let truncated = sample.len() == 64 * 1024;
+    let bom = if sample.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some("UTF-8")
+    } else if sample.starts_with(&[0xFF, 0xFE]) {
+        Some("UTF-16LE")
+    } else if sample.starts_with(&[0xFE, 0xFF]) {
+        Some("UTF-16BE")
+    } else {
+        None
+    };
+    let encodings =
+        encodings.unwrap_or_else(|| ENCODING_CANDIDATES.iter().map(|e| e.to_string()).collect());
+
+    let previews = encodings
+        .into_iter()
+        .map(|encoding| {
+            let mut preview = EncodingPreview {
+                encoding,
+                headers: Vec::new(),
+                rows: Vec::new(),
+                delimiter: String::new(),
+                replacements: 0,
+                error: None,
+            };
+            let mut text = match decode_sample(&sample, &preview.encoding) {
+                Ok(text) => text,
+                Err(e) => {
+                    preview.error = Some(e);
+                    return preview;
+                }
+            };
+            // Drop the last line of a cut-off sample; it may end mid-character.
+            if truncated {
+                if let Some(end) = text.rfind('\n') {
+                    text.truncate(end + 1);
+                }
+            }
+            preview.replacements = text.matches(char::REPLACEMENT_CHARACTER).count();
+            let delimiter = match dialect.delimiter.as_deref() {
+                Some(value) => parse_delimiter(value),
+                None => detect_delimiter(&text),
+            };
+            preview.delimiter = delimiter_label(delimiter);
+            let mut reader = dialect
+                .with_delimiter(delimiter)
+                .reader_builder()
+                .flexible(true)
+                .from_reader(text.as_bytes());
+            let parsed = reader
+                .headers()
+                .map(|h| h.iter().map(|s| s.to_string()).collect())
+                .and_then(|headers| {
+                    preview.headers = headers;
+                    reader
+                        .records()
+                        .take(limit)
+                        .map(|rec| rec.map(|r| r.iter().map(|s| s.to_string()).collect()))
+                        .collect::<Result<Vec<_>, _>>()
+                });
+            match parsed {
+                Ok(rows) => preview.rows = rows,
+                Err(e) => preview.error = Some(e.to_string()),
+            }
+            preview
+        })
+        .collect();
+
+    Ok(EncodingComparison {
+        path: path.to_string(),
+        bom: bom.map(str::to_string),
+        previews,
+    })
+}
+
+/// Records of the 64 KiB sample [`analyze_file_for_import`] looks at.
+const IMPORT_SAMPLE_ROWS: usize = 1000;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImportChoice {
+    pub value: String,
+    /// How plausible the value is, 0 to 1; a heuristic, not a probability.
+    pub confidence: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImportHeader {
+    pub has_header: bool,
+    pub confidence: f64,
+    /// Column names to open with: the first row, or `column_1`, ... without a header.
+    pub names: Vec<String>,
+    pub duplicates: Vec<DuplicateHeader>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImportColumn {
+    pub name: String,
+    /// `integer`, `number`, `boolean`, `date`, `datetime` or `string`, as
+    /// `export_with_types` would infer it from the sample.
+    pub kind: ImportChoice,
+    /// Types at least half the column's non-empty sampled values parse as.
+    pub alternatives: Vec<ImportChoice>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImportProposal {
+    pub path: String,
+    pub file_len: u64,
+    pub encoding: ImportChoice,
+    pub encoding_alternatives: Vec<ImportChoice>,
+    pub delimiter: ImportChoice,
+    pub delimiter_alternatives: Vec<ImportChoice>,
+    pub header: ImportHeader,
+    pub columns: Vec<ImportColumn>,
+    /// The first sampled data rows, parsed as proposed.
+    pub rows: Vec<Vec<String>>,
+    pub sampled_rows: usize,
+    /// Ready to pass to `open_csv_session`, which opens a read-only UTF-8 copy when the
+    /// encoding is not UTF-8 or the file has no header row.
+    pub dialect: CsvDialect,
+}
+
+/// How plausible `text` is as CSV decoded with the right encoding: undecodable bytes,
+/// control characters and UTF-8 misread as a single-byte encoding count against it, and
+/// text with no line break or delimiter at all is almost certainly misdecoded.
+fn decoding_score(text: &str) -> f64 {
+    let mut bad = text
+        .chars()
+        .filter(|c| *c == char::REPLACEMENT_CHARACTER || (c.is_control() && !"\t\r\n".contains(*c)))
+        .count();
+    if let Some(repaired) = repair_mojibake(text) {
+        bad += text.chars().count() - repaired.chars().count();
+    }
+    if !text.contains(['\n', ',', ';', '\t', '|']) {
+        bad += 10;
+    }
+    1.0 / (1.0 + bad as f64)
+}
+
+/// Pick the best scored `(value, score, key)`, earliest on ties. The alternatives leave
+/// out candidates sharing its key, which are equivalent for this sample.
+fn rank_import_choices<K: PartialEq>(
+    candidates: Vec<(String, f64, K)>,
+) -> Option<(ImportChoice, Vec<ImportChoice>)> {
+    let best = (0..candidates.len()).reduce(|best, i| {
+        if candidates[i].1 > candidates[best].1 {
+            i
+        } else {
+            best
+        }
+    })?;
+    let mut alternatives = Vec::new();
+    let mut choice = None;
+    for (i, (value, confidence, key)) in candidates.iter().enumerate() {
+        if i == best {
+            choice = Some(ImportChoice {
+                value: value.clone(),
+                confidence: *confidence,
+            });
+        } else if *key != candidates[best].2 {
+            alternatives.push(ImportChoice {
+                value: value.clone(),
+                confidence: *confidence,
+            });
+        }
+    }
+    alternatives.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    Some((choice?, alternatives))
+}
+
+/// Analyze the start of a file for the import wizard: encoding, delimiter, whether the
+/// first row is a header, and column types, each with a confidence and the alternatives.
+/// Options set in `dialect` are taken as given.
+#[tauri::command]
+fn analyze_file_for_import(
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+) -> Result<ImportProposal, String> {
+    let file_len = fs::metadata(&path).map_err(|e| e.to_string())?.len();
+    let mut sample = Vec::new();
+    File::open(&path)
+        .map_err(|e| e.to_string())?
+        .take(64 * 1024)
+        .read_to_end(&mut sample)
+        .map_err(|e| e.to_string())?;
+    let truncated = sample.len() == 64 * 1024;
+    let mut proposal = propose_import(&sample, truncated, dialect.unwrap_or_default())?;
+    proposal.path = path.to_string();
+    proposal.file_len = file_len;
+    Ok(proposal)
+}
+
+/// [`analyze_file_for_import`] on the first bytes of a file, `truncated` when there are
+/// more; `path` and `file_len` are left empty.
+fn propose_import(
+    sample: &[u8],
+    truncated: bool,
+    given: CsvDialect,
+) -> Result<ImportProposal, String> {
+    let bom = [
+        ("UTF-8", &[0xEF, 0xBB, 0xBF][..]),
+        ("UTF-16LE", &[0xFF, 0xFE][..]),
+        ("UTF-16BE", &[0xFE, 0xFF][..]),
+    ]
+    .into_iter()
+    .find(|(_, mark)| sample.starts_with(mark))
+    .map(|(encoding, _)| encoding);
+
+    let encodings: Vec<String> = match (given.encoding.clone(), bom) {
+        (Some(encoding), _) => vec![encoding],
+        (None, Some(encoding)) => vec![encoding.to_string()],
+        (None, None) => ENCODING_CANDIDATES.iter().map(|e| e.to_string()).collect(),
+    };
+    let mut decoded = Vec::new();
+    for encoding in encodings {
+        let mut text = decode_sample(sample, &encoding)?;
+        // Drop the last line of a cut-off sample; it may end mid-character or mid-record.
+        if truncated {
+            if let Some(end) = text.rfind('\n') {
+                text.truncate(end + 1);
+            }
+        }
+        let score = if given.encoding.is_some() || bom.is_some() {
+            1.0
+        } else {
+            decoding_score(&text)
+        };
+        decoded.push((encoding, score, text));
+    }
+    let (encoding, encoding_alternatives) =
+        rank_import_choices(decoded.clone()).ok_or("no encoding to try")?;
+    let text = decoded
+        .into_iter()
+        .find(|(name, _, _)| *name == encoding.value)
+        .map(|(_, _, text)| text)
+        .unwrap_or_default();
+
+    // A delimiter scores the share of records with the most common field count, and
+    // nothing when that count is one.
+    let parse = |delimiter: u8| {
+        given
+            .with_delimiter(delimiter)
+            .reader_builder()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes())
+            .records()
+            .take(IMPORT_SAMPLE_ROWS)
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>()
+    };
+    let delimiters: Vec<u8> = match given.delimiter.as_deref() {
+        Some(value) => vec![parse_delimiter(value)],
+        None => vec![b',', b';', b'\t', b'|'],
+    };
+    let scored = delimiters
+        .iter()
+        .map(|&delimiter| {
+            let records = parse(delimiter);
+            let mut widths: HashMap<usize, usize> = HashMap::new();
+            for record in &records {
+                *widths.entry(record.len()).or_default() += 1;
+            }
+            let (width, count) = widths
+                .into_iter()
+                .max_by_key(|&(width, count)| (count, width))
+                .unwrap_or((0, 0));
+            let score = if given.delimiter.is_some() {
+                1.0
+            } else if width > 1 {
+                count as f64 / records.len() as f64
+            } else {
+                0.0
+            };
+            (delimiter_label(delimiter), score, delimiter)
+        })
+        .collect();
+    let (mut delimiter, delimiter_alternatives) =
+        rank_import_choices(scored).ok_or("no delimiter to try")?;
+    if delimiter.confidence == 0.0 && given.delimiter.is_none() {
+        delimiter.value = delimiter_label(detect_delimiter(&text));
+    }
+    let records = parse(parse_delimiter(&delimiter.value));
+    let width = records.iter().map(|record| record.len()).max().unwrap_or(0);
+
+    // The first row is a header when its cells don't parse as the types the rows below
+    // them have; columns that are all text don't tell either way.
+    let data = records.get(1..).unwrap_or_default();
+    let tally = |rows: &[csv::StringRecord], col: usize| {
+        let mut counts = [0usize; 5];
+        let mut non_empty = 0;
+        for value in rows.iter().filter_map(|record| record.get(col)) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            non_empty += 1;
+            for (kind, count) in counts.iter_mut().enumerate() {
+                if value_fits_type(kind, value) {
+                    *count += 1;
+                }
+            }
+        }
+        (counts, non_empty)
+    };
+    let (mut votes, mut typed) = (0i64, 0usize);
+    for col in 0..width {
+        let (counts, non_empty) = tally(data, col);
+        let Some(kind) = (0..5).find(|kind| non_empty > 0 && counts[*kind] == non_empty) else {
+            continue;
+        };
+        let first = records[0].get(col).unwrap_or("").trim();
+        if first.is_empty() {
+            continue;
+        }
+        typed += 1;
+        votes += if value_fits_type(kind, first) { -1 } else { 1 };
+    }
+    let has_header = votes >= 0;
+    let header_confidence = if typed == 0 {
+        0.5
+    } else {
+        votes.unsigned_abs() as f64 / typed as f64
+    };
+    let names: Vec<String> = (0..width)
+        .map(
+            |col| match records.first().and_then(|first| first.get(col)) {
+                Some(name) if has_header => name.to_string(),
+                _ => format!("column_{}", col + 1),
+            },
+        )
+        .collect();
+    let rows = if has_header { data } else { &records[..] };
+
+    let columns = names
+        .iter()
+        .enumerate()
+        .map(|(col, name)| {
+            let (counts, non_empty) = tally(rows, col);
+            let fitting = (0..5).find(|kind| non_empty > 0 && counts[*kind] == non_empty);
+            let share = |kind: usize| counts[kind] as f64 / non_empty.max(1) as f64;
+            // More agreeing values make a type firmer; a text column is only as firm as
+            // its values are unlike any type.
+            let kind = match fitting {
+                Some(kind) => ImportChoice {
+                    value: INFERRED_TYPES[kind].to_string(),
+                    confidence: 1.0 - 1.0 / (1.0 + non_empty as f64),
+                },
+                None => ImportChoice {
+                    value: "string".to_string(),
+                    confidence: 1.0 - (0..5).map(share).fold(0.0, f64::max),
+                },
+            };
+            let mut alternatives: Vec<ImportChoice> = (0..5)
+                .filter(|kind| Some(*kind) != fitting && non_empty > 0 && share(*kind) >= 0.5)
+                .map(|kind| ImportChoice {
+                    value: INFERRED_TYPES[kind].to_string(),
+                    confidence: share(kind),
+                })
+                .collect();
+            alternatives.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+            ImportColumn {
+                name: name.clone(),
+                kind,
+                alternatives,
+            }
+        })
+        .collect();
+
+    let dialect = CsvDialect {
+        delimiter: Some(delimiter.value.clone()),
+        encoding: Some(encoding.value.clone()),
+        bom: Some(bom.is_some()),
+        eol: Some(if text.contains("\r\n") { "CRLF" } else { "LF" }.to_string()),
+        has_header: (!has_header).then_some(false),
+        ..given
+    };
+    Ok(ImportProposal {
+        path: String::new(),
+        file_len: 0,
+        encoding,
+        encoding_alternatives,
+        delimiter,
+        delimiter_alternatives,
+        header: ImportHeader {
+            has_header,
+            confidence: header_confidence,
+            duplicates: find_duplicate_headers(&names),
+            names,
+        },
+        columns,
+        rows: rows
+            .iter()
+            .take(20)
+            .map(|record| record.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        sampled_rows: rows.len(),
+        dialect,
+    })
+}
+
+/// Line breaks tried after a seek before giving up on finding a record boundary.
+const PREVIEW_SYNC_ATTEMPTS: usize = 16;
+
+/// Bytes before the end of the file read for the tail of a sampled preview.
+const PREVIEW_TAIL_WINDOW: u64 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+pub struct PreviewSection {
+    /// `head`, `middle` or `tail`.
+    pub part: String,
+    /// Byte offset of the section's first row.
+    pub byte: u64,
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SampledPreview {
+    pub headers: Vec<String>,
+    pub delimiter: String,
+    pub path: String,
+    pub file_len: u64,
+    /// Just `head` when the whole file fits in the preview.
+    pub sections: Vec<PreviewSection>,
+}
+
+/// The first record boundary after byte `offset`. A seek can land inside a quoted field,
+/// so a line break only counts as a boundary when the next few records have `width`
+/// fields. `None` at end of file.
+fn record_boundary_after(
+    path: &str,
+    dialect: &CsvDialect,
+    offset: u64,
+    width: usize,
+) -> Result<Option<u64>, String> {
+    let mut candidate = offset;
+    for _ in 0..PREVIEW_SYNC_ATTEMPTS {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start(candidate))
+            .map_err(|e| e.to_string())?;
+        let mut input = BufReader::new(file);
+        let skipped = std::io::BufRead::read_until(&mut input, b'\n', &mut Vec::new())
+            .map_err(|e| e.to_string())?;
+        if skipped == 0 {
+            return Ok(None);
+        }
+        let start = candidate + skipped as u64;
+        let aligned = dialect
+            .reader_builder()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(input)
+            .records()
+            .take(3)
+            .all(|record| record.is_ok_and(|record| record.len() == width));
+        if aligned {
+            return Ok(Some(start));
+        }
+        candidate = start;
+    }
+    Ok(None)
+}
+
+/// Preview rows from the start, the middle and the end of the file, found by seeking, so
+/// a tail that differs from the head shows up. `limit` (default 200) is split between the
+/// three parts.
+#[tauri::command]
+fn preview_csv_sampled(
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+    limit: Option<usize>,
+) -> Result<SampledPreview, String> {
+    let dialect = dialect.unwrap_or_default();
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+    let file_len = fs::metadata(&path).map_err(|e| e.to_string())?.len();
+    let per_part = (limit.unwrap_or(200) / 3).max(1);
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    // Read one row past the preview to tell whether the file is longer than it.
+    let mut head = Vec::new();
+    let mut head_byte = file_len;
+    let mut covered = 0;
+    let mut record = csv::StringRecord::new();
+    while head.len() <= per_part * 3
+        && reader.read_record(&mut record).map_err(|e| e.to_string())?
+    {
+        if let Some(position) = record.position().filter(|_| head.is_empty()) {
+            head_byte = source_positions(&path, std::slice::from_ref(position), (0, 1))?[0].byte;
+        }
+        head.push(record.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        if head.len() == per_part {
+            covered = reader.position().byte();
+        }
+    }
+    let whole = head.len() <= per_part * 3;
+    if !whole {
+        head.truncate(per_part);
+    }
+    let mut sections = vec![PreviewSection {
+        part: "head".to_string(),
+        byte: head_byte,
+        rows: head,
+    }];
+    if whole {
+        return Ok(SampledPreview {
+            headers,
+            delimiter: dialect.delimiter.unwrap_or_default(),
+            path: path.to_string(),
+            file_len,
+            sections,
+        });
+    }
+
+    if let Some(byte) =
+        record_boundary_after(&path, &dialect, (file_len / 2).max(covered), headers.len())?
+    {
+        let mut reader = dialect.reader_at(&path, byte)?;
+        let mut rows = Vec::new();
+        while rows.len() < per_part && reader.read_record(&mut record).map_err(|e| e.to_string())? {
+            rows.push(record.iter().map(|s| s.to_string()).collect());
+        }
+        covered = byte + reader.position().byte();
+        sections.push(PreviewSection {
+            part: "middle".to_string(),
+            byte,
+            rows,
+        });
+    }
+
+    let tail_offset = file_len.saturating_sub(PREVIEW_TAIL_WINDOW).max(covered);
+    if let Some(byte) = record_boundary_after(&path, &dialect, tail_offset, headers.len())? {
+        let mut reader = dialect.reader_at(&path, byte)?;
+        let mut rows = std::collections::VecDeque::with_capacity(per_part + 1);
+        while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+            let position = record
+                .position()
+                .cloned()
+                .unwrap_or_else(csv::Position::new);
+            rows.push_back((position, record.iter().map(|s| s.to_string()).collect()));
+            if rows.len() > per_part {
+                rows.pop_front();
+            }
+        }
+        if let Some((first, _)) = rows.front() {
+            // Positions after a seek are relative to the boundary.
+            sections.push(PreviewSection {
+                part: "tail".to_string(),
+                byte: source_positions(&path, std::slice::from_ref(first), (byte, 1))?[0].byte,
+                rows: rows.into_iter().map(|(_, row)| row).collect(),
+            });
+        }
+    }
+
+    Ok(SampledPreview {
+        headers,
+        delimiter: dialect.delimiter.unwrap_or_default(),
+        path: path.to_string(),
+        file_len,
+        sections,
+    })
+}
+
+/// Suffix the second and later copies of a repeated name with `<separator>2`, `3`, ...,
+/// skipping suffixes that collide with another name. The first copy keeps its name.
+fn dedupe_names(names: &mut [String], separator: &str) {
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut repeated: HashSet<String> = HashSet::new();
+    for name in names.iter() {
+        if !taken.insert(name.clone()) {
+            repeated.insert(name.clone());
+        }
+    }
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for name in names.iter_mut() {
+        if !repeated.contains(name) {
+            continue;
+        }
+        let count = counts.entry(name.clone()).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            continue;
+        }
+        let mut suffix = *count;
+        while taken.contains(&format!("{}{}{}", name, separator, suffix)) {
+            suffix += 1;
+        }
+        *count = suffix;
+        *name = format!("{}{}{}", name, separator, suffix);
+        taken.insert(name.clone());
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DuplicateHeader {
+    pub name: String,
+    pub columns: Vec<usize>,
+}
+
+fn find_duplicate_headers(headers: &[String]) -> Vec<DuplicateHeader> {
+    let mut duplicates: Vec<DuplicateHeader> = Vec::new();
+    for (col, name) in headers.iter().enumerate() {
+        match duplicates.iter_mut().find(|dup| &dup.name == name) {
+            Some(dup) => dup.columns.push(col),
+            None => duplicates.push(DuplicateHeader {
+                name: name.clone(),
+                columns: vec![col],
+            }),
+        }
+    }
+    duplicates.retain(|dup| dup.columns.len() > 1);
+    duplicates
+}
+
+const PATH_GRANTS_FILE: &str = "path_grants.json";
+const PATH_GRANTS_LIMIT: usize = 500;
+
+/// The fs scope the dialog plugin adds picked files and folders to. Commands only touch
+/// paths inside it.
+static PATH_SCOPE: OnceLock<tauri::scope::fs::Scope> = OnceLock::new();
+
+/// Paths the user has handed to the app, restored into the scope on startup so recent files
+/// keep opening. `folders` came from folder pickers and cover their direct children;
+/// `directories` were granted with `grant_directory` and cover everything below them.
+#[derive(Serialize, Deserialize, Default)]
+pub struct PathGrants {
+    #[serde(default)]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub folders: Vec<String>,
+    #[serde(default)]
+    pub directories: Vec<String>,
+}
+
+fn load_path_grants(app: &tauri::AppHandle) -> PathGrants {
+    app_config_file(app, PATH_GRANTS_FILE)
+        .and_then(|path| read_json_config(&path))
+        .unwrap_or_default()
+}
+
+/// Record a path the scope was extended with, most recent last.
+fn remember_path_grant(app: &tauri::AppHandle, path: &std::path::Path) {
+    let mut grants = load_path_grants(app);
+    let path = path.to_string_lossy().to_string();
+    if grants.directories.contains(&path) {
+        return;
+    }
+    let list = if std::path::Path::new(&path).is_dir() {
+        &mut grants.folders
+    } else {
+        &mut grants.files
+    };
+    list.retain(|existing| existing != &path);
+    list.push(path);
+    if list.len() > PATH_GRANTS_LIMIT {
+        let excess = list.len() - PATH_GRANTS_LIMIT;
+        list.drain(..excess);
+    }
+    if let Err(err) = app_config_file(app, PATH_GRANTS_FILE)
+        .and_then(|config| write_json_config(&config, &grants))
+    {
+        tracing::warn!(error = %err, "could not save path grants");
+    }
+}
+
+/// Restore saved grants, allow the temp dir (stream captures), and start recording what the
+/// dialogs allow from here on.
+fn init_path_grants(app: &tauri::AppHandle) {
+    use tauri_plugin_fs::FsExt;
+    let scope = app.fs_scope();
+    let grants = load_path_grants(app);
+    for file in &grants.files {
+        let _ = scope.allow_file(file);
+    }
+    for folder in &grants.folders {
+        let _ = scope.allow_directory(folder, false);
+    }
+    for directory in &grants.directories {
+        let _ = scope.allow_directory(directory, true);
+    }
+    let _ = scope.allow_directory(temp_dir(), false);
+    let handle = app.clone();
+    scope.listen(move |event| {
+        if let tauri::scope::fs::Event::PathAllowed(path) = event {
+            remember_path_grant(&handle, path);
+        }
+    });
+    let _ = PATH_SCOPE.set(scope);
+}
+
+/// Let the webview use a file the OS or the command line handed over.
+fn grant_file(path: &str) {
+    if let Some(scope) = PATH_SCOPE.get() {
+        let _ = scope.allow_file(path);
+    }
+}
+
+/// Reject a path from the webview unless the user picked it (or its folder) in a dialog,
+/// granted a directory containing it, or the OS handed it over. Command arguments are
+/// checked through [`UserPath`]; paths read from projects, jobs and rules are checked with
+/// this directly.
+fn authorize_path(path: &str) -> Result<(), String> {
+    let escapes = std::path::Path::new(path)
+        .components()
+        .any(|component| matches!(component, std::path::Component::ParentDir));
+    let allowed = !escapes && PATH_SCOPE.get().is_some_and(|scope| scope.is_allowed(path));
+    if allowed {
+        Ok(())
+    } else {
+        Err(structured_error(
+            "path_not_authorized",
+            &format!(
+                "{} was not chosen in a file dialog or inside a granted folder",
+                path
+            ),
+            serde_json::json!({ "path": path }),
+        ))
+    }
+}
+
+/// A path argument from the webview or the HTTP API. Deserializing one runs
+/// [`authorize_path`], so every command taking a `UserPath` is checked before its body runs
+/// and cannot forget to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
+pub struct UserPath(String);
+
+impl UserPath {
+    fn checked(path: String) -> Result<Self, String> {
+        authorize_path(&path)?;
+        Ok(Self(path))
+    }
+
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for UserPath {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::checked(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+impl std::ops::Deref for UserPath {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for UserPath {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<std::path::Path> for UserPath {
+    fn as_ref(&self) -> &std::path::Path {
+        self.0.as_ref()
+    }
+}
+
+impl AsRef<std::ffi::OsStr> for UserPath {
+    fn as_ref(&self) -> &std::ffi::OsStr {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq<String> for UserPath {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+impl PartialEq<UserPath> for String {
+    fn eq(&self, other: &UserPath) -> bool {
+        self == &other.0
+    }
+}
+
+impl std::fmt::Display for UserPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<UserPath> for String {
+    fn from(path: UserPath) -> Self {
+        path.0
+    }
+}
+
+/// Directories granted with `grant_directory`.
+#[tauri::command]
+fn list_granted_directories(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    Ok(load_path_grants(&app).directories)
+}
+
+/// Ask for a folder with the native picker and allow everything below it, for the HTTP API
+/// and projects whose outputs land next to their sources. Returns the granted directories.
+#[cfg(desktop)]
+#[tauri::command]
+async fn grant_directory(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let Some(folder) = app.dialog().file().blocking_pick_folder() else {
+        return Ok(load_path_grants(&app).directories);
+    };
+    let folder = folder.into_path().map_err(|e| e.to_string())?;
+    let mut grants = load_path_grants(&app);
+    let path = folder.to_string_lossy().to_string();
+    if !grants.directories.contains(&path) {
+        grants.directories.push(path);
+    }
+    write_json_config(&app_config_file(&app, PATH_GRANTS_FILE)?, &grants)?;
+    if let Some(scope) = PATH_SCOPE.get() {
+        scope
+            .allow_directory(&folder, true)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(grants.directories)
+}
+
+/// Drop a granted directory. It stays forbidden until the app restarts, even for files
+/// picked inside it.
+#[tauri::command]
+fn revoke_directory(app: tauri::AppHandle, path: String) -> Result<Vec<String>, String> {
+    let mut grants = load_path_grants(&app);
+    grants.directories.retain(|existing| existing != &path);
+    write_json_config(&app_config_file(&app, PATH_GRANTS_FILE)?, &grants)?;
+    if let Some(scope) = PATH_SCOPE.get() {
+        scope
+            .forbid_directory(&path, true)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(grants.directories)
+}
+
+/// Open a session owned by the calling window; it is closed when that window is.
+#[tauri::command]
+fn open_csv_session(
+    window: tauri::Window,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+    read_only: Option<bool>,
+    disambiguate_headers: Option<bool>,
+) -> Result<CsvSessionInfo, String> {
+    start_csv_session(
+        &state,
+        Some(window.label().to_string()),
+        path,
+        dialect,
+        read_only,
+        disambiguate_headers,
+    )
+}
+
+/// Open a session; `owner` is the label of the window it belongs to, if any.
+fn start_csv_session(
+    state: &AppState,
+    owner: Option<String>,
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+    read_only: Option<bool>,
+    disambiguate_headers: Option<bool>,
+) -> Result<CsvSessionInfo, String> {
+    let path_buf = PathBuf::from(&path);
+    let writability = path_writability(&path_buf);
+    let read_only_reason = if read_only.unwrap_or(false) {
+        Some("requested".to_string())
+    } else if !writability.writable {
+        writability.reason
+    } else {
+        None
+    };
+
+    let dialect = dialect.unwrap_or_default();
+    if dialect.foreign_encoding().is_some() || dialect.has_header == Some(false) {
+        let (copy, copy_dialect) = import_copy(&path, &dialect)?;
+        let mut info = start_csv_session(
+            state,
+            owner,
+            UserPath::checked(copy)?,
+            Some(copy_dialect),
+            Some(true),
+            disambiguate_headers,
+        )?;
+        info.read_only_reason = Some("imported".to_string());
+        return Ok(info);
+    }
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+    let mut reader = dialect.reader(&path)?;
+
+    let original_headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let duplicate_headers = find_duplicate_headers(&original_headers);
+    let mut headers = original_headers.clone();
+    if disambiguate_headers.unwrap_or(false) {
+        dedupe_names(&mut headers, "_");
+    }
+
+    let session_id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    sessions.insert(
+        session_id,
+        CsvSession {
+            reader,
+            row_index: 0,
+            eof: false,
+            path: path.to_string(),
+            dialect: dialect.clone(),
+            read_only: read_only_reason.is_some(),
+            changes: Default::default(),
+            owner,
+        },
+    );
+
+    Ok(CsvSessionInfo {
+        session_id,
+        headers,
+        original_headers,
+        duplicate_headers,
+        delimiter: dialect.delimiter.clone().unwrap_or_default(),
+        dialect,
+        path: path.to_string(),
+        read_only: read_only_reason.is_some(),
+        read_only_reason,
+    })
+}
+
+/// Error payload for failures the UI needs to branch on. Serialized as JSON inside the
+/// command's error string so existing `String(err)` handling keeps working.
+fn structured_error(code: &str, message: &str, details: serde_json::Value) -> String {
+    serde_json::json!({ "code": code, "message": message, "details": details }).to_string()
+}
+
+/// `data.csv` → `data (1).csv`, `data (2).csv`, ... : the first name next to `target` that
+/// is not taken.
+fn non_clashing_path(target: &str) -> String {
+    let path = std::path::Path::new(target);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let parent = path.parent().unwrap_or(std::path::Path::new(""));
+    (1..)
+        .map(|n| parent.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .map(|candidate| candidate.to_string_lossy().to_string())
+        .unwrap_or_else(|| target.to_string())
+}
+
+/// Pre-flight for commands that write a file: an existing `target` is only replaced with
+/// `overwrite: true`. Otherwise fail with `would_overwrite`, suggesting a free name.
+fn ensure_can_write(target: &str, overwrite: Option<bool>) -> Result<(), String> {
+    if overwrite.unwrap_or(false) || !std::path::Path::new(target).exists() {
+        return Ok(());
+    }
+    Err(structured_error(
+        "would_overwrite",
+        &format!("{} already exists", target),
+        serde_json::json!({ "path": target, "suggested_path": non_clashing_path(target) }),
+    ))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PathWritability {
+    pub path: String,
+    pub exists: bool,
+    pub writable: bool,
+    pub reason: Option<String>,
+}
+
+/// Probe whether a path can be written: permission bits first, then an actual open for
+/// writing (without truncation), which also catches read-only mounts.
+fn path_writability(path: &std::path::Path) -> PathWritability {
+    let display = path.to_string_lossy().to_string();
+    let Ok(metadata) = fs::metadata(path) else {
+        let parent_ok = path
+            .parent()
+            .map(|parent| parent.as_os_str().is_empty() || parent.is_dir())
+            .unwrap_or(false);
+        return PathWritability {
+            path: display,
+            exists: false,
+            writable: parent_ok,
+            reason: (!parent_ok).then(|| "parent directory does not exist".to_string()),
+        };
+    };
+    let reason = if metadata.permissions().readonly() {
+        Some("file is marked read-only".to_string())
+    } else {
+        File::options()
+            .write(true)
+            .open(path)
+            .err()
+            .map(|e| format!("not writable: {}", e))
+    };
+    PathWritability {
+        path: display,
+        exists: true,
+        writable: reason.is_none(),
+        reason,
+    }
+}
+
+#[tauri::command]
+fn check_path_writable(path: UserPath) -> Result<PathWritability, String> {
+    Ok(path_writability(std::path::Path::new(&path)))
+}
+
+#[tauri::command]
+fn set_session_read_only(
+    state: tauri::State<AppState>,
+    session_id: u64,
+    read_only: bool,
+) -> Result<bool, String> {
+    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    let session = sessions
+        .get_mut(&session_id)
+        .ok_or_else(|| "session not found".to_string())?;
+    session.read_only = read_only;
+    Ok(session.read_only)
+}
+
+/// Refuse to write over a file that an open session holds read-only.
+fn ensure_not_read_only(state: &AppState, target_path: &str) -> Result<(), String> {
+    let sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    if sessions
+        .values()
+        .any(|session| session.read_only && session.path == target_path)
+    {
+        return Err(structured_error(
+            "read_only",
+            "the file is open in read-only mode",
+            serde_json::json!({ "path": target_path }),
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn read_csv_rows(
+    state: tauri::State<AppState>,
+    session_id: u64,
+    limit: usize,
+) -> Result<CsvSlice, String> {
+    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    let session = sessions
+        .get_mut(&session_id)
+        .ok_or_else(|| "session not found".to_string())?;
+
+    if session.eof {
+        return Ok(CsvSlice {
+            rows: Vec::new(),
+            start: session.row_index,
+            end: session.row_index,
+            eof: true,
+            positions: Vec::new(),
+        });
+    }
+
+    let start = session.row_index;
+    let mut rows = Vec::new();
+    let mut positions = Vec::new();
+    for rec in session.reader.records() {
+        let record = rec.map_err(|e| e.to_string())?;
+        if let Some(position) = record.position() {
+            positions.push(position.clone());
+        }
+        rows.push(record.iter().map(|s| s.to_string()).collect());
+        session.row_index += 1;
+        if rows.len() >= limit {
+            break;
+        }
+    }
+
+    if rows.len() < limit {
+        session.eof = true;
+    }
+
+    let end = start + rows.len();
+    let positions = source_positions(&session.path, &positions, (0, 1))?;
+
+    Ok(CsvSlice {
+        rows,
+        start,
+        end,
+        eof: session.eof,
+        positions,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GotoRowResult {
+    pub row_index: usize,
+    /// Seeked with the row index rather than by reading records.
+    pub indexed: bool,
+    pub eof: bool,
+}
+
+/// Reposition a session so the next `read_csv_rows` starts at `row_index`. With a fresh
+/// row index this seeks to the nearest indexed row, so it's fast in either direction;
+/// otherwise it reads forward, starting over from the top for rows already passed.
+#[tauri::command]
+fn goto_row(
+    state: tauri::State<AppState>,
+    session_id: u64,
+    row_index: usize,
+) -> Result<GotoRowResult, String> {
+    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    let session = sessions
+        .get_mut(&session_id)
+        .ok_or_else(|| "session not found".to_string())?;
+    let index = fresh_index(&state.indexes, &session.path, &session.dialect)?;
+    let indexed = index.is_some();
+    match index {
+        Some(index) => {
+            // Index entries hold the reader's own positions, so the seeked reader reports
+            // the same positions a full scan would.
+            let (base_row, byte, line) = find_index_base(&index, row_index);
+            let mut position = csv::Position::new();
+            position
+                .set_byte(byte)
+                .set_line(line)
+                .set_record(base_row as u64 + 1);
+            session
+                .reader
+                .seek_raw(SeekFrom::Start(byte), position)
+                .map_err(|e| e.to_string())?;
+            session.row_index = base_row;
+        }
+        None if row_index < session.row_index => {
+            session.reader = session.dialect.reader(&session.path)?;
+            session.row_index = 0;
+        }
+        None => {}
+    }
+    session.eof = false;
+    let mut record = csv::StringRecord::new();
+    while session.row_index < row_index {
+        if !session
+            .reader
+            .read_record(&mut record)
+            .map_err(|e| e.to_string())?
+        {
+            session.eof = true;
+            break;
+        }
+        session.row_index += 1;
+    }
+    Ok(GotoRowResult {
+        row_index: session.row_index,
+        indexed,
+        eof: session.eof,
+    })
+}
+
+/// Up to `limit` rows just before `before_row`, for scrolling up past what a session has
+/// loaded. The session's own cursor doesn't move. Fast with a row index; without one the
+/// file is read from the top. `eof` is set once the slice reaches the first row.
+#[tauri::command]
+fn read_csv_rows_before(
+    state: tauri::State<AppState>,
+    session_id: u64,
+    before_row: usize,
+    limit: usize,
+) -> Result<CsvSlice, String> {
+    let (path, dialect) = {
+        let sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| "session not found".to_string())?;
+        (session.path.clone(), session.dialect.clone())
+    };
+    let start = before_row.saturating_sub(limit);
+    let mut slice = read_rows_window(
+        &state.indexes,
+        &path,
+        Some(dialect),
+        start,
+        before_row - start,
+    )?;
+    slice.eof = start == 0;
+    Ok(slice)
+}
+
+#[tauri::command]
+fn read_csv_rows_window(
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+    start: usize,
+    limit: usize,
+) -> Result<CsvSlice, String> {
+    read_rows_window(&state.indexes, &path, dialect, start, limit)
+}
+
+/// The index of `path` read with `dialect` if it still matches the file; a stale one is
+/// dropped.
+fn fresh_index(
+    indexes: &Mutex<HashMap<String, CsvIndex>>,
+    path: &str,
+    dialect: &CsvDialect,
+) -> Result<Option<CsvIndex>, String> {
+    let signature = file_signature(&PathBuf::from(path))?;
+    let key = index_key(path, dialect);
+    let mut indexes = indexes.lock().map_err(|_| "lock poisoned")?;
+    if let Some(candidate) = indexes.get(&key) {
+        if candidate.file_len == signature.0 && candidate.modified == signature.1 {
+            Ok(Some(candidate.clone()))
+        } else {
+            indexes.remove(&key);
+            Ok(None)
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// Data rows `rows` of `path`, read in one pass that starts at the fresh index entry
+/// nearest the first of them, or at the top.
+fn read_rows_at(
+    indexes: &Mutex<HashMap<String, CsvIndex>>,
+    path: &str,
+    dialect: &CsvDialect,
+    rows: &std::collections::BTreeSet<usize>,
+) -> Result<HashMap<usize, Vec<String>>, String> {
+    let mut found = HashMap::with_capacity(rows.len());
+    let (Some(&first), Some(&last)) = (rows.first(), rows.last()) else {
+        return Ok(found);
+    };
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(path)?);
+    let (mut reader, mut current) = match fresh_index(indexes, path, &dialect)? {
+        Some(index) => {
+            let (base_row, base_offset, _) = find_index_base(&index, first);
+            (dialect.reader_at(path, base_offset)?, base_row)
+        }
+        None => {
+            let mut reader = dialect.reader(path)?;
+            let _ = reader.headers().map_err(|e| e.to_string())?;
+            (reader, 0)
+        }
+    };
+    let mut record = csv::StringRecord::new();
+    while current <= last && reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        if rows.contains(&current) {
+            found.insert(current, record.iter().map(|s| s.to_string()).collect());
+        }
+        current += 1;
+    }
+    Ok(found)
+}
+
+/// Window read that seeks from the nearest fresh index entry, or scans from the top.
+fn read_rows_window(
+    indexes: &Mutex<HashMap<String, CsvIndex>>,
+    path: &str,
+    dialect: Option<CsvDialect>,
+    start: usize,
+    limit: usize,
+) -> Result<CsvSlice, String> {
+    let dialect = dialect.unwrap_or_default();
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(path)?);
+
+    if let Some(index) = fresh_index(indexes, path, &dialect)? {
+        let (base_row, base_offset, base_line) = find_index_base(&index, start);
+        let mut reader = dialect.reader_at(path, base_offset)?;
+
+        let mut record = csv::StringRecord::new();
+        let mut current = base_row;
+        while current < start {
+            if !reader.read_record(&mut record).map_err(|e| e.to_string())? {
+                break;
+            }
+            current += 1;
+        }
+
+        let mut rows = Vec::new();
+        let mut positions = Vec::new();
+        while rows.len() < limit {
+            if !reader.read_record(&mut record).map_err(|e| e.to_string())? {
+                break;
+            }
+            if let Some(position) = record.position() {
+                positions.push(position.clone());
+            }
+            rows.push(record.iter().map(|s| s.to_string()).collect());
+            current += 1;
+        }
+
+        let eof = rows.len() < limit;
+        let end = start + rows.len();
+        let positions = source_positions(path, &positions, (base_offset, base_line))?;
+
+        return Ok(CsvSlice {
+            rows,
+            start,
+            end,
+            eof,
+            positions,
+        });
+    }
+
+    let mut reader = dialect.reader(path)?;
+    let _ = reader.headers().map_err(|e| e.to_string())?;
+
+    let mut rows = Vec::new();
+    let mut positions = Vec::new();
+    let mut current = 0usize;
+    for rec in reader.records() {
+        let record = rec.map_err(|e| e.to_string())?;
+        if current >= start {
+            if let Some(position) = record.position() {
+                positions.push(position.clone());
+            }
+            rows.push(record.iter().map(|s| s.to_string()).collect());
+            if rows.len() >= limit {
+                break;
+            }
+        }
+        current += 1;
+    }
+
+    let eof = rows.len() < limit;
+    let end = start + rows.len();
+    let positions = source_positions(path, &positions, (0, 1))?;
+
+    Ok(CsvSlice {
+        rows,
+        start,
+        end,
+        eof,
+        positions,
+    })
+}
+
+/// Whether the file's first line ends in CRLF.
+fn ends_lines_with_crlf(path: &str) -> Result<bool, String> {
+    let mut head = Vec::new();
+    File::open(path)
+        .map_err(|e| e.to_string())?
+        .take(64 * 1024)
+        .read_to_end(&mut head)
+        .map_err(|e| e.to_string())?;
+    Ok(head
+        .iter()
+        .position(|b| *b == b'\n')
+        .is_some_and(|lf| lf > 0 && head[lf - 1] == b'\r'))
+}
+
+/// Record positions from a reader that started at `base` (byte, line) in `path`. `csv`
+/// places a record that follows a CRLF on its LF, a byte and a line early; each start is
+/// checked against the file, as one file can end its lines both ways.
+fn source_positions(
+    path: &str,
+    positions: &[csv::Position],
+    base: (u64, u64),
+) -> Result<Vec<RowPosition>, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    positions
+        .iter()
+        .map(|position| {
+            let byte = base.0 + position.byte();
+            let mut first = [0u8];
+            file.seek(SeekFrom::Start(byte))
+                .map_err(|e| e.to_string())?;
+            let skip = u64::from(file.read_exact(&mut first).is_ok() && first[0] == b'\n');
+            Ok(RowPosition {
+                byte: byte + skip,
+                line: base.1 + position.line() - 1 + skip,
+            })
+        })
+        .collect()
+}
+
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path, rows = tracing::field::Empty))]
+fn count_csv_rows(path: UserPath, dialect: Option<CsvDialect>) -> Result<usize, String> {
+    let dialect = dialect.unwrap_or_default();
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+    let mut reader = dialect.reader(&path)?;
+
+    let _ = reader.headers().map_err(|e| e.to_string())?;
+
+    let mut count = 0usize;
+    for rec in reader.records() {
+        rec.map_err(|e| e.to_string())?;
+        count += 1;
+    }
+
+    tracing::Span::current().record("rows", count);
+    Ok(count)
+}
+
+#[tauri::command]
+fn close_csv_session(state: tauri::State<AppState>, session_id: u64) -> Result<bool, String> {
+    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    Ok(sessions.remove(&session_id).is_some())
+}
+
+/// Add edits to a session's tracked changes. A change back to the original is a revert and
+/// dropped; the first recorded original for a cell is kept.
+fn record_cell_changes(
+    state: &AppState,
+    session_id: u64,
+    changes: Vec<CellChange>,
+) -> Result<usize, String> {
+    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    let session = sessions
+        .get_mut(&session_id)
+        .ok_or_else(|| "session not found".to_string())?;
+    for mut change in changes {
+        let key = (change.row, change.col);
+        if let Some(existing) = session.changes.get(&key) {
+            change.original = existing.original.clone();
+        }
+        if change.value == change.original {
+            session.changes.remove(&key);
+        } else {
+            session.changes.insert(key, change);
+        }
+    }
+    Ok(session.changes.len())
+}
+
+/// The file row behind output row `row`, or `None` for an inserted row.
+fn source_row(segments: &[RowSegment], mut row: usize) -> Option<usize> {
+    for segment in segments {
+        let len = match segment {
+            RowSegment::Input { start, end } => end - start,
+            RowSegment::Inserted(_) => 1,
+        };
+        if row < len {
+            return match segment {
+                RowSegment::Input { start, .. } => Some(start + row),
+                RowSegment::Inserted(_) => None,
+            };
+        }
+        row -= len;
+    }
+    None
+}
+
+/// Sync a session's tracked changes with the document's pending edits: `patches`, `row_ops`
+/// and `column_ops` as they will be passed to `save_csv_with_patches`. Patches are mapped
+/// back to file rows and columns and compared with the file itself, so cells of inserted
+/// rows or columns are not tracked and edits of deleted rows drop out. Returns the number
+/// of changed cells.
+#[tauri::command]
+fn track_cell_changes(
+    state: tauri::State<AppState>,
+    session_id: u64,
+    patches: Vec<CsvPatch>,
+    row_ops: Option<Vec<RowOp>>,
+    column_ops: Option<Vec<ColumnOp>>,
+) -> Result<usize, String> {
+    let (path, dialect, mut previous) = {
+        let sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| "session not found".to_string())?;
+        (
+            session.path.clone(),
+            session.dialect.clone(),
+            session.changes.clone(),
+        )
+    };
+    let segments = plan_row_segments(&row_ops.unwrap_or_default());
+    let mut columns: Vec<Option<usize>> = (0..read_csv_headers(&path, &dialect)?.len())
+        .map(Some)
+        .collect();
+    apply_column_ops_to_row(&mut columns, &column_ops.unwrap_or_default());
+
+    // By file cell; a later patch of the same cell wins.
+    let patched: std::collections::BTreeMap<(usize, usize), String> = patches
+        .into_iter()
+        .filter_map(|patch| {
+            let row = source_row(&segments, patch.row)?;
+            let col = columns.get(patch.col).copied().flatten()?;
+            Some(((row, col), patch.value))
+        })
+        .collect();
+    let unread = patched
+        .keys()
+        .filter(|cell| !previous.contains_key(cell))
+        .map(|(row, _)| *row)
+        .collect();
+    let originals = read_rows_at(&state.indexes, &path, &dialect, &unread)?;
+
+    let mut changes = std::collections::BTreeMap::new();
+    for ((row, col), value) in patched {
+        let change = match previous.remove(&(row, col)) {
+            Some(existing) => CellChange {
+                source: if existing.value == value {
+                    existing.source
+                } else {
+                    "patch".to_string()
+                },
+                value,
+                ..existing
+            },
+            None => CellChange {
+                row,
+                col,
+                original: originals
+                    .get(&row)
+                    .and_then(|record| record.get(col))
+                    .cloned()
+                    .unwrap_or_default(),
+                value,
+                source: "patch".to_string(),
+            },
+        };
+        if change.value != change.original {
+            changes.insert((row, col), change);
+        }
+    }
+    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    let session = sessions
+        .get_mut(&session_id)
+        .ok_or_else(|| "session not found".to_string())?;
+    session.changes = changes;
+    Ok(session.changes.len())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChangedCellsPage {
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+    pub changes: Vec<CellChange>,
+}
+
+/// Page through tracked changes in row/column order for review before saving.
+#[tauri::command]
+fn get_changed_cells(
+    state: tauri::State<AppState>,
+    session_id: u64,
+    page: usize,
+    page_size: Option<usize>,
+) -> Result<ChangedCellsPage, String> {
+    let page_size = page_size.unwrap_or(100).max(1);
+    let sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| "session not found".to_string())?;
+    Ok(ChangedCellsPage {
+        total: session.changes.len(),
+        page,
+        page_size,
+        changes: session
+            .changes
+            .values()
+            .skip(page.saturating_mul(page_size))
+            .take(page_size)
+            .cloned()
+            .collect(),
+    })
+}
+
+#[tauri::command]
+fn clear_cell_changes(state: tauri::State<AppState>, session_id: u64) -> Result<usize, String> {
+    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+    let session = sessions
+        .get_mut(&session_id)
+        .ok_or_else(|| "session not found".to_string())?;
+    let cleared = session.changes.len();
+    session.changes.clear();
+    Ok(cleared)
+}
+
+/// Split `segments` so that one starts at output row `row`; returns its index, or `None`
+/// when `row` is past the end.
+fn split_row_segments(segments: &mut Vec<RowSegment>, row: usize) -> Option<usize> {
+    let mut position = 0usize;
+    for idx in 0..segments.len() {
+        let len = match &segments[idx] {
+            RowSegment::Input { start, end } => end - start,
+            RowSegment::Inserted(_) => 1,
+        };
+        if row >= position.saturating_add(len) {
+            position = position.saturating_add(len);
+            continue;
+        }
+        let offset = row - position;
+        if offset == 0 {
+            return Some(idx);
+        }
+        if let RowSegment::Input { start, end } = segments[idx] {
+            segments[idx] = RowSegment::Input {
+                start,
+                end: start + offset,
+            };
+            segments.insert(
+                idx + 1,
+                RowSegment::Input {
+                    start: start + offset,
+                    end,
+                },
+            );
+        }
+        return Some(idx + 1);
+    }
+    None
+}
+
+/// Replay row ops, in order, into the output layout.
+fn plan_row_segments(ops: &[RowOp]) -> Vec<RowSegment> {
+    let mut segments = vec![RowSegment::Input {
+        start: 0,
+        end: usize::MAX,
+    }];
+    for op in ops {
+        match op {
+            RowOp::Insert { index, values } => {
+                if let Some(idx) = split_row_segments(&mut segments, *index) {
+                    segments.insert(idx, RowSegment::Inserted(values.clone()));
+                }
+            }
+            RowOp::Delete { index } => {
+                let Some(idx) = split_row_segments(&mut segments, *index) else {
+                    continue;
+                };
+                match segments[idx] {
+                    RowSegment::Input { start, end } if end - start > 1 => {
+                        segments[idx] = RowSegment::Input {
+                            start: start + 1,
+                            end,
+                        };
+                    }
+                    _ => {
+                        segments.remove(idx);
+                    }
+                }
+            }
+        }
+    }
+    segments
+}
+
+fn apply_column_ops_to_headers(headers: &mut Vec<String>, column_ops: &[ColumnOp]) {
+    for op in column_ops {
+        match op {
+            ColumnOp::Insert { index, name } => {
+                let idx = (*index).min(headers.len());
+                headers.insert(idx, name.clone());
+            }
+            ColumnOp::Delete { index } => {
+                if *index < headers.len() {
+                    headers.remove(*index);
+                }
+            }
+            ColumnOp::Rename { index, name } => {
+                if *index < headers.len() {
+                    headers[*index] = name.clone();
+                }
+            }
+        }
+    }
+}
+
+fn apply_column_ops_to_row<T: Default>(row: &mut Vec<T>, column_ops: &[ColumnOp]) {
+    for op in column_ops {
+        match op {
+            ColumnOp::Insert { index, .. } => {
+                let idx = (*index).min(row.len());
+                row.insert(idx, T::default());
+            }
+            ColumnOp::Delete { index } => {
+                if *index < row.len() {
+                    row.remove(*index);
+                }
+            }
+            ColumnOp::Rename { .. } => {}
+        }
+    }
+}
+
+/// Re-encode one record for the incremental save. Line breaks around the record are kept
+/// as they were; the reader leaves the `\n` of a CRLF at the start of the next record.
+fn encode_patched_record(
+    delimiter: u8,
+    record: &csv::ByteRecord,
+    raw: &[u8],
+    row_patches: &HashMap<usize, String>,
+) -> Result<Vec<u8>, String> {
+    let mut fields: Vec<&[u8]> = record.iter().collect();
+    for (col_idx, value) in row_patches {
+        if *col_idx >= fields.len() {
+            fields.resize(col_idx + 1, b"");
+        }
+        fields[*col_idx] = value.as_bytes();
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(true)
+        .delimiter(delimiter)
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+    writer.write_record(&fields).map_err(|e| e.to_string())?;
+    let record_bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    let leading = raw
+        .iter()
+        .take_while(|b| matches!(b, b'\r' | b'\n'))
+        .count();
+    let mut encoded = raw[..leading].to_vec();
+    encoded.extend_from_slice(&record_bytes[..record_bytes.len() - 1]);
+    if raw.ends_with(b"\r\n") {
+        encoded.extend_from_slice(b"\r\n");
+    } else if raw.ends_with(b"\n") || raw.ends_with(b"\r") {
+        encoded.push(raw[raw.len() - 1]);
+    }
+    Ok(encoded)
+}
+
+/// Cell-only save that reuses the original bytes: with a fresh row index, row groups
+/// (`INDEX_STRIDE` rows) without patches are copied verbatim and only patched records are
+/// re-encoded. Returns the index of the written file, or `None` when the dialect, BOM or
+/// line endings would change, or a group no longer hashes as it did when indexed, and a
+/// full rewrite is needed.
+fn save_patches_incrementally(
+    state: &AppState,
+    path: &str,
+    write_target: &str,
+    dialect: &CsvDialect,
+    patch_map: &HashMap<usize, HashMap<usize, String>>,
+) -> Result<Option<CsvIndex>, String> {
+    let delimiter = dialect.delimiter_byte();
+    let utf8 = dialect
+        .encoding
+        .as_deref()
+        .is_none_or(|encoding| encoding.eq_ignore_ascii_case("UTF-8"));
+    if !utf8
+        || dialect.quote_byte() != b'"'
+        || dialect.escape_byte() != b'"'
+        || dialect.quoting.is_some()
+        || dialect.rewrites_records()
+    {
+        return Ok(None);
+    }
+    let index = match state.indexes.lock() {
+        Ok(indexes) => indexes.get(&index_key(path, dialect)).cloned(),
+        Err(_) => None,
+    };
+    let Some(index) = index else {
+        return Ok(None);
+    };
+    if file_signature(&PathBuf::from(path))? != (index.file_len, index.modified)
+        || index.offsets.is_empty()
+        || patch_map.keys().any(|row| *row >= index.total_rows)
+    {
+        return Ok(None);
+    }
+
+    let mut source = File::open(path).map_err(|e| e.to_string())?;
+    let mut head = vec![0u8; index.data_start as usize];
+    source.read_exact(&mut head).map_err(|e| e.to_string())?;
+    let mut after_head = [0u8; 1];
+    let crlf = head.ends_with(b"\r")
+        && source.read(&mut after_head).map_err(|e| e.to_string())? == 1
+        && after_head[0] == b'\n';
+    let eol_matches = match normalize_terminator(dialect.eol.clone()) {
+        csv::Terminator::CRLF => crlf,
+        _ => head.ends_with(b"\n"),
+    };
+    let has_bom = head.starts_with(&[0xEF, 0xBB, 0xBF]);
+    if has_bom != dialect.bom.unwrap_or(false) || !eol_matches {
+        return Ok(None);
+    }
+
+    let mut output =
+        std::io::BufWriter::new(File::create(write_target).map_err(|e| e.to_string())?);
+    output.write_all(&head).map_err(|e| e.to_string())?;
+    let mut written = index.data_start;
+    // Patched cells can add or drop line breaks, moving the lines of later entries.
+    let mut line_shift = 0i64;
+    let mut offsets = Vec::with_capacity(index.offsets.len());
+    let mut buffer = vec![0u8; 64 * 1024];
+    for (group, entry) in index.offsets.iter().enumerate() {
+        let next = index.offsets.get(group + 1);
+        let end_byte = next.map(|e| e.byte).unwrap_or(index.file_len);
+        let end_row = next.map(|e| e.row).unwrap_or(index.total_rows);
+        let line = entry.line.saturating_add_signed(line_shift);
+        source
+            .seek(SeekFrom::Start(entry.byte))
+            .map_err(|e| e.to_string())?;
+        let mut chunk = (&mut source).take(end_byte - entry.byte);
+        // The signature misses edits that keep the size within the same second, so each
+        // group is checked against its hash before its offsets are trusted.
+        if !(entry.row..end_row).any(|row| patch_map.contains_key(&row)) {
+            let mut copied = HashingReader::new(&mut chunk);
+            loop {
+                let read = copied.read(&mut buffer).map_err(|e| e.to_string())?;
+                if read == 0 {
+                    break;
+                }
+                output
+                    .write_all(&buffer[..read])
+                    .map_err(|e| e.to_string())?;
+                copied.hash_up_to(u64::MAX);
+            }
+            if copied.take_hash() != entry.hash {
+                return Ok(None);
+            }
+            offsets.push(CsvIndexEntry {
+                row: entry.row,
+                byte: written,
+                line,
+                hash: entry.hash,
+            });
+            written += end_byte - entry.byte;
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+        chunk.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+        if group_hash(&bytes) != entry.hash {
+            return Ok(None);
+        }
+        let group_start = written;
+        let mut encoded_group = Vec::with_capacity(bytes.len());
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(delimiter)
+            .from_reader(bytes.as_slice());
+        let mut record = csv::ByteRecord::new();
+        let mut start = 0usize;
+        let mut row = entry.row;
+        while reader
+            .read_byte_record(&mut record)
+            .map_err(|e| e.to_string())?
+        {
+            let end = reader.position().byte() as usize;
+            let raw = &bytes[start..end];
+            let encoded = match patch_map.get(&row) {
+                Some(row_patches) => encode_patched_record(delimiter, &record, raw, row_patches)?,
+                None => raw.to_vec(),
+            };
+            let breaks = |bytes: &[u8]| bytes.iter().filter(|b| **b == b'\n').count() as i64;
+            line_shift += breaks(&encoded) - breaks(raw);
+            encoded_group.extend_from_slice(&encoded);
+            start = end;
+            row += 1;
+        }
+        encoded_group.extend_from_slice(&bytes[start..]);
+        output
+            .write_all(&encoded_group)
+            .map_err(|e| e.to_string())?;
+        written += encoded_group.len() as u64;
+        offsets.push(CsvIndexEntry {
+            row: entry.row,
+            byte: group_start,
+            line,
+            hash: group_hash(&encoded_group),
+        });
+    }
+    output.flush().map_err(|e| e.to_string())?;
+
+    Ok(Some(CsvIndex {
+        offsets,
+        file_len: written,
+        ..index
+    }))
+}
+
+/// Patched cells compared by a post-save verification, spread evenly over the patches.
+const VERIFY_SAMPLE_CELLS: usize = 200;
+
+/// Re-read `path` with `dialect` and compare it with what was meant to be written:
+/// `expected_rows` data rows under `headers`, and `cells` as (row, column, value). Headers
+/// and cells are in the layout before `dialect.columns` selects from it. The file is
+/// already in place, so a failure to read it back is reported rather than returned.
+fn verify_output(
+    path: &str,
+    dialect: &CsvDialect,
+    headers: &[String],
+    expected_rows: usize,
+    cells: &[(usize, usize, String)],
+) -> SaveVerification {
+    let layout: Vec<usize> = match &dialect.columns {
+        Some(columns) => columns
+            .iter()
+            .filter_map(|name| headers.iter().position(|header| header == name))
+            .collect(),
+        None => (0..headers.len()).collect(),
+    };
+    let mut verification = SaveVerification {
+        ok: false,
+        rows: 0,
+        expected_rows,
+        columns: 0,
+        expected_columns: layout.len(),
+        cells_checked: 0,
+        mismatches: Vec::new(),
+        error: None,
+    };
+    if let Err(err) = read_back_output(path, dialect, &layout, cells, &mut verification) {
+        tracing::warn!(path, error = %err, "output could not be read back");
+        verification.error = Some(err);
+        return verification;
+    }
+    verification.ok = verification.rows == expected_rows
+        && verification.columns == verification.expected_columns
+        && verification.mismatches.is_empty();
+    if !verification.ok {
+        tracing::warn!(
+            path,
+            rows = verification.rows,
+            expected_rows,
+            mismatches = verification.mismatches.len(),
+            "output verification failed"
+        );
+    }
+    verification
+}
+
+/// Count the rows and columns of `path` into `verification` and compare a sample of `cells`.
+fn read_back_output(
+    path: &str,
+    dialect: &CsvDialect,
+    layout: &[usize],
+    cells: &[(usize, usize, String)],
+    verification: &mut SaveVerification,
+) -> Result<(), String> {
+    let prefix = dialect.formula_prefix()?;
+    let step = cells.len().div_ceil(VERIFY_SAMPLE_CELLS).max(1);
+    let mut expected: HashMap<usize, Vec<(usize, String)>> = HashMap::new();
+    for (row, col, value) in cells.iter().step_by(step) {
+        let Some(out_col) = layout.iter().position(|c| c == col) else {
+            continue;
+        };
+        let value = match &dialect.null_token {
+            Some(token) if value.is_empty() => token.as_str(),
+            _ => value.as_str(),
+        };
+        let value = match prefix {
+            Some(prefix) => guard_formula(value, prefix).into_owned(),
+            None => value.to_string(),
+        };
+        expected.entry(*row).or_default().push((out_col, value));
+    }
+
+    let input: Box<dyn Read> = if dialect.is_utf16() {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let text = String::from_utf16(&units).map_err(|e| e.to_string())?;
+        Box::new(std::io::Cursor::new(text.into_bytes()))
+    } else {
+        Box::new(BufReader::new(File::open(path).map_err(|e| e.to_string())?))
+    };
+    let mut reader = dialect
+        .reader_builder()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(input);
+    let mut records = reader.records();
+    if dialect.sep_hint.unwrap_or(false) {
+        records.next().transpose().map_err(|e| e.to_string())?;
+    }
+    verification.columns = match records.next() {
+        Some(header) => header.map_err(|e| e.to_string())?.len(),
+        None => 0,
+    };
+    for record in records {
+        let record = record.map_err(|e| e.to_string())?;
+        if let Some(cells) = expected.get(&verification.rows) {
+            for (col, value) in cells {
+                let found = record.get(*col).unwrap_or("");
+                verification.cells_checked += 1;
+                if found != value {
+                    verification.mismatches.push((
+                        verification.rows,
+                        *col,
+                        value.clone(),
+                        found.to_string(),
+                    ));
+                }
+            }
+        }
+        verification.rows += 1;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    skip_all,
+    fields(path = %path, target = %target_path, rows = tracing::field::Empty)
+)]
+fn save_csv_with_patches(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    target_path: UserPath,
+    dialect: CsvDialect,
+    patches: Vec<CsvPatch>,
+    row_ops: Vec<RowOp>,
+    column_ops: Vec<ColumnOp>,
+    overwrite: Option<bool>,
+    profile: Option<String>,
+    verify: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<SaveResult, String> {
+    if target_path != path {
+        ensure_can_write(&target_path, overwrite)?;
+    }
+    ensure_not_read_only(&state, &target_path)?;
+    // Read with the document's dialect, write with the profile's options on top of it.
+    let output_dialect = match profile.as_deref() {
+        Some(name) => export_dialect_for(&app, &dialect, name)?,
+        None => dialect.clone(),
+    };
+    ensure_space_for_rewrite(&path, &target_path, &output_dialect, "save")?;
+    let mut audit_params = serde_json::json!({
+        "delimiter": output_dialect.delimiter,
+        "encoding": output_dialect.encoding,
+        "profile": profile,
+        "patches": patches.len(),
+        "row_ops": row_ops.len(),
+        "column_ops": column_ops.len(),
+    });
+    let patch_count = patches.len();
+    let mut patch_map: HashMap<usize, HashMap<usize, String>> = HashMap::new();
+    for patch in patches {
+        patch_map
+            .entry(patch.row)
+            .or_default()
+            .insert(patch.col, patch.value);
+    }
+    // The layout to verify against is read before the source can be replaced.
+    let verify_against = match verify {
+        Some(true) => {
+            let mut headers = dialect
+                .reader(&path)?
+                .headers()
+                .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+                .map_err(|e| e.to_string())?;
+            apply_column_ops_to_headers(&mut headers, &column_ops);
+            let mut cells: Vec<(usize, usize, String)> = patch_map
+                .iter()
+                .flat_map(|(row, cols)| cols.iter().map(|(col, value)| (*row, *col, value.clone())))
+                .collect();
+            cells.sort_by_key(|(row, col, _)| (*row, *col));
+            Some((headers, cells))
+        }
+        _ => None,
+    };
+
+    let needs_replace = target_path == path;
+    let write_target = if needs_replace {
+        format!("{}.tmp", path)
+    } else {
+        target_path.to_string()
+    };
+
+    let dry_run = dry_run.unwrap_or(false);
+    let incremental = if row_ops.is_empty() && column_ops.is_empty() && !dry_run {
+        save_patches_incrementally(&state, &path, &write_target, &output_dialect, &patch_map)?
+    } else {
+        None
+    };
+    audit_params["incremental"] = incremental.is_some().into();
+    let mut written_hashes = None;
+    let counts = match &incremental {
+        // The incremental path rejects patches past the last row, so every one is applied.
+        Some(index) => RowCounts {
+            rows_read: index.total_rows,
+            rows_written: index.total_rows,
+            cells_modified: patch_map.values().map(HashMap::len).sum(),
+            ..Default::default()
+        },
+        None => {
+            let mut reader = dialect.reader(&path)?;
+            let mut writer = output_dialect.output_writer(&write_target, dry_run)?;
+            let counts =
+                write_patched_csv(&mut reader, &mut writer, &patch_map, &row_ops, &column_ops)?;
+            written_hashes = Some(output_dialect.finish_writer(writer, &write_target)?);
+            counts
+        }
+    };
+    let rows = counts.rows_written;
+    tracing::Span::current().record("rows", rows);
+    if let Some(hashes) = written_hashes.as_ref().filter(|_| dry_run) {
+        return Ok(SaveResult {
+            output_path: target_path.to_string(),
+            hashes: hashes.clone(),
+            counts: Some(counts),
+            verification: None,
+            dry_run,
+        });
+    }
+
+    let output_path = if needs_replace {
+        let final_path = PathBuf::from(&path);
+        if final_path.exists() {
+            fs::remove_file(&final_path).map_err(|e| e.to_string())?;
+        }
+        fs::rename(&write_target, &final_path).map_err(|e| e.to_string())?;
+        // The edits are in the file now, so nothing differs from it any more.
+        if let Ok(mut sessions) = state.sessions.lock() {
+            for session in sessions.values_mut().filter(|session| session.path == path) {
+                session.changes.clear();
+            }
+        }
+        path.to_string()
+    } else {
+        write_target
+    };
+    if let Some(mut index) = incremental {
+        (index.file_len, index.modified) = file_signature(&PathBuf::from(&output_path))?;
+        if let Ok(mut indexes) = state.indexes.lock() {
+            indexes.insert(index_key(&output_path, &index.dialect), index);
+        }
+    }
+
+    record_operation(
+        &app,
+        "save_csv_with_patches",
+        Some(&path),
+        Some(&output_path),
+        Some(patch_count),
+        audit_params,
+    );
+    let verification = verify_against.map(|(headers, cells)| {
+        verify_output(&output_path, &output_dialect, &headers, rows, &cells)
+    });
+    // The incremental path patched the file in place, so it is read back.
+    let hashes = match written_hashes {
+        Some(hashes) => hashes,
+        None => output_hashes(&output_path)?,
+    };
+    if let Err(err) = snapshot_saved_file(&app, &output_path, &hashes) {
+        tracing::warn!(path = %output_path, error = %err, "version snapshot failed");
+    }
+    Ok(SaveResult {
+        hashes,
+        output_path,
+        counts: Some(counts),
+        verification,
+        dry_run,
+    })
+}
+
+/// Languages whose locales write decimals with a comma, so Excel expects `;` between fields.
+const DECIMAL_COMMA_LANGUAGES: &[&str] = &[
+    "af", "bg", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr", "hu", "id",
+    "is", "it", "lt", "lv", "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv",
+    "tr", "uk", "vi",
+];
+
+/// Export dialects that ship with the app, by name. `excel` writes what Excel opens with a
+/// double click: UTF-8 with BOM, CRLF, formula guard, and `;` for decimal-comma locales.
+fn builtin_export_profile(name: &str, locale: &str) -> Option<CsvDialect> {
+    match name {
+        "excel" => {
+            let language = locale
+                .split(['-', '_'])
+                .next()
+                .unwrap_or("")
+                .to_ascii_lowercase();
+            let delimiter = if DECIMAL_COMMA_LANGUAGES.contains(&language.as_str()) {
+                ";"
+            } else {
+                ","
+            };
+            Some(CsvDialect {
+                delimiter: Some(delimiter.to_string()),
+                eol: Some("CRLF".to_string()),
+                bom: Some(true),
+                encoding: Some("UTF-8".to_string()),
+                formula_guard: Some("quote".to_string()),
+                ..Default::default()
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Resolve a named export profile to the dialect to save with. `locale` defaults to the
+/// app's language; `sep_hint` adds the `sep=` line, which makes Excel ignore the BOM.
+#[tauri::command]
+fn get_export_profile(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    name: String,
+    locale: Option<String>,
+    sep_hint: Option<bool>,
+) -> Result<CsvDialect, String> {
+    let locale = match locale {
+        Some(locale) => locale,
+        None => state
+            .menu_locale
+            .lock()
+            .map_err(|_| "lock poisoned")?
+            .clone(),
+    };
+    let mut dialect = resolve_export_profile(&app, &name, &locale)?;
+    if sep_hint.is_some() {
+        dialect.sep_hint = sep_hint.filter(|hint| *hint);
+    }
+    Ok(dialect)
+}
+
+/// A saved combination of output options, selectable by name when saving or running a
+/// project.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExportProfile {
+    pub name: String,
+    #[serde(flatten)]
+    pub dialect: CsvDialect,
+}
+
+const EXPORT_PROFILES_FILE: &str = "export_profiles.json";
+
+/// A saved export profile by name, falling back to the built-in ones.
+fn resolve_export_profile(
+    app: &tauri::AppHandle,
+    name: &str,
+    locale: &str,
+) -> Result<CsvDialect, String> {
+    let profiles: Vec<ExportProfile> =
+        read_json_config(&app_config_file(app, EXPORT_PROFILES_FILE)?)?;
+    if let Some(profile) = profiles.into_iter().find(|profile| profile.name == name) {
+        return Ok(profile.dialect);
+    }
+    builtin_export_profile(name, locale).ok_or_else(|| format!("unknown export profile '{}'", name))
+}
+
+/// Resolve `name` for an output: the profile's options override `dialect`.
+fn export_dialect_for(
+    app: &tauri::AppHandle,
+    dialect: &CsvDialect,
+    name: &str,
+) -> Result<CsvDialect, String> {
+    use tauri::Manager;
+    let locale = app
+        .state::<AppState>()
+        .menu_locale
+        .lock()
+        .map_err(|_| "lock poisoned")?
+        .clone();
+    Ok(dialect.with_profile(&resolve_export_profile(app, name, &locale)?))
+}
+
+#[tauri::command]
+fn list_export_profiles(app: tauri::AppHandle) -> Result<Vec<ExportProfile>, String> {
+    read_json_config(&app_config_file(&app, EXPORT_PROFILES_FILE)?)
+}
+
+/// Insert or replace an export profile by name. Built-in names can't be reused.
+#[tauri::command]
+fn save_export_profile(
+    app: tauri::AppHandle,
+    profile: ExportProfile,
+) -> Result<Vec<ExportProfile>, String> {
+    if profile.name.trim().is_empty() {
+        return Err("export profile needs a name".to_string());
+    }
+    if builtin_export_profile(&profile.name, "en").is_some() {
+        return Err(format!("'{}' is a built-in export profile", profile.name));
+    }
+    if let Some(quoting) = profile.dialect.quoting.as_deref() {
+        if !["necessary", "always", "non_numeric", "never"].contains(&quoting) {
+            return Err(format!("unknown quoting policy '{}'", quoting));
+        }
+    }
+    if let Some(guard) = profile.dialect.formula_guard.as_deref() {
+        if !["quote", "space"].contains(&guard) {
+            return Err(format!("unknown formula guard '{}'", guard));
+        }
+    }
+    if profile.dialect.columns.as_ref().is_some_and(Vec::is_empty) {
+        return Err("export profile selects no columns".to_string());
+    }
+    let path = app_config_file(&app, EXPORT_PROFILES_FILE)?;
+    let mut profiles: Vec<ExportProfile> = read_json_config(&path)?;
+    profiles.retain(|existing| existing.name != profile.name);
+    profiles.push(profile);
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    write_json_config(&path, &profiles)?;
+    Ok(profiles)
+}
+
+#[tauri::command]
+fn delete_export_profile(
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<Vec<ExportProfile>, String> {
+    let path = app_config_file(&app, EXPORT_PROFILES_FILE)?;
+    let mut profiles: Vec<ExportProfile> = read_json_config(&path)?;
+    profiles.retain(|existing| existing.name != name);
+    write_json_config(&path, &profiles)?;
+    Ok(profiles)
+}
+
+/// Full-rewrite core of `save_csv_with_patches`: copies `reader` to `writer` with the row
+/// ops, column ops and patches applied. Patches address output rows and columns; each one
+/// applied counts as a modified cell.
+fn write_patched_csv<R: Read, W: Write>(
+    reader: &mut csv::Reader<R>,
+    writer: &mut csv::Writer<W>,
+    patch_map: &HashMap<usize, HashMap<usize, String>>,
+    row_ops: &[RowOp],
+    column_ops: &[ColumnOp],
+) -> Result<RowCounts, String> {
+    let mut headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    apply_column_ops_to_headers(&mut headers, column_ops);
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+
+    let mut output_index = 0usize;
+    let mut cells_modified = 0usize;
+    let mut write_row = |mut row: Vec<String>| -> Result<(), String> {
+        apply_column_ops_to_row(&mut row, column_ops);
+        if let Some(row_patches) = patch_map.get(&output_index) {
+            for (col_idx, value) in row_patches {
+                if *col_idx >= row.len() {
+                    row.resize(col_idx + 1, String::new());
+                }
+                row[*col_idx] = value.clone();
+            }
+            cells_modified += row_patches.len();
+        }
+        output_index += 1;
+        writer.write_record(&row).map_err(|e| e.to_string())
+    };
+
+    let segments = plan_row_segments(row_ops);
+    let mut segment = 0usize;
+    let mut input_index = 0usize;
+    let mut inserted = 0usize;
+    let mut kept = 0usize;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        // The last segment is open-ended, so every input row lands in one.
+        loop {
+            match &segments[segment] {
+                RowSegment::Inserted(values) => {
+                    write_row(values.clone())?;
+                    inserted += 1;
+                    segment += 1;
+                }
+                RowSegment::Input { start, .. } if input_index < *start => break,
+                RowSegment::Input { end, .. } if input_index >= *end => segment += 1,
+                RowSegment::Input { .. } => {
+                    write_row(record.iter().map(|s| s.to_string()).collect())?;
+                    kept += 1;
+                    break;
+                }
+            }
+        }
+        input_index += 1;
+    }
+    for remaining in &segments[segment..] {
+        if let RowSegment::Inserted(values) = remaining {
+            write_row(values.clone())?;
+            inserted += 1;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(RowCounts {
+        rows_read: input_index,
+        rows_written: output_index,
+        rows_inserted: inserted,
+        rows_deleted: input_index - kept,
+        cells_modified,
+    })
+}
+
+/// Per-run state shared by every row of a macro pass.
+#[derive(Default)]
+struct MacroRunState {
+    pseudonyms: HashMap<String, String>,
+    sequence_index: i64,
+    failed: usize,
+    inserted_column: Option<usize>,
+    condition_regex: Option<regex::Regex>,
+    condition_timezone: Option<chrono_tz::Tz>,
+    timezones: Option<(chrono_tz::Tz, TargetZone)>,
+    rates: HashMap<String, f64>,
+    running_sum: f64,
+    previous_number: Option<f64>,
+    last_filled: Option<String>,
+    headers: Vec<String>,
+    rows_seen: usize,
+    script: Option<Box<MacroScript>>,
+    plugin: Option<Box<WasmPlugin>>,
+    /// Type override per row position, from the file's settings.
+    column_types: Vec<Option<String>>,
+}
+
+/// Output zone for `convert_timezone`: an IANA zone or a fixed offset such as `+05:30`.
+#[derive(Clone, Copy)]
+enum TargetZone {
+    Named(chrono_tz::Tz),
+    Fixed(chrono::FixedOffset),
+}
+
+impl TargetZone {
+    fn parse(name: &str) -> Result<Self, String> {
+        if let Ok(offset) = name.parse::<chrono::FixedOffset>() {
+            return Ok(Self::Fixed(offset));
+        }
+        parse_timezone(Some(name)).map(Self::Named)
+    }
+}
+
+/// Compiled Rhai script plus the sandboxed engine it runs in.
+struct MacroScript {
+    engine: rhai::Engine,
+    ast: rhai::AST,
+    /// Start of the running call; the time limit applies to each row, not the whole file.
+    call_started: Arc<Mutex<Instant>>,
+}
+
+const SCRIPT_MAX_OPERATIONS: u64 = 100_000;
+const SCRIPT_DEFAULT_TIME_LIMIT_MS: u64 = 30_000;
+
+impl MacroScript {
+    fn compile(source: &str, time_limit_ms: u64) -> Result<Self, String> {
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_string_size(1024 * 1024);
+        engine.set_max_array_size(10_000);
+        engine.set_max_map_size(10_000);
+        engine.disable_symbol("eval");
+        let call_started = Arc::new(Mutex::new(Instant::now()));
+        let started = call_started.clone();
+        let limit = Duration::from_millis(time_limit_ms);
+        engine.on_progress(move |_| {
+            let elapsed = started.lock().map(|started| started.elapsed()).ok()?;
+            (elapsed > limit).then(|| rhai::Dynamic::from("script time limit exceeded"))
+        });
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        Ok(Self {
+            engine,
+            ast,
+            call_started,
+        })
+    }
+
+    /// Evaluate the script for one row, restarting the time limit.
+    fn run(&self, scope: &mut rhai::Scope) -> Result<rhai::Dynamic, String> {
+        if let Ok(mut started) = self.call_started.lock() {
+            *started = Instant::now();
+        }
+        self.engine
+            .eval_ast_with_scope(scope, &self.ast)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl MacroRunState {
+    fn new(
+        spec: &CsvMacroSpec,
+        headers: &[String],
+        inserted_column: Option<usize>,
+    ) -> Result<Self, String> {
+        let condition_regex = match &spec.condition {
+            Some(condition) if condition.operator == "regex" => Some(
+                regex::Regex::new(condition.value.as_deref().unwrap_or(""))
+                    .map_err(|e| e.to_string())?,
+            ),
+            _ => None,
+        };
+        if let Some(condition) = &spec.condition {
+            let kind = condition.value.as_deref().unwrap_or("");
+            if condition.operator.starts_with("checksum_") && check_digit_valid(kind, "").is_none()
+            {
+                return Err(format!("unknown checksum '{}'", kind));
+            }
+        }
+        let condition_timezone = spec
+            .condition
+            .as_ref()
+            .and_then(|condition| condition.timezone.as_deref())
+            .map(|name| parse_timezone(Some(name)))
+            .transpose()?;
+        let timezones = if spec.op == "convert_timezone" {
+            let target = spec
+                .target_timezone
+                .as_deref()
+                .ok_or_else(|| "target timezone is required".to_string())?;
+            if let Some(format) = &spec.output_format {
+                check_strftime(format)?;
+            }
+            Some((
+                parse_timezone(spec.source_timezone.as_deref())?,
+                TargetZone::parse(target)?,
+            ))
+        } else {
+            None
+        };
+        let rates = if spec.op == "convert_rate" {
+            load_macro_rates(spec)?
+        } else {
+            HashMap::new()
+        };
+        let script = if spec.op == "script" {
+            let source = spec.script.as_deref().unwrap_or("");
+            let time_limit = spec.time_limit_ms.unwrap_or(SCRIPT_DEFAULT_TIME_LIMIT_MS);
+            Some(Box::new(MacroScript::compile(source, time_limit)?))
+        } else {
+            None
+        };
+        Ok(Self {
+            inserted_column,
+            condition_regex,
+            condition_timezone,
+            timezones,
+            rates,
+            headers: headers.to_vec(),
+            script,
+            ..Default::default()
+        })
+    }
+
+    /// Map a column index of the input file to its position after the inserted column.
+    fn input_index(&self, col: usize) -> usize {
+        match self.inserted_column {
+            Some(idx) if col >= idx => col + 1,
+            _ => col,
+        }
+    }
+}
+
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%Y/%m/%d %H:%M:%S",
+];
+const DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%d.%m.%Y",
+    "%d %b %Y",
+    "%b %d, %Y",
+    "%d %B %Y",
+    "%B %d, %Y",
+];
+
+fn parse_timezone(name: Option<&str>) -> Result<chrono_tz::Tz, String> {
+    let name = name.unwrap_or("UTC");
+    name.parse::<chrono_tz::Tz>()
+        .map_err(|_| format!("unknown timezone: {}", name))
+}
+
+/// Reject a strftime pattern chrono cannot read, before any cell is formatted with it.
+fn check_strftime(format: &str) -> Result<(), String> {
+    use chrono::format::{Item, StrftimeItems};
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid date format '{}'", format));
+    }
+    Ok(())
+}
+
+/// `formatted.to_string()` without the panic: chrono reports a pattern it cannot apply
+/// (invalid, or `%z` on a time without an offset) as a formatting error.
+fn format_or_none(formatted: impl std::fmt::Display) -> Option<String> {
+    use std::fmt::Write;
+    let mut out = String::new();
+    write!(out, "{}", formatted).ok()?;
+    Some(out)
+}
+
+/// Parse a cell as an instant. Values carrying an offset keep it; naive values are read
+/// in `tz`. An explicit `format` replaces the built-in list.
+fn parse_datetime_value(
+    value: &str,
+    format: Option<&str>,
+    tz: chrono_tz::Tz,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    let local = |naive: chrono::NaiveDateTime| {
+        tz.from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+    };
+    let date_only = |date: chrono::NaiveDate| date.and_hms_opt(0, 0, 0).and_then(local);
+    if let Some(format) = format {
+        if let Ok(dt) = chrono::DateTime::parse_from_str(value, format) {
+            return Some(dt.with_timezone(&chrono::Utc));
+        }
+        if let Ok(naive) = chrono::NaiveDateTime::parse_from_str(value, format) {
+            return local(naive);
+        }
+        return chrono::NaiveDate::parse_from_str(value, format)
+            .ok()
+            .and_then(date_only);
+    }
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&chrono::Utc));
+    }
+    if let Some(naive) = DATETIME_FORMATS
+        .iter()
+        .find_map(|f| chrono::NaiveDateTime::parse_from_str(value, f).ok())
+    {
+        return local(naive);
+    }
+    DATE_FORMATS
+        .iter()
+        .find_map(|f| chrono::NaiveDate::parse_from_str(value, f).ok())
+        .and_then(date_only)
+}
+
+/// Start of the `unit` (minute/hour/day/week/month/quarter/year) containing `dt`, with
+/// boundaries taken in `tz`. Weeks start on Monday.
+fn truncate_datetime(
+    dt: chrono::DateTime<chrono::Utc>,
+    unit: &str,
+    tz: chrono_tz::Tz,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::{Datelike, TimeZone, Timelike};
+    let local = dt.with_timezone(&tz).naive_local();
+    let date = local.date();
+    let start = match unit {
+        "minute" => date.and_hms_opt(local.hour(), local.minute(), 0)?,
+        "hour" => date.and_hms_opt(local.hour(), 0, 0)?,
+        "day" => date.and_hms_opt(0, 0, 0)?,
+        "week" => (date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64))
+            .and_hms_opt(0, 0, 0)?,
+        "month" => date.with_day(1)?.and_hms_opt(0, 0, 0)?,
+        "quarter" => chrono::NaiveDate::from_ymd_opt(date.year(), date.month0() / 3 * 3 + 1, 1)?
+            .and_hms_opt(0, 0, 0)?,
+        "year" => chrono::NaiveDate::from_ymd_opt(date.year(), 1, 1)?.and_hms_opt(0, 0, 0)?,
+        _ => return None,
+    };
+    tz.from_local_datetime(&start)
+        .earliest()
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+}
+
+/// Compare numerically when both sides parse as numbers, otherwise as strings.
+fn compare_cell_values(left: &str, right: &str) -> std::cmp::Ordering {
+    match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => left.cmp(right),
+    }
+}
+
+/// Evaluate a macro condition against the row. Without an explicit column the condition
+/// tests the macro input value.
+fn macro_condition_holds(
+    condition: &MacroCondition,
+    input: &str,
+    row: &[impl AsRef<str>],
+    state: &MacroRunState,
+) -> bool {
+    let subject = match condition.column {
+        Some(col) => row
+            .get(state.input_index(col))
+            .map(|v| v.as_ref())
+            .unwrap_or(""),
+        None => input,
+    };
+    let operand = match condition.value_column {
+        Some(col) => row
+            .get(state.input_index(col))
+            .map(|v| v.as_ref())
+            .unwrap_or(""),
+        None => condition.value.as_deref().unwrap_or(""),
+    };
+    let kind = condition
+        .column
+        .and_then(|col| state.column_types.get(state.input_index(col)))
+        .and_then(|kind| kind.as_deref());
+    match condition.operator.as_str() {
+        "eq" => subject == operand,
+        "ne" => subject != operand,
+        "gt" => compare_typed_values(kind, subject, operand).is_gt(),
+        "ge" => compare_typed_values(kind, subject, operand).is_ge(),
+        "lt" => compare_typed_values(kind, subject, operand).is_lt(),
+        "le" => compare_typed_values(kind, subject, operand).is_le(),
+        "contains" => subject.contains(operand),
+        "starts_with" => subject.starts_with(operand),
+        "ends_with" => subject.ends_with(operand),
+        "empty" => subject.trim().is_empty(),
+        "not_empty" => !subject.trim().is_empty(),
+        "regex" => state
+            .condition_regex
+            .as_ref()
+            .map(|re| re.is_match(subject))
+            .unwrap_or(false),
+        "before" | "after" | "between" => {
+            let tz = state.condition_timezone.unwrap_or(chrono_tz::UTC);
+            let format = condition.date_format.as_deref();
+            let parse = |text: &str| parse_datetime_value(text, format, tz);
+            let (Some(at), Some(bound)) = (parse(subject), parse(operand)) else {
+                return false;
+            };
+            match condition.operator.as_str() {
+                "before" => at < bound,
+                "after" => at > bound,
+                _ => condition
+                    .value_end
+                    .as_deref()
+                    .and_then(parse)
+                    .is_some_and(|end| at >= bound && at <= end),
+            }
+        }
+        // The operand names the algorithm; blank cells match neither operator.
+        "checksum_valid" | "checksum_invalid" => {
+            !subject.trim().is_empty()
+                && check_digit_valid(operand, subject)
+                    .is_some_and(|valid| valid == (condition.operator == "checksum_valid"))
+        }
+        _ => false,
+    }
+}
+
+fn gtin_check(digits: &[u32]) -> bool {
+    let (body, check) = digits.split_at(digits.len() - 1);
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d * 3 } else { *d })
+        .sum();
+    (10 - sum % 10) % 10 == check[0]
+}
+
+/// Verify the check digit of `value` for `kind`: `luhn`, `isbn` (10 or 13), `isbn10`,
+/// `isbn13`, `ean`/`gtin` (8, 12, 13 or 14 digits) or `iban`. Spaces and hyphens are
+/// ignored. Returns `None` for an unknown kind.
+fn check_digit_valid(kind: &str, value: &str) -> Option<bool> {
+    let compact: String = value
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-'))
+        .collect::<String>()
+        .to_ascii_uppercase();
+    let digits: Option<Vec<u32>> = compact.chars().map(|c| c.to_digit(10)).collect();
+    let isbn10 = || {
+        compact.len() == 10
+            && compact
+                .chars()
+                .enumerate()
+                .all(|(i, c)| c.is_ascii_digit() || (i == 9 && c == 'X'))
+            && compact
+                .chars()
+                .zip((1..=10).rev())
+                .map(|(c, weight)| c.to_digit(10).unwrap_or(10) * weight)
+                .sum::<u32>()
+                % 11
+                == 0
+    };
+    let isbn13 = || {
+        (compact.starts_with("978") || compact.starts_with("979"))
+            && digits
+                .as_ref()
+                .is_some_and(|d| d.len() == 13 && gtin_check(d))
+    };
+    Some(match kind {
+        "luhn" => digits.as_ref().is_some_and(|d| {
+            d.len() >= 2
+                && d.iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(i, d)| match (i % 2 == 1, d * 2) {
+                        (true, doubled) if doubled > 9 => doubled - 9,
+                        (true, doubled) => doubled,
+                        (false, _) => *d,
+                    })
+                    .sum::<u32>()
+                    % 10
+                    == 0
+        }),
+        "isbn" => isbn10() || isbn13(),
+        "isbn10" => isbn10(),
+        "isbn13" => isbn13(),
+        "ean" | "gtin" => digits
+            .as_ref()
+            .is_some_and(|d| matches!(d.len(), 8 | 12 | 13 | 14) && gtin_check(d)),
+        "iban" => {
+            let bytes = compact.as_bytes();
+            (15..=34).contains(&bytes.len())
+                && bytes[..2].iter().all(|b| b.is_ascii_uppercase())
+                && bytes[2..4].iter().all(|b| b.is_ascii_digit())
+                && bytes.iter().all(|b| b.is_ascii_alphanumeric())
+                && compact[4..]
+                    .chars()
+                    .chain(compact[..4].chars())
+                    .fold(0u32, |rem, c| {
+                        let n = c.to_digit(36).unwrap_or(0);
+                        if n >= 10 {
+                            (rem * 100 + n) % 97
+                        } else {
+                            (rem * 10 + n) % 97
+                        }
+                    })
+                    == 1
+        }
+        _ => return None,
+    })
+}
+fn random_uuid_v4<R: rand::Rng>(rng: &mut R) -> String {
+    let mut bytes: [u8; 16] = rng.gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn sha256_hex(input: &str, salt: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(input.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Mask all but the last `keep` chars, e.g. `****1234`.
+fn mask_value(value: &str, keep: usize, mask_char: char) -> String {
+    let total = value.chars().count();
+    let masked = total.saturating_sub(keep);
+    value
+        .chars()
+        .enumerate()
+        .map(|(idx, ch)| if idx < masked { mask_char } else { ch })
+        .collect()
+}
+
+fn pad_value(value: &str, width: usize, pad_char: char, left: bool) -> String {
+    let missing = width.saturating_sub(value.chars().count());
+    let padding: String = std::iter::repeat_n(pad_char, missing).collect();
+    if left {
+        format!("{}{}", padding, value)
+    } else {
+        format!("{}{}", value, padding)
+    }
+}
+
+/// Cut to at most `max_len` chars; the ellipsis counts towards the limit.
+fn truncate_value(value: &str, max_len: usize, ellipsis: &str) -> String {
+    if value.chars().count() <= max_len {
+        return value.to_string();
+    }
+    let keep = max_len.saturating_sub(ellipsis.chars().count());
+    let mut out: String = value.chars().take(keep).collect();
+    out.push_str(ellipsis);
+    out
+}
+
+/// Char-based substring; a negative start counts from the end of the value.
+fn substring_value(value: &str, start: i64, length: Option<usize>) -> String {
+    let total = value.chars().count() as i64;
+    let from = if start < 0 {
+        (total + start).max(0)
+    } else {
+        start.min(total)
+    } as usize;
+    let chars = value.chars().skip(from);
+    match length {
+        Some(len) => chars.take(len).collect(),
+        None => chars.collect(),
+    }
+}
+
+/// `value` without its digit grouping, or `None` when `grouping` appears anywhere but between
+/// groups of three integer digits (`1,234,567`), so `3.14` read with `.` grouping fails
+/// instead of becoming `314`.
+fn strip_digit_grouping(value: &str, grouping: &str, decimal: &str) -> Option<String> {
+    if grouping.is_empty() {
+        return Some(value.to_string());
+    }
+    let (integer, fraction) = value.split_once(decimal).unwrap_or((value, ""));
+    if fraction.contains(grouping) {
+        return None;
+    }
+    if integer.contains(grouping) {
+        let mut groups = integer.split(grouping);
+        let lead = groups
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches(['-', '+']);
+        if lead.is_empty()
+            || lead.chars().count() > 3
+            || groups.any(|group| group.chars().count() != 3)
+        {
+            return None;
+        }
+    }
+    Some(value.replace(grouping, ""))
+}
+
+/// Normalize a numeric cell. Blank cells are left alone; unparsable ones are counted as failed.
+fn numeric_macro_value(spec: &CsvMacroSpec, current: &str, state: &mut MacroRunState) -> String {
+    let trimmed = current.trim();
+    if trimmed.is_empty() {
+        return current.to_string();
+    }
+    let candidate = match spec.op.as_str() {
+        "strip_thousands" => {
+            let separator = spec.separator.as_deref().unwrap_or(",");
+            let decimal = if separator == "." { "," } else { "." };
+            strip_digit_grouping(trimmed, separator, decimal)
+        }
+        "decimal_to_point" => {
+            strip_digit_grouping(trimmed, ".", ",").map(|value| value.replace(',', "."))
+        }
+        "decimal_to_comma" => strip_digit_grouping(trimmed, ",", "."),
+        _ => Some(trimmed.to_string()),
+    };
+    let Some((candidate, number)) = candidate.and_then(|candidate| {
+        let number = candidate.parse::<f64>().ok()?;
+        Some((candidate, number))
+    }) else {
+        state.failed += 1;
+        return current.to_string();
+    };
+    match spec.op.as_str() {
+        "decimal_to_comma" => candidate.replace('.', ","),
+        "round" => {
+            let factor = 10f64.powi(spec.decimals.unwrap_or(0) as i32);
+            ((number * factor).round() / factor).to_string()
+        }
+        "scale" => (number * spec.factor.unwrap_or(1.0)).to_string(),
+        "format_number" => format!("{:.*}", spec.decimals.unwrap_or(2), number),
+        _ => candidate,
+    }
+}
+
+/// Zero-width characters and stray BOMs that make otherwise identical values differ.
+fn is_invisible_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}'
+    )
+}
+
+fn is_non_breaking_space(ch: char) -> bool {
+    matches!(ch, '\u{00A0}' | '\u{2007}' | '\u{202F}')
+}
+
+/// Control characters other than the line breaks of multi-line cells.
+fn is_stray_control(ch: char) -> bool {
+    ch.is_control() && ch != '\n' && ch != '\r' && ch != '\t'
+}
+
+/// What `find_invisible_characters` reports for a cell, in a fixed order.
+fn invisible_issues(value: &str) -> Vec<&'static str> {
+    let mut issues = Vec::new();
+    if value.starts_with(char::is_whitespace) {
+        issues.push("leading_whitespace");
+    }
+    if value.ends_with(char::is_whitespace) {
+        issues.push("trailing_whitespace");
+    }
+    if value.contains(is_non_breaking_space) {
+        issues.push("non_breaking_space");
+    }
+    if value.trim().contains('\t') {
+        issues.push("tab");
+    }
+    if value.contains(is_stray_control) {
+        issues.push("control");
+    }
+    if value.contains(is_invisible_char) {
+        issues.push("zero_width");
+    }
+    issues
+}
+
+/// Fix what [`invisible_issues`] finds: non-breaking spaces and inner tabs become spaces,
+/// control and zero-width characters are dropped, and the value is trimmed.
+fn clean_invisible(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .filter(|ch| !is_stray_control(*ch) && !is_invisible_char(*ch))
+        .map(|ch| {
+            if is_non_breaking_space(ch) || ch == '\t' {
+                ' '
+            } else {
+                ch
+            }
+        })
+        .collect();
+    cleaned.trim().to_string()
+}
+
+/// Windows-1252 characters for bytes 0x80..=0x9F; the five unassigned bytes map to the C1
+/// control of the same value, as Latin-1 decoders do.
+const CP1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+fn cp1252_byte(ch: char) -> Option<u8> {
+    match ch as u32 {
+        0..=0x7F | 0xA0..=0xFF => Some(ch as u8),
+        _ => CP1252_HIGH
+            .iter()
+            .position(|c| *c == ch)
+            .map(|idx| 0x80 + idx as u8),
+    }
+}
+
+/// Undo UTF-8 that was decoded as Windows-1252 or Latin-1, so `CafÃ©` becomes `Café`;
+/// text mangled more than once is unwound too. `None` when the value doesn't look mangled.
+fn repair_mojibake(value: &str) -> Option<String> {
+    let mut current = value.to_string();
+    let mut repaired = false;
+    for _ in 0..3 {
+        if current.is_ascii() {
+            break;
+        }
+        let Some(bytes) = current
+            .chars()
+            .map(cp1252_byte)
+            .collect::<Option<Vec<u8>>>()
+        else {
+            break;
+        };
+        match String::from_utf8(bytes) {
+            Ok(decoded) if decoded != current => {
+                current = decoded;
+                repaired = true;
+            }
+            _ => break,
+        }
+    }
+    repaired.then_some(current)
+}
+
+/// Decompose and drop combining marks, so `café` becomes `cafe`.
+fn strip_accents(value: &str) -> String {
+    use unicode_normalization::char::is_combining_mark;
+    use unicode_normalization::UnicodeNormalization;
+    value.nfkd().filter(|ch| !is_combining_mark(*ch)).collect()
+}
+
+const TITLE_CASE_MINOR_WORDS_EN: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to",
+    "via", "with",
+];
+
+/// Uppercase the first char of a word, honouring the Turkish dotted/dotless i.
+fn capitalize_word(word: &str, turkic: bool) -> String {
+    let mut chars = word.chars();
+    let Some(first) = chars.next() else {
+        return String::new();
+    };
+    let rest: String = chars.as_str().to_lowercase();
+    let head = match first {
+        'i' if turkic => "İ".to_string(),
+        other => other.to_uppercase().collect(),
+    };
+    format!("{}{}", head, rest)
+}
+
+fn title_case(value: &str, locale: &str) -> String {
+    let locale = locale.to_lowercase();
+    let english = locale.is_empty() || locale.starts_with("en");
+    let turkic = locale.starts_with("tr") || locale.starts_with("az");
+    let mut out = String::with_capacity(value.len());
+    let mut first_word = true;
+    for (idx, word) in value.split(' ').enumerate() {
+        if idx > 0 {
+            out.push(' ');
+        }
+        if word.is_empty() {
+            continue;
+        }
+        let lower = word.to_lowercase();
+        if english && !first_word && TITLE_CASE_MINOR_WORDS_EN.contains(&lower.as_str()) {
+            out.push_str(&lower);
+        } else {
+            out.push_str(&capitalize_word(word, turkic));
+        }
+        first_word = false;
+    }
+    out
+}
+
+fn sentence_case(value: &str) -> String {
+    let lower = value.to_lowercase();
+    let mut out = String::with_capacity(lower.len());
+    let mut capitalize_next = true;
+    for ch in lower.chars() {
+        if capitalize_next && ch.is_alphanumeric() {
+            out.extend(ch.to_uppercase());
+            capitalize_next = false;
+        } else {
+            out.push(ch);
+        }
+        if matches!(ch, '.' | '!' | '?') {
+            capitalize_next = true;
+        }
+    }
+    out
+}
+
+/// Split an identifier-ish value into lowercase words on punctuation and camelCase humps.
+fn identifier_words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for ch in value.chars() {
+        if !ch.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if ch.is_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = ch.is_lowercase() || ch.is_numeric();
+        current.extend(ch.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn camel_case(value: &str, upper_first: bool) -> String {
+    identifier_words(value)
+        .iter()
+        .enumerate()
+        .map(|(idx, word)| {
+            if idx == 0 && !upper_first {
+                word.clone()
+            } else {
+                capitalize_word(word, false)
+            }
+        })
+        .collect()
+}
+
+const HTML_NAMED_ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{A0}'),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("euro", '€'),
+    ("pound", '£'),
+    ("yen", '¥'),
+    ("cent", '¢'),
+    ("sect", '§'),
+    ("deg", '°'),
+    ("hellip", '…'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+];
+
+/// Decode named and numeric HTML entities; unknown entities are left as-is.
+fn html_decode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let tail = &rest[amp..];
+        let decoded = tail.find(';').filter(|end| *end <= 12).and_then(|end| {
+            let entity = &tail[1..end];
+            let ch = if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse::<u32>().ok().and_then(char::from_u32)
+            } else {
+                HTML_NAMED_ENTITIES
+                    .iter()
+                    .find(|(name, _)| *name == entity)
+                    .map(|(_, ch)| *ch)
+            };
+            ch.map(|ch| (ch, end))
+        });
+        match decoded {
+            Some((ch, end)) => {
+                out.push(ch);
+                rest = &tail[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn html_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Encoding/decoding ops. Values that fail to decode are left unchanged and counted as failed.
+fn codec_macro_value(op: &str, current: &str, state: &mut MacroRunState) -> String {
+    use base64::Engine;
+    let decoded = match op {
+        "url_encode" => {
+            return percent_encoding::utf8_percent_encode(
+                current,
+                percent_encoding::NON_ALPHANUMERIC,
+            )
+            .to_string()
+        }
+        "url_decode" => percent_encoding::percent_decode_str(current)
+            .decode_utf8()
+            .map(|text| text.into_owned())
+            .map_err(|e| e.to_string()),
+        "html_encode" => return html_encode(current),
+        "html_decode" => return html_decode(current),
+        "base64_encode" => return base64::engine::general_purpose::STANDARD.encode(current),
+        "base64_decode" => base64::engine::general_purpose::STANDARD
+            .decode(current.trim())
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| String::from_utf8(bytes).map_err(|e| e.to_string())),
+        _ => return current.to_string(),
+    };
+    match decoded {
+        Ok(text) => text,
+        Err(_) => {
+            state.failed += 1;
+            current.to_string()
+        }
+    }
+}
+
+/// Resolve `$.a.b[0]`-style paths (or `/a/b/0` JSON pointers) against a parsed value.
+fn json_path_lookup<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    if path.starts_with('/') {
+        return value.pointer(path);
+    }
+    let trimmed = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+    for segment in trimmed.split('.').filter(|s| !s.is_empty()) {
+        let (key, indexes) = match segment.find('[') {
+            Some(pos) => (&segment[..pos], &segment[pos..]),
+            None => (segment, ""),
+        };
+        if !key.is_empty() {
+            current = match current {
+                serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?)?,
+                other => other.get(key)?,
+            };
+        }
+        for index in indexes.split('[').filter(|s| !s.is_empty()) {
+            let inner = index
+                .strip_suffix(']')?
+                .trim_matches(|c| c == '\'' || c == '"');
+            current = match inner.parse::<usize>() {
+                Ok(idx) => current.get(idx)?,
+                Err(_) => current.get(inner)?,
+            };
+        }
+    }
+    Some(current)
+}
+
+/// Extract a JSON value from the cell. Invalid JSON is counted as failed; a missing
+/// path leaves the target untouched.
+fn json_extract_value(
+    spec: &CsvMacroSpec,
+    input: &str,
+    state: &mut MacroRunState,
+) -> Option<String> {
+    if input.trim().is_empty() {
+        return None;
+    }
+    let Ok(parsed) = serde_json::from_str::<serde_json::Value>(input) else {
+        state.failed += 1;
+        return None;
+    };
+    let path = spec.json_path.as_deref().unwrap_or("$");
+    match json_path_lookup(&parsed, path)? {
+        serde_json::Value::String(text) => Some(text.clone()),
+        serde_json::Value::Null => Some(String::new()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Re-express a datetime in another zone. Naive values are read in the source zone;
+/// unparseable values count as failed and stay unchanged.
+fn timezone_macro_value(
+    spec: &CsvMacroSpec,
+    input: &str,
+    state: &mut MacroRunState,
+) -> Option<String> {
+    if input.trim().is_empty() {
+        return None;
+    }
+    let (source, target) = state.timezones?;
+    let Some(instant) = parse_datetime_value(input, spec.date_format.as_deref(), source) else {
+        state.failed += 1;
+        return None;
+    };
+    let format = spec
+        .output_format
+        .as_deref()
+        .unwrap_or("%Y-%m-%dT%H:%M:%S%:z");
+    let formatted = match target {
+        TargetZone::Named(tz) => format_or_none(instant.with_timezone(&tz).format(format)),
+        TargetZone::Fixed(offset) => format_or_none(instant.with_timezone(&offset).format(format)),
+    };
+    if formatted.is_none() {
+        state.failed += 1;
+    }
+    formatted
+}
+
+/// Build the rate table for `convert_rate`; codes are matched case-insensitively.
+fn load_macro_rates(spec: &CsvMacroSpec) -> Result<HashMap<String, f64>, String> {
+    let mut rates = HashMap::new();
+    if let Some(path) = &spec.rates_path {
+        let mut reader = csv::Reader::from_path(path).map_err(|e| e.to_string())?;
+        for record in reader.records() {
+            let record = record.map_err(|e| e.to_string())?;
+            let code = record.get(0).unwrap_or("").trim().to_uppercase();
+            let rate_text = record.get(1).unwrap_or("").trim();
+            let rate = rate_text
+                .parse::<f64>()
+                .map_err(|_| format!("invalid rate for {}: {}", code, rate_text))?;
+            rates.insert(code, rate);
+        }
+    }
+    for (code, rate) in spec.rates.iter().flatten() {
+        rates.insert(code.trim().to_uppercase(), *rate);
+    }
+    if rates.is_empty() {
+        return Err("convert_rate needs rates or a rates file".to_string());
+    }
+    Ok(rates)
+}
+
+/// Multiply (or divide) by the rate for the row's code. Non-numeric values and unknown
+/// codes count as failed and stay unchanged.
+fn rate_macro_value(
+    spec: &CsvMacroSpec,
+    input: &str,
+    row: &[impl AsRef<str>],
+    state: &mut MacroRunState,
+) -> Option<String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let code = match spec.rate_column {
+        Some(col) => row
+            .get(state.input_index(col))
+            .map(|v| v.as_ref())
+            .unwrap_or(""),
+        None => spec.rate_key.as_deref().unwrap_or(""),
+    };
+    let divide = spec.divide.unwrap_or(false);
+    let rate = state
+        .rates
+        .get(&code.trim().to_uppercase())
+        .copied()
+        .filter(|rate| !(divide && *rate == 0.0));
+    let (Ok(number), Some(rate)) = (trimmed.parse::<f64>(), rate) else {
+        state.failed += 1;
+        return None;
+    };
+    let converted = if divide { number / rate } else { number * rate };
+    Some(match spec.decimals {
+        Some(decimals) => format!("{:.*}", decimals, converted),
+        None => converted.to_string(),
+    })
+}
+
+/// Parse a latitude/longitude in decimal degrees or degrees-minutes-seconds, e.g.
+/// `40°26'46"N`, `N 40d 26.77m`, `-73.9857`. A hemisphere letter decides the axis when
+/// `axis` (`latitude` or `longitude`) is not given; the value must be within range.
+fn parse_coordinate(input: &str, axis: Option<&str>) -> Result<f64, String> {
+    let mut text = input.trim();
+    let mut hemisphere = None;
+    if let Some(first) = text.chars().next().filter(|c| "NSEW".contains(*c)) {
+        hemisphere = Some(first);
+        text = text[1..].trim_start();
+    } else if let Some(last) = text.chars().last().filter(|c| "NSEW".contains(*c)) {
+        hemisphere = Some(last);
+        text = text[..text.len() - 1].trim_end();
+    }
+    let negative = text.starts_with('-');
+    let text = text.trim_start_matches(['-', '+']).replace("deg", "°");
+    if text
+        .chars()
+        .any(|c| !(c.is_ascii_digit() || c.is_whitespace() || ".°º'′’\"″”:dms".contains(c)))
+    {
+        return Err(format!("'{}' is not a coordinate", input));
+    }
+    let parts = text
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("'{}' is not a coordinate", input))?;
+    if parts.is_empty() || parts.len() > 3 || parts[1..].iter().any(|p| *p >= 60.0) {
+        return Err(format!("'{}' is not a coordinate", input));
+    }
+    let mut value = parts[0]
+        + parts.get(1).copied().unwrap_or(0.0) / 60.0
+        + parts.get(2).copied().unwrap_or(0.0) / 3600.0;
+    let southern = matches!(hemisphere, Some('S' | 'W'));
+    if negative && southern {
+        return Err(format!("'{}' has conflicting signs", input));
+    }
+    if negative || southern {
+        value = -value;
+    }
+
+    let implied = hemisphere.map(|h| {
+        if matches!(h, 'N' | 'S') {
+            "latitude"
+        } else {
+            "longitude"
+        }
+    });
+    if let (Some(axis), Some(implied)) = (axis, implied) {
+        if axis != implied {
+            return Err(format!("'{}' is not a {}", input, axis));
+        }
+    }
+    let axis = axis.or(implied);
+    let limit = if axis == Some("latitude") {
+        90.0
+    } else {
+        180.0
+    };
+    if value.abs() > limit {
+        return Err(format!(
+            "{} {} is out of range",
+            axis.unwrap_or("coordinate"),
+            value
+        ));
+    }
+    Ok(value)
+}
+
+/// Rewrite a coordinate as decimal degrees, rounded to `decimals` when set. Blank cells
+/// are left alone; invalid or out-of-range values count as failed and stay unchanged.
+fn coordinate_macro_value(
+    spec: &CsvMacroSpec,
+    input: &str,
+    state: &mut MacroRunState,
+) -> Option<String> {
+    if input.trim().is_empty() {
+        return None;
+    }
+    let Ok(value) = parse_coordinate(input, spec.axis.as_deref()) else {
+        state.failed += 1;
+        return None;
+    };
+    Some(match spec.decimals {
+        Some(decimals) => format!("{:.*}", decimals, value),
+        None => value.to_string(),
+    })
+}
+/// Ops that carry context from earlier rows; rows are visited in file order.
+fn cross_row_macro_value(op: &str, current: &str, state: &mut MacroRunState) -> Option<String> {
+    if op == "fill_down" {
+        if current.trim().is_empty() {
+            return state.last_filled.clone();
+        }
+        state.last_filled = Some(current.to_string());
+        return None;
+    }
+    let trimmed = current.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let Ok(number) = trimmed.parse::<f64>() else {
+        state.failed += 1;
+        return None;
+    };
+    match op {
+        "cumulative_sum" => {
+            state.running_sum += number;
+            Some(state.running_sum.to_string())
+        }
+        "diff_previous" => {
+            let previous = state.previous_number.replace(number);
+            Some(
+                previous
+                    .map(|prev| (number - prev).to_string())
+                    .unwrap_or_default(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Compute the new value of the macro's target cell from `current` (the target cell, or the
+/// source column when one is set). `None` leaves the target cell untouched.
+fn macro_cell_value(
+    spec: &CsvMacroSpec,
+    current: &str,
+    row: &[impl AsRef<str>],
+    state: &mut MacroRunState,
+) -> Option<String> {
+    if spec.op == "if" {
+        let holds = spec
+            .condition
+            .as_ref()
+            .map(|condition| macro_condition_holds(condition, current, row, state))
+            .unwrap_or(false);
+        return if holds {
+            spec.then_value.clone()
+        } else {
+            spec.else_value.clone()
+        };
+    }
+    // Any other op can be guarded by a condition and is skipped when it does not hold.
+    if let Some(condition) = &spec.condition {
+        if !macro_condition_holds(condition, current, row, state) {
+            return None;
+        }
+    }
+    let value = match spec.op.as_str() {
+        "replace" => {
+            let find = spec.find.clone().unwrap_or_default();
+            let replace = spec.replace.clone().unwrap_or_default();
+            if find.is_empty() {
+                current.to_string()
+            } else {
+                current.replace(&find, &replace)
+            }
+        }
+        "uppercase" => current.to_uppercase(),
+        "lowercase" => current.to_lowercase(),
+        "trim" => current.trim().to_string(),
+        "prefix" => format!("{}{}", spec.text.clone().unwrap_or_default(), current),
+        "suffix" => format!("{}{}", current, spec.text.clone().unwrap_or_default()),
+        "hash" => {
+            if current.is_empty() {
+                current.to_string()
+            } else {
+                sha256_hex(current, spec.salt.as_deref().unwrap_or(""))
+            }
+        }
+        "mask" => {
+            let mask_char = spec
+                .mask_char
+                .as_deref()
+                .and_then(|m| m.chars().next())
+                .unwrap_or('*');
+            mask_value(current, spec.keep.unwrap_or(4), mask_char)
+        }
+        "redact" => spec
+            .text
+            .clone()
+            .unwrap_or_else(|| "[REDACTED]".to_string()),
+        "pseudonymize" => {
+            if current.is_empty() {
+                return None;
+            }
+            let next_id = state.pseudonyms.len() + 1;
+            state
+                .pseudonyms
+                .entry(current.to_string())
+                .or_insert_with(|| {
+                    format!("{}{:06}", spec.text.as_deref().unwrap_or("anon_"), next_id)
+                })
+                .clone()
+        }
+        "sequence" => {
+            let value = spec.start.unwrap_or(1) + spec.step.unwrap_or(1) * state.sequence_index;
+            state.sequence_index += 1;
+            let width = spec.width.unwrap_or(0);
+            if value < 0 {
+                format!("-{:0width$}", value.unsigned_abs(), width = width)
+            } else {
+                format!("{:0width$}", value, width = width)
+            }
+        }
+        "uuid" => random_uuid_v4(&mut rand::thread_rng()),
+        "pad_left" | "pad_right" => {
+            let pad_char = spec
+                .pad_char
+                .as_deref()
+                .and_then(|p| p.chars().next())
+                .unwrap_or(' ');
+            pad_value(
+                current,
+                spec.width.unwrap_or(0),
+                pad_char,
+                spec.op == "pad_left",
+            )
+        }
+        "truncate" => match spec.length {
+            Some(max_len) => {
+                truncate_value(current, max_len, spec.ellipsis.as_deref().unwrap_or(""))
+            }
+            None => current.to_string(),
+        },
+        "substring" => substring_value(current, spec.start.unwrap_or(0), spec.length),
+        "strip_thousands" | "decimal_to_point" | "decimal_to_comma" | "round" | "scale"
+        | "format_number" => numeric_macro_value(spec, current, state),
+        "nfc" => {
+            use unicode_normalization::UnicodeNormalization;
+            current.nfc().collect()
+        }
+        "nfkc" => {
+            use unicode_normalization::UnicodeNormalization;
+            current.nfkc().collect()
+        }
+        "strip_invisible" => current
+            .chars()
+            .filter(|ch| !is_invisible_char(*ch))
+            .collect(),
+        "strip_accents" => strip_accents(current),
+        "repair_mojibake" => repair_mojibake(current).unwrap_or_else(|| current.to_string()),
+        "clean_invisible" => clean_invisible(current),
+        "title_case" => title_case(current, spec.locale.as_deref().unwrap_or("en")),
+        "sentence_case" => sentence_case(current),
+        "snake_case" => identifier_words(current).join("_"),
+        "kebab_case" => identifier_words(current).join("-"),
+        "camel_case" => camel_case(current, false),
+        "pascal_case" => camel_case(current, true),
+        "url_encode" | "url_decode" | "html_encode" | "html_decode" | "base64_encode"
+        | "base64_decode" => codec_macro_value(&spec.op, current, state),
+        "json_extract" => return json_extract_value(spec, current, state),
+        "convert_timezone" => return timezone_macro_value(spec, current, state),
+        "convert_rate" => return rate_macro_value(spec, current, row, state),
+        "normalize_coordinate" => return coordinate_macro_value(spec, current, state),
+        "cumulative_sum" | "diff_previous" | "fill_down" => {
+            return cross_row_macro_value(&spec.op, current, state)
+        }
+        _ => current.to_string(),
+    };
+    Some(value)
+}
+
+/// Run the user script with `row` bound to a header → value map. The script may mutate `row`
+/// or return a map; any returned keys that match headers overwrite those cells.
+fn apply_script_to_row(
+    spec: &CsvMacroSpec,
+    row: &mut [String],
+    state: &mut MacroRunState,
+) -> Result<bool, String> {
+    let Some(script) = state.script.as_ref() else {
+        return Ok(false);
+    };
+    if let Some(max_rows) = spec.max_rows {
+        if state.rows_seen > max_rows {
+            return Err(format!("script row limit of {} exceeded", max_rows));
+        }
+    }
+    let mut map = rhai::Map::new();
+    for (idx, name) in state.headers.iter().enumerate() {
+        let value = row.get(idx).cloned().unwrap_or_default();
+        map.insert(name.as_str().into(), value.into());
+    }
+    let mut scope = rhai::Scope::new();
+    scope.push("row", map);
+    scope.push("row_index", (state.rows_seen - 1) as i64);
+    let result = script
+        .run(&mut scope)
+        .map_err(|e| format!("script error at row {}: {}", state.rows_seen - 1, e))?;
+    let updated = match result.try_cast::<rhai::Map>() {
+        Some(returned) => returned,
+        None => scope.get_value::<rhai::Map>("row").unwrap_or_default(),
+    };
+    let mut changed = false;
+    for (idx, name) in state.headers.iter().enumerate() {
+        let Some(value) = updated.get(name.as_str()) else {
+            continue;
+        };
+        let value = value.to_string();
+        if idx < row.len() && row[idx] != value {
+            row[idx] = value;
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Fuel granted to a plugin for each row; exhausting it aborts the macro.
+const PLUGIN_FUEL_PER_ROW: u64 = 10_000_000;
+
+/// A loaded WASM transform plugin.
+///
+/// Plugin ABI: the module exports `memory`, `alloc(len: i32) -> i32` and
+/// `transform(ptr: i32, len: i32) -> i64`. The input is the row as a UTF-8 JSON array of
+/// strings; the result packs `(out_ptr << 32) | out_len` pointing at the transformed row
+/// in the same JSON format.
+struct WasmPlugin {
+    store: wasmi::Store<()>,
+    memory: wasmi::Memory,
+    alloc: wasmi::TypedFunc<i32, i32>,
+    transform: wasmi::TypedFunc<(i32, i32), i64>,
+}
+
+impl WasmPlugin {
+    fn load(path: &PathBuf) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = wasmi::Engine::new(&config);
+        let module = wasmi::Module::new(&engine, &bytes).map_err(|e| e.to_string())?;
+        let mut store = wasmi::Store::new(&engine, ());
+        store
+            .set_fuel(PLUGIN_FUEL_PER_ROW)
+            .map_err(|e| e.to_string())?;
+        let linker = wasmi::Linker::<()>::new(&engine);
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| e.to_string())?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| "plugin does not export `memory`".to_string())?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(|e| format!("plugin `alloc` export: {}", e))?;
+        let transform = instance
+            .get_typed_func::<(i32, i32), i64>(&store, "transform")
+            .map_err(|e| format!("plugin `transform` export: {}", e))?;
+        Ok(Self {
+            store,
+            memory,
+            alloc,
+            transform,
+        })
+    }
+
+    fn transform_row(&mut self, row: &[String]) -> Result<Vec<String>, String> {
+        self.store
+            .set_fuel(PLUGIN_FUEL_PER_ROW)
+            .map_err(|e| e.to_string())?;
+        let input = serde_json::to_vec(row).map_err(|e| e.to_string())?;
+        let len = i32::try_from(input.len()).map_err(|e| e.to_string())?;
+        let ptr = self
+            .alloc
+            .call(&mut self.store, len)
+            .map_err(|e| e.to_string())?;
+        self.memory
+            .write(&mut self.store, ptr as usize, &input)
+            .map_err(|e| e.to_string())?;
+        let packed = self
+            .transform
+            .call(&mut self.store, (ptr, len))
+            .map_err(|e| e.to_string())?;
+        let out_ptr = (packed as u64 >> 32) as usize;
+        let out_len = (packed as u64 & 0xFFFF_FFFF) as usize;
+        let mut output = vec![0u8; out_len];
+        self.memory
+            .read(&self.store, out_ptr, &mut output)
+            .map_err(|e| e.to_string())?;
+        serde_json::from_slice(&output).map_err(|e| format!("plugin returned invalid row: {}", e))
+    }
+}
+
+fn apply_plugin_to_row(row: &mut Vec<String>, state: &mut MacroRunState) -> Result<bool, String> {
+    let row_number = state.rows_seen - 1;
+    let Some(plugin) = state.plugin.as_mut() else {
+        return Ok(false);
+    };
+    let next = plugin
+        .transform_row(row)
+        .map_err(|e| format!("plugin error at row {}: {}", row_number, e))?;
+    if next == *row {
+        return Ok(false);
+    }
+    *row = next;
+    Ok(true)
+}
+
+/// Apply the macro to one row in place, returning whether the target cell changed.
+fn apply_macro_to_row(
+    spec: &CsvMacroSpec,
+    row: &mut Vec<String>,
+    state: &mut MacroRunState,
+) -> Result<bool, String> {
+    state.rows_seen += 1;
+    if let Some(idx) = state.inserted_column {
+        row.insert(idx.min(row.len()), String::new());
+    }
+    if spec.op == "script" {
+        return apply_script_to_row(spec, row, state);
+    }
+    if spec.op == "plugin" {
+        return apply_plugin_to_row(row, state);
+    }
+    let col = state.inserted_column.unwrap_or(spec.column);
+    if col >= row.len() {
+        row.resize(col + 1, String::new());
+    }
+    let input = match spec.source_column {
+        Some(source) => row
+            .get(state.input_index(source))
+            .cloned()
+            .unwrap_or_default(),
+        None => row[col].clone(),
+    };
+    match macro_cell_value(spec, &input, row, state) {
+        Some(next) if next != row[col] => {
+            row[col] = next;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Borrowing variant of `apply_macro_to_row` for single-cell ops on a row that already
+/// has the target column; returns the new value only when the cell changed.
+fn macro_field_update(
+    spec: &CsvMacroSpec,
+    fields: &[&str],
+    state: &mut MacroRunState,
+) -> Option<String> {
+    state.rows_seen += 1;
+    let current = fields[spec.column];
+    let input = match spec.source_column {
+        Some(source) => fields.get(source).copied().unwrap_or(""),
+        None => current,
+    };
+    macro_cell_value(spec, input, fields, state).filter(|next| next != current)
+}
+
+/// Set up a macro run: inserts the optional new column into `headers` (at `spec.column`)
+/// and loads the plugin for plugin ops.
+fn prepare_macro_state(
+    app: &tauri::AppHandle,
+    spec: &CsvMacroSpec,
+    headers: &mut Vec<String>,
+) -> Result<MacroRunState, String> {
+    let new_column_index = spec.new_column.as_ref().map(|name| {
+        let idx = spec.column.min(headers.len());
+        headers.insert(idx, name.clone());
+        idx
+    });
+    let mut run_state = MacroRunState::new(spec, headers, new_column_index)?;
+    if spec.op == "plugin" {
+        let plugin_path = plugin_path(app, spec.plugin.as_deref().unwrap_or(""))?;
+        run_state.plugin = Some(Box::new(WasmPlugin::load(&plugin_path)?));
+    }
+    Ok(run_state)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn apply_macro_to_file(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    target_path: UserPath,
+    dialect: CsvDialect,
+    spec: CsvMacroSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<CsvMacroResult, String> {
+    ensure_can_write(&target_path, overwrite)?;
+    ensure_space_for_rewrite(&path, &target_path, &dialect, "apply_macro_to_file")?;
+    let started = Instant::now();
+    let _taskbar = TaskbarProgress::start(&app);
+    let dry_run = dry_run.unwrap_or(false);
+    let result = execute_macro_file(
+        app.clone(),
+        state,
+        path.to_string(),
+        target_path.to_string(),
+        dialect,
+        spec,
+        dry_run,
+    );
+    let title = tr_app_with(&app, "job_macro", &[]);
+    notify_job_finished(&app, started, &title, &result, |done| {
+        tr_app_with(
+            &app,
+            "summary_macro",
+            &[
+                ("applied", &done.applied),
+                ("failed", &done.failed),
+                ("file", &path_display_name(&done.output_path)),
+            ],
+        )
+    });
+    result
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(path = %path, op = %spec.op, applied = tracing::field::Empty)
+)]
+fn execute_macro_file(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: String,
+    target_path: String,
+    dialect: CsvDialect,
+    spec: CsvMacroSpec,
+    dry_run: bool,
+) -> Result<CsvMacroResult, String> {
+    ensure_not_read_only(&state, &target_path)?;
+    let mut reader = dialect.reader(&path)?;
+
+    let mut headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    let mut run_state = prepare_macro_state(&app, &spec, &mut headers)?;
+    run_state.column_types = column_types_for(&headers, &load_file_settings(&app, &path));
+
+    let mut writer = dialect.output_writer(&target_path, dry_run)?;
+
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+
+    let (applied, counts) = macro_file_pass(&mut reader, &mut writer, &spec, &mut run_state)?;
+    let hashes = dialect.finish_writer(writer, &target_path)?;
+    if dry_run {
+        return Ok(CsvMacroResult {
+            hashes,
+            output_path: target_path,
+            applied,
+            failed: run_state.failed,
+            counts,
+            dry_run,
+        });
+    }
+
+    tracing::Span::current().record("applied", applied);
+    record_operation(
+        &app,
+        "apply_macro_to_file",
+        Some(&path),
+        Some(&target_path),
+        Some(applied),
+        serde_json::to_value(&spec).unwrap_or_default(),
+    );
+    Ok(CsvMacroResult {
+        hashes,
+        output_path: target_path,
+        applied,
+        failed: run_state.failed,
+        counts,
+        dry_run,
+    })
+}
+
+/// Row loop of `apply_macro_to_file`; the headers are already written. Returns the
+/// number of changed rows and the run's counts.
+fn macro_file_pass<W: Write>(
+    reader: &mut csv::Reader<BufReader<File>>,
+    writer: &mut csv::Writer<W>,
+    spec: &CsvMacroSpec,
+    run_state: &mut MacroRunState,
+) -> Result<(usize, RowCounts), String> {
+    let mut applied = 0usize;
+    let mut counts = RowCounts::default();
+    // Single-cell ops borrow the record's fields and only allocate the changed value.
+    let borrowed =
+        run_state.inserted_column.is_none() && !matches!(spec.op.as_str(), "script" | "plugin");
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        counts.rows_read += 1;
+        counts.rows_written += 1;
+        if borrowed && spec.column < record.len() {
+            let mut fields: Vec<&str> = record.iter().collect();
+            let next = macro_field_update(spec, &fields, run_state);
+            if let Some(next) = &next {
+                fields[spec.column] = next;
+                applied += 1;
+                counts.cells_modified += 1;
+            }
+            writer.write_record(&fields).map_err(|e| e.to_string())?;
+            continue;
+        }
+        let mut row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
+        if apply_macro_to_row(spec, &mut row, run_state)? {
+            applied += 1;
+            let before = record.iter().map(|s| s.to_string()).collect();
+            counts.cells_modified += count_changed_cells(before, run_state.inserted_column, &row);
+        }
+        writer.write_record(&row).map_err(|e| e.to_string())?;
+    }
+
+    Ok((applied, counts))
+}
+
+const HLL_PRECISION: u32 = 12;
+
+/// Fixed-memory cardinality sketch (2^12 one-byte registers, ~1.6% standard error).
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rest = hash << HLL_PRECISION;
+        let rank = (rest.leading_zeros() + 1).min(64 - HLL_PRECISION + 1) as u8;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    fn estimate(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        // Linear counting is more accurate while many registers are still empty.
+        if raw <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            raw.round() as usize
+        }
+    }
+}
+
+const FILE_SETTINGS_FILE: &str = "file_settings.json";
+const COLUMN_TYPES: &[&str] = &["integer", "number", "boolean", "date", "datetime", "string"];
+
+/// Settings remembered per document.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct FileSettings {
+    /// Header → type chosen over the inferred one. Stats, sorting, filters, validation and
+    /// typed export use it instead of looking at the data.
+    #[serde(default)]
+    pub column_types: std::collections::BTreeMap<String, String>,
+}
+
+fn load_file_settings(app: &tauri::AppHandle, path: &str) -> FileSettings {
+    app_config_file(app, FILE_SETTINGS_FILE)
+        .and_then(|file| read_json_config::<HashMap<String, FileSettings>>(&file))
+        .ok()
+        .and_then(|mut settings| settings.remove(path))
+        .unwrap_or_default()
+}
+
+/// The type override for each of `headers`, in order.
+fn column_types_for(headers: &[String], settings: &FileSettings) -> Vec<Option<String>> {
+    headers
+        .iter()
+        .map(|header| settings.column_types.get(header).cloned())
+        .collect()
+}
+
+#[tauri::command]
+fn get_file_settings(app: tauri::AppHandle, path: UserPath) -> Result<FileSettings, String> {
+    Ok(load_file_settings(&app, &path))
+}
+
+/// Override the type of the column named `column`; `None` goes back to inference.
+#[tauri::command]
+fn set_column_type(
+    app: tauri::AppHandle,
+    path: UserPath,
+    column: String,
+    column_type: Option<String>,
+) -> Result<FileSettings, String> {
+    if let Some(kind) = &column_type {
+        if !COLUMN_TYPES.contains(&kind.as_str()) {
+            return Err(format!("unknown column type: {}", kind));
+        }
+    }
+    let file = app_config_file(&app, FILE_SETTINGS_FILE)?;
+    let mut all: HashMap<String, FileSettings> = read_json_config(&file)?;
+    let settings = all.entry(path.to_string()).or_default();
+    match column_type {
+        Some(kind) => settings.column_types.insert(column, kind),
+        None => settings.column_types.remove(&column),
+    };
+    let settings = settings.clone();
+    if settings.column_types.is_empty() {
+        all.remove(path.as_str());
+    }
+    write_json_config(&file, &all)?;
+    Ok(settings)
+}
+
+/// A cell read as a column type, for ordering.
+enum TypedValue<'a> {
+    Number(f64),
+    Instant(chrono::DateTime<chrono::Utc>),
+    Text(&'a str),
+}
+
+impl TypedValue<'_> {
+    /// Parse `cell` as `kind` from [`COLUMN_TYPES`]; `None` when it doesn't parse. Dates
+    /// accept any of the built-in formats, so mixed columns still order chronologically.
+    fn parse<'a>(kind: &str, cell: &'a str) -> Option<TypedValue<'a>> {
+        match kind {
+            "integer" | "number" => cell
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|n| !n.is_nan())
+                .map(TypedValue::Number),
+            "boolean" => parse_boolean_cell(cell.trim())
+                .map(|flag| TypedValue::Number(if flag { 1.0 } else { 0.0 })),
+            "date" | "datetime" => {
+                parse_datetime_value(cell, None, chrono_tz::UTC).map(TypedValue::Instant)
+            }
+            _ => Some(TypedValue::Text(cell)),
+        }
+    }
+
+    fn parse_pair<'a>(
+        kind: &str,
+        left: &'a str,
+        right: &'a str,
+    ) -> (Option<TypedValue<'a>>, Option<TypedValue<'a>>) {
+        (Self::parse(kind, left), Self::parse(kind, right))
+    }
+
+    fn compare(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (TypedValue::Number(a), TypedValue::Number(b)) => a.total_cmp(b),
+            (TypedValue::Instant(a), TypedValue::Instant(b)) => a.cmp(b),
+            (TypedValue::Text(a), TypedValue::Text(b)) => a.cmp(b),
+            _ => std::cmp::Ordering::Equal,
+        }
+    }
+}
+
+/// Order untyped sort cells: empty, then numbers, then text. Unlike [`compare_cell_values`]
+/// this is a total order, so mixed columns sort the same way however rows arrive.
+fn compare_sort_cells(left: &str, right: &str) -> std::cmp::Ordering {
+    let rank = |cell: &str| match cell.trim() {
+        "" => (0, None),
+        trimmed => match trimmed.parse::<f64>() {
+            Ok(number) => (1, Some(number)),
+            Err(_) => (2, None),
+        },
+    };
+    match (rank(left), rank(right)) {
+        ((_, Some(a)), (_, Some(b))) => a.total_cmp(&b),
+        ((a, _), (b, _)) if a != b => a.cmp(&b),
+        _ => left.cmp(right),
+    }
+}
+
+/// Compare two cells as a column `kind`, falling back to [`compare_cell_values`] without a
+/// type or when either side doesn't parse.
+fn compare_typed_values(kind: Option<&str>, left: &str, right: &str) -> std::cmp::Ordering {
+    match kind.map(|kind| TypedValue::parse_pair(kind, left, right)) {
+        Some((Some(a), Some(b))) => a.compare(&b),
+        _ => compare_cell_values(left, right),
+    }
+}
+
+/// Distinct-set cap per column once the input is above the soft size limit.
+const LARGE_FILE_MAX_DISTINCT: usize = 1000;
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn compute_column_stats(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    max_distinct: Option<usize>,
+) -> Result<Vec<ColumnStat>, String> {
+    let max_distinct = max_distinct.unwrap_or(5000);
+    let max_distinct = if check_file_size(&app, &path, "compute_column_stats")? {
+        max_distinct.min(LARGE_FILE_MAX_DISTINCT)
+    } else {
+        max_distinct
+    };
+    collect_column_stats(
+        &path,
+        &dialect,
+        max_distinct,
+        &load_file_settings(&app, &path),
+    )
+}
+
+const COLUMN_STAT_EXAMPLES: usize = 3;
+
+/// Single streaming pass behind `compute_column_stats` and the data dictionary. A type
+/// override in `settings` replaces the inferred type.
+fn collect_column_stats(
+    path: &str,
+    dialect: &CsvDialect,
+    max_distinct: usize,
+    settings: &FileSettings,
+) -> Result<Vec<ColumnStat>, String> {
+    let mut reader = dialect.reader(path)?;
+
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    struct StatInternal {
+        non_empty: usize,
+        number_count: usize,
+        distinct: HashSet<String>,
+        distinct_truncated: bool,
+        sketch: HyperLogLog,
+        min_number: Option<(f64, String)>,
+        max_number: Option<(f64, String)>,
+        min_text: Option<String>,
+        max_text: Option<String>,
+        examples: Vec<String>,
+    }
+
+    let mut stats: Vec<StatInternal> = headers
+        .iter()
+        .map(|_| StatInternal {
+            non_empty: 0,
+            number_count: 0,
+            distinct: HashSet::new(),
+            distinct_truncated: false,
+            sketch: HyperLogLog::new(),
+            min_number: None,
+            max_number: None,
+            min_text: None,
+            max_text: None,
+            examples: Vec::new(),
+        })
+        .collect();
+
+    let mut rows = 0usize;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        rows += 1;
+        for (idx, value) in record.iter().enumerate() {
+            if idx >= stats.len() {
+                continue;
+            }
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let stat = &mut stats[idx];
+            stat.non_empty += 1;
+            if let Ok(number) = value.parse::<f64>() {
+                stat.number_count += 1;
+                if stat
+                    .min_number
+                    .as_ref()
+                    .is_none_or(|(min, _)| number < *min)
+                {
+                    stat.min_number = Some((number, value.to_string()));
+                }
+                if stat
+                    .max_number
+                    .as_ref()
+                    .is_none_or(|(max, _)| number > *max)
+                {
+                    stat.max_number = Some((number, value.to_string()));
+                }
+            }
+            if stat.min_text.as_deref().is_none_or(|min| value < min) {
+                stat.min_text = Some(value.to_string());
+            }
+            if stat.max_text.as_deref().is_none_or(|max| value > max) {
+                stat.max_text = Some(value.to_string());
+            }
+            if stat.examples.len() < COLUMN_STAT_EXAMPLES
+                && !stat.examples.iter().any(|e| e == value)
+            {
+                stat.examples.push(value.to_string());
+            }
+            stat.sketch.insert(value);
+            if !stat.distinct_truncated && !stat.distinct.contains(value) {
+                if stat.distinct.len() < max_distinct {
+                    stat.distinct.insert(value.to_string());
+                } else {
+                    stat.distinct_truncated = true;
+                }
+            }
+        }
+    }
+
+    let results = headers
+        .into_iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            let stat = &stats[idx];
+            let (inferred, is_number) = match settings.column_types.get(&name) {
+                Some(kind) => (kind.as_str(), kind == "integer" || kind == "number"),
+                None => {
+                    let is_number = stat.non_empty > 0 && stat.number_count == stat.non_empty;
+                    (if is_number { "number" } else { "text" }, is_number)
+                }
+            };
+            let (min, max) = if is_number {
+                (
+                    stat.min_number.as_ref().map(|(_, raw)| raw.clone()),
+                    stat.max_number.as_ref().map(|(_, raw)| raw.clone()),
+                )
+            } else {
+                (stat.min_text.clone(), stat.max_text.clone())
+            };
+            ColumnStat {
+                name,
+                rows,
+                non_empty: stat.non_empty,
+                distinct: stat.distinct.len(),
+                distinct_truncated: stat.distinct_truncated,
+                distinct_estimate: if stat.distinct_truncated {
+                    stat.sketch.estimate().max(stat.distinct.len())
+                } else {
+                    stat.distinct.len()
+                },
+                inferred: inferred.to_string(),
+                min,
+                max,
+                examples: stat.examples.clone(),
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+/// Memory one column index may use before its row lists spill to disk.
+const COLUMN_INDEX_MEMORY: usize = 64 * 1024 * 1024;
+
+/// Column indexes kept at once; the least recently used goes first.
+const COLUMN_INDEX_CACHE: usize = 8;
+
+/// Distinct values one column index may hold. Values stay in memory, so a column that is
+/// unique per row (ids, timestamps) is refused rather than indexed.
+const COLUMN_INDEX_MAX_VALUES: usize = 1_000_000;
+
+/// Rows holding one value: blocks already spilled, then the ones still in memory.
+#[derive(Default)]
+struct ColumnPostings {
+    count: usize,
+    /// (row, record start byte).
+    rows: Vec<(u64, u64)>,
+    /// (offset in the spill file, entries).
+    spilled: Vec<(u64, usize)>,
+}
+
+/// Value → rows of one column, built in one pass and reused while the file is unchanged.
+struct ColumnIndex {
+    file_len: u64,
+    modified: u64,
+    total_rows: usize,
+    values: HashMap<String, ColumnPostings>,
+    /// Row lists moved out of memory once the index outgrew `COLUMN_INDEX_MEMORY`.
+    spill: Option<TempFile>,
+    last_used: Instant,
+}
+
+impl ColumnIndex {
+    /// Row lists spill once the index passes `memory_limit` bytes, if they hold at least a
+    /// quarter of it; the values themselves stay in memory, so more than `max_values` of
+    /// them is an error.
+    fn build(
+        path: &str,
+        dialect: &CsvDialect,
+        column: usize,
+        spill_dir: &std::path::Path,
+        memory_limit: usize,
+        max_values: usize,
+    ) -> Result<Self, String> {
+        let (file_len, modified) = file_signature(&PathBuf::from(path))?;
+        let mut reader = dialect.reader(path)?;
+        if column >= reader.headers().map_err(|e| e.to_string())?.len() {
+            return Err("column out of range".to_string());
+        }
+        let mut index = Self {
+            file_len,
+            modified,
+            total_rows: 0,
+            values: HashMap::new(),
+            spill: None,
+            last_used: Instant::now(),
+        };
+        let (mut value_memory, mut row_memory) = (0, 0);
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+            let value = record.get(column).unwrap_or("");
+            let byte = record.position().map_or(0, |position| position.byte());
+            if !index.values.contains_key(value) {
+                if index.values.len() >= max_values {
+                    return Err(structured_error(
+                        "too_many_values",
+                        &format!("column has more than {} distinct values", max_values),
+                        serde_json::json!({ "column": column, "limit": max_values }),
+                    ));
+                }
+                value_memory += value.len() + 64;
+                index
+                    .values
+                    .insert(value.to_string(), ColumnPostings::default());
+            }
+            if let Some(postings) = index.values.get_mut(value) {
+                postings.count += 1;
+                postings.rows.push((index.total_rows as u64, byte));
+            }
+            row_memory += 16;
+            index.total_rows += 1;
+            if value_memory + row_memory > memory_limit && row_memory >= memory_limit / 4 {
+                value_memory += index.spill_rows(spill_dir)? * 16;
+                row_memory = 0;
+            }
+        }
+        Ok(index)
+    }
+
+    /// Append every in-memory row list to the spill file. Returns the blocks written.
+    fn spill_rows(&mut self, spill_dir: &std::path::Path) -> Result<usize, String> {
+        if self.spill.is_none() {
+            self.spill = Some(TempFile::new_in(spill_dir, "column-index", "bin")?);
+        }
+        let Some(spill) = &self.spill else {
+            return Err("no spill file".to_string());
+        };
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(spill.path())
+            .map_err(|e| e.to_string())?;
+        let mut offset = file.metadata().map_err(|e| e.to_string())?.len();
+        let mut output = std::io::BufWriter::new(file);
+        let mut blocks = 0;
+        for postings in self.values.values_mut() {
+            if postings.rows.is_empty() {
+                continue;
+            }
+            for (row, byte) in &postings.rows {
+                output
+                    .write_all(&row.to_le_bytes())
+                    .and_then(|_| output.write_all(&byte.to_le_bytes()))
+                    .map_err(|e| e.to_string())?;
+            }
+            postings.spilled.push((offset, postings.rows.len()));
+            offset += postings.rows.len() as u64 * 16;
+            postings.rows = Vec::new();
+            blocks += 1;
+        }
+        output.flush().map_err(|e| e.to_string())?;
+        Ok(blocks)
+    }
+
+    /// (row, record start byte) of every row holding `value`, in file order.
+    fn rows_with(&self, value: &str) -> Result<Vec<(u64, u64)>, String> {
+        let Some(postings) = self.values.get(value) else {
+            return Ok(Vec::new());
+        };
+        let mut rows = Vec::with_capacity(postings.count);
+        if let Some(spill) = self.spill.as_ref().filter(|_| !postings.spilled.is_empty()) {
+            let mut file = File::open(spill.path()).map_err(|e| e.to_string())?;
+            for (offset, entries) in &postings.spilled {
+                let mut block = vec![0u8; entries * 16];
+                file.seek(SeekFrom::Start(*offset))
+                    .and_then(|_| file.read_exact(&mut block))
+                    .map_err(|e| e.to_string())?;
+                rows.extend(block.chunks_exact(16).map(|entry| {
+                    let (row, byte) = entry.split_at(8);
+                    (
+                        u64::from_le_bytes(row.try_into().unwrap_or_default()),
+                        u64::from_le_bytes(byte.try_into().unwrap_or_default()),
+                    )
+                }));
+            }
+        }
+        rows.extend_from_slice(&postings.rows);
+        Ok(rows)
+    }
+}
+
+/// The records starting at each of `starts`, as `csv` reports record positions.
+fn read_records_at(
+    path: &str,
+    dialect: &CsvDialect,
+    starts: &[u64],
+) -> Result<Vec<Vec<String>>, String> {
+    let mut reader = dialect
+        .reader_builder()
+        .has_headers(false)
+        .from_reader(File::open(path).map_err(|e| e.to_string())?);
+    let mut record = csv::StringRecord::new();
+    let mut rows = Vec::with_capacity(starts.len());
+    for start in starts {
+        reader
+            .seek_raw(SeekFrom::Start(*start), csv::Position::new())
+            .map_err(|e| e.to_string())?;
+        if !reader.read_record(&mut record).map_err(|e| e.to_string())? {
+            return Err("file changed since it was indexed".to_string());
+        }
+        rows.push(record.iter().map(|s| s.to_string()).collect());
+    }
+    Ok(rows)
+}
+
+/// Run `f` on the cached index of `column`, building it first when there is none or the
+/// file changed since. Also returns whether the cached index was used. Builds happen
+/// outside the cache lock, so other columns stay usable meanwhile.
+fn with_column_index<T>(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    path: &str,
+    dialect: &CsvDialect,
+    column: usize,
+    f: impl FnOnce(&ColumnIndex) -> Result<T, String>,
+) -> Result<(T, bool), String> {
+    let key = format!("{}#{}", index_key(path, dialect), column);
+    let signature = file_signature(&PathBuf::from(path))?;
+    let cached = state
+        .column_indexes
+        .lock()
+        .map_err(|_| "lock poisoned")?
+        .get(&key)
+        .is_some_and(|index| (index.file_len, index.modified) == signature);
+    let built = if cached {
+        None
+    } else {
+        let spill_dir = spill_dir(&load_spill_settings(app));
+        Some(ColumnIndex::build(
+            path,
+            dialect,
+            column,
+            &spill_dir,
+            COLUMN_INDEX_MEMORY,
+            COLUMN_INDEX_MAX_VALUES,
+        )?)
+    };
+    let mut indexes = state.column_indexes.lock().map_err(|_| "lock poisoned")?;
+    if let Some(index) = built {
+        indexes.remove(&key);
+        if indexes.len() >= COLUMN_INDEX_CACHE {
+            let oldest = indexes
+                .iter()
+                .min_by_key(|(_, index)| index.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                indexes.remove(&oldest);
+            }
+        }
+        indexes.insert(key.clone(), index);
+    }
+    let index = indexes.get_mut(&key).ok_or("column index missing")?;
+    index.last_used = Instant::now();
+    Ok((f(index)?, cached))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnFilterResult {
+    pub rows: Vec<Vec<String>>,
+    /// Source row of each entry in `rows`.
+    pub row_indexes: Vec<usize>,
+    /// Matching rows in the whole file.
+    pub total: usize,
+    /// False when this call had to build the column index.
+    pub cached: bool,
+}
+
+/// Rows whose `column` equals one of `values`, in file order, paged by `offset` and `limit`
+/// (default 1000). The column's value index is built on first use and kept, so filtering
+/// the same column again only reads the matching rows. Runs off the main thread, as a first
+/// call reads the whole file.
+#[tauri::command]
+async fn filter_by_column_values(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+    column: usize,
+    values: Vec<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<ColumnFilterResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dialect = dialect.unwrap_or_default();
+        let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+        let state = app.state::<AppState>();
+        let ((total, matches), cached) =
+            with_column_index(&app, &state, &path, &dialect, column, |index| {
+                let mut matches = Vec::new();
+                for value in values.iter().collect::<HashSet<_>>() {
+                    matches.extend(index.rows_with(value)?);
+                }
+                matches.sort_unstable();
+                Ok((matches.len(), matches))
+            })?;
+
+        let (row_indexes, starts): (Vec<usize>, Vec<u64>) = matches
+            .into_iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(1000))
+            .map(|(row, byte)| (row as usize, byte))
+            .unzip();
+        let rows = read_records_at(&path, &dialect, &starts)?;
+        Ok(ColumnFilterResult {
+            rows,
+            row_indexes,
+            total,
+            cached,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DistinctValue {
+    pub value: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DistinctValues {
+    /// Most frequent first, ties by value.
+    pub values: Vec<DistinctValue>,
+    /// Distinct values in the column, including ones past `limit`.
+    pub distinct: usize,
+    pub total_rows: usize,
+    pub cached: bool,
+}
+
+/// Distinct values of `column` with their row counts, up to `limit` (default 1000), from
+/// the same cached index as `filter_by_column_values`.
+#[tauri::command]
+async fn list_distinct_values(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+    column: usize,
+    limit: Option<usize>,
+) -> Result<DistinctValues, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dialect = dialect.unwrap_or_default();
+        let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+        let state = app.state::<AppState>();
+        let (result, cached) = with_column_index(&app, &state, &path, &dialect, column, |index| {
+            let mut values: Vec<DistinctValue> = index
+                .values
+                .iter()
+                .map(|(value, postings)| DistinctValue {
+                    value: value.clone(),
+                    count: postings.count,
+                })
+                .collect();
+            values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+            values.truncate(limit.unwrap_or(1000));
+            Ok(DistinctValues {
+                values,
+                distinct: index.values.len(),
+                total_rows: index.total_rows,
+                cached: true,
+            })
+        })?;
+        Ok(DistinctValues { cached, ..result })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Drop the cached column indexes of `path`, or all of them, freeing their memory and
+/// spill files.
+#[tauri::command]
+fn clear_column_indexes(state: tauri::State<AppState>, path: Option<String>) -> Result<(), String> {
+    let mut indexes = state.column_indexes.lock().map_err(|_| "lock poisoned")?;
+    match path {
+        Some(path) => indexes.retain(|key, _| !key.starts_with(&format!("{}::", path))),
+        None => indexes.clear(),
+    }
+    Ok(())
+}
+
+/// A `top_k_rows` candidate; entries that rank worse compare greater, so the heap's top is
+/// the one to drop.
+struct TopKEntry {
+    key: TypedValue<'static>,
+    descending: bool,
+    row: usize,
+    values: Vec<String>,
+}
+
+impl Ord for TopKEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let by_key = self.key.compare(&other.key);
+        let by_key = if self.descending {
+            by_key.reverse()
+        } else {
+            by_key
+        };
+        // Among equal values the earlier row wins.
+        by_key.then(self.row.cmp(&other.row))
+    }
+}
+
+impl PartialOrd for TopKEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for TopKEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for TopKEntry {}
+
+#[derive(Serialize, Deserialize)]
+pub struct TopKRows {
+    /// Best first.
+    pub rows: Vec<Vec<String>>,
+    /// Source row of each entry in `rows`.
+    pub row_indexes: Vec<usize>,
+    /// The type the column was compared as.
+    pub value_type: String,
+    pub rows_read: usize,
+    /// Rows whose cell is empty or doesn't parse as `value_type`; never returned.
+    pub skipped: usize,
+}
+
+/// The `k` rows with the largest values in `column` (smallest with `descending: false`), in
+/// one pass that keeps only `k` rows in memory. The column is compared as `value_type`, its
+/// type override, or `number`; only number and date types are accepted.
+#[tauri::command]
+fn top_k_rows(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: Option<CsvDialect>,
+    column: usize,
+    k: usize,
+    descending: Option<bool>,
+    value_type: Option<String>,
+) -> Result<TopKRows, String> {
+    if k == 0 {
+        return Err("k must be at least 1".to_string());
+    }
+    let dialect = dialect.unwrap_or_default();
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+    let descending = descending.unwrap_or(true);
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader.headers().map_err(|e| e.to_string())?;
+    let Some(header) = headers.get(column) else {
+        return Err("column out of range".to_string());
+    };
+    let value_type = value_type
+        .or_else(|| load_file_settings(&app, &path).column_types.remove(header))
+        .unwrap_or_else(|| "number".to_string());
+    if !matches!(
+        value_type.as_str(),
+        "integer" | "number" | "date" | "datetime"
+    ) {
+        return Err(format!("cannot rank a {} column", value_type));
+    }
+    top_k_from(&mut reader, column, k, descending, value_type)
+}
+
+/// The heap pass of [`top_k_rows`] over the records left in `reader`.
+fn top_k_from<R: Read>(
+    reader: &mut csv::Reader<R>,
+    column: usize,
+    k: usize,
+    descending: bool,
+    value_type: String,
+) -> Result<TopKRows, String> {
+    let mut heap: std::collections::BinaryHeap<TopKEntry> =
+        std::collections::BinaryHeap::with_capacity(k + 1);
+    let (mut rows_read, mut skipped) = (0, 0);
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let row = rows_read;
+        rows_read += 1;
+        let key = match record
+            .get(column)
+            .and_then(|cell| TypedValue::parse(&value_type, cell))
+        {
+            Some(TypedValue::Number(number)) => TypedValue::Number(number),
+            Some(TypedValue::Instant(instant)) => TypedValue::Instant(instant),
+            _ => {
+                skipped += 1;
+                continue;
+            }
+        };
+        let mut entry = TopKEntry {
+            key,
+            descending,
+            row,
+            values: Vec::new(),
+        };
+        if heap.len() == k && heap.peek().is_some_and(|worst| entry >= *worst) {
+            continue;
+        }
+        entry.values = record.iter().map(|s| s.to_string()).collect();
+        heap.push(entry);
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+
+    let (row_indexes, rows) = heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|entry| (entry.row, entry.values))
+        .unzip();
+    Ok(TopKRows {
+        rows,
+        row_indexes,
+        value_type,
+        rows_read,
+        skipped,
+    })
+}
+
+/// Compiled find/replace, shared by the file and in-memory commands.
+struct FindReplacer {
+    spec: FindReplaceSpec,
+    /// `None` for a case-sensitive literal search.
+    regex: Option<regex::Regex>,
+}
+
+impl FindReplacer {
+    fn new(spec: &FindReplaceSpec) -> Result<Self, String> {
+        let regex = if spec.regex {
+            Some(
+                regex::RegexBuilder::new(&spec.find)
+                    .case_insensitive(!spec.match_case)
+                    .build()
+                    .map_err(|e| e.to_string())?,
+            )
+        } else if !spec.match_case {
+            Some(
+                regex::RegexBuilder::new(&regex::escape(&spec.find))
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| e.to_string())?,
+            )
+        } else {
+            None
+        };
+        Ok(Self {
+            spec: spec.clone(),
+            regex,
+        })
+    }
+
+    /// The replaced cell, or `None` when nothing matched.
+    fn replace_cell(&self, current: &str) -> Option<String> {
+        let next = match &self.regex {
+            Some(regex) => match regex.replace_all(current, self.spec.replace.as_str()) {
+                std::borrow::Cow::Borrowed(_) => return None,
+                std::borrow::Cow::Owned(next) => next,
+            },
+            None if !current.contains(&self.spec.find) => return None,
+            None => current.replace(&self.spec.find, &self.spec.replace),
+        };
+        (next != current).then_some(next)
+    }
+
+    /// Each match in `cell` with the text it would become, leaving out the ones the
+    /// replacement doesn't change. Ranges are byte offsets.
+    fn cell_matches(&self, cell: &str) -> Vec<(std::ops::Range<usize>, String)> {
+        let found: Vec<_> = match &self.regex {
+            Some(regex) => regex
+                .captures_iter(cell)
+                .map(|caps| {
+                    let mut replacement = String::new();
+                    caps.expand(&self.spec.replace, &mut replacement);
+                    (caps.get(0).map_or(0..0, |m| m.range()), replacement)
+                })
+                .collect(),
+            None => cell
+                .match_indices(self.spec.find.as_str())
+                .map(|(start, found)| (start..start + found.len(), self.spec.replace.clone()))
+                .collect(),
+        };
+        found
+            .into_iter()
+            .filter(|(range, replacement)| cell[range.clone()] != *replacement)
+            .collect()
+    }
+
+    fn matches(&self, cell: &str) -> bool {
+        match &self.regex {
+            Some(regex) => regex.is_match(cell),
+            None => cell.contains(&self.spec.find),
+        }
+    }
+
+    fn columns(&self, len: usize) -> std::ops::Range<usize> {
+        match self.spec.column {
+            Some(col) => col..col + 1,
+            None => 0..len,
+        }
+    }
+
+    /// Replace in the spec's column (or every column); returns the number of changed cells.
+    fn apply_to_row(&self, row: &mut [String]) -> usize {
+        let mut applied = 0;
+        for col in self.columns(row.len()) {
+            if let Some(next) = row.get(col).and_then(|current| self.replace_cell(current)) {
+                row[col] = next;
+                applied += 1;
+            }
+        }
+        applied
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn apply_find_replace_to_file(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    target_path: UserPath,
+    dialect: CsvDialect,
+    spec: FindReplaceSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<FindReplaceResult, String> {
+    ensure_can_write(&target_path, overwrite)?;
+    ensure_space_for_rewrite(&path, &target_path, &dialect, "apply_find_replace_to_file")?;
+    let started = Instant::now();
+    let _taskbar = TaskbarProgress::start(&app);
+    let dry_run = dry_run.unwrap_or(false);
+    let result = execute_find_replace_file(
+        app.clone(),
+        state,
+        path.to_string(),
+        target_path.to_string(),
+        dialect,
+        spec,
+        dry_run,
+    );
+    let title = tr_app_with(&app, "job_find_replace", &[]);
+    notify_job_finished(&app, started, &title, &result, |done| {
+        tr_app_with(
+            &app,
+            "summary_find_replace",
+            &[
+                ("applied", &done.applied),
+                ("file", &path_display_name(&done.output_path)),
+            ],
+        )
+    });
+    result
+}
+
+#[tracing::instrument(skip_all, fields(path = %path, applied = tracing::field::Empty))]
+fn execute_find_replace_file(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: String,
+    target_path: String,
+    dialect: CsvDialect,
+    spec: FindReplaceSpec,
+    dry_run: bool,
+) -> Result<FindReplaceResult, String> {
+    ensure_not_read_only(&state, &target_path)?;
+
+    let mut reader = dialect.reader(&path)?;
+
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    let mut writer = dialect.output_writer(&target_path, dry_run)?;
+
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+
+    let replacer = FindReplacer::new(&spec)?;
+    let counts = find_replace_file_pass(&mut reader, &mut writer, &replacer)?;
+    let applied = counts.cells_modified;
+    let hashes = dialect.finish_writer(writer, &target_path)?;
+    if dry_run {
+        return Ok(FindReplaceResult {
+            hashes,
+            output_path: target_path,
+            applied,
+            counts,
+            dry_run,
+        });
+    }
+
+    tracing::Span::current().record("applied", applied);
+    record_operation(
+        &app,
+        "apply_find_replace_to_file",
+        Some(&path),
+        Some(&target_path),
+        Some(applied),
+        serde_json::to_value(&spec).unwrap_or_default(),
+    );
+    if let Err(err) = add_search_history(app.clone(), spec) {
+        tracing::warn!(error = %err, "search history not updated");
+    }
+    Ok(FindReplaceResult {
+        hashes,
+        output_path: target_path,
+        applied,
+        counts,
+        dry_run,
+    })
+}
+
+/// Row loop of `apply_find_replace_to_file`; unchanged records are written as read.
+fn find_replace_file_pass<W: Write>(
+    reader: &mut csv::Reader<BufReader<File>>,
+    writer: &mut csv::Writer<W>,
+    replacer: &FindReplacer,
+) -> Result<RowCounts, String> {
+    let mut counts = RowCounts::default();
+    let mut record = csv::StringRecord::new();
+    let mut changed: Vec<(usize, String)> = Vec::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        counts.rows_read += 1;
+        counts.rows_written += 1;
+        changed.clear();
+        for col in replacer.columns(record.len()) {
+            if let Some(next) = record
+                .get(col)
+                .and_then(|current| replacer.replace_cell(current))
+            {
+                changed.push((col, next));
+            }
+        }
+        if changed.is_empty() {
+            writer.write_record(&record).map_err(|e| e.to_string())?;
+            continue;
+        }
+        counts.cells_modified += changed.len();
+        let mut fields: Vec<&str> = record.iter().collect();
+        for (col, next) in &changed {
+            fields[*col] = next;
+        }
+        writer.write_record(&fields).map_err(|e| e.to_string())?;
+    }
+
+    Ok(counts)
+}
+
+/// A match the step-through replace stopped at, with its row for context.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReplaceMatch {
+    pub row: usize,
+    pub col: usize,
+    pub column: String,
+    /// The cell as read from the file.
+    pub value: String,
+    /// Byte range of the match in `value`.
+    pub start: usize,
+    pub end: usize,
+    /// What the match becomes when confirmed.
+    pub replacement: String,
+    pub context: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReplaceStep {
+    pub session_id: u64,
+    /// `None` once every match has been confirmed or skipped.
+    pub current: Option<ReplaceMatch>,
+    pub confirmed: usize,
+    pub skipped: usize,
+}
+
+/// Replace sessions left unanswered this long are dropped when another one starts.
+const REPLACE_SESSION_IDLE: Duration = Duration::from_secs(30 * 60);
+
+/// An interactive replace over a file: matches are visited in file order and confirmed
+/// replacements are kept as patches until the final write.
+struct ReplaceSession {
+    path: String,
+    dialect: CsvDialect,
+    replacer: FindReplacer,
+    signature: (u64, u64),
+    headers: Vec<String>,
+    reader: csv::Reader<BufReader<File>>,
+    record: csv::StringRecord,
+    row: usize,
+    /// Cell being visited, its matches not yet shown and the ones confirmed so far.
+    col: usize,
+    pending: std::collections::VecDeque<(std::ops::Range<usize>, String)>,
+    accepted: Vec<(std::ops::Range<usize>, String)>,
+    next_col: usize,
+    done: bool,
+    current: Option<ReplaceMatch>,
+    confirmed: HashMap<usize, HashMap<usize, String>>,
+    confirmed_count: usize,
+    skipped: usize,
+    /// Label of the window that started it; the session closes with that window.
+    owner: Option<String>,
+    touched: Instant,
+}
+
+impl ReplaceSession {
+    fn open(
+        path: &str,
+        dialect: CsvDialect,
+        spec: &FindReplaceSpec,
+        owner: Option<String>,
+    ) -> Result<Self, String> {
+        let replacer = FindReplacer::new(spec)?;
+        let mut reader = dialect.reader(path)?;
+        let headers = reader
+            .headers()
+            .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+            .map_err(|e| e.to_string())?;
+        let mut record = csv::StringRecord::new();
+        let done = !reader.read_record(&mut record).map_err(|e| e.to_string())?;
+        let mut session = ReplaceSession {
+            signature: file_signature(&PathBuf::from(path))?,
+            path: path.to_string(),
+            dialect,
+            replacer,
+            headers,
+            reader,
+            record,
+            row: 0,
+            col: 0,
+            pending: Default::default(),
+            accepted: Vec::new(),
+            next_col: 0,
+            done,
+            current: None,
+            confirmed: HashMap::new(),
+            confirmed_count: 0,
+            skipped: 0,
+            owner,
+            touched: Instant::now(),
+        };
+        session.advance()?;
+        Ok(session)
+    }
+
+    /// Move `current` to the next match after it, in this cell or a later one.
+    fn advance(&mut self) -> Result<(), String> {
+        self.current = None;
+        while !self.done {
+            if let Some((range, replacement)) = self.pending.pop_front() {
+                self.current = Some(ReplaceMatch {
+                    row: self.row,
+                    col: self.col,
+                    column: self.headers.get(self.col).cloned().unwrap_or_default(),
+                    value: self.record[self.col].to_string(),
+                    start: range.start,
+                    end: range.end,
+                    replacement,
+                    context: self.record.iter().map(|s| s.to_string()).collect(),
+                });
+                return Ok(());
+            }
+            let found = self
+                .replacer
+                .columns(self.record.len())
+                .filter(|col| *col >= self.next_col)
+                .find_map(|col| {
+                    let matches = self.replacer.cell_matches(self.record.get(col)?);
+                    (!matches.is_empty()).then_some((col, matches))
+                });
+            if let Some((col, matches)) = found {
+                self.col = col;
+                self.next_col = col + 1;
+                self.pending = matches.into();
+                self.accepted.clear();
+                continue;
+            }
+            self.done = !self
+                .reader
+                .read_record(&mut self.record)
+                .map_err(|e| e.to_string())?;
+            self.row += 1;
+            self.next_col = 0;
+        }
+        Ok(())
+    }
+
+    /// Accept (`confirm`) or skip the current match and move to the next one. A cell's
+    /// patch holds only the matches confirmed in it.
+    fn answer(&mut self, confirm: bool) -> Result<(), String> {
+        let current = self
+            .current
+            .take()
+            .ok_or_else(|| "no match left to answer".to_string())?;
+        self.touched = Instant::now();
+        if confirm {
+            self.accepted
+                .push((current.start..current.end, current.replacement));
+            let mut next = String::with_capacity(current.value.len());
+            let mut last = 0;
+            for (range, replacement) in &self.accepted {
+                next.push_str(&current.value[last..range.start]);
+                next.push_str(replacement);
+                last = range.end;
+            }
+            next.push_str(&current.value[last..]);
+            self.confirmed
+                .entry(current.row)
+                .or_default()
+                .insert(current.col, next);
+            self.confirmed_count += 1;
+        } else {
+            self.skipped += 1;
+        }
+        self.advance()
+    }
+
+    fn step(&self, session_id: u64) -> ReplaceStep {
+        ReplaceStep {
+            session_id,
+            current: self.current.clone(),
+            confirmed: self.confirmed_count,
+            skipped: self.skipped,
+        }
+    }
+}
+
+/// Start a step-through replace: returns the first match. Confirm or skip each one, then
+/// `finish_replace_session` writes the confirmed replacements in one pass.
+#[tauri::command]
+fn start_replace_session(
+    window: tauri::Window,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: CsvDialect,
+    spec: FindReplaceSpec,
+) -> Result<ReplaceStep, String> {
+    let session = ReplaceSession::open(&path, dialect, &spec, Some(window.label().to_string()))?;
+    let session_id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    let step = session.step(session_id);
+    let mut sessions = state.replace_sessions.lock().map_err(|_| "lock poisoned")?;
+    sessions.retain(|_, session| session.touched.elapsed() < REPLACE_SESSION_IDLE);
+    sessions.insert(session_id, session);
+    Ok(step)
+}
+
+fn answer_replace_match(
+    state: &AppState,
+    session_id: u64,
+    confirm: bool,
+) -> Result<ReplaceStep, String> {
+    let mut sessions = state.replace_sessions.lock().map_err(|_| "lock poisoned")?;
+    let session = sessions
+        .get_mut(&session_id)
+        .ok_or_else(|| "replace session not found".to_string())?;
+    session.answer(confirm)?;
+    Ok(session.step(session_id))
+}
+
+#[tauri::command]
+fn confirm_replace(state: tauri::State<AppState>, session_id: u64) -> Result<ReplaceStep, String> {
+    answer_replace_match(&state, session_id, true)
+}
+
+#[tauri::command]
+fn skip_match(state: tauri::State<AppState>, session_id: u64) -> Result<ReplaceStep, String> {
+    answer_replace_match(&state, session_id, false)
+}
+
+#[tauri::command]
+fn cancel_replace_session(state: tauri::State<AppState>, session_id: u64) -> Result<(), String> {
+    state
+        .replace_sessions
+        .lock()
+        .map_err(|_| "lock poisoned")?
+        .remove(&session_id);
+    Ok(())
+}
+
+/// End a step-through replace and write the confirmed replacements to `target_path`, which
+/// may be the source itself. Matches not yet answered are left as they are. A dry run
+/// leaves the session open, so it can still be finished for real.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(session = session_id, target = %target_path))]
+fn finish_replace_session(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    session_id: u64,
+    target_path: UserPath,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<FindReplaceResult, String> {
+    let session = state
+        .replace_sessions
+        .lock()
+        .map_err(|_| "lock poisoned")?
+        .remove(&session_id)
+        .ok_or_else(|| "replace session not found".to_string())?;
+    let dry_run = dry_run.unwrap_or(false);
+    let result = match write_replace_session(&state, &session, &target_path, overwrite, dry_run) {
+        Ok(result) if !dry_run => result,
+        // Kept so a dry run can be finished for real and a refused write retried.
+        other => {
+            state
+                .replace_sessions
+                .lock()
+                .map_err(|_| "lock poisoned")?
+                .insert(session_id, session);
+            return other;
+        }
+    };
+    let path = session.path.clone();
+    let params = serde_json::to_value(&session.replacer.spec).unwrap_or_default();
+    // The session still holds the source open, which Windows won't rename over.
+    drop(session);
+    if target_path.as_str() == path {
+        let write_target = format!("{}.tmp", path);
+        if let Err(e) = fs::rename(&write_target, &path) {
+            let _ = fs::remove_file(&write_target);
+            return Err(e.to_string());
+        }
+    }
+
+    record_operation(
+        &app,
+        "finish_replace_session",
+        Some(&path),
+        Some(&target_path),
+        Some(result.applied),
+        params,
+    );
+    Ok(result)
+}
+
+/// Write the session's confirmed replacements to `target_path`, or next to the source
+/// (`<path>.tmp`) when replacing it in place.
+fn write_replace_session(
+    state: &AppState,
+    session: &ReplaceSession,
+    target_path: &str,
+    overwrite: Option<bool>,
+    dry_run: bool,
+) -> Result<FindReplaceResult, String> {
+    let path = session.path.as_str();
+    let dialect = &session.dialect;
+    if target_path != path {
+        ensure_can_write(target_path, overwrite)?;
+    }
+    ensure_not_read_only(state, target_path)?;
+    if file_signature(&PathBuf::from(path))? != session.signature {
+        return Err(format!("{} changed during the replace session", path));
+    }
+    ensure_space_for_rewrite(path, target_path, dialect, "finish_replace_session")?;
+
+    let write_target = if target_path == path {
+        format!("{}.tmp", path)
+    } else {
+        target_path.to_string()
+    };
+    let mut reader = dialect.reader(path)?;
+    let mut writer = dialect.output_writer(&write_target, dry_run)?;
+    let counts = write_patched_csv(&mut reader, &mut writer, &session.confirmed, &[], &[])?;
+    let hashes = dialect.finish_writer(writer, &write_target)?;
+    Ok(FindReplaceResult {
+        hashes,
+        output_path: target_path.to_string(),
+        applied: counts.cells_modified,
+        counts,
+        dry_run,
+    })
+}
+
+/// Rows for the in-memory engines: either sent by the caller or read from a session's
+/// file. Patch row numbers are counted from `start` either way.
+#[derive(Serialize, Deserialize)]
+pub struct RowsInput {
+    pub rows: Option<Vec<Vec<String>>>,
+    /// Needed by ops that look at headers (scripts, `new_column`); read from the file
+    /// for a session range.
+    pub headers: Option<Vec<String>>,
+    #[serde(default)]
+    pub start: usize,
+    pub session_id: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RowsEditResult {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Changed cells, addressed in the returned column layout.
+    pub patches: Vec<CsvPatch>,
+    pub applied: usize,
+    pub failed: usize,
+    /// Set when the macro added a column at this index.
+    pub inserted_column: Option<usize>,
+}
+
+fn load_rows_input(
+    state: &tauri::State<AppState>,
+    input: RowsInput,
+) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    if let Some(rows) = input.rows {
+        return Ok((input.headers.unwrap_or_default(), rows));
+    }
+    let session_id = input
+        .session_id
+        .ok_or_else(|| "rows or a session range is required".to_string())?;
+    let (path, dialect) = {
+        let sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+        sessions
+            .get(&session_id)
+            .map(|session| (session.path.clone(), session.dialect.clone()))
+            .ok_or_else(|| "session not found".to_string())?
+    };
+    let headers = read_csv_headers(&path, &dialect)?;
+    // The session's path was checked when it was opened.
+    let slice = read_csv_rows_window(
+        state.clone(),
+        UserPath(path),
+        Some(dialect),
+        input.start,
+        input.limit.unwrap_or(1000),
+    )?;
+    Ok((headers, slice.rows))
+}
+
+/// The input column behind output column `col`, or `None` for the inserted column.
+fn source_column(col: usize, inserted_column: Option<usize>) -> Option<usize> {
+    match inserted_column {
+        Some(idx) if col == idx => None,
+        Some(idx) if col > idx => Some(col - 1),
+        _ => Some(col),
+    }
+}
+
+/// Cells that differ between `before` and `after`, skipping over an inserted column.
+fn row_patches(
+    row: usize,
+    before: &[String],
+    after: &[String],
+    inserted_column: Option<usize>,
+    patches: &mut Vec<CsvPatch>,
+) {
+    for (col, value) in after.iter().enumerate() {
+        let original = source_column(col, inserted_column).and_then(|col| before.get(col));
+        if original.map(|s| s.as_str()).unwrap_or("") != value {
+            patches.push(CsvPatch {
+                row,
+                col,
+                value: value.clone(),
+            });
+        }
+    }
+}
+
+/// Engine patches of rows read from a session's file, as changes against that file.
+fn engine_changes(
+    before: &[String],
+    patches: &[CsvPatch],
+    inserted_column: Option<usize>,
+    source: &str,
+) -> Vec<CellChange> {
+    patches
+        .iter()
+        .filter_map(|patch| {
+            let col = source_column(patch.col, inserted_column)?;
+            Some(CellChange {
+                row: patch.row,
+                col,
+                original: before.get(col).cloned().unwrap_or_default(),
+                value: patch.value.clone(),
+                source: source.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Run a macro over rows in memory (the loaded slice) and return the rewritten rows
+/// with patches, so "loaded only" edits use the same engine as `apply_macro_to_file`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(op = %spec.op))]
+fn apply_macro_to_rows(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    spec: CsvMacroSpec,
+    input: RowsInput,
+) -> Result<RowsEditResult, String> {
+    let start = input.start;
+    // Rows read from a session's file are edits of that file, tracked like the grid's.
+    let session = input.session_id.filter(|_| input.rows.is_none());
+    let (mut headers, rows) = load_rows_input(&state, input)?;
+    let mut run_state = prepare_macro_state(&app, &spec, &mut headers)?;
+    let inserted_column = run_state.inserted_column;
+
+    let mut applied = 0usize;
+    let mut patches = Vec::new();
+    let mut changes = Vec::new();
+    let mut output = Vec::with_capacity(rows.len());
+    for (offset, before) in rows.into_iter().enumerate() {
+        let mut row = before.clone();
+        if apply_macro_to_row(&spec, &mut row, &mut run_state)? {
+            applied += 1;
+        }
+        let first = patches.len();
+        row_patches(start + offset, &before, &row, inserted_column, &mut patches);
+        changes.extend(engine_changes(
+            &before,
+            &patches[first..],
+            inserted_column,
+            "macro",
+        ));
+        output.push(row);
+    }
+    if let Some(session_id) = session {
+        record_cell_changes(&state, session_id, changes)?;
+    }
+    Ok(RowsEditResult {
+        headers,
+        rows: output,
+        patches,
+        applied,
+        failed: run_state.failed,
+        inserted_column,
+    })
+}
+
+/// In-memory counterpart of `apply_find_replace_to_file`.
+#[tauri::command]
+fn apply_find_replace_to_rows(
+    state: tauri::State<AppState>,
+    spec: FindReplaceSpec,
+    input: RowsInput,
+) -> Result<RowsEditResult, String> {
+    let start = input.start;
+    let session = input.session_id.filter(|_| input.rows.is_none());
+    let (headers, rows) = load_rows_input(&state, input)?;
+    let replacer = FindReplacer::new(&spec)?;
+
+    let mut applied = 0usize;
+    let mut patches = Vec::new();
+    let mut changes = Vec::new();
+    let mut output = Vec::with_capacity(rows.len());
+    for (offset, before) in rows.into_iter().enumerate() {
+        let mut row = before.clone();
+        applied += replacer.apply_to_row(&mut row);
+        let first = patches.len();
+        row_patches(start + offset, &before, &row, None, &mut patches);
+        changes.extend(engine_changes(
+            &before,
+            &patches[first..],
+            None,
+            "find_replace",
+        ));
+        output.push(row);
+    }
+    if let Some(session_id) = session {
+        record_cell_changes(&state, session_id, changes)?;
+    }
+    Ok(RowsEditResult {
+        headers,
+        rows: output,
+        patches,
+        applied,
+        failed: 0,
+        inserted_column: None,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SimilarRowsSpec {
+    pub key_columns: Vec<usize>,
+    pub method: Option<String>,
+    pub max_distance: Option<usize>,
+    pub min_similarity: Option<f64>,
+    pub window: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SimilarRowCluster {
+    pub rows: Vec<usize>,
+    pub keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SimilarRowsResult {
+    pub clusters: Vec<SimilarRowCluster>,
+    pub total_clusters: usize,
+    pub offset: usize,
+}
+
+/// Build the comparison key for a row: trimmed, lowercased key columns with collapsed whitespace.
+fn similarity_key(record: &csv::StringRecord, columns: &[usize]) -> String {
+    columns
+        .iter()
+        .map(|col| record.get(*col).unwrap_or(""))
+        .flat_map(|value| value.split_whitespace())
+        .map(|part| part.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Levenshtein distance over chars; gives up early once every path exceeds `limit`.
+fn edit_distance(a: &str, b: &str, limit: usize) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > limit {
+        return limit + 1;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0usize; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        let mut row_min = curr[0];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+            row_min = row_min.min(curr[j + 1]);
+        }
+        if row_min > limit {
+            return limit + 1;
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+fn similarity_tokens(key: &str) -> HashSet<&str> {
+    key.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+/// Jaccard similarity of the alphanumeric token sets of two keys.
+fn token_similarity(a: &str, b: &str) -> f64 {
+    let left = similarity_tokens(a);
+    let right = similarity_tokens(b);
+    if left.is_empty() && right.is_empty() {
+        return 1.0;
+    }
+    let shared = left.intersection(&right).count();
+    let union = left.len() + right.len() - shared;
+    shared as f64 / union as f64
+}
+
+fn union_find_root(parent: &mut [usize], mut node: usize) -> usize {
+    while parent[node] != node {
+        parent[node] = parent[parent[node]];
+        node = parent[node];
+    }
+    node
+}
+
+/// Cluster rows whose key columns are near-duplicates. Uses a sorted-neighbourhood pass
+/// (each key is only compared with its `window` neighbours) so the scan stays near-linear.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn find_similar_rows(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    spec: SimilarRowsSpec,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<SimilarRowsResult, String> {
+    if spec.key_columns.is_empty() {
+        return Err("at least one key column is required".to_string());
+    }
+    check_file_size(&app, &path, "find_similar_rows")?;
+    let token_mode = spec.method.as_deref() == Some("token");
+    let max_distance = spec.max_distance.unwrap_or(2);
+    let min_similarity = spec.min_similarity.unwrap_or(0.8);
+    let window = spec.window.unwrap_or(10).max(1);
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(50);
+
+    let mut reader = dialect.reader(&path)?;
+
+    let _ = reader.headers().map_err(|e| e.to_string())?;
+
+    let mut entries: Vec<(usize, String)> = Vec::new();
+    let mut record = csv::StringRecord::new();
+    let mut row_index = 0usize;
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let key = similarity_key(&record, &spec.key_columns);
+        if !key.is_empty() {
+            entries.push((row_index, key));
+        }
+        row_index += 1;
+    }
+
+    let is_similar = |a: &str, b: &str| {
+        if token_mode {
+            token_similarity(a, b) >= min_similarity
+        } else {
+            edit_distance(a, b, max_distance) <= max_distance
+        }
+    };
+
+    // Edit-distance neighbours can differ in their first characters, so also sort by the
+    // reversed key. Token mode sorts by the sorted token list to line up reordered words.
+    let orderings: Vec<Vec<String>> = if token_mode {
+        vec![entries
+            .iter()
+            .map(|(_, key)| {
+                let mut tokens: Vec<&str> = similarity_tokens(key).into_iter().collect();
+                tokens.sort_unstable();
+                tokens.join(" ")
+            })
+            .collect()]
+    } else {
+        vec![
+            entries.iter().map(|(_, key)| key.clone()).collect(),
+            entries
+                .iter()
+                .map(|(_, key)| key.chars().rev().collect())
+                .collect(),
+        ]
+    };
+
+    let mut parent: Vec<usize> = (0..entries.len()).collect();
+    for sort_keys in orderings {
+        let mut order: Vec<usize> = (0..entries.len()).collect();
+        order.sort_by(|a, b| sort_keys[*a].cmp(&sort_keys[*b]));
+        for (pos, &left) in order.iter().enumerate() {
+            for &right in order.iter().skip(pos + 1).take(window) {
+                if union_find_root(&mut parent, left) == union_find_root(&mut parent, right) {
+                    continue;
+                }
+                if is_similar(&entries[left].1, &entries[right].1) {
+                    let root_left = union_find_root(&mut parent, left);
+                    let root_right = union_find_root(&mut parent, right);
+                    parent[root_left.max(root_right)] = root_left.min(root_right);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for idx in 0..entries.len() {
+        let root = union_find_root(&mut parent, idx);
+        groups.entry(root).or_default().push(idx);
+    }
+
+    let mut clusters: Vec<SimilarRowCluster> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| SimilarRowCluster {
+            rows: members.iter().map(|idx| entries[*idx].0).collect(),
+            keys: members.iter().map(|idx| entries[*idx].1.clone()).collect(),
+        })
+        .collect();
+    clusters.sort_by_key(|cluster| cluster.rows.first().copied().unwrap_or(0));
+
+    let total_clusters = clusters.len();
+    let clusters = clusters.into_iter().skip(offset).take(limit).collect();
+
+    Ok(SimilarRowsResult {
+        clusters,
+        total_clusters,
+        offset,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MojibakeCell {
+    pub row: usize,
+    pub col: usize,
+    pub value: String,
+    pub repaired: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MojibakeColumn {
+    pub column: usize,
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MojibakeReport {
+    pub rows_scanned: usize,
+    pub cell_count: usize,
+    /// Columns with at least one affected cell.
+    pub columns: Vec<MojibakeColumn>,
+    /// The first `limit` affected cells with their repaired value.
+    pub cells: Vec<MojibakeCell>,
+}
+
+/// Find cells that look like double-encoded UTF-8 (`Ã©`, `â€™`). Fix them with the
+/// `repair_mojibake` macro op, whose applied count reports the repaired cells.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn detect_mojibake(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    limit: Option<usize>,
+) -> Result<MojibakeReport, String> {
+    check_file_size(&app, &path, "detect_mojibake")?;
+    let limit = limit.unwrap_or(100);
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let mut counts = vec![0usize; headers.len()];
+    let mut report = MojibakeReport {
+        rows_scanned: 0,
+        cell_count: 0,
+        columns: Vec::new(),
+        cells: Vec::new(),
+    };
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        for (col, value) in record.iter().enumerate() {
+            let Some(repaired) = repair_mojibake(value) else {
+                continue;
+            };
+            if col >= counts.len() {
+                counts.resize(col + 1, 0);
+            }
+            counts[col] += 1;
+            report.cell_count += 1;
+            if report.cells.len() < limit {
+                report.cells.push(MojibakeCell {
+                    row: report.rows_scanned,
+                    col,
+                    value: value.to_string(),
+                    repaired,
+                });
+            }
+        }
+        report.rows_scanned += 1;
+    }
+    report.columns = counts
+        .into_iter()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .map(|(column, count)| MojibakeColumn {
+            column,
+            name: headers.get(column).cloned().unwrap_or_default(),
+            count,
+        })
+        .collect();
+    Ok(report)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InvisibleFinding {
+    pub row: usize,
+    pub col: usize,
+    /// `leading_whitespace`, `trailing_whitespace`, `non_breaking_space`, `tab`, `control`
+    /// or `zero_width`.
+    pub issues: Vec<String>,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InvisibleCharactersReport {
+    pub total: usize,
+    pub offset: usize,
+    pub findings: Vec<InvisibleFinding>,
+    /// Affected cells by issue.
+    pub counts: std::collections::BTreeMap<String, usize>,
+    /// One `clean_invisible` macro per affected column; it only changes cells with findings.
+    pub cleanup: Vec<CsvMacroSpec>,
+}
+
+/// Locate cells with surrounding whitespace, non-breaking spaces, inner tabs, control or
+/// zero-width characters, paged by `offset`/`limit` in row order.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn find_invisible_characters(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<InvisibleCharactersReport, String> {
+    check_file_size(&app, &path, "find_invisible_characters")?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(100);
+    let mut reader = dialect.reader(&path)?;
+    let mut report = InvisibleCharactersReport {
+        total: 0,
+        offset,
+        findings: Vec::new(),
+        counts: Default::default(),
+        cleanup: Vec::new(),
+    };
+    let mut columns = std::collections::BTreeSet::new();
+    let mut record = csv::StringRecord::new();
+    let mut row = 0usize;
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        for (col, value) in record.iter().enumerate() {
+            let issues = invisible_issues(value);
+            if issues.is_empty() {
+                continue;
+            }
+            for issue in &issues {
+                *report.counts.entry(issue.to_string()).or_default() += 1;
+            }
+            columns.insert(col);
+            if report.total >= offset && report.findings.len() < limit {
+                report.findings.push(InvisibleFinding {
+                    row,
+                    col,
+                    issues: issues.into_iter().map(str::to_string).collect(),
+                    value: value.to_string(),
+                });
+            }
+            report.total += 1;
+        }
+        row += 1;
+    }
+    report.cleanup = columns
+        .into_iter()
+        .map(|column| CsvMacroSpec {
+            op: "clean_invisible".to_string(),
+            column,
+            ..Default::default()
+        })
+        .collect();
+    Ok(report)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QuoteAnomaly {
+    /// Data row, or `None` for the header.
+    pub row: Option<usize>,
+    /// 1-based line and 0-based byte offset of the offending quote or character.
+    pub line: u64,
+    pub byte: u64,
+    /// `quote_in_unquoted_field`, `text_after_closing_quote` or `unterminated_quote`.
+    pub kind: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QuoteAnomalyReport {
+    pub total: usize,
+    pub offset: usize,
+    pub anomalies: Vec<QuoteAnomaly>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum QuoteScanState {
+    FieldStart,
+    Unquoted,
+    Quoted,
+    QuoteInQuoted,
+    Escaped,
+}
+
+/// Walk the raw bytes of one record (`len` bytes from `raw`) and return its first quoting
+/// problem as `(kind, line, byte)`. Strict parsers only accept quotes that wrap a whole field.
+fn scan_record_quotes(
+    raw: &mut impl Read,
+    len: u64,
+    (mut line, mut byte): (u64, u64),
+    dialect: &CsvDialect,
+) -> Result<Option<(&'static str, u64, u64)>, String> {
+    let (delimiter, quote, escape) = (
+        dialect.delimiter_byte(),
+        dialect.quote_byte(),
+        dialect.escape_byte(),
+    );
+    let mut state = QuoteScanState::FieldStart;
+    let mut found = None;
+    let mut opened_at = (line, byte);
+    let mut chunk = [0u8; 64 * 1024];
+    let mut raw = raw.take(len);
+    loop {
+        let read = raw.read(&mut chunk).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        for &b in &chunk[..read] {
+            let line_end = b == b'\n' || b == b'\r';
+            state = match state {
+                QuoteScanState::FieldStart | QuoteScanState::Unquoted if line_end => {
+                    QuoteScanState::FieldStart
+                }
+                QuoteScanState::FieldStart
+                | QuoteScanState::Unquoted
+                | QuoteScanState::QuoteInQuoted
+                    if b == delimiter =>
+                {
+                    QuoteScanState::FieldStart
+                }
+                QuoteScanState::FieldStart if b == quote => {
+                    opened_at = (line, byte);
+                    QuoteScanState::Quoted
+                }
+                QuoteScanState::FieldStart | QuoteScanState::Unquoted if b == quote => {
+                    found.get_or_insert(("quote_in_unquoted_field", line, byte));
+                    QuoteScanState::Unquoted
+                }
+                QuoteScanState::FieldStart | QuoteScanState::Unquoted => QuoteScanState::Unquoted,
+                QuoteScanState::Quoted if b == escape && escape != quote => QuoteScanState::Escaped,
+                QuoteScanState::Quoted if b == quote => QuoteScanState::QuoteInQuoted,
+                QuoteScanState::Quoted | QuoteScanState::Escaped => QuoteScanState::Quoted,
+                QuoteScanState::QuoteInQuoted if b == quote && escape == quote => {
+                    QuoteScanState::Quoted
+                }
+                QuoteScanState::QuoteInQuoted if line_end => QuoteScanState::FieldStart,
+                QuoteScanState::QuoteInQuoted => {
+                    found.get_or_insert(("text_after_closing_quote", line, byte));
+                    QuoteScanState::Unquoted
+                }
+            };
+            if b == b'\n' {
+                line += 1;
+            }
+            byte += 1;
+        }
+    }
+    if matches!(state, QuoteScanState::Quoted | QuoteScanState::Escaped) {
+        found.get_or_insert(("unterminated_quote", opened_at.0, opened_at.1));
+    }
+    Ok(found)
+}
+
+/// Report records whose raw text would trip a strict CSV parser: quotes inside unquoted
+/// fields, text after a closing quote, or a quote that never closes (which swallows the
+/// rest of the file). One entry per record, paged by `offset`/`limit`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn find_quote_anomalies(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<QuoteAnomalyReport, String> {
+    check_file_size(&app, &path, "find_quote_anomalies")?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(100);
+    let dialect = dialect.with_delimiter(dialect.resolve_delimiter(&path)?);
+    let mut reader = dialect.reader(&path)?;
+    let mut raw = BufReader::new(File::open(&path).map_err(|e| e.to_string())?);
+    let mut report = QuoteAnomalyReport {
+        total: 0,
+        offset,
+        anomalies: Vec::new(),
+    };
+
+    // Records are scanned once the next one's start (or the end of file) is known. The
+    // header starts after a UTF-8 BOM, if any.
+    let mut bom = [0u8; 3];
+    let bom_len = match raw.read_exact(&mut bom) {
+        Ok(()) if bom == [0xEF, 0xBB, 0xBF] => 3,
+        _ => 0,
+    };
+    raw.seek(SeekFrom::Start(bom_len))
+        .map_err(|e| e.to_string())?;
+    let mut previous: (Option<usize>, u64, u64) = (None, 1, bom_len);
+    let mut record = csv::ByteRecord::new();
+    let mut row = 0usize;
+    loop {
+        let more = reader
+            .read_byte_record(&mut record)
+            .map_err(|e| e.to_string())?;
+        let next = record
+            .position()
+            .filter(|_| more)
+            .map(|position| (position.line(), position.byte()));
+        let (previous_row, line, byte) = previous;
+        let len = next.map_or(u64::MAX, |(_, end)| end.saturating_sub(byte));
+        if let Some((kind, line, byte)) = scan_record_quotes(&mut raw, len, (line, byte), &dialect)?
+        {
+            if report.total >= offset && report.anomalies.len() < limit {
+                report.anomalies.push(QuoteAnomaly {
+                    row: previous_row,
+                    line,
+                    byte,
+                    kind: kind.to_string(),
+                });
+            }
+            report.total += 1;
+        }
+        let Some((line, byte)) = next else {
+            break;
+        };
+        previous = (Some(row), line, byte);
+        row += 1;
+    }
+    Ok(report)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnWidth {
+    pub column: usize,
+    pub name: String,
+    /// Display width of the header.
+    pub header: usize,
+    /// Widest value seen, in terminal-style columns.
+    pub max: usize,
+    pub p95: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnWidthReport {
+    pub rows_measured: usize,
+    /// False when only the first `max_rows` rows were measured.
+    pub complete: bool,
+    pub columns: Vec<ColumnWidth>,
+}
+
+/// Width buckets kept per column; wider values share the last one.
+const WIDTH_BUCKETS: usize = 512;
+
+/// Columns a value takes on screen, by the Unicode East Asian Width of each grapheme (two
+/// at most), measured on its longest line.
+fn display_width(value: &str) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+    use unicode_width::UnicodeWidthStr;
+    value
+        .lines()
+        .map(|line| {
+            line.graphemes(true)
+                .map(|grapheme| match grapheme.chars().next() {
+                    Some(ch) if ch.is_control() => 0,
+                    _ => grapheme.width().min(2),
+                })
+                .sum()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Max and 95th-percentile display width per column, from the first `max_rows` rows
+/// (10,000 by default) or the whole file with `scan_all`, for sizing grid columns.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn measure_column_widths(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    max_rows: Option<usize>,
+    scan_all: Option<bool>,
+) -> Result<ColumnWidthReport, String> {
+    check_file_size(&app, &path, "measure_column_widths")?;
+    let max_rows = if scan_all.unwrap_or(false) {
+        usize::MAX
+    } else {
+        max_rows.unwrap_or(10_000)
+    };
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let mut histograms = vec![vec![0usize; WIDTH_BUCKETS]; headers.len()];
+    let mut widest = vec![0usize; headers.len()];
+    let mut record = csv::StringRecord::new();
+    let mut rows = 0usize;
+    let mut complete = true;
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        if rows == max_rows {
+            complete = false;
+            break;
+        }
+        for (col, value) in record.iter().enumerate().take(headers.len()) {
+            let width = display_width(value);
+            histograms[col][width.min(WIDTH_BUCKETS - 1)] += 1;
+            widest[col] = widest[col].max(width);
+        }
+        rows += 1;
+    }
+
+    let percentile = |histogram: &[usize], max: usize| {
+        let target = (histogram.iter().sum::<usize>() * 95).div_ceil(100);
+        let mut seen = 0usize;
+        for (width, count) in histogram.iter().enumerate() {
+            seen += count;
+            if seen >= target.max(1) {
+                return width.min(max);
+            }
+        }
+        max
+    };
+    let columns = headers
+        .iter()
+        .enumerate()
+        .map(|(column, name)| ColumnWidth {
+            column,
+            name: name.clone(),
+            header: display_width(name),
+            max: widest[column],
+            p95: percentile(&histograms[column], widest[column]),
+        })
+        .collect();
+    Ok(ColumnWidthReport {
+        rows_measured: rows,
+        complete,
+        columns,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FormulaColumn {
+    pub column: usize,
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FormulaCellReport {
+    /// Cells a `formula_guard` would prefix, header included.
+    pub cells: usize,
+    pub rows: usize,
+    pub columns: Vec<FormulaColumn>,
+    /// The first few affected cells as (row, column, value); the header is row `None`.
+    pub samples: Vec<(Option<usize>, usize, String)>,
+}
+
+/// Count the cells an export with `formula_guard` would neutralize, before choosing it.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn count_formula_cells(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+) -> Result<FormulaCellReport, String> {
+    check_file_size(&app, &path, "count_formula_cells")?;
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let mut counts = vec![0usize; headers.len()];
+    let mut report = FormulaCellReport {
+        cells: 0,
+        rows: 0,
+        columns: Vec::new(),
+        samples: Vec::new(),
+    };
+    let mut tally = |row: Option<usize>, col: usize, value: &str| {
+        if !starts_like_formula(value) {
+            return false;
+        }
+        if col >= counts.len() {
+            counts.resize(col + 1, 0);
+        }
+        counts[col] += 1;
+        report.cells += 1;
+        if report.samples.len() < 20 {
+            report.samples.push((row, col, value.to_string()));
+        }
+        true
+    };
+    for (col, name) in headers.iter().enumerate() {
+        tally(None, col, name);
+    }
+    let mut record = csv::StringRecord::new();
+    let mut row = 0usize;
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let mut hit = false;
+        for (col, value) in record.iter().enumerate() {
+            hit |= tally(Some(row), col, value);
+        }
+        report.rows += usize::from(hit);
+        row += 1;
+    }
+    report.columns = counts
+        .into_iter()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .map(|(column, count)| FormulaColumn {
+            column,
+            name: headers.get(column).cloned().unwrap_or_default(),
+            count,
+        })
+        .collect();
+    Ok(report)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OutlierHit {
+    pub row: usize,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnOutliers {
+    pub column: usize,
+    pub name: String,
+    pub method: String,
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+    pub count: usize,
+    pub hits: Vec<OutlierHit>,
+    pub truncated: bool,
+}
+
+/// Linear-interpolated quantile of an already sorted slice.
+fn sorted_quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let pos = q * (sorted.len() - 1) as f64;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    let weight = pos - lower as f64;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * weight
+}
+
+const OUTLIER_SAMPLE_SIZE: usize = 100_000;
+
+/// Flag numeric values beyond a z-score or IQR fence. The first pass collects per-column
+/// moments (and values for IQR, reservoir-sampled above the soft size limit), the second
+/// pass reports row positions of the outliers.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn detect_outliers(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    columns: Option<Vec<usize>>,
+    method: Option<String>,
+    threshold: Option<f64>,
+    max_hits: Option<usize>,
+) -> Result<Vec<ColumnOutliers>, String> {
+    use rand::Rng;
+    let use_iqr = method.as_deref() == Some("iqr");
+    let threshold = threshold.unwrap_or(if use_iqr { 1.5 } else { 3.0 });
+    let max_hits = max_hits.unwrap_or(1000);
+    // Above the soft limit the IQR quartiles come from a fixed-size reservoir sample.
+    let sample_cap = if check_file_size(&app, &path, "detect_outliers")? {
+        OUTLIER_SAMPLE_SIZE
+    } else {
+        usize::MAX
+    };
+    let mut rng = rand::thread_rng();
+
+    let mut reader = dialect.reader(&path)?;
+
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    struct OutlierInternal {
+        non_empty: usize,
+        count: usize,
+        mean: f64,
+        m2: f64,
+        values: Vec<f64>,
+    }
+
+    let targets: Vec<usize> = columns
+        .unwrap_or_else(|| (0..headers.len()).collect())
+        .into_iter()
+        .filter(|col| *col < headers.len())
+        .collect();
+    let mut stats: Vec<OutlierInternal> = targets
+        .iter()
+        .map(|_| OutlierInternal {
+            non_empty: 0,
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            values: Vec::new(),
+        })
+        .collect();
+
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        for (slot, col) in targets.iter().enumerate() {
+            let value = record.get(*col).unwrap_or("").trim();
+            if value.is_empty() {
+                continue;
+            }
+            let stat = &mut stats[slot];
+            stat.non_empty += 1;
+            if let Ok(number) = value.parse::<f64>() {
+                if !number.is_finite() {
+                    continue;
+                }
+                stat.count += 1;
+                let delta = number - stat.mean;
+                stat.mean += delta / stat.count as f64;
+                stat.m2 += delta * (number - stat.mean);
+                if use_iqr {
+                    if stat.values.len() < sample_cap {
+                        stat.values.push(number);
+                    } else {
+                        let slot = rng.gen_range(0..stat.count);
+                        if slot < sample_cap {
+                            stat.values[slot] = number;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Only columns that are entirely numeric are checked; mixed columns are skipped.
+    let mut fences: Vec<Option<(f64, f64)>> = Vec::with_capacity(targets.len());
+    for stat in &mut stats {
+        if stat.count < 2 || stat.count != stat.non_empty {
+            fences.push(None);
+            continue;
+        }
+        if use_iqr {
+            stat.values.sort_by(|a, b| a.total_cmp(b));
+            let q1 = sorted_quantile(&stat.values, 0.25);
+            let q3 = sorted_quantile(&stat.values, 0.75);
+            let iqr = q3 - q1;
+            fences.push(Some((q1 - threshold * iqr, q3 + threshold * iqr)));
+            stat.values = Vec::new();
+        } else {
+            let std_dev = (stat.m2 / (stat.count - 1) as f64).sqrt();
+            fences.push(Some((
+                stat.mean - threshold * std_dev,
+                stat.mean + threshold * std_dev,
+            )));
+        }
+    }
+
+    let mut results: Vec<ColumnOutliers> = targets
+        .iter()
+        .zip(&fences)
+        .filter_map(|(col, fence)| {
+            fence.map(|(lower_bound, upper_bound)| ColumnOutliers {
+                column: *col,
+                name: headers[*col].clone(),
+                method: if use_iqr { "iqr" } else { "zscore" }.to_string(),
+                lower_bound,
+                upper_bound,
+                count: 0,
+                hits: Vec::new(),
+                truncated: false,
+            })
+        })
+        .collect();
+    if results.is_empty() {
+        return Ok(results);
+    }
+
+    let mut reader = dialect.reader(&path)?;
+    let _ = reader.headers().map_err(|e| e.to_string())?;
+
+    let mut row_index = 0usize;
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        for result in &mut results {
+            let value = record.get(result.column).unwrap_or("").trim();
+            let Ok(number) = value.parse::<f64>() else {
+                continue;
+            };
+            if number < result.lower_bound || number > result.upper_bound {
+                result.count += 1;
+                if result.hits.len() < max_hits {
+                    result.hits.push(OutlierHit {
+                        row: row_index,
+                        value: value.to_string(),
+                    });
+                } else {
+                    result.truncated = true;
+                }
+            }
+        }
+        row_index += 1;
+    }
+
+    Ok(results)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PatternCount {
+    pub pattern: String,
+    pub count: usize,
+    pub example: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnPatternProfile {
+    pub column: usize,
+    pub name: String,
+    pub non_empty: usize,
+    pub distinct_patterns: usize,
+    pub patterns_truncated: bool,
+    pub patterns: Vec<PatternCount>,
+}
+
+const PATTERN_MAX_LEN: usize = 64;
+
+/// Map a value to its shape: `A`/`a` for upper/lower letters, `9` for digits, other chars kept.
+fn value_shape(value: &str) -> String {
+    let mut shape = String::new();
+    for (idx, ch) in value.chars().enumerate() {
+        if idx >= PATTERN_MAX_LEN {
+            shape.push('…');
+            break;
+        }
+        let mapped = if ch.is_ascii_digit() {
+            '9'
+        } else if ch.is_uppercase() {
+            'A'
+        } else if ch.is_alphabetic() {
+            'a'
+        } else if ch.is_whitespace() {
+            ' '
+        } else {
+            ch
+        };
+        shape.push(mapped);
+    }
+    shape
+}
+
+#[tauri::command]
+fn profile_column_patterns(
+    path: UserPath,
+    dialect: CsvDialect,
+    columns: Option<Vec<usize>>,
+    max_patterns: Option<usize>,
+    top: Option<usize>,
+) -> Result<Vec<ColumnPatternProfile>, String> {
+    let max_patterns = max_patterns.unwrap_or(1000);
+    let top = top.unwrap_or(20);
+
+    let mut reader = dialect.reader(&path)?;
+
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    let targets: Vec<usize> = columns
+        .unwrap_or_else(|| (0..headers.len()).collect())
+        .into_iter()
+        .filter(|col| *col < headers.len())
+        .collect();
+
+    struct PatternInternal {
+        non_empty: usize,
+        patterns: HashMap<String, (usize, String)>,
+        truncated: bool,
+    }
+
+    let mut profiles: Vec<PatternInternal> = targets
+        .iter()
+        .map(|_| PatternInternal {
+            non_empty: 0,
+            patterns: HashMap::new(),
+            truncated: false,
+        })
+        .collect();
+
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        for (slot, col) in targets.iter().enumerate() {
+            let value = record.get(*col).unwrap_or("");
+            if value.trim().is_empty() {
+                continue;
+            }
+            let profile = &mut profiles[slot];
+            profile.non_empty += 1;
+            let shape = value_shape(value);
+            if let Some(entry) = profile.patterns.get_mut(&shape) {
+                entry.0 += 1;
+            } else if profile.patterns.len() < max_patterns {
+                profile.patterns.insert(shape, (1, value.to_string()));
+            } else {
+                profile.truncated = true;
+            }
+        }
+    }
+
+    let results = targets
+        .iter()
+        .zip(profiles)
+        .map(|(col, profile)| {
+            let distinct_patterns = profile.patterns.len();
+            let mut patterns: Vec<PatternCount> = profile
+                .patterns
+                .into_iter()
+                .map(|(pattern, (count, example))| PatternCount {
+                    pattern,
+                    count,
+                    example,
+                })
+                .collect();
+            patterns.sort_by(|a, b| {
+                b.count
+                    .cmp(&a.count)
+                    .then_with(|| a.pattern.cmp(&b.pattern))
+            });
+            patterns.truncate(top);
+            ColumnPatternProfile {
+                column: *col,
+                name: headers[*col].clone(),
+                non_empty: profile.non_empty,
+                distinct_patterns,
+                patterns_truncated: profile.truncated,
+                patterns,
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnCorrelation {
+    pub left: usize,
+    pub right: usize,
+    pub left_name: String,
+    pub right_name: String,
+    pub pearson: Option<f64>,
+    pub pairs: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FunctionalDependency {
+    pub determinant: usize,
+    pub dependent: usize,
+    pub determinant_name: String,
+    pub dependent_name: String,
+    /// `None` when the determinant exceeded `max_distinct` values and tracking stopped.
+    pub holds: Option<bool>,
+    pub violations: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnRelationships {
+    pub correlations: Vec<ColumnCorrelation>,
+    pub dependencies: Vec<FunctionalDependency>,
+}
+
+/// Pairwise Pearson correlation between fully numeric columns, plus a check of whether
+/// each column functionally determines each other one (same A value, same B value).
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn analyze_column_relationships(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    columns: Option<Vec<usize>>,
+    max_distinct: Option<usize>,
+) -> Result<ColumnRelationships, String> {
+    check_file_size(&app, &path, "analyze_column_relationships")?;
+    let max_distinct = max_distinct.unwrap_or(10_000);
+
+    let mut reader = dialect.reader(&path)?;
+
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    let targets: Vec<usize> = columns
+        .unwrap_or_else(|| (0..headers.len()).collect())
+        .into_iter()
+        .filter(|col| *col < headers.len())
+        .collect();
+    let count = targets.len();
+
+    #[derive(Default, Clone)]
+    struct PairMoments {
+        n: usize,
+        sx: f64,
+        sy: f64,
+        sxx: f64,
+        syy: f64,
+        sxy: f64,
+    }
+
+    struct DependencyInternal {
+        // Determinant value -> first dependent value per target, `None` once it conflicted.
+        mapping: HashMap<String, Vec<Option<String>>>,
+        violations: Vec<usize>,
+        truncated: bool,
+    }
+
+    let mut numeric = vec![true; count];
+    let mut moments = vec![PairMoments::default(); count * count];
+    let mut dependencies: Vec<DependencyInternal> = targets
+        .iter()
+        .map(|_| DependencyInternal {
+            mapping: HashMap::new(),
+            violations: vec![0; count],
+            truncated: false,
+        })
+        .collect();
+
+    let mut record = csv::StringRecord::new();
+    let mut numbers: Vec<Option<f64>> = Vec::with_capacity(count);
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let mut values: Vec<&str> = Vec::with_capacity(count);
+        numbers.clear();
+        for (slot, col) in targets.iter().enumerate() {
+            let value = record.get(*col).unwrap_or("").trim();
+            values.push(value);
+            let number = value.parse::<f64>().ok().filter(|n| n.is_finite());
+            if number.is_none() && !value.is_empty() {
+                numeric[slot] = false;
+            }
+            numbers.push(number);
+        }
+
+        for left in 0..count {
+            let Some(x) = numbers[left].filter(|_| numeric[left]) else {
+                continue;
+            };
+            for right in (left + 1)..count {
+                let Some(y) = numbers[right].filter(|_| numeric[right]) else {
+                    continue;
+                };
+                let pair = &mut moments[left * count + right];
+                pair.n += 1;
+                pair.sx += x;
+                pair.sy += y;
+                pair.sxx += x * x;
+                pair.syy += y * y;
+                pair.sxy += x * y;
+            }
+        }
+
+        for (slot, dependency) in dependencies.iter_mut().enumerate() {
+            if dependency.truncated {
+                continue;
+            }
+            let key = values[slot];
+            let full = dependency.mapping.len() >= max_distinct;
+            match dependency.mapping.get_mut(key) {
+                Some(seen) => {
+                    for (other, first) in seen.iter_mut().enumerate() {
+                        if first.as_deref().is_some_and(|v| v != values[other]) {
+                            *first = None;
+                            dependency.violations[other] += 1;
+                        }
+                    }
+                }
+                None if full => {
+                    dependency.truncated = true;
+                    dependency.mapping = HashMap::new();
+                }
+                None => {
+                    let firsts = values.iter().map(|v| Some(v.to_string())).collect();
+                    dependency.mapping.insert(key.to_string(), firsts);
+                }
+            }
+        }
+    }
+
+    let mut correlations = Vec::new();
+    for left in 0..count {
+        for right in (left + 1)..count {
+            if !numeric[left] || !numeric[right] {
+                continue;
+            }
+            let pair = &moments[left * count + right];
+            let n = pair.n as f64;
+            let cov = pair.sxy - pair.sx * pair.sy / n;
+            let var_x = pair.sxx - pair.sx * pair.sx / n;
+            let var_y = pair.syy - pair.sy * pair.sy / n;
+            let pearson = (pair.n >= 2 && var_x > 0.0 && var_y > 0.0)
+                .then(|| (cov / (var_x * var_y).sqrt()).clamp(-1.0, 1.0));
+            correlations.push(ColumnCorrelation {
+                left: targets[left],
+                right: targets[right],
+                left_name: headers[targets[left]].clone(),
+                right_name: headers[targets[right]].clone(),
+                pearson,
+                pairs: pair.n,
+            });
+        }
+    }
+
+    let mut dependency_results = Vec::new();
+    for (slot, dependency) in dependencies.iter().enumerate() {
+        for other in 0..count {
+            if other == slot {
+                continue;
+            }
+            dependency_results.push(FunctionalDependency {
+                determinant: targets[slot],
+                dependent: targets[other],
+                determinant_name: headers[targets[slot]].clone(),
+                dependent_name: headers[targets[other]].clone(),
+                holds: (!dependency.truncated).then_some(dependency.violations[other] == 0),
+                violations: dependency.violations[other],
+            });
+        }
+    }
+
+    Ok(ColumnRelationships {
+        correlations,
+        dependencies: dependency_results,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KeyCandidate {
+    pub columns: Vec<usize>,
+    pub names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KeyColumnSuggestions {
+    pub rows: usize,
+    pub candidates: Vec<KeyCandidate>,
+    /// Set when combinations beyond `max_candidates` per size were not checked.
+    pub truncated: bool,
+}
+
+fn column_combinations(columns: &[usize], size: usize) -> Vec<Vec<usize>> {
+    if size == 0 {
+        return vec![Vec::new()];
+    }
+    let mut result = Vec::new();
+    for (idx, col) in columns.iter().enumerate() {
+        for mut rest in column_combinations(&columns[idx + 1..], size - 1) {
+            rest.insert(0, *col);
+            result.push(rest);
+        }
+    }
+    result
+}
+
+/// Stream the file once and report, per candidate, whether its values are unique and
+/// whether they are filled on every row. Values are tracked as 64-bit hashes; a candidate's
+/// set is dropped at its first duplicate or empty cell.
+fn scan_key_candidates(
+    path: &str,
+    dialect: &CsvDialect,
+    candidates: &[Vec<usize>],
+) -> Result<(usize, Vec<bool>, Vec<bool>), String> {
+    use std::hash::{Hash, Hasher};
+    let mut reader = dialect.reader(path)?;
+    let _ = reader.headers().map_err(|e| e.to_string())?;
+
+    let mut seen: Vec<Option<HashSet<u64>>> =
+        candidates.iter().map(|_| Some(HashSet::new())).collect();
+    let mut filled = vec![true; candidates.len()];
+    let mut rows = 0usize;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        rows += 1;
+        for (idx, columns) in candidates.iter().enumerate() {
+            if !filled[idx] {
+                continue;
+            }
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            let mut has_empty = false;
+            for col in columns {
+                let value = record.get(*col).unwrap_or("").trim();
+                has_empty |= value.is_empty();
+                value.hash(&mut hasher);
+            }
+            if has_empty {
+                filled[idx] = false;
+                seen[idx] = None;
+            } else if let Some(set) = &mut seen[idx] {
+                if !set.insert(hasher.finish()) {
+                    seen[idx] = None;
+                }
+            }
+        }
+    }
+    let unique = seen.iter().map(|slot| slot.is_some()).collect();
+    Ok((rows, unique, filled))
+}
+
+/// Find minimal unique, non-empty column sets: single columns first, then combinations of
+/// always-filled columns up to `max_size`, skipping supersets of keys already found.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn suggest_key_columns(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    max_size: Option<usize>,
+    max_candidates: Option<usize>,
+) -> Result<KeyColumnSuggestions, String> {
+    check_file_size(&app, &path, "suggest_key_columns")?;
+    let max_size = max_size.unwrap_or(2).clamp(1, 3);
+    let max_candidates = max_candidates.unwrap_or(200);
+
+    let headers = {
+        let mut reader = dialect.reader(&path)?;
+        reader
+            .headers()
+            .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+            .map_err(|e| e.to_string())?
+    };
+
+    let singles: Vec<Vec<usize>> = (0..headers.len()).map(|col| vec![col]).collect();
+    let (rows, unique, filled) = scan_key_candidates(&path, &dialect, &singles)?;
+    let mut keys: Vec<Vec<usize>> = singles
+        .into_iter()
+        .zip(unique)
+        .filter_map(|(columns, unique)| unique.then_some(columns))
+        .collect();
+
+    // Combinations only draw from columns that are never empty and not keys on their own.
+    let pool: Vec<usize> = (0..headers.len())
+        .filter(|col| filled[*col] && !keys.iter().any(|key| key[..] == [*col]))
+        .collect();
+
+    let mut truncated = false;
+    for size in 2..=max_size {
+        let mut combos: Vec<Vec<usize>> = column_combinations(&pool, size)
+            .into_iter()
+            .filter(|combo| {
+                !keys
+                    .iter()
+                    .any(|key| key.iter().all(|col| combo.contains(col)))
+            })
+            .collect();
+        if combos.len() > max_candidates {
+            combos.truncate(max_candidates);
+            truncated = true;
+        }
+        if combos.is_empty() {
+            continue;
+        }
+        let (_, unique, _) = scan_key_candidates(&path, &dialect, &combos)?;
+        keys.extend(
+            combos
+                .into_iter()
+                .zip(unique)
+                .filter_map(|(columns, unique)| unique.then_some(columns)),
+        );
+    }
+
+    Ok(KeyColumnSuggestions {
+        rows,
+        candidates: keys
+            .into_iter()
+            .map(|columns| KeyCandidate {
+                names: columns.iter().map(|col| headers[*col].clone()).collect(),
+                columns,
+            })
+            .collect(),
+        truncated,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LanguageShare {
+    /// ISO 639-3 code.
+    pub code: String,
+    pub name: String,
+    pub count: usize,
+    pub share: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnLanguages {
+    pub column: usize,
+    pub name: String,
+    /// Text cells looked at (cells without letters are skipped).
+    pub sampled: usize,
+    /// Sampled cells too short or mixed to detect reliably.
+    pub unreliable: usize,
+    /// Most frequent first.
+    pub languages: Vec<LanguageShare>,
+}
+
+/// Detect the language of up to `sample_size` text cells per column and report each
+/// column's languages by frequency. Reading stops once every column has its sample.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn detect_column_languages(
+    path: UserPath,
+    dialect: CsvDialect,
+    columns: Option<Vec<usize>>,
+    sample_size: Option<usize>,
+) -> Result<Vec<ColumnLanguages>, String> {
+    let sample_size = sample_size.unwrap_or(500).max(1);
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let targets: Vec<usize> = columns
+        .unwrap_or_else(|| (0..headers.len()).collect())
+        .into_iter()
+        .filter(|col| *col < headers.len())
+        .collect();
+
+    let mut sampled = vec![0usize; targets.len()];
+    let mut unreliable = vec![0usize; targets.len()];
+    let mut counts: Vec<HashMap<whatlang::Lang, usize>> = vec![HashMap::new(); targets.len()];
+    let mut record = csv::StringRecord::new();
+    while sampled.iter().any(|n| *n < sample_size)
+        && reader.read_record(&mut record).map_err(|e| e.to_string())?
+    {
+        for (slot, col) in targets.iter().enumerate() {
+            let value = record.get(*col).unwrap_or("").trim();
+            if sampled[slot] >= sample_size || !value.chars().any(|c| c.is_alphabetic()) {
+                continue;
+            }
+            sampled[slot] += 1;
+            match whatlang::detect(value).filter(|info| info.is_reliable()) {
+                Some(info) => *counts[slot].entry(info.lang()).or_insert(0) += 1,
+                None => unreliable[slot] += 1,
+            }
+        }
+    }
+
+    Ok(targets
+        .iter()
+        .enumerate()
+        .map(|(slot, col)| {
+            let mut languages: Vec<LanguageShare> = counts[slot]
+                .iter()
+                .map(|(lang, count)| LanguageShare {
+                    code: lang.code().to_string(),
+                    name: lang.eng_name().to_string(),
+                    count: *count,
+                    share: *count as f64 / sampled[slot] as f64,
+                })
+                .collect();
+            languages.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.code.cmp(&b.code)));
+            ColumnLanguages {
+                column: *col,
+                name: headers[*col].clone(),
+                sampled: sampled[slot],
+                unreliable: unreliable[slot],
+                languages,
+            }
+        })
+        .collect())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GeneratedColumnSpec {
+    pub name: String,
+    pub generator: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub precision: Option<usize>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub choices: Option<Vec<String>>,
+    pub null_percent: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GenerateCsvSpec {
+    pub columns: Vec<GeneratedColumnSpec>,
+    pub rows: usize,
+    pub seed: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GenerateCsvResult {
+    pub output_path: String,
+    pub rows: usize,
+    pub hashes: FileHashes,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+const FAKE_FIRST_NAMES: &[&str] = &[
+    "James", "Mary", "Wei", "Fatima", "Lucas", "Sofia", "Hiro", "Amara", "Noah", "Elena", "Omar",
+    "Chloe", "Ravi", "Ingrid", "Mateo", "Yuki",
+];
+const FAKE_LAST_NAMES: &[&str] = &[
+    "Smith", "Garcia", "Chen", "Khan", "Muller", "Rossi", "Tanaka", "Okafor", "Silva", "Novak",
+    "Dubois", "Kim", "Patel", "Larsen", "Cohen", "Ivanova",
+];
+
+fn parse_generator_date(
+    value: Option<&str>,
+    fallback: chrono::NaiveDate,
+) -> Result<chrono::NaiveDate, String> {
+    match value {
+        Some(text) => chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .map_err(|e| format!("{}: {}", text, e)),
+        None => Ok(fallback),
+    }
+}
+
+fn generate_value(
+    column: &GeneratedColumnSpec,
+    row: usize,
+    rng: &mut rand::rngs::StdRng,
+) -> Result<String, String> {
+    use rand::Rng;
+
+    if let Some(percent) = column.null_percent {
+        if rng.gen_range(0.0..100.0) < percent {
+            return Ok(String::new());
+        }
+    }
+
+    let value = match column.generator.as_str() {
+        "uuid" => random_uuid_v4(rng),
+        "sequence" => {
+            let start = column.min.unwrap_or(1.0) as i64;
+            (start + row as i64).to_string()
+        }
+        "int" => {
+            let min = column.min.unwrap_or(0.0) as i64;
+            let max = column.max.unwrap_or(100.0) as i64;
+            if max < min {
+                return Err(format!("{}: max is below min", column.name));
+            }
+            rng.gen_range(min..=max).to_string()
+        }
+        "float" => {
+            let min = column.min.unwrap_or(0.0);
+            let max = column.max.unwrap_or(1.0);
+            if max < min {
+                return Err(format!("{}: max is below min", column.name));
+            }
+            let value = if max > min {
+                rng.gen_range(min..max)
+            } else {
+                min
+            };
+            format!("{:.*}", column.precision.unwrap_or(2), value)
+        }
+        "date" => {
+            let default_start = chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap_or_default();
+            let default_end = chrono::NaiveDate::from_ymd_opt(2030, 12, 31).unwrap_or_default();
+            let start = parse_generator_date(column.start.as_deref(), default_start)?;
+            let end = parse_generator_date(column.end.as_deref(), default_end)?;
+            let span = (end - start).num_days();
+            if span < 0 {
+                return Err(format!("{}: end date is before start date", column.name));
+            }
+            let date = start + chrono::Duration::days(rng.gen_range(0..=span));
+            date.format("%Y-%m-%d").to_string()
+        }
+        "choice" => {
+            let choices = column.choices.as_deref().unwrap_or(&[]);
+            if choices.is_empty() {
+                return Err(format!(
+                    "{}: choice generator needs a choice list",
+                    column.name
+                ));
+            }
+            choices[rng.gen_range(0..choices.len())].clone()
+        }
+        "first_name" => FAKE_FIRST_NAMES[rng.gen_range(0..FAKE_FIRST_NAMES.len())].to_string(),
+        "last_name" => FAKE_LAST_NAMES[rng.gen_range(0..FAKE_LAST_NAMES.len())].to_string(),
+        "name" => format!(
+            "{} {}",
+            FAKE_FIRST_NAMES[rng.gen_range(0..FAKE_FIRST_NAMES.len())],
+            FAKE_LAST_NAMES[rng.gen_range(0..FAKE_LAST_NAMES.len())]
+        ),
+        "email" => format!(
+            "{}.{}{}@example.com",
+            FAKE_FIRST_NAMES[rng.gen_range(0..FAKE_FIRST_NAMES.len())].to_lowercase(),
+            FAKE_LAST_NAMES[rng.gen_range(0..FAKE_LAST_NAMES.len())].to_lowercase(),
+            rng.gen_range(1..1000)
+        ),
+        other => return Err(format!("unknown generator: {}", other)),
+    };
+    Ok(value)
+}
+
+/// Write a synthetic CSV from a column spec. A fixed `seed` makes the output reproducible.
+#[tauri::command]
+fn generate_csv(
+    target_path: UserPath,
+    dialect: CsvDialect,
+    spec: GenerateCsvSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<GenerateCsvResult, String> {
+    use rand::SeedableRng;
+
+    ensure_can_write(&target_path, overwrite)?;
+    if spec.columns.is_empty() {
+        return Err("at least one column is required".to_string());
+    }
+    let mut rng = match spec.seed {
+        Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+        None => rand::rngs::StdRng::from_entropy(),
+    };
+
+    let dry_run = dry_run.unwrap_or(false);
+    let mut writer = dialect.output_writer(&target_path, dry_run)?;
+
+    let headers: Vec<&str> = spec.columns.iter().map(|c| c.name.as_str()).collect();
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+
+    let mut row = Vec::with_capacity(spec.columns.len());
+    for row_index in 0..spec.rows {
+        row.clear();
+        for column in &spec.columns {
+            row.push(generate_value(column, row_index, &mut rng)?);
+        }
+        writer.write_record(&row).map_err(|e| e.to_string())?;
+    }
+
+    let hashes = dialect.finish_writer(writer, &target_path)?;
+    Ok(GenerateCsvResult {
+        hashes,
+        output_path: target_path.to_string(),
+        rows: spec.rows,
+        dry_run,
+    })
+}
+
+/// Background jobs quicker than this finish without a notification.
+const NOTIFY_AFTER: Duration = Duration::from_secs(10);
+
+fn path_display_name(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn send_notification(app: &tauri::AppHandle, title: &str, body: &str) {
+    use tauri_plugin_notification::NotificationExt;
+    if let Err(err) = app.notification().builder().title(title).body(body).show() {
+        tracing::warn!(error = %err, "failed to show notification");
+    }
+}
+
+/// Show a native notification for a job that ran at least `NOTIFY_AFTER`, so the user can
+/// switch away during long runs.
+fn notify_job_finished<T>(
+    app: &tauri::AppHandle,
+    started: Instant,
+    title: &str,
+    result: &Result<T, String>,
+    summary: impl FnOnce(&T) -> String,
+) {
+    if started.elapsed() < NOTIFY_AFTER {
+        return;
+    }
+    match result {
+        Ok(value) => {
+            send_notification(app, &tr_app(app, "notify_finished", title), &summary(value))
+        }
+        Err(err) => send_notification(app, &tr_app(app, "notify_failed", title), err),
+    }
+}
+
+/// Resolve a file in the app config dir, creating the directory on first use.
+fn app_config_file(app: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    use tauri::Manager;
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(name))
+}
+
+fn read_json_config<T: serde::de::DeserializeOwned + Default>(path: &PathBuf) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+fn write_json_config<T: Serialize>(path: &PathBuf, value: &T) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    fs::write(path, content).map_err(|e| e.to_string())
+}
+
+const FILE_SIZE_LIMITS_FILE: &str = "file_size_limits.json";
+
+/// Input size limits for commands that buffer data. Above the soft limit they switch to
+/// bounded or disk-backed algorithms; above the hard limit they refuse to run.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FileSizeLimits {
+    pub soft_limit_bytes: u64,
+    pub hard_limit_bytes: u64,
+}
+
+impl Default for FileSizeLimits {
+    fn default() -> Self {
+        Self {
+            soft_limit_bytes: 512 * 1024 * 1024,
+            hard_limit_bytes: 8 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+fn load_file_size_limits(app: &tauri::AppHandle) -> FileSizeLimits {
+    app_config_file(app, FILE_SIZE_LIMITS_FILE)
+        .and_then(|path| read_json_config(&path))
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+fn get_file_size_limits(app: tauri::AppHandle) -> FileSizeLimits {
+    load_file_size_limits(&app)
+}
+
+#[tauri::command]
+fn set_file_size_limits(
+    app: tauri::AppHandle,
+    limits: FileSizeLimits,
+) -> Result<FileSizeLimits, String> {
+    if limits.soft_limit_bytes == 0 || limits.soft_limit_bytes > limits.hard_limit_bytes {
+        return Err("soft limit must be positive and not above the hard limit".to_string());
+    }
+    write_json_config(&app_config_file(&app, FILE_SIZE_LIMITS_FILE)?, &limits)?;
+    Ok(limits)
+}
+
+/// Check `path` against the configured limits. Returns `true` when the file is above the
+/// soft limit and the caller should use its bounded variant.
+fn check_file_size(app: &tauri::AppHandle, path: &str, operation: &str) -> Result<bool, String> {
+    let size = fs::metadata(path).map_err(|e| e.to_string())?.len();
+    let limits = load_file_size_limits(app);
+    if size > limits.hard_limit_bytes {
+        return Err(structured_error(
+            "file_too_large",
+            &format!("{} refuses files above the hard size limit", operation),
+            serde_json::json!({
+                "path": path,
+                "size": size,
+                "hard_limit_bytes": limits.hard_limit_bytes,
+            }),
+        ));
+    }
+    Ok(size > limits.soft_limit_bytes)
+}
+
+/// Room left on a volume after a write, so the system and other apps keep working.
+const DISK_SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// Fail with `insufficient_disk_space` when writing about `bytes` at `target` would fill its
+/// volume, rather than dying mid-write and leaving a truncated output. Goes ahead when the
+/// platform can't report free space.
+fn ensure_disk_space(target: &std::path::Path, bytes: u64, operation: &str) -> Result<(), String> {
+    let Some(available) = target
+        .ancestors()
+        .find(|dir| dir.is_dir())
+        .and_then(|dir| fs2::available_space(dir).ok())
+    else {
+        return Ok(());
+    };
+    let needed = bytes.saturating_add(DISK_SPACE_MARGIN);
+    if available >= needed {
+        return Ok(());
+    }
+    Err(structured_error(
+        "insufficient_disk_space",
+        &format!("not enough free disk space for {}", operation),
+        serde_json::json!({
+            "path": target.to_string_lossy(),
+            "needed_bytes": needed,
+            "available_bytes": available,
+        }),
+    ))
+}
+
+/// Pre-flight for rewriting `source` into `target`: about the source's size, double for
+/// UTF-16 output.
+fn ensure_space_for_rewrite(
+    source: &str,
+    target: &str,
+    output: &CsvDialect,
+    operation: &str,
+) -> Result<(), String> {
+    let size = fs::metadata(source).map(|m| m.len()).unwrap_or(0);
+    let size = if output.is_utf16() { size * 2 } else { size };
+    ensure_disk_space(std::path::Path::new(target), size, operation)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OperationRecord {
+    pub timestamp_ms: u64,
+    pub operation: String,
+    pub input_path: Option<String>,
+    pub output_path: Option<String>,
+    pub applied: Option<usize>,
+    pub params: serde_json::Value,
+}
+
+const AUDIT_LOG_FILE: &str = "operations.jsonl";
+/// Parameters kept out of the audit log, which `export_diagnostics` ships: the hashing
+/// salt, script bodies and access tokens.
+const REDACTED_PARAMS: &[&str] = &["salt", "script", "token"];
+const AUDIT_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+const AUDIT_LOG_KEEP: usize = 3;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn audit_log_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    use tauri::Manager;
+    let dir = app.path().app_log_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+/// Shift `operations.jsonl` → `.1` → `.2` … once it grows past the size cap.
+fn rotate_audit_log(dir: &std::path::Path) -> Result<(), String> {
+    let current = dir.join(AUDIT_LOG_FILE);
+    let size = fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+    if size < AUDIT_LOG_MAX_BYTES {
+        return Ok(());
+    }
+    for idx in (1..AUDIT_LOG_KEEP).rev() {
+        let from = dir.join(format!("{}.{}", AUDIT_LOG_FILE, idx));
+        if from.exists() {
+            let to = dir.join(format!("{}.{}", AUDIT_LOG_FILE, idx + 1));
+            fs::rename(&from, &to).map_err(|e| e.to_string())?;
+        }
+    }
+    fs::rename(&current, dir.join(format!("{}.1", AUDIT_LOG_FILE))).map_err(|e| e.to_string())
+}
+
+fn append_operation(app: &tauri::AppHandle, record: &OperationRecord) -> Result<(), String> {
+    let dir = audit_log_dir(app)?;
+    rotate_audit_log(&dir)?;
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    let mut file = File::options()
+        .create(true)
+        .append(true)
+        .open(dir.join(AUDIT_LOG_FILE))
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+/// Append a destructive operation to the audit log. Logging failures never fail the
+/// operation itself.
+fn record_operation(
+    app: &tauri::AppHandle,
+    operation: &str,
+    input_path: Option<&str>,
+    output_path: Option<&str>,
+    applied: Option<usize>,
+    mut params: serde_json::Value,
+) {
+    redact_params(&mut params);
+    let record = OperationRecord {
+        timestamp_ms: now_millis(),
+        operation: operation.to_string(),
+        input_path: input_path.map(|p| p.to_string()),
+        output_path: output_path.map(|p| p.to_string()),
+        applied,
+        params,
+    };
+    let _ = append_operation(app, &record);
+}
+
+/// Replace the values of `REDACTED_PARAMS` keys at any depth, so nested macro specs are
+/// covered too.
+fn redact_params(params: &mut serde_json::Value) {
+    match params {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if !REDACTED_PARAMS.contains(&key.as_str()) {
+                    redact_params(value);
+                } else if !value.is_null() {
+                    *value = serde_json::Value::String("[redacted]".to_string());
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_params),
+        _ => {}
+    }
+}
+
+/// Most recent operations first, reading through the rotated files as needed.
+#[tauri::command]
+fn get_operation_history(
+    app: tauri::AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<OperationRecord>, String> {
+    let limit = limit.unwrap_or(200);
+    let dir = audit_log_dir(&app)?;
+    let mut files = vec![dir.join(AUDIT_LOG_FILE)];
+    files.extend((1..=AUDIT_LOG_KEEP).map(|idx| dir.join(format!("{}.{}", AUDIT_LOG_FILE, idx))));
+
+    let mut history = Vec::new();
+    for file in files {
+        if history.len() >= limit {
+            break;
+        }
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        for line in content.lines().rev() {
+            if history.len() >= limit {
+                break;
+            }
+            if let Ok(record) = serde_json::from_str::<OperationRecord>(line) {
+                history.push(record);
+            }
+        }
+    }
+    Ok(history)
+}
+
+static LOG_GUARD: OnceLock<tracing_appender::non_blocking::WorkerGuard> = OnceLock::new();
+
+const LOG_FILE_PREFIX: &str = "nmeditor.log";
+
+/// Install the global tracing subscriber writing daily-rotated logs to the app log dir.
+/// Spans log their duration when they close.
+fn init_logging(app: &tauri::AppHandle) -> Result<(), String> {
+    let dir = audit_log_dir(app)?;
+    let appender = tracing_appender::rolling::daily(&dir, LOG_FILE_PREFIX);
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_max_level(if cfg!(debug_assertions) {
+            tracing::Level::DEBUG
+        } else {
+            tracing::Level::INFO
+        })
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    let _ = LOG_GUARD.set(guard);
+    Ok(())
+}
+
+const DIAGNOSTIC_LOG_FILES: usize = 3;
+const DIAGNOSTIC_LOG_TAIL_BYTES: u64 = 512 * 1024;
+
+/// Write a plain-text bug-report bundle: environment, recent operations and the tail of
+/// the most recent log files.
+#[tauri::command]
+fn export_diagnostics(
+    app: tauri::AppHandle,
+    target_path: UserPath,
+    overwrite: Option<bool>,
+) -> Result<String, String> {
+    ensure_can_write(&target_path, overwrite)?;
+    let dir = audit_log_dir(&app)?;
+    let mut out = File::create(&target_path).map_err(|e| e.to_string())?;
+    let info = app.package_info();
+    writeln!(out, "nmeditor {} diagnostics", info.version).map_err(|e| e.to_string())?;
+    writeln!(
+        out,
+        "os: {} {}\ngenerated_ms: {}\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        now_millis()
+    )
+    .map_err(|e| e.to_string())?;
+
+    writeln!(out, "== recent operations ==").map_err(|e| e.to_string())?;
+    for mut record in get_operation_history(app.clone(), Some(50))? {
+        // Entries written before redaction existed may still hold secrets.
+        redact_params(&mut record.params);
+        let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+        writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+    }
+
+    let mut logs: Vec<(SystemTime, PathBuf)> = fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(LOG_FILE_PREFIX)
+        })
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    logs.sort_by_key(|entry| std::cmp::Reverse(entry.0));
+
+    for (_, log_path) in logs.into_iter().take(DIAGNOSTIC_LOG_FILES) {
+        writeln!(out, "\n== {} ==", log_path.display()).map_err(|e| e.to_string())?;
+        let mut file = File::open(&log_path).map_err(|e| e.to_string())?;
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if len > DIAGNOSTIC_LOG_TAIL_BYTES {
+            file.seek(SeekFrom::Start(len - DIAGNOSTIC_LOG_TAIL_BYTES))
+                .map_err(|e| e.to_string())?;
+        }
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).map_err(|e| e.to_string())?;
+        out.write_all(&tail).map_err(|e| e.to_string())?;
+    }
+
+    Ok(target_path.to_string())
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScriptPreset {
+    pub name: String,
+    pub script: String,
+    pub description: Option<String>,
+}
+
+const SCRIPT_PRESETS_FILE: &str = "script_presets.json";
+
+#[tauri::command]
+fn list_script_presets(app: tauri::AppHandle) -> Result<Vec<ScriptPreset>, String> {
+    read_json_config(&app_config_file(&app, SCRIPT_PRESETS_FILE)?)
+}
+
+/// Insert or replace a script preset by name.
+#[tauri::command]
+fn save_script_preset(
+    app: tauri::AppHandle,
+    preset: ScriptPreset,
+) -> Result<Vec<ScriptPreset>, String> {
+    let path = app_config_file(&app, SCRIPT_PRESETS_FILE)?;
+    let mut presets: Vec<ScriptPreset> = read_json_config(&path)?;
+    presets.retain(|existing| existing.name != preset.name);
+    presets.push(preset);
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    write_json_config(&path, &presets)?;
+    Ok(presets)
+}
+
+#[tauri::command]
+fn delete_script_preset(app: tauri::AppHandle, name: String) -> Result<Vec<ScriptPreset>, String> {
+    let path = app_config_file(&app, SCRIPT_PRESETS_FILE)?;
+    let mut presets: Vec<ScriptPreset> = read_json_config(&path)?;
+    presets.retain(|existing| existing.name != name);
+    write_json_config(&path, &presets)?;
+    Ok(presets)
+}
+
+const SEARCH_HISTORY_FILE: &str = "search_history.json";
+
+const SEARCH_HISTORY_LIMIT: usize = 50;
+
+/// Most recent first.
+#[tauri::command]
+fn list_search_history(app: tauri::AppHandle) -> Result<Vec<FindReplaceSpec>, String> {
+    read_json_config(&app_config_file(&app, SEARCH_HISTORY_FILE)?)
+}
+
+/// Move `spec` to the top of the search history.
+#[tauri::command]
+fn add_search_history(
+    app: tauri::AppHandle,
+    spec: FindReplaceSpec,
+) -> Result<Vec<FindReplaceSpec>, String> {
+    let path = app_config_file(&app, SEARCH_HISTORY_FILE)?;
+    let mut history: Vec<FindReplaceSpec> = read_json_config(&path)?;
+    history.retain(|existing| existing != &spec);
+    history.insert(0, spec);
+    history.truncate(SEARCH_HISTORY_LIMIT);
+    write_json_config(&path, &history)?;
+    Ok(history)
+}
+
+#[tauri::command]
+fn clear_search_history(app: tauri::AppHandle) -> Result<(), String> {
+    write_json_config(
+        &app_config_file(&app, SEARCH_HISTORY_FILE)?,
+        &Vec::<FindReplaceSpec>::new(),
+    )
+}
+
+/// A find/replace kept under a name: pattern, column scope and flags.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedSearch {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub spec: FindReplaceSpec,
+}
+
+const SAVED_SEARCHES_FILE: &str = "saved_searches.json";
+
+#[tauri::command]
+fn list_saved_searches(app: tauri::AppHandle) -> Result<Vec<SavedSearch>, String> {
+    read_json_config(&app_config_file(&app, SAVED_SEARCHES_FILE)?)
+}
+
+/// Insert or replace a saved search by name; the pattern must compile.
+#[tauri::command]
+fn save_search(app: tauri::AppHandle, search: SavedSearch) -> Result<Vec<SavedSearch>, String> {
+    FindReplacer::new(&search.spec)?;
+    let path = app_config_file(&app, SAVED_SEARCHES_FILE)?;
+    let mut searches: Vec<SavedSearch> = read_json_config(&path)?;
+    searches.retain(|existing| existing.name != search.name);
+    searches.push(search);
+    searches.sort_by(|a, b| a.name.cmp(&b.name));
+    write_json_config(&path, &searches)?;
+    Ok(searches)
+}
+
+#[tauri::command]
+fn delete_saved_search(app: tauri::AppHandle, name: String) -> Result<Vec<SavedSearch>, String> {
+    let path = app_config_file(&app, SAVED_SEARCHES_FILE)?;
+    let mut searches: Vec<SavedSearch> = read_json_config(&path)?;
+    searches.retain(|existing| existing.name != name);
+    write_json_config(&path, &searches)?;
+    Ok(searches)
+}
+
+fn plugins_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    use tauri::Manager;
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("plugins");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+/// The `.wasm` file for plugin `name`. Names are file stems, so anything that could leave
+/// the plugins dir is refused.
+fn plugin_path(app: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    check_plugin_name(name)?;
+    Ok(plugins_dir(app)?.join(format!("{}.wasm", name)))
+}
+
+fn check_plugin_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains("..") || name.contains(['/', '\\', ':', '\0']) {
+        return Err(format!("invalid plugin name: {}", name));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PluginInfo {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+/// List `*.wasm` plugins in the app data `plugins` dir, validating that each one loads and
+/// exports the transform ABI.
+#[tauri::command]
+fn list_plugins(app: tauri::AppHandle) -> Result<Vec<PluginInfo>, String> {
+    let dir = plugins_dir(&app)?;
+    let mut plugins = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("wasm") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let error = WasmPlugin::load(&path).err();
+        plugins.push(PluginInfo {
+            name,
+            path: path.to_string_lossy().to_string(),
+            size,
+            valid: error.is_none(),
+            error,
+        });
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(plugins)
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ProjectSortKey {
+    pub column: usize,
+    #[serde(default)]
+    pub descending: bool,
+    /// Compare as this column type instead of the file's override for the column.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub value_type: Option<String>,
+    /// Where cells that don't parse as the type go: `last` (default) or `first`, whatever
+    /// the direction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unparsed: Option<String>,
+    /// Where empty cells go: `first` or `last`, whatever the direction. Unset, they sort as
+    /// the smallest value untyped and with the unparsed cells when typed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nulls: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ProjectExport {
+    pub output_path: Option<String>,
+    /// Named export profile whose options override `dialect`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Re-read the output after the run and report whether it matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify: Option<bool>,
+    /// Output format; the delimiter defaults to the source's.
+    #[serde(flatten)]
+    pub dialect: CsvDialect,
+}
+
+/// A reproducible cleanup recipe: source, dialect, column ops, filters, sort, macros, window
+/// columns and export settings. Steps run in that order.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PipelineProject {
+    pub name: String,
+    pub source_path: String,
+    /// How the source is read: delimiter, quote, escape, encoding and header row.
+    #[serde(flatten)]
+    pub dialect: CsvDialect,
+    #[serde(default)]
+    pub column_ops: Vec<ColumnOp>,
+    #[serde(default)]
+    pub filters: Vec<MacroCondition>,
+    #[serde(default)]
+    pub sort: Vec<ProjectSortKey>,
+    #[serde(default)]
+    pub macros: Vec<CsvMacroSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<WindowColumn>,
+    #[serde(default)]
+    pub export: ProjectExport,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectRunResult {
+    pub output_path: String,
+    pub rows_read: usize,
+    pub rows_written: usize,
+    pub macro_applied: Vec<usize>,
+    pub hashes: FileHashes,
+    /// Set when the sort spilled to disk.
+    pub spill: Option<SpillStats>,
+    /// Filtered-out rows count as deleted.
+    pub counts: RowCounts,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<SaveVerification>,
+}
+
+#[tauri::command]
+fn save_project(
+    path: UserPath,
+    project: PipelineProject,
+    overwrite: Option<bool>,
+) -> Result<String, String> {
+    ensure_can_write(&path, overwrite)?;
+    write_json_config(&PathBuf::from(&path), &project)?;
+    Ok(path.to_string())
+}
+
+#[tauri::command]
+fn load_project(path: UserPath) -> Result<PipelineProject, String> {
+    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+/// Rows per sorted run when a sort spills to disk.
+const SORT_CHUNK_ROWS: usize = 100_000;
+
+/// `types` holds the column type overrides by position.
+fn compare_rows_by_keys(
+    a: &[String],
+    b: &[String],
+    keys: &[ProjectSortKey],
+    types: &[Option<String>],
+) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    for key in keys {
+        let left = a.get(key.column).map(|v| v.as_str()).unwrap_or("");
+        let right = b.get(key.column).map(|v| v.as_str()).unwrap_or("");
+        let kind = key
+            .value_type
+            .as_deref()
+            .or_else(|| types.get(key.column).and_then(|kind| kind.as_deref()));
+        let directed = |ordering: Ordering| {
+            if key.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        if let Some(nulls) = key.nulls.as_deref() {
+            let before = if nulls == "first" {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            };
+            match (left.trim().is_empty(), right.trim().is_empty()) {
+                (true, true) => continue,
+                (true, false) => return before,
+                (false, true) => return before.reverse(),
+                (false, false) => {}
+            }
+        }
+        let unparsed_first = key.unparsed.as_deref() == Some("first");
+        let ordering = match kind.map(|kind| TypedValue::parse_pair(kind, left, right)) {
+            None => directed(compare_sort_cells(left, right)),
+            Some((Some(a), Some(b))) => directed(a.compare(&b)),
+            Some((None, None)) => directed(left.cmp(right)),
+            Some((Some(_), None)) if unparsed_first => Ordering::Greater,
+            Some((Some(_), None)) => Ordering::Less,
+            Some((None, Some(_))) if unparsed_first => Ordering::Less,
+            Some((None, Some(_))) => Ordering::Greater,
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
+/// A column appended on export, computed over the rows in output order: `running_total` or
+/// `moving_average` (over the last `window` rows) of `column`, or `rank` by `column`. Each
+/// restarts at every value of `partition`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WindowColumn {
+    pub name: String,
+    pub function: String,
+    pub column: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<usize>,
+    /// `rank`: largest first.
+    #[serde(default)]
+    pub descending: bool,
+    /// Fixed precision for totals and averages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<usize>,
+}
+
+/// Window columns of one project run. Totals and averages are computed as rows arrive,
+/// keeping one running state per partition. Ranks need the whole partition, so rows are
+/// held back until the partition value changes; that needs rows grouped by it, so the
+/// project must sort on it first. A partition past `chunk_rows` rows moves to disk and is
+/// ranked there.
+struct WindowRunner {
+    specs: Vec<WindowColumn>,
+    /// Type overrides of the columns before the window columns.
+    types: Vec<Option<String>>,
+    width: usize,
+    totals: Vec<HashMap<String, f64>>,
+    recent: Vec<HashMap<String, std::collections::VecDeque<Option<f64>>>>,
+    /// Partition column shared by the `rank` windows, if any rank.
+    rank_partition: Option<Option<usize>>,
+    pending: Vec<Vec<String>>,
+    pending_key: Option<String>,
+    seen_keys: HashSet<String>,
+    /// Earlier rows of the pending partition, in arrival order, each followed by its
+    /// position in the partition.
+    pending_runs: Vec<TempFile>,
+    pending_rows: usize,
+    chunk_rows: usize,
+    spill_dir: PathBuf,
+}
+
+impl WindowRunner {
+    /// Validate `specs` against the project's `sort` and append their names to `headers`.
+    fn new(
+        specs: &[WindowColumn],
+        headers: &mut Vec<String>,
+        types: Vec<Option<String>>,
+        sort: &[ProjectSortKey],
+        spill_dir: PathBuf,
+    ) -> Result<Self, String> {
+        let width = headers.len();
+        let mut rank_partition = None;
+        for spec in specs {
+            if !matches!(
+                spec.function.as_str(),
+                "running_total" | "moving_average" | "rank"
+            ) {
+                return Err(format!("unknown window function '{}'", spec.function));
+            }
+            if spec.column >= width || spec.partition.is_some_and(|col| col >= width) {
+                return Err("column out of range".to_string());
+            }
+            if spec.function == "moving_average" && spec.window.unwrap_or(0) == 0 {
+                return Err(format!(
+                    "window '{}' needs a window of at least 1 row",
+                    spec.name
+                ));
+            }
+            if spec.function == "rank"
+                && *rank_partition.get_or_insert(spec.partition) != spec.partition
+            {
+                return Err("rank windows must share one partition column".to_string());
+            }
+        }
+        // Checked before anything is written, rather than failing part way through when a
+        // partition value comes back.
+        if let Some(Some(column)) = rank_partition {
+            if sort.first().map(|key| key.column) != Some(column) {
+                return Err(format!(
+                    "rank windows need rows grouped by their partition column; sort the \
+                     project by '{}' first",
+                    headers[column]
+                ));
+            }
+        }
+        headers.extend(specs.iter().map(|spec| spec.name.clone()));
+        Ok(Self {
+            specs: specs.to_vec(),
+            types,
+            width,
+            totals: vec![HashMap::new(); specs.len()],
+            recent: vec![HashMap::new(); specs.len()],
+            rank_partition,
+            pending: Vec::new(),
+            pending_key: None,
+            seen_keys: HashSet::new(),
+            pending_runs: Vec::new(),
+            pending_rows: 0,
+            chunk_rows: SORT_CHUNK_ROWS,
+            spill_dir,
+        })
+    }
+
+    fn push(
+        &mut self,
+        mut row: Vec<String>,
+        emit: &mut dyn FnMut(&[String]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        if self.specs.is_empty() {
+            return emit(&row);
+        }
+        row.resize(self.width, String::new());
+        for (slot, spec) in self.specs.iter().enumerate() {
+            let partition = spec
+                .partition
+                .map(|col| row[col].clone())
+                .unwrap_or_default();
+            let number = row[spec.column]
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite());
+            let value = match spec.function.as_str() {
+                "running_total" => {
+                    let total = self.totals[slot].entry(partition).or_insert(0.0);
+                    *total += number.unwrap_or(0.0);
+                    Some(*total)
+                }
+                "moving_average" => {
+                    let recent = self.recent[slot].entry(partition).or_default();
+                    recent.push_back(number);
+                    if recent.len() > spec.window.unwrap_or(1) {
+                        recent.pop_front();
+                    }
+                    let values: Vec<f64> = recent.iter().flatten().copied().collect();
+                    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+                }
+                // Filled in when the partition ends.
+                _ => None,
+            };
+            row.push(match (value, spec.decimals) {
+                (Some(value), Some(decimals)) => format!("{:.*}", decimals, value),
+                (Some(value), None) => value.to_string(),
+                (None, _) => String::new(),
+            });
+        }
+        let Some(partition) = self.rank_partition else {
+            return emit(&row);
+        };
+        let key = partition.map(|col| row[col].clone()).unwrap_or_default();
+        if self.pending_key.as_ref() != Some(&key) {
+            self.flush(emit)?;
+            if !self.seen_keys.insert(key.clone()) {
+                return Err(format!(
+                    "rank windows need rows grouped by their partition column; '{}' comes back \
+                     after other values, so sort by that column first",
+                    key
+                ));
+            }
+            self.pending_key = Some(key);
+        }
+        self.pending.push(row);
+        if self.pending.len() >= self.chunk_rows {
+            self.spill_pending()?;
+        }
+        Ok(())
+    }
+
+    /// Move the held-back rows to a run file, tagged with their position in the partition.
+    fn spill_pending(&mut self) -> Result<(), String> {
+        for row in &mut self.pending {
+            row.push(self.pending_rows.to_string());
+            self.pending_rows += 1;
+        }
+        // No keys: the run keeps arrival order.
+        let run = spill_sorted_chunk(&mut self.pending, &[], &self.types, &self.spill_dir)?;
+        self.pending_runs.push(run);
+        Ok(())
+    }
+
+    /// Rank the held-back partition and write it out.
+    fn flush(
+        &mut self,
+        emit: &mut dyn FnMut(&[String]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        if !self.pending_runs.is_empty() {
+            return self.flush_spilled(emit);
+        }
+        for (slot, spec) in self.specs.iter().enumerate() {
+            if spec.function != "rank" {
+                continue;
+            }
+            let kind = self.types[spec.column].as_deref().unwrap_or("number");
+            let mut keyed: Vec<(usize, TypedValue)> = self
+                .pending
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| !row[spec.column].trim().is_empty())
+                .filter_map(|(i, row)| {
+                    TypedValue::parse(kind, &row[spec.column]).map(|key| (i, key))
+                })
+                .collect();
+            keyed.sort_by(|a, b| {
+                let ordering = a.1.compare(&b.1);
+                if spec.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+            // Ties share a rank and the next rank skips past them: 1, 2, 2, 4.
+            let mut ranks = vec![None; self.pending.len()];
+            let mut previous: Option<(&TypedValue, usize)> = None;
+            for (position, (i, key)) in keyed.iter().enumerate() {
+                let rank = match previous {
+                    Some((last, rank)) if last.compare(key).is_eq() => rank,
+                    _ => position + 1,
+                };
+                ranks[*i] = Some(rank);
+                previous = Some((key, rank));
+            }
+            for (row, rank) in self.pending.iter_mut().zip(ranks) {
+                row[self.width + slot] = rank.map(|rank| rank.to_string()).unwrap_or_default();
+            }
+        }
+        for row in self.pending.drain(..) {
+            emit(&row)?;
+        }
+        Ok(())
+    }
+
+    /// `flush` for a partition that spilled: per rank window, sort the rows by its column
+    /// on disk, fill in ranks in that order, then sort back by position. Memory stays at
+    /// `chunk_rows` rows whatever the partition's size.
+    fn flush_spilled(
+        &mut self,
+        emit: &mut dyn FnMut(&[String]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.spill_pending()?;
+        let position = self.width + self.specs.len();
+        let by_position = [ProjectSortKey {
+            column: position,
+            value_type: Some("integer".to_string()),
+            ..Default::default()
+        }];
+        let mut runs = std::mem::take(&mut self.pending_runs);
+        let mut tail = Vec::new();
+        for (slot, spec) in self.specs.iter().enumerate() {
+            if spec.function != "rank" {
+                continue;
+            }
+            let kind = self.types[spec.column].as_deref().unwrap_or("number");
+            // Unranked cells (empty or not of the type) go last.
+            let by_value = [ProjectSortKey {
+                column: spec.column,
+                descending: spec.descending,
+                value_type: Some(kind.to_string()),
+                unparsed: Some("last".to_string()),
+                nulls: Some("last".to_string()),
+            }];
+            let mut sorted_runs = Vec::new();
+            let mut buffer = Vec::new();
+            merge_sorted_chunks(&runs, tail, &by_position, &self.types, |row| {
+                buffer.push(row);
+                if buffer.len() >= self.chunk_rows {
+                    sorted_runs.push(spill_sorted_chunk(
+                        &mut buffer,
+                        &by_value,
+                        &self.types,
+                        &self.spill_dir,
+                    )?);
+                }
+                Ok(())
+            })?;
+            buffer.sort_by(|a, b| compare_rows_by_keys(a, b, &by_value, &self.types));
+
+            let mut ranked_runs = Vec::new();
+            let mut ranked = Vec::new();
+            let mut count = 0usize;
+            let mut previous: Option<(String, usize)> = None;
+            merge_sorted_chunks(&sorted_runs, buffer, &by_value, &self.types, |mut row| {
+                let cell = row[spec.column].clone();
+                let key = (!cell.trim().is_empty())
+                    .then(|| TypedValue::parse(kind, &cell))
+                    .flatten();
+                let rank = key.map(|key| {
+                    count += 1;
+                    match &previous {
+                        Some((last, rank))
+                            if TypedValue::parse(kind, last)
+                                .is_some_and(|last| last.compare(&key).is_eq()) =>
+                        {
+                            *rank
+                        }
+                        _ => count,
+                    }
+                });
+                if let Some(rank) = rank {
+                    previous = Some((cell.clone(), rank));
+                }
+                row[self.width + slot] = rank.map(|rank| rank.to_string()).unwrap_or_default();
+                ranked.push(row);
+                if ranked.len() >= self.chunk_rows {
+                    ranked_runs.push(spill_sorted_chunk(
+                        &mut ranked,
+                        &by_position,
+                        &self.types,
+                        &self.spill_dir,
+                    )?);
+                }
+                Ok(())
+            })?;
+            ranked.sort_by(|a, b| compare_rows_by_keys(a, b, &by_position, &self.types));
+            runs = ranked_runs;
+            tail = ranked;
+        }
+        self.pending_rows = 0;
+        merge_sorted_chunks(&runs, tail, &by_position, &self.types, |mut row| {
+            row.truncate(position);
+            emit(&row)
+        })?;
+        Ok(())
+    }
+}
+
+const SPILL_SETTINGS_FILE: &str = "spill_settings.json";
+
+/// Where external sorts write their runs. `directory` (a fast scratch disk, say) defaults to
+/// the app temp dir; `max_bytes` caps what one job may spill.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct SpillSettings {
+    pub directory: Option<String>,
+    pub max_bytes: Option<u64>,
+}
+
+/// Spill usage of one job.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct SpillStats {
+    pub directory: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+fn load_spill_settings(app: &tauri::AppHandle) -> SpillSettings {
+    app_config_file(app, SPILL_SETTINGS_FILE)
+        .and_then(|path| read_json_config(&path))
+        .unwrap_or_default()
+}
+
+/// This process's spill dir: `nmeditor-<pid>` under the configured directory, or the temp dir.
+fn spill_dir(settings: &SpillSettings) -> PathBuf {
+    match &settings.directory {
+        Some(directory) => {
+            PathBuf::from(directory).join(format!("nmeditor-{}", std::process::id()))
+        }
+        None => temp_dir(),
+    }
+}
+
+#[tauri::command]
+fn get_spill_settings(app: tauri::AppHandle) -> Result<SpillSettings, String> {
+    Ok(load_spill_settings(&app))
+}
+
+#[tauri::command]
+fn set_spill_settings(
+    app: tauri::AppHandle,
+    settings: SpillSettings,
+) -> Result<SpillSettings, String> {
+    if let Some(directory) = &settings.directory {
+        authorize_path(directory)?;
+        if !std::path::Path::new(directory).is_dir() {
+            return Err(format!("{} is not a directory", directory));
+        }
+    }
+    write_json_config(&app_config_file(&app, SPILL_SETTINGS_FILE)?, &settings)?;
+    Ok(settings)
+}
+
+/// Sort `rows` and write them to a temporary CSV run, leaving `rows` empty.
+fn spill_sorted_chunk(
+    rows: &mut Vec<Vec<String>>,
+    keys: &[ProjectSortKey],
+    types: &[Option<String>],
+    dir: &std::path::Path,
+) -> Result<TempFile, String> {
+    rows.sort_by(|a, b| compare_rows_by_keys(a, b, keys, types));
+    let chunk = TempFile::new_in(dir, "sort", "csv")?;
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(chunk.path())
+        .map_err(|e| e.to_string())?;
+    for row in rows.drain(..) {
+        writer.write_record(&row).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(chunk)
+}
+
+/// Merge the spilled runs plus the final in-memory run (already sorted), passing each row
+/// to `emit`. Returns the number of rows merged.
+fn merge_sorted_chunks(
+    chunks: &[TempFile],
+    tail: Vec<Vec<String>>,
+    keys: &[ProjectSortKey],
+    types: &[Option<String>],
+    mut emit: impl FnMut(Vec<String>) -> Result<(), String>,
+) -> Result<usize, String> {
+    let mut readers = chunks
+        .iter()
+        .map(|chunk| {
+            csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_path(chunk.path())
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut tail = tail.into_iter();
+    let mut next_row = |source: usize| -> Result<Option<Vec<String>>, String> {
+        if source == readers.len() {
+            return Ok(tail.next());
+        }
+        let mut record = csv::StringRecord::new();
+        let more = readers[source]
+            .read_record(&mut record)
+            .map_err(|e| e.to_string())?;
+        Ok(more.then(|| record.iter().map(|s| s.to_string()).collect()))
+    };
+    let mut heads = (0..=chunks.len())
+        .map(&mut next_row)
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut written = 0usize;
+    loop {
+        let mut best: Option<usize> = None;
+        for (idx, head) in heads.iter().enumerate() {
+            let Some(row) = head else { continue };
+            let better = match best.and_then(|b| heads[b].as_ref()) {
+                Some(current) => compare_rows_by_keys(row, current, keys, types).is_lt(),
+                None => true,
+            };
+            if better {
+                best = Some(idx);
+            }
+        }
+        let Some(source) = best else { break };
+        if let Some(row) = heads[source].take() {
+            emit(row)?;
+            written += 1;
+        }
+        heads[source] = next_row(source)?;
+    }
+    Ok(written)
+}
+
+/// Run a project end to end. `source_path`/`target_path` override the stored paths so the
+/// same recipe can be applied to next month's file.
+#[tauri::command]
+fn run_project(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    project: PipelineProject,
+    source_path: Option<String>,
+    target_path: Option<String>,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<ProjectRunResult, String> {
+    authorize_path(source_path.as_deref().unwrap_or(&project.source_path))?;
+    let target = target_path.as_deref();
+    // The stored output path is checked as well: it is just as much overwritten.
+    if let Some(target) = target.or(project.export.output_path.as_deref()) {
+        authorize_path(target)?;
+        ensure_can_write(target, overwrite)?;
+    }
+    let started = Instant::now();
+    let _taskbar = TaskbarProgress::start(&app);
+    let title = tr_app(&app, "job_project", &project.name);
+    let dry_run = dry_run.unwrap_or(false);
+    let result = execute_project(
+        app.clone(),
+        state,
+        project,
+        source_path,
+        target_path,
+        dry_run,
+    );
+    notify_job_finished(&app, started, &title, &result, |done| {
+        tr_app_with(
+            &app,
+            "summary_project",
+            &[
+                ("written", &done.rows_written),
+                ("read", &done.rows_read),
+                ("file", &path_display_name(&done.output_path)),
+            ],
+        )
+    });
+    result
+}
+
+/// `run_project` without the notification. Sorting buffers the filtered rows.
+#[tracing::instrument(skip_all, fields(project = %project.name, rows = tracing::field::Empty))]
+fn execute_project(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    project: PipelineProject,
+    source_path: Option<String>,
+    target_path: Option<String>,
+    dry_run: bool,
+) -> Result<ProjectRunResult, String> {
+    let source = source_path.unwrap_or_else(|| project.source_path.clone());
+    let target = target_path
+        .or_else(|| project.export.output_path.clone())
+        .ok_or_else(|| "project has no output path".to_string())?;
+    if source == target {
+        return Err("output path must differ from the source file".to_string());
+    }
+    ensure_not_read_only(&state, &target)?;
+    for key in &project.sort {
+        if let Some(kind) = &key.value_type {
+            if !COLUMN_TYPES.contains(&kind.as_str()) {
+                return Err(format!("unknown column type: {}", kind));
+            }
+        }
+        for (option, place) in [("unparsed", &key.unparsed), ("nulls", &key.nulls)] {
+            if let Some(place) = place {
+                if place != "first" && place != "last" {
+                    return Err(format!("{} must be first or last, got {}", option, place));
+                }
+            }
+        }
+    }
+    let spill_sort = check_file_size(&app, &source, "run_project")?;
+    let export_dialect = match project.export.profile.as_deref() {
+        Some(name) => export_dialect_for(&app, &project.export.dialect, name)?,
+        None => project.export.dialect.clone(),
+    };
+    ensure_space_for_rewrite(&source, &target, &export_dialect, "run_project")?;
+    let spill_settings = load_spill_settings(&app);
+    let mut spill = SpillStats {
+        directory: spill_dir(&spill_settings).to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    let ranks = project
+        .windows
+        .iter()
+        .any(|window| window.function == "rank");
+    if spill_sort && (!project.sort.is_empty() || ranks) {
+        let size = fs::metadata(&source).map(|m| m.len()).unwrap_or(0);
+        ensure_disk_space(&spill_dir(&spill_settings), size, "sort spill files")?;
+    }
+
+    let (read_path, source_dialect) = readable_source(&source, &project.dialect)?;
+    let export_dialect = match export_dialect.delimiter {
+        Some(_) => export_dialect,
+        None => export_dialect.with_delimiter(source_dialect.delimiter_byte()),
+    };
+
+    let mut reader = source_dialect.reader(&read_path)?;
+
+    let mut headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    apply_column_ops_to_headers(&mut headers, &project.column_ops);
+    let file_settings = load_file_settings(&app, &source);
+
+    // Filters reuse the macro condition evaluator; each gets its own state for its regex.
+    let filters = project
+        .filters
+        .iter()
+        .map(|condition| {
+            let spec = CsvMacroSpec {
+                op: "if".to_string(),
+                condition: Some(condition.clone()),
+                ..Default::default()
+            };
+            let mut state = MacroRunState::new(&spec, &headers, None)?;
+            state.column_types = column_types_for(&headers, &file_settings);
+            Ok((condition, state))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut macro_states = Vec::with_capacity(project.macros.len());
+    for spec in &project.macros {
+        let mut state = prepare_macro_state(&app, spec, &mut headers)?;
+        state.column_types = column_types_for(&headers, &file_settings);
+        macro_states.push(state);
+    }
+    let sort_types = column_types_for(&headers, &file_settings);
+    let mut windows = WindowRunner::new(
+        &project.windows,
+        &mut headers,
+        sort_types.clone(),
+        &project.sort,
+        PathBuf::from(&spill.directory),
+    )?;
+    let mut macro_applied = vec![0usize; project.macros.len()];
+
+    let mut writer = export_dialect.output_writer(&target, dry_run)?;
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+    let mut emit = |row: &[String]| writer.write_record(row).map_err(|e| e.to_string());
+
+    let mut rows_read = 0usize;
+    let mut rows_written = 0usize;
+    let mut cells_modified = 0usize;
+    let mut buffered: Vec<Vec<String>> = Vec::new();
+    let mut spilled: Vec<TempFile> = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        rows_read += 1;
+        let mut row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
+        apply_column_ops_to_row(&mut row, &project.column_ops);
+        let keep = filters.iter().all(|(condition, state)| {
+            let subject = row
+                .get(condition.column.unwrap_or(0))
+                .cloned()
+                .unwrap_or_default();
+            macro_condition_holds(condition, &subject, &row, state)
+        });
+        if !keep {
+            continue;
+        }
+        let before = (!project.macros.is_empty()).then(|| row.clone());
+        for ((spec, state), applied) in project
+            .macros
+            .iter()
+            .zip(macro_states.iter_mut())
+            .zip(macro_applied.iter_mut())
+        {
+            if apply_macro_to_row(spec, &mut row, state)? {
+                *applied += 1;
+            }
+        }
+        if let Some(before) = before {
+            let inserted = macro_states
+                .iter()
+                .filter_map(|state| state.inserted_column);
+            cells_modified += count_changed_cells(before, inserted, &row);
+        }
+        if project.sort.is_empty() {
+            windows.push(row, &mut emit)?;
+            rows_written += 1;
+        } else {
+            buffered.push(row);
+            if spill_sort && buffered.len() >= SORT_CHUNK_ROWS {
+                let chunk = spill_sorted_chunk(
+                    &mut buffered,
+                    &project.sort,
+                    &sort_types,
+                    std::path::Path::new(&spill.directory),
+                )?;
+                spill.files += 1;
+                spill.bytes += fs::metadata(chunk.path()).map(|m| m.len()).unwrap_or(0);
+                spilled.push(chunk);
+                if spill_settings
+                    .max_bytes
+                    .is_some_and(|max_bytes| spill.bytes > max_bytes)
+                {
+                    return Err(structured_error(
+                        "spill_limit_exceeded",
+                        "the sort needs more spill space than allowed",
+                        serde_json::json!({
+                            "directory": spill.directory,
+                            "spilled_bytes": spill.bytes,
+                            "max_bytes": spill_settings.max_bytes,
+                        }),
+                    ));
+                }
+            }
+        }
+    }
+
+    if !project.sort.is_empty() {
+        buffered.sort_by(|a, b| compare_rows_by_keys(a, b, &project.sort, &sort_types));
+        if spilled.is_empty() {
+            rows_written = buffered.len();
+            for row in buffered {
+                windows.push(row, &mut emit)?;
+            }
+        } else {
+            rows_written =
+                merge_sorted_chunks(&spilled, buffered, &project.sort, &sort_types, |row| {
+                    windows.push(row, &mut emit)
+                })?;
+        }
+    }
+    windows.flush(&mut emit)?;
+
+    let hashes = export_dialect.finish_writer(writer, &target)?;
+    tracing::Span::current().record("rows", rows_written);
+    let counts = RowCounts {
+        rows_read,
+        rows_written,
+        rows_deleted: rows_read - rows_written,
+        cells_modified,
+        ..Default::default()
+    };
+    let spill = (spill.files > 0).then_some(spill);
+    if dry_run {
+        return Ok(ProjectRunResult {
+            hashes,
+            output_path: target,
+            rows_read,
+            rows_written,
+            macro_applied,
+            spill,
+            counts,
+            verification: None,
+            dry_run,
+        });
+    }
+
+    let verification = match project.export.verify {
+        Some(true) => Some(verify_output(
+            &target,
+            &export_dialect,
+            &headers,
+            rows_written,
+            &[],
+        )),
+        _ => None,
+    };
+    record_operation(
+        &app,
+        "run_project",
+        Some(&source),
+        Some(&target),
+        Some(rows_written),
+        serde_json::json!({ "project": project.name, "macros": project.macros.len() }),
+    );
+    Ok(ProjectRunResult {
+        hashes,
+        output_path: target,
+        rows_read,
+        rows_written,
+        macro_applied,
+        spill,
+        counts,
+        verification,
+        dry_run,
+    })
+}
+
+const WATCH_FOLDERS_FILE: &str = "watch_folders.json";
+
+/// Seconds between scans of a watched folder.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Run a saved project on every new file that lands in `folder`, writing the result under
+/// the same name in `output_dir`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WatchFolderRule {
+    pub name: String,
+    pub folder: String,
+    pub project_path: String,
+    pub output_dir: String,
+    /// File extensions to pick up, case-insensitive.
+    #[serde(default = "default_watch_extensions")]
+    pub extensions: Vec<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_watch_extensions() -> Vec<String> {
+    vec!["csv".to_string(), "tsv".to_string(), "txt".to_string()]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize, Clone)]
+struct WatchFolderEvent {
+    rule: String,
+    source_path: String,
+    output_path: Option<String>,
+    rows_written: Option<usize>,
+    error: Option<String>,
+}
+
+/// Files directly in `folder` with one of `extensions`, with their size and mtime.
+fn watched_files(folder: &str, extensions: &[String]) -> HashMap<PathBuf, (u64, u64)> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    extensions
+                        .iter()
+                        .any(|allowed| allowed.eq_ignore_ascii_case(ext))
+                })
+        })
+        .filter_map(|path| file_signature(&path).ok().map(|sig| (path, sig)))
+        .collect()
+}
+
+fn process_watched_file(app: &tauri::AppHandle, rule: &WatchFolderRule, source: &std::path::Path) {
+    let source_path = source.to_string_lossy().to_string();
+    let target = PathBuf::from(&rule.output_dir).join(source.file_name().unwrap_or_default());
+    let result = UserPath::checked(rule.project_path.clone())
+        .and_then(load_project)
+        .and_then(|project| {
+            execute_project(
+                app.clone(),
+                app.state::<AppState>(),
+                project,
+                Some(source_path.clone()),
+                Some(target.to_string_lossy().to_string()),
+                false,
+            )
+        });
+    let event = match result {
+        Ok(run) => {
+            tracing::info!(rule = %rule.name, source = %source_path, rows = run.rows_written, "watch folder processed file");
+            WatchFolderEvent {
+                rule: rule.name.clone(),
+                source_path,
+                output_path: Some(run.output_path),
+                rows_written: Some(run.rows_written),
+                error: None,
+            }
+        }
+        Err(err) => {
+            tracing::warn!(rule = %rule.name, source = %source_path, error = %err, "watch folder run failed");
+            WatchFolderEvent {
+                rule: rule.name.clone(),
+                source_path,
+                output_path: None,
+                rows_written: None,
+                error: Some(err),
+            }
+        }
+    };
+    let file = path_display_name(&event.source_path);
+    match (&event.error, event.rows_written) {
+        (Some(err), _) => send_notification(app, &tr_app(app, "notify_failed", &file), err),
+        (None, rows) => send_notification(
+            app,
+            &tr_app(app, "notify_finished", &file),
+            &tr_app_with(
+                app,
+                "summary_watch",
+                &[("rows", &rows.unwrap_or(0)), ("rule", &rule.name)],
+            ),
+        ),
+    }
+    let _ = app.emit("watch-folder-processed", event);
+}
+
+/// Poll `rule.folder` until `stop` is set. Files present at start are skipped; a new file
+/// is processed once its size and mtime hold still for one interval, so half-copied files
+/// are not picked up.
+fn watch_folder_loop(app: tauri::AppHandle, rule: WatchFolderRule, stop: Arc<AtomicBool>) {
+    let mut seen: HashMap<PathBuf, (u64, u64)> = watched_files(&rule.folder, &rule.extensions);
+    let mut pending: HashMap<PathBuf, (u64, u64)> = HashMap::new();
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(WATCH_POLL_INTERVAL);
+        let current = watched_files(&rule.folder, &rule.extensions);
+        for (path, signature) in &current {
+            if seen.get(path) == Some(signature) {
+                continue;
+            }
+            if pending.get(path) == Some(signature) {
+                pending.remove(path);
+                seen.insert(path.clone(), *signature);
+                process_watched_file(&app, &rule, path);
+            } else {
+                pending.insert(path.clone(), *signature);
+            }
+        }
+        seen.retain(|path, _| current.contains_key(path));
+        pending.retain(|path, _| current.contains_key(path));
+    }
+}
+
+/// Stop the watcher for `name` if one runs, then start one for `rule` when enabled.
+fn restart_watch_folder(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    name: &str,
+    rule: Option<WatchFolderRule>,
+) -> Result<(), String> {
+    let mut watchers = state.watch_folders.lock().map_err(|_| "lock poisoned")?;
+    if let Some(stop) = watchers.remove(name) {
+        stop.store(true, Ordering::Relaxed);
+    }
+    if let Some(rule) = rule.filter(|rule| rule.enabled) {
+        let stop = Arc::new(AtomicBool::new(false));
+        watchers.insert(rule.name.clone(), stop.clone());
+        let app = app.clone();
+        std::thread::spawn(move || watch_folder_loop(app, rule, stop));
+    }
+    Ok(())
+}
+
+/// Start watchers for every enabled saved rule.
+fn start_saved_watch_folders(app: &tauri::AppHandle) -> Result<(), String> {
+    let rules: Vec<WatchFolderRule> = read_json_config(&app_config_file(app, WATCH_FOLDERS_FILE)?)?;
+    let state = app.state::<AppState>();
+    for rule in rules {
+        restart_watch_folder(app, &state, &rule.name.clone(), Some(rule))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn list_watch_folders(app: tauri::AppHandle) -> Result<Vec<WatchFolderRule>, String> {
+    read_json_config(&app_config_file(&app, WATCH_FOLDERS_FILE)?)
+}
+
+/// Insert or replace a rule by name and (re)start its watcher.
+#[tauri::command]
+fn save_watch_folder(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    rule: WatchFolderRule,
+) -> Result<Vec<WatchFolderRule>, String> {
+    authorize_path(&rule.folder)?;
+    authorize_path(&rule.output_dir)?;
+    if !PathBuf::from(&rule.folder).is_dir() {
+        return Err(format!("watch folder not found: {}", rule.folder));
+    }
+    load_project(UserPath::checked(rule.project_path.clone())?)?;
+    let folder = fs::canonicalize(&rule.folder).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&rule.output_dir).map_err(|e| e.to_string())?;
+    if fs::canonicalize(&rule.output_dir).map_err(|e| e.to_string())? == folder {
+        return Err("output folder must differ from the watched folder".to_string());
+    }
+
+    let path = app_config_file(&app, WATCH_FOLDERS_FILE)?;
+    let mut rules: Vec<WatchFolderRule> = read_json_config(&path)?;
+    rules.retain(|existing| existing.name != rule.name);
+    rules.push(rule.clone());
+    rules.sort_by(|a, b| a.name.cmp(&b.name));
+    write_json_config(&path, &rules)?;
+    restart_watch_folder(&app, &state, &rule.name.clone(), Some(rule))?;
+    Ok(rules)
+}
+
+#[tauri::command]
+fn delete_watch_folder(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    name: String,
+) -> Result<Vec<WatchFolderRule>, String> {
+    let path = app_config_file(&app, WATCH_FOLDERS_FILE)?;
+    let mut rules: Vec<WatchFolderRule> = read_json_config(&path)?;
+    rules.retain(|existing| existing.name != name);
+    write_json_config(&path, &rules)?;
+    restart_watch_folder(&app, &state, &name, None)?;
+    Ok(rules)
+}
+
+const SCHEDULED_JOBS_FILE: &str = "scheduled_jobs.json";
+
+/// Finished runs kept in memory for `list_job_history`.
+const JOB_HISTORY_LIMIT: usize = 200;
+
+/// A project run on a cron schedule. When the source is a folder, every matching file in it
+/// is processed into `output_path`, which is then a folder too.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScheduledJob {
+    pub name: String,
+    /// Five-field cron expression (`minute hour day month weekday`) in local time, or one of
+    /// `@hourly`, `@daily`, `@weekly`, `@monthly`.
+    pub cron: String,
+    pub project_path: String,
+    /// Defaults to the project's source.
+    pub source_path: Option<String>,
+    /// Defaults to the project's output path.
+    pub output_path: Option<String>,
+    #[serde(default = "default_watch_extensions")]
+    pub extensions: Vec<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct JobRun {
+    pub job: String,
+    pub started_at: String,
+    pub finished_at: String,
+    pub files: usize,
+    pub failed: usize,
+    pub rows_written: usize,
+    /// Sort spill written across the run's files.
+    pub spilled_bytes: u64,
+    pub error: Option<String>,
+}
+
+/// Parsed cron expression; each field is a bitmask of allowed values.
+struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// The day or weekday field starts with `*` (`*`, `*/2`), as cron checks it.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        text.parse::<u32>()
+            .ok()
+            .filter(|v| (min..=max).contains(v))
+            .ok_or_else(|| format!("cron value out of range {}-{}: {}", min, max, text))
+    };
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid cron step: {}", part))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            None if step > 1 => (value(range)?, max),
+            None => {
+                let single = value(range)?;
+                (single, single)
+            }
+        };
+        if start > end {
+            return Err(format!("invalid cron range: {}", part));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+impl CronSchedule {
+    fn parse(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("cron needs 5 fields: {}", expression));
+        };
+        let mut weekdays = parse_cron_field(weekday, 0, 7)?;
+        // 7 is Sunday as well.
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_cron_field(minute, 0, 59)?,
+            hours: parse_cron_field(hour, 0, 23)?,
+            days: parse_cron_field(day, 1, 31)?,
+            months: parse_cron_field(month, 1, 12)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// Standard cron rule: when neither day field starts with `*`, either may match;
+    /// otherwise both must.
+    fn matches<T: chrono::Datelike + chrono::Timelike>(&self, at: &T) -> bool {
+        let has = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = has(self.days, at.day());
+        let weekday = has(self.weekdays, at.weekday().num_days_from_sunday());
+        let day_matches = if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        };
+        has(self.minutes, at.minute())
+            && has(self.hours, at.hour())
+            && has(self.months, at.month())
+            && day_matches
+    }
+}
+
+fn run_scheduled_job(app: &tauri::AppHandle, job: &ScheduledJob) -> JobRun {
+    let started_at = chrono::Local::now().to_rfc3339();
+    let mut run = JobRun {
+        job: job.name.clone(),
+        started_at,
+        finished_at: String::new(),
+        files: 0,
+        failed: 0,
+        rows_written: 0,
+        spilled_bytes: 0,
+        error: None,
+    };
+    let mut run_one = |project: PipelineProject, source: String, target: Option<String>| {
+        run.files += 1;
+        match execute_project(
+            app.clone(),
+            app.state::<AppState>(),
+            project,
+            Some(source),
+            target,
+            false,
+        ) {
+            Ok(result) => {
+                run.rows_written += result.rows_written;
+                run.spilled_bytes += result.spill.map_or(0, |spill| spill.bytes);
+            }
+            Err(err) => {
+                run.failed += 1;
+                run.error.get_or_insert(err);
+            }
+        }
+    };
+    match UserPath::checked(job.project_path.clone()).and_then(load_project) {
+        Ok(project) => {
+            let source = job
+                .source_path
+                .clone()
+                .unwrap_or_else(|| project.source_path.clone());
+            if PathBuf::from(&source).is_dir() {
+                let output_dir = job
+                    .output_path
+                    .clone()
+                    .or_else(|| project.export.output_path.clone())
+                    .map(PathBuf::from);
+                match output_dir.map(|dir| fs::create_dir_all(&dir).map(|_| dir)) {
+                    Some(Ok(output_dir)) => {
+                        let mut files: Vec<PathBuf> = watched_files(&source, &job.extensions)
+                            .into_keys()
+                            .collect();
+                        files.sort();
+                        for file in files {
+                            let target = output_dir.join(file.file_name().unwrap_or_default());
+                            run_one(
+                                project.clone(),
+                                file.to_string_lossy().to_string(),
+                                Some(target.to_string_lossy().to_string()),
+                            );
+                        }
+                    }
+                    Some(Err(err)) => run.error = Some(err.to_string()),
+                    None => run.error = Some("job has no output folder".to_string()),
+                }
+            } else {
+                run_one(project, source, job.output_path.clone());
+            }
+        }
+        Err(err) => run.error = Some(err),
+    }
+    run.finished_at = chrono::Local::now().to_rfc3339();
+    run
+}
+
+/// Run `job` on its own thread, record it in the history and emit `scheduled-job-finished`.
+fn spawn_scheduled_job(app: &tauri::AppHandle, job: ScheduledJob) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        tracing::info!(job = %job.name, "scheduled job started");
+        let taskbar = TaskbarProgress::start(&app);
+        let run = run_scheduled_job(&app, &job);
+        drop(taskbar);
+        tracing::info!(job = %job.name, files = run.files, failed = run.failed, rows = run.rows_written, "scheduled job finished");
+        let summary = match &run.error {
+            Some(err) if run.files == 0 => err.clone(),
+            Some(err) => tr_app_with(
+                &app,
+                "summary_schedule_failed",
+                &[
+                    ("failed", &run.failed),
+                    ("files", &run.files),
+                    ("rows", &run.rows_written),
+                    ("error", err),
+                ],
+            ),
+            None => tr_app_with(
+                &app,
+                "summary_schedule",
+                &[("files", &run.files), ("rows", &run.rows_written)],
+            ),
+        };
+        let status = if run.error.is_some() {
+            "notify_failed"
+        } else {
+            "notify_finished"
+        };
+        send_notification(&app, &tr_app(&app, status, &job.name), &summary);
+        if let Ok(mut history) = app.state::<AppState>().job_history.lock() {
+            if history.len() >= JOB_HISTORY_LIMIT {
+                history.remove(0);
+            }
+            history.push(run.clone());
+        }
+        let _ = app.emit("scheduled-job-finished", run);
+    });
+}
+
+/// Wake at each minute boundary and start the enabled jobs whose cron matches. The job file
+/// is re-read every minute so edits apply without a restart.
+fn start_job_scheduler(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let now = chrono::Local::now();
+        let into_minute = u64::from(chrono::Timelike::second(&now)) * 1000
+            + u64::from(chrono::Timelike::nanosecond(&now) / 1_000_000);
+        std::thread::sleep(Duration::from_millis(60_000u64.saturating_sub(into_minute)));
+
+        let now = chrono::Local::now();
+        let jobs: Vec<ScheduledJob> = match app_config_file(&app, SCHEDULED_JOBS_FILE)
+            .and_then(|path| read_json_config(&path))
+        {
+            Ok(jobs) => jobs,
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to read scheduled jobs");
+                continue;
+            }
+        };
+        for job in jobs.into_iter().filter(|job| job.enabled) {
+            match CronSchedule::parse(&job.cron) {
+                Ok(schedule) if schedule.matches(&now) => spawn_scheduled_job(&app, job),
+                Ok(_) => {}
+                Err(err) => tracing::warn!(job = %job.name, error = %err, "invalid cron"),
+            }
+        }
+    });
+}
+
+#[tauri::command]
+fn list_scheduled_jobs(app: tauri::AppHandle) -> Result<Vec<ScheduledJob>, String> {
+    read_json_config(&app_config_file(&app, SCHEDULED_JOBS_FILE)?)
+}
+
+/// Insert or replace a job by name.
+#[tauri::command]
+fn save_scheduled_job(
+    app: tauri::AppHandle,
+    job: ScheduledJob,
+) -> Result<Vec<ScheduledJob>, String> {
+    if let Some(source_path) = &job.source_path {
+        authorize_path(source_path)?;
+    }
+    if let Some(output_path) = &job.output_path {
+        authorize_path(output_path)?;
+    }
+    CronSchedule::parse(&job.cron)?;
+    load_project(UserPath::checked(job.project_path.clone())?)?;
+    let path = app_config_file(&app, SCHEDULED_JOBS_FILE)?;
+    let mut jobs: Vec<ScheduledJob> = read_json_config(&path)?;
+    jobs.retain(|existing| existing.name != job.name);
+    jobs.push(job);
+    jobs.sort_by(|a, b| a.name.cmp(&b.name));
+    write_json_config(&path, &jobs)?;
+    Ok(jobs)
+}
+
+#[tauri::command]
+fn delete_scheduled_job(app: tauri::AppHandle, name: String) -> Result<Vec<ScheduledJob>, String> {
+    let path = app_config_file(&app, SCHEDULED_JOBS_FILE)?;
+    let mut jobs: Vec<ScheduledJob> = read_json_config(&path)?;
+    jobs.retain(|existing| existing.name != name);
+    write_json_config(&path, &jobs)?;
+    Ok(jobs)
+}
+
+/// Start a saved job now, outside its schedule; the result arrives as an event.
+#[tauri::command]
+fn run_scheduled_job_now(app: tauri::AppHandle, name: String) -> Result<(), String> {
+    let jobs: Vec<ScheduledJob> = read_json_config(&app_config_file(&app, SCHEDULED_JOBS_FILE)?)?;
+    let job = jobs
+        .into_iter()
+        .find(|job| job.name == name)
+        .ok_or_else(|| format!("no scheduled job named {}", name))?;
+    spawn_scheduled_job(&app, job);
+    Ok(())
+}
+
+/// Runs since startup, newest first, optionally for one job.
+#[tauri::command]
+fn list_job_history(
+    state: tauri::State<AppState>,
+    job: Option<String>,
+) -> Result<Vec<JobRun>, String> {
+    let history = state.job_history.lock().map_err(|_| "lock poisoned")?;
+    Ok(history
+        .iter()
+        .rev()
+        .filter(|run| job.as_ref().is_none_or(|name| &run.job == name))
+        .cloned()
+        .collect())
+}
+
+#[derive(Serialize, Clone)]
+struct HashProgress {
+    path: String,
+    bytes_read: u64,
+    total_bytes: u64,
+}
+
+/// Checksum a file off the main thread, emitting `hash-file-progress` events roughly
+/// every percent. `algorithms` accepts `md5` and `sha256` (both when omitted).
+#[tauri::command]
+async fn hash_file(
+    app: tauri::AppHandle,
+    path: UserPath,
+    algorithms: Option<Vec<String>>,
+) -> Result<FileHashes, String> {
+    let wants = |name: &str| {
+        algorithms
+            .as_ref()
+            .is_none_or(|list| list.iter().any(|algo| algo.eq_ignore_ascii_case(name)))
+    };
+    let (md5, sha256) = (wants("md5"), wants("sha256"));
+    if !md5 && !sha256 {
+        return Err("no supported hash algorithm requested".to_string());
+    }
+    let started = Instant::now();
+    let notify_app = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let taskbar = TaskbarProgress::start(&app);
+        let mut last_percent = 0u64;
+        compute_file_hashes(&path, md5, sha256, |bytes_read, total_bytes| {
+            let percent = (bytes_read * 100).checked_div(total_bytes).unwrap_or(100);
+            if percent > last_percent || bytes_read == total_bytes {
+                last_percent = percent;
+                taskbar.set(percent);
+                let _ = app.emit(
+                    "hash-file-progress",
+                    HashProgress {
+                        path: path.to_string(),
+                        bytes_read,
+                        total_bytes,
+                    },
+                );
+            }
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    let title = tr_app_with(&notify_app, "job_checksum", &[]);
+    notify_job_finished(&notify_app, started, &title, &result, |hashes| {
+        tr_app_with(&notify_app, "summary_checksum", &[("bytes", &hashes.bytes)])
+    });
+    result
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DataDictionaryColumn {
+    pub name: String,
+    pub inferred: String,
+    pub null_rate: f64,
+    pub distinct: usize,
+    pub distinct_approximate: bool,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub examples: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DataDictionary {
+    pub source: String,
+    pub rows: usize,
+    pub columns: Vec<DataDictionaryColumn>,
+}
+
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// Write a companion document describing each column of `path`, built from the column
+/// stats. `format` is `csv`, `markdown` or `json`; when omitted it follows the target
+/// extension.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path, target = %target_path))]
+fn generate_data_dictionary(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    format: Option<String>,
+    overwrite: Option<bool>,
+) -> Result<SaveResult, String> {
+    ensure_can_write(&target_path, overwrite)?;
+    let max_distinct = if check_file_size(&app, &path, "generate_data_dictionary")? {
+        LARGE_FILE_MAX_DISTINCT
+    } else {
+        5000
+    };
+    let stats = collect_column_stats(
+        &path,
+        &dialect,
+        max_distinct,
+        &load_file_settings(&app, &path),
+    )?;
+    let dictionary = DataDictionary {
+        source: path.to_string(),
+        rows: stats.first().map(|stat| stat.rows).unwrap_or(0),
+        columns: stats
+            .into_iter()
+            .map(|stat| DataDictionaryColumn {
+                null_rate: if stat.rows == 0 {
+                    0.0
+                } else {
+                    (stat.rows - stat.non_empty) as f64 / stat.rows as f64
+                },
+                distinct: stat.distinct_estimate,
+                distinct_approximate: stat.distinct_truncated,
+                name: stat.name,
+                inferred: stat.inferred,
+                min: stat.min,
+                max: stat.max,
+                examples: stat.examples,
+            })
+            .collect(),
+    };
+
+    let format = format.unwrap_or_else(|| {
+        PathBuf::from(&target_path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    });
+    match format.as_str() {
+        "json" => write_json_config(&PathBuf::from(&target_path), &dictionary)?,
+        "md" | "markdown" => {
+            let mut out = format!(
+                "# Data dictionary\n\nSource: `{}`  \nRows: {}\n\n",
+                dictionary.source, dictionary.rows
+            );
+            out.push_str("| Column | Type | Null rate | Distinct | Min | Max | Examples |\n");
+            out.push_str("|---|---|---|---|---|---|---|\n");
+            for column in &dictionary.columns {
+                out.push_str(&format!(
+                    "| {} | {} | {:.2}% | {}{} | {} | {} | {} |\n",
+                    markdown_cell(&column.name),
+                    column.inferred,
+                    column.null_rate * 100.0,
+                    if column.distinct_approximate { "~" } else { "" },
+                    column.distinct,
+                    markdown_cell(column.min.as_deref().unwrap_or("")),
+                    markdown_cell(column.max.as_deref().unwrap_or("")),
+                    markdown_cell(&column.examples.join(", ")),
+                ));
+            }
+            fs::write(&target_path, out).map_err(|e| e.to_string())?;
+        }
+        "csv" => {
+            let mut writer = csv::Writer::from_path(&target_path).map_err(|e| e.to_string())?;
+            writer
+                .write_record([
+                    "column",
+                    "type",
+                    "null_rate",
+                    "distinct",
+                    "distinct_approximate",
+                    "min",
+                    "max",
+                    "examples",
+                ])
+                .map_err(|e| e.to_string())?;
+            for column in &dictionary.columns {
+                writer
+                    .write_record([
+                        column.name.clone(),
+                        column.inferred.clone(),
+                        format!("{:.4}", column.null_rate),
+                        column.distinct.to_string(),
+                        column.distinct_approximate.to_string(),
+                        column.min.clone().unwrap_or_default(),
+                        column.max.clone().unwrap_or_default(),
+                        column.examples.join("; "),
+                    ])
+                    .map_err(|e| e.to_string())?;
+            }
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+        other => return Err(format!("unsupported dictionary format: {}", other)),
+    }
+
+    record_operation(
+        &app,
+        "generate_data_dictionary",
+        Some(&path),
+        Some(&target_path),
+        Some(dictionary.columns.len()),
+        serde_json::json!({ "format": format }),
+    );
+    Ok(SaveResult {
+        hashes: output_hashes(&target_path)?,
+        output_path: target_path.to_string(),
+        counts: None,
+        verification: None,
+        dry_run: false,
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaConstraints {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unique: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub allowed: Option<Vec<serde_json::Value>>,
+}
+
+/// A Frictionless Table Schema field. CSVW columns are normalized into the same shape.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SchemaField {
+    pub name: String,
+    #[serde(rename = "type", default = "default_schema_type")]
+    pub field_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Country (ISO alpha-2 or calling code) for `phone` values written without one.
+    #[serde(rename = "defaultCountry", skip_serializing_if = "Option::is_none")]
+    pub default_country: Option<String>,
+    #[serde(default)]
+    pub constraints: SchemaConstraints,
+}
+
+fn default_schema_type() -> String {
+    "string".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TableSchema {
+    pub fields: Vec<SchemaField>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub primary_key: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LoadedTableSchema {
+    pub metadata_path: String,
+    pub kind: String,
+    pub schema: TableSchema,
+}
+
+fn schema_value_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Translate a CSVW date picture (`dd.MM.yyyy`, `yyyy-MM-dd'T'HH:mm:ssXXX`) into the
+/// strftime pattern chrono parses with. `None` for fields it has no equivalent for.
+fn uts35_to_strftime(picture: &str) -> Option<String> {
+    let chars: Vec<char> = picture.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let run = chars[i..].iter().take_while(|&&next| next == c).count();
+        match c {
+            '\'' => {
+                let end = chars[i + 1..].iter().position(|&next| next == '\'')? + i + 1;
+                if end == i + 1 {
+                    out.push('\'');
+                }
+                for &literal in &chars[i + 1..end] {
+                    if literal == '%' {
+                        out.push('%');
+                    }
+                    out.push(literal);
+                }
+                i = end + 1;
+                continue;
+            }
+            'y' | 'u' => out.push_str(if run == 2 { "%y" } else { "%Y" }),
+            'M' => out.push_str(match run {
+                1 | 2 => "%m",
+                3 => "%b",
+                _ => "%B",
+            }),
+            'd' => out.push_str("%d"),
+            'E' => out.push_str(if run >= 4 { "%A" } else { "%a" }),
+            'H' => out.push_str("%H"),
+            'h' => out.push_str("%I"),
+            'a' => out.push_str("%p"),
+            'm' => out.push_str("%M"),
+            's' => out.push_str("%S"),
+            'S' => out.push_str(match run {
+                3 => "%3f",
+                6 => "%6f",
+                9 => "%9f",
+                _ => "%f",
+            }),
+            'X' | 'x' => out.push_str(if run == 2 { "%z" } else { "%:z" }),
+            'Z' => out.push_str("%z"),
+            '%' => out.push_str("%%"),
+            c if c.is_ascii_alphabetic() => return None,
+            c => {
+                for _ in 0..run {
+                    out.push(c);
+                }
+            }
+        }
+        i += run;
+    }
+    Some(out)
+}
+
+/// Map a CSVW `tableSchema` onto the Frictionless field model.
+fn csvw_table_schema(table_schema: &serde_json::Value) -> TableSchema {
+    let columns = table_schema["columns"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let fields = columns
+        .iter()
+        .filter(|column| !column["virtual"].as_bool().unwrap_or(false))
+        .map(|column| {
+            let name = column["name"]
+                .as_str()
+                .or_else(|| column["titles"].as_str())
+                .or_else(|| column["titles"][0].as_str())
+                .unwrap_or("")
+                .to_string();
+            let datatype = &column["datatype"];
+            let base = datatype
+                .as_str()
+                .or_else(|| datatype["base"].as_str())
+                .unwrap_or("string");
+            let field_type = match base {
+                "integer" | "int" | "long" | "short" | "byte" | "nonNegativeInteger"
+                | "positiveInteger" => "integer",
+                "decimal" | "double" | "float" | "number" => "number",
+                "boolean" => "boolean",
+                "date" => "date",
+                "dateTime" | "datetime" => "datetime",
+                _ => "string",
+            };
+            // `format` is a regex for strings but a UTS #35 picture for dates.
+            let format = datatype["format"].as_str();
+            SchemaField {
+                name,
+                field_type: field_type.to_string(),
+                format: match field_type {
+                    "date" | "datetime" => format.and_then(uts35_to_strftime),
+                    _ => None,
+                },
+                default_country: None,
+                constraints: SchemaConstraints {
+                    required: column["required"].as_bool(),
+                    minimum: datatype.get("minimum").cloned(),
+                    maximum: datatype.get("maximum").cloned(),
+                    min_length: datatype["minLength"].as_u64().map(|n| n as usize),
+                    max_length: datatype["maxLength"].as_u64().map(|n| n as usize),
+                    pattern: format
+                        .filter(|_| field_type == "string")
+                        .map(|s| s.to_string()),
+                    ..Default::default()
+                },
+            }
+        })
+        .collect();
+    let primary_key = match &table_schema["primaryKey"] {
+        serde_json::Value::String(key) => vec![key.clone()],
+        serde_json::Value::Array(keys) => keys
+            .iter()
+            .filter_map(|key| key.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    TableSchema {
+        fields,
+        primary_key,
+    }
+}
+
+/// Find the resource in a `datapackage.json` whose `path` names `file_name`.
+fn datapackage_schema(
+    package_path: &std::path::Path,
+    file_name: &str,
+) -> Result<Option<TableSchema>, String> {
+    let content = fs::read_to_string(package_path).map_err(|e| e.to_string())?;
+    let package: serde_json::Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let resources = package["resources"].as_array().cloned().unwrap_or_default();
+    let resource = resources.iter().find(|resource| {
+        let names_file = |value: &serde_json::Value| {
+            value
+                .as_str()
+                .is_some_and(|p| p.rsplit(['/', '\\']).next() == Some(file_name))
+        };
+        match &resource["path"] {
+            serde_json::Value::Array(paths) => paths.iter().any(names_file),
+            other => names_file(other),
+        }
+    });
+    let Some(resource) = resource else {
+        return Ok(None);
+    };
+    let schema = match &resource["schema"] {
+        serde_json::Value::String(schema_path) => {
+            let base = package_path.parent().unwrap_or(std::path::Path::new("."));
+            let content = fs::read_to_string(base.join(schema_path)).map_err(|e| e.to_string())?;
+            serde_json::from_str(&content).map_err(|e| e.to_string())?
+        }
+        serde_json::Value::Null => return Ok(None),
+        inline => serde_json::from_value(inline.clone()).map_err(|e| e.to_string())?,
+    };
+    Ok(Some(schema))
+}
+
+/// Look next to the CSV for `datapackage.json`, then CSVW `<file>-metadata.json` and
+/// `csv-metadata.json`.
+fn find_table_schema(path: &str) -> Result<Option<LoadedTableSchema>, String> {
+    let csv_path = std::path::Path::new(path);
+    let dir = csv_path.parent().unwrap_or(std::path::Path::new("."));
+    let file_name = csv_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let package = dir.join("datapackage.json");
+    if package.is_file() {
+        if let Some(schema) = datapackage_schema(&package, &file_name)? {
+            return Ok(Some(LoadedTableSchema {
+                metadata_path: package.to_string_lossy().to_string(),
+                kind: "frictionless".to_string(),
+                schema,
+            }));
+        }
+    }
+    for candidate in [
+        dir.join(format!("{}-metadata.json", file_name)),
+        dir.join("csv-metadata.json"),
+    ] {
+        if !candidate.is_file() {
+            continue;
+        }
+        let content = fs::read_to_string(&candidate).map_err(|e| e.to_string())?;
+        let metadata: serde_json::Value =
+            serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        let table = metadata["tables"]
+            .as_array()
+            .and_then(|tables| {
+                tables.iter().find(|table| {
+                    table["url"]
+                        .as_str()
+                        .is_some_and(|url| url.rsplit('/').next() == Some(file_name.as_str()))
+                })
+            })
+            .unwrap_or(&metadata);
+        if table.get("tableSchema").is_some() {
+            return Ok(Some(LoadedTableSchema {
+                metadata_path: candidate.to_string_lossy().to_string(),
+                kind: "csvw".to_string(),
+                schema: csvw_table_schema(&table["tableSchema"]),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[tauri::command]
+fn load_table_schema(path: UserPath) -> Result<Option<LoadedTableSchema>, String> {
+    find_table_schema(&path)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SchemaViolation {
+    pub row: Option<usize>,
+    pub column: Option<usize>,
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SchemaValidationResult {
+    pub rows: usize,
+    pub error_count: usize,
+    pub errors: Vec<SchemaViolation>,
+    pub truncated: bool,
+}
+
+fn schema_type_error(field: &SchemaField, value: &str) -> Option<String> {
+    let ok = match field.field_type.as_str() {
+        "integer" => value.parse::<i64>().is_ok(),
+        "number" => value.parse::<f64>().is_ok(),
+        "boolean" => matches!(
+            value,
+            "true" | "True" | "TRUE" | "1" | "false" | "False" | "FALSE" | "0"
+        ),
+        "date" => match schema_date_format(field) {
+            Some(format) => chrono::NaiveDate::parse_from_str(value, format).is_ok(),
+            None => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+        },
+        "datetime" => match schema_date_format(field) {
+            Some(format) => {
+                chrono::DateTime::parse_from_str(value, format).is_ok()
+                    || chrono::NaiveDateTime::parse_from_str(value, format).is_ok()
+            }
+            None => {
+                chrono::DateTime::parse_from_rfc3339(value).is_ok()
+                    || chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").is_ok()
+            }
+        },
+        "year" => value.len() == 4 && value.parse::<i32>().is_ok(),
+        "geopoint" => parse_geopoint(value, field.format.as_deref()).is_ok(),
+        _ => true,
+    };
+    (!ok).then(|| format!("'{}' is not a valid {}", value, field.field_type))
+}
+
+/// The strftime pattern a date/datetime field is parsed with; `None` for the ISO default.
+fn schema_date_format(field: &SchemaField) -> Option<&str> {
+    match field.format.as_deref()? {
+        "default" | "any" => None,
+        format => Some(format.strip_prefix("fmt:").unwrap_or(format)),
+    }
+}
+
+/// Table Schema `geopoint`: `lon, lat` by default, or a JSON `[lon, lat]` array /
+/// `{"lon": .., "lat": ..}` object per the field's format.
+fn parse_geopoint(value: &str, format: Option<&str>) -> Result<(f64, f64), String> {
+    let (lon, lat) = match format {
+        Some("array") => match serde_json::from_str::<serde_json::Value>(value) {
+            Ok(serde_json::Value::Array(items)) if items.len() == 2 => {
+                (schema_value_text(&items[0]), schema_value_text(&items[1]))
+            }
+            _ => return Err(format!("'{}' is not a [lon, lat] array", value)),
+        },
+        Some("object") => match serde_json::from_str::<serde_json::Value>(value) {
+            Ok(serde_json::Value::Object(map))
+                if map.contains_key("lon") && map.contains_key("lat") =>
+            {
+                (
+                    schema_value_text(&map["lon"]),
+                    schema_value_text(&map["lat"]),
+                )
+            }
+            _ => return Err(format!("'{}' is not a lon/lat object", value)),
+        },
+        _ => match value.split_once(',') {
+            Some((lon, lat)) => (lon.to_string(), lat.to_string()),
+            None => return Err(format!("'{}' is not a 'lon, lat' pair", value)),
+        },
+    };
+    Ok((
+        parse_coordinate(&lon, Some("longitude"))?,
+        parse_coordinate(&lat, Some("latitude"))?,
+    ))
+}
+
+/// Calling codes for `defaultCountry` given as an ISO 3166 alpha-2 code. Digits (`44`,
+/// `+44`) are accepted for anything not listed.
+const CALLING_CODES: &[(&str, &str)] = &[
+    ("US", "1"),
+    ("CA", "1"),
+    ("GB", "44"),
+    ("IE", "353"),
+    ("FR", "33"),
+    ("DE", "49"),
+    ("NL", "31"),
+    ("BE", "32"),
+    ("LU", "352"),
+    ("CH", "41"),
+    ("AT", "43"),
+    ("IT", "39"),
+    ("ES", "34"),
+    ("PT", "351"),
+    ("DK", "45"),
+    ("SE", "46"),
+    ("NO", "47"),
+    ("FI", "358"),
+    ("PL", "48"),
+    ("CZ", "420"),
+    ("GR", "30"),
+    ("TR", "90"),
+    ("RU", "7"),
+    ("UA", "380"),
+    ("IL", "972"),
+    ("AE", "971"),
+    ("IN", "91"),
+    ("CN", "86"),
+    ("JP", "81"),
+    ("KR", "82"),
+    ("SG", "65"),
+    ("AU", "61"),
+    ("NZ", "64"),
+    ("ZA", "27"),
+    ("BR", "55"),
+    ("MX", "52"),
+    ("AR", "54"),
+];
+
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.rsplit_once('@') else {
+        return false;
+    };
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local
+            .chars()
+            .all(|c| c.is_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-".contains(c));
+    let labels: Vec<&str> = domain.split('.').collect();
+    local_ok
+        && domain.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+        && labels[labels.len() - 1].chars().count() >= 2
+        && !labels[labels.len() - 1].chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_uuid(value: &str) -> bool {
+    let groups: Vec<&str> = value.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Normalize a phone number to E.164 (`+` and 8-15 digits). Numbers without a `+`/`00`
+/// prefix take `default_country`, dropping a leading trunk `0`.
+fn e164_phone(value: &str, default_country: Option<&str>) -> Result<String, String> {
+    let compact: String = value
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')' | '/'))
+        .collect();
+    if let Some(c) = compact.chars().find(|c| !c.is_ascii_digit() && *c != '+') {
+        return Err(format!("'{}' contains '{}'", value, c));
+    }
+    let digits = if let Some(rest) = compact.strip_prefix('+') {
+        rest.to_string()
+    } else if let Some(rest) = compact.strip_prefix("00") {
+        rest.to_string()
+    } else {
+        let country = default_country
+            .ok_or_else(|| format!("'{}' has no country code", value))?
+            .trim();
+        let code = CALLING_CODES
+            .iter()
+            .find(|(iso, _)| iso.eq_ignore_ascii_case(country))
+            .map(|(_, code)| code.to_string())
+            .or_else(|| {
+                let digits = country.trim_start_matches('+');
+                (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+                    .then(|| digits.to_string())
+            })
+            .ok_or_else(|| format!("unknown country '{}'", country))?;
+        format!("{}{}", code, compact.trim_start_matches('0'))
+    };
+    if digits.contains('+') || digits.starts_with('0') || !(8..=15).contains(&digits.len()) {
+        return Err(format!("'{}' is not a valid E.164 number", value));
+    }
+    Ok(format!("+{}", digits))
+}
+
+/// Built-in string formats: `email`, `uri`/`url`, `phone` (E.164, using the field's
+/// `defaultCountry`), `ipv4`, `ipv6`, `ip`, `uuid` and the check-digit kinds
+/// (`luhn`, `isbn`, `ean`, `iban`, ...).
+fn schema_format_error(field: &SchemaField, value: &str) -> Option<String> {
+    if field.field_type != "string" {
+        return None;
+    }
+    let format = field.format.as_deref()?;
+    let ok = match format {
+        "email" => is_email(value),
+        "uri" | "url" => url::Url::parse(value).is_ok_and(|url| !url.cannot_be_a_base()),
+        "phone" => {
+            return e164_phone(value, field.default_country.as_deref()).err();
+        }
+        "ipv4" => value.parse::<std::net::Ipv4Addr>().is_ok(),
+        "ipv6" => value.parse::<std::net::Ipv6Addr>().is_ok(),
+        "ip" => value.parse::<std::net::IpAddr>().is_ok(),
+        "uuid" => is_uuid(value),
+        kind => check_digit_valid(kind, value).unwrap_or(true),
+    };
+    (!ok).then(|| format!("'{}' is not a valid {}", value, format))
+}
+
+fn schema_bound_error(field: &SchemaField, value: &str) -> Option<String> {
+    let numeric = matches!(field.field_type.as_str(), "integer" | "number");
+    let below = |bound: &serde_json::Value| {
+        if numeric {
+            let bound = bound
+                .as_f64()
+                .or_else(|| schema_value_text(bound).parse().ok());
+            matches!((value.parse::<f64>(), bound), (Ok(v), Some(b)) if v < b)
+        } else {
+            value < schema_value_text(bound).as_str()
+        }
+    };
+    let above = |bound: &serde_json::Value| {
+        if numeric {
+            let bound = bound
+                .as_f64()
+                .or_else(|| schema_value_text(bound).parse().ok());
+            matches!((value.parse::<f64>(), bound), (Ok(v), Some(b)) if v > b)
+        } else {
+            value > schema_value_text(bound).as_str()
+        }
+    };
+    let constraints = &field.constraints;
+    if let Some(minimum) = constraints.minimum.as_ref().filter(|b| below(b)) {
+        return Some(format!("below minimum {}", schema_value_text(minimum)));
+    }
+    if let Some(maximum) = constraints.maximum.as_ref().filter(|b| above(b)) {
+        return Some(format!("above maximum {}", schema_value_text(maximum)));
+    }
+    let length = value.chars().count();
+    if constraints.min_length.is_some_and(|min| length < min) {
+        return Some("shorter than minLength".to_string());
+    }
+    if constraints.max_length.is_some_and(|max| length > max) {
+        return Some("longer than maxLength".to_string());
+    }
+    if let Some(allowed) = &constraints.allowed {
+        if !allowed.iter().any(|item| schema_value_text(item) == value) {
+            return Some(format!("'{}' is not an allowed value", value));
+        }
+    }
+    None
+}
+
+/// Check every row against a Table Schema: declared columns present, types and formats
+/// (email, url, phone, ip, uuid, check digits) parse, and required/unique/range/length/pattern/enum
+/// constraints hold. Uses the schema found next to the file when `schema` is not given.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn validate_against_schema(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    schema: Option<TableSchema>,
+    max_errors: Option<usize>,
+) -> Result<SchemaValidationResult, String> {
+    check_file_size(&app, &path, "validate_against_schema")?;
+    let mut schema = match schema {
+        Some(schema) => schema,
+        None => {
+            find_table_schema(&path)?
+                .ok_or_else(|| "no datapackage.json or CSVW metadata found".to_string())?
+                .schema
+        }
+    };
+    let settings = load_file_settings(&app, &path);
+    for field in &mut schema.fields {
+        if let Some(kind) = settings.column_types.get(&field.name) {
+            field.field_type = kind.clone();
+        }
+    }
+    let max_errors = max_errors.unwrap_or(1000);
+
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    let mut result = SchemaValidationResult {
+        rows: 0,
+        error_count: 0,
+        errors: Vec::new(),
+        truncated: false,
+    };
+    let report = |result: &mut SchemaValidationResult, violation: SchemaViolation| {
+        result.error_count += 1;
+        if result.errors.len() < max_errors {
+            result.errors.push(violation);
+        } else {
+            result.truncated = true;
+        }
+    };
+
+    struct FieldCheck<'a> {
+        field: &'a SchemaField,
+        column: usize,
+        pattern: Option<regex::Regex>,
+        seen: Option<HashSet<String>>,
+    }
+    let mut checks = Vec::new();
+    for field in &schema.fields {
+        let Some(column) = headers.iter().position(|h| h == &field.name) else {
+            report(
+                &mut result,
+                SchemaViolation {
+                    row: None,
+                    column: None,
+                    field: field.name.clone(),
+                    message: "declared field is missing from the file".to_string(),
+                },
+            );
+            continue;
+        };
+        let pattern = match &field.constraints.pattern {
+            Some(pattern) => {
+                Some(regex::Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| e.to_string())?)
+            }
+            None => None,
+        };
+        checks.push(FieldCheck {
+            field,
+            column,
+            pattern,
+            seen: (field.constraints.unique == Some(true)).then(HashSet::new),
+        });
+    }
+    for (column, header) in headers.iter().enumerate() {
+        if !schema.fields.iter().any(|field| &field.name == header) {
+            report(
+                &mut result,
+                SchemaViolation {
+                    row: None,
+                    column: Some(column),
+                    field: header.clone(),
+                    message: "column is not declared in the schema".to_string(),
+                },
+            );
+        }
+    }
+
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let row = result.rows;
+        result.rows += 1;
+        for check in &mut checks {
+            let value = record.get(check.column).unwrap_or("");
+            let field = check.field;
+            let message = if value.is_empty() {
+                (field.constraints.required == Some(true)).then(|| "value is required".to_string())
+            } else if let Some(message) = schema_type_error(field, value) {
+                Some(message)
+            } else if let Some(message) = schema_format_error(field, value) {
+                Some(message)
+            } else if let Some(message) = schema_bound_error(field, value) {
+                Some(message)
+            } else if check.pattern.as_ref().is_some_and(|re| !re.is_match(value)) {
+                Some(format!("'{}' does not match the pattern", value))
+            } else if check
+                .seen
+                .as_mut()
+                .is_some_and(|seen| !seen.insert(value.to_string()))
+            {
+                Some(format!("duplicate value '{}'", value))
+            } else {
+                None
+            };
+            if let Some(message) = message {
+                report(
+                    &mut result,
+                    SchemaViolation {
+                        row: Some(row),
+                        column: Some(check.column),
+                        field: field.name.clone(),
+                        message,
+                    },
+                );
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Report every invalid coordinate with its row: values that do not parse as decimal
+/// degrees or DMS, or fall outside ±90 latitude / ±180 longitude. Blank cells are skipped.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn validate_coordinates(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    latitude_column: Option<usize>,
+    longitude_column: Option<usize>,
+    max_errors: Option<usize>,
+) -> Result<SchemaValidationResult, String> {
+    check_file_size(&app, &path, "validate_coordinates")?;
+    let max_errors = max_errors.unwrap_or(1000);
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let columns: Vec<(usize, &str)> = [
+        (latitude_column, "latitude"),
+        (longitude_column, "longitude"),
+    ]
+    .into_iter()
+    .filter_map(|(column, axis)| column.map(|column| (column, axis)))
+    .collect();
+    if columns.is_empty() {
+        return Err("no coordinate columns given".to_string());
+    }
+    if columns.iter().any(|(column, _)| *column >= headers.len()) {
+        return Err("column out of range".to_string());
+    }
+
+    let mut result = SchemaValidationResult {
+        rows: 0,
+        error_count: 0,
+        errors: Vec::new(),
+        truncated: false,
+    };
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let row = result.rows;
+        result.rows += 1;
+        for (column, axis) in &columns {
+            let value = record.get(*column).unwrap_or("");
+            if value.trim().is_empty() {
+                continue;
+            }
+            let Err(message) = parse_coordinate(value, Some(axis)) else {
+                continue;
+            };
+            result.error_count += 1;
+            if result.errors.len() < max_errors {
+                result.errors.push(SchemaViolation {
+                    row: Some(row),
+                    column: Some(*column),
+                    field: headers[*column].clone(),
+                    message,
+                });
+            } else {
+                result.truncated = true;
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Emit a schema matching the file's current header: fields are carried over from `base`
+/// by name, new columns get an inferred type. `kind` is `frictionless` (a datapackage
+/// resource) or `csvw`.
+#[tauri::command]
+fn write_table_schema(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    base: Option<TableSchema>,
+    kind: Option<String>,
+    overwrite: Option<bool>,
+) -> Result<TableSchema, String> {
+    ensure_can_write(&target_path, overwrite)?;
+    let base = match base {
+        Some(base) => base,
+        None => find_table_schema(&path)?
+            .map(|loaded| loaded.schema)
+            .unwrap_or_default(),
+    };
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+
+    let needs_inference = headers
+        .iter()
+        .any(|header| !base.fields.iter().any(|field| &field.name == header));
+    let stats = if needs_inference {
+        collect_column_stats(&path, &dialect, 0, &load_file_settings(&app, &path))?
+    } else {
+        Vec::new()
+    };
+    let fields = headers
+        .iter()
+        .enumerate()
+        .map(|(idx, header)| {
+            base.fields
+                .iter()
+                .find(|field| &field.name == header)
+                .cloned()
+                .unwrap_or_else(|| SchemaField {
+                    name: header.clone(),
+                    field_type: match stats.get(idx).map(|stat| stat.inferred.as_str()) {
+                        Some("text") | None => "string".to_string(),
+                        Some(kind) => kind.to_string(),
+                    },
+                    format: None,
+                    default_country: None,
+                    constraints: SchemaConstraints::default(),
+                })
+        })
+        .collect();
+    let schema = TableSchema {
+        fields,
+        primary_key: base
+            .primary_key
+            .into_iter()
+            .filter(|key| headers.contains(key))
+            .collect(),
+    };
+
+    let file_name = std::path::Path::new(&path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let document = if kind.as_deref() == Some("csvw") {
+        let columns: Vec<serde_json::Value> = schema
+            .fields
+            .iter()
+            .map(|field| {
+                let base_type = match field.field_type.as_str() {
+                    "datetime" => "dateTime",
+                    other => other,
+                };
+                serde_json::json!({
+                    "name": field.name,
+                    "titles": field.name,
+                    "datatype": base_type,
+                    "required": field.constraints.required.unwrap_or(false),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "@context": "http://www.w3.org/ns/csvw",
+            "url": file_name,
+            "tableSchema": { "columns": columns, "primaryKey": schema.primary_key },
+        })
+    } else {
+        // Update the matching resource in an existing package, keeping everything else.
+        let mut package: serde_json::Value = fs::read_to_string(&target_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_else(|| serde_json::json!({ "resources": [] }));
+        let schema_value = serde_json::to_value(&schema).map_err(|e| e.to_string())?;
+        if !package["resources"].is_array() {
+            package["resources"] = serde_json::json!([]);
+        }
+        let resources = package["resources"]
+            .as_array_mut()
+            .ok_or_else(|| "invalid datapackage resources".to_string())?;
+        match resources
+            .iter_mut()
+            .find(|resource| resource["path"].as_str() == Some(file_name.as_str()))
+        {
+            Some(resource) => resource["schema"] = schema_value,
+            None => resources.push(serde_json::json!({
+                "name": file_name.trim_end_matches(".csv").to_lowercase(),
+                "path": file_name,
+                "profile": "tabular-data-resource",
+                "schema": schema_value,
+            })),
+        }
+        package
+    };
+    write_json_config(&PathBuf::from(&target_path), &document)?;
+    Ok(schema)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Annotation {
+    pub id: u64,
+    pub row: usize,
+    pub column: Option<usize>,
+    /// `bookmark` or `comment`.
+    pub kind: String,
+    pub text: Option<String>,
+    pub created_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct AnnotationStore {
+    next_id: u64,
+    annotations: Vec<Annotation>,
+}
+
+/// Annotations live in a sidecar next to the document so they travel with it.
+fn annotation_sidecar(path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.annotations.json", path))
+}
+
+#[tauri::command]
+fn add_annotation(
+    path: UserPath,
+    row: usize,
+    column: Option<usize>,
+    kind: String,
+    text: Option<String>,
+) -> Result<Annotation, String> {
+    if kind != "bookmark" && kind != "comment" {
+        return Err(format!("unknown annotation kind: {}", kind));
+    }
+    let sidecar = annotation_sidecar(&path);
+    let mut store: AnnotationStore = read_json_config(&sidecar)?;
+    store.next_id += 1;
+    let annotation = Annotation {
+        id: store.next_id,
+        row,
+        column,
+        kind,
+        text,
+        created_ms: now_millis(),
+    };
+    store.annotations.push(annotation.clone());
+    write_json_config(&sidecar, &store)?;
+    Ok(annotation)
+}
+
+#[tauri::command]
+fn list_annotations(path: UserPath) -> Result<Vec<Annotation>, String> {
+    let store: AnnotationStore = read_json_config(&annotation_sidecar(&path))?;
+    let mut annotations = store.annotations;
+    annotations.sort_by_key(|a| (a.row, a.column, a.id));
+    Ok(annotations)
+}
+
+#[tauri::command]
+fn remove_annotation(path: UserPath, id: u64) -> Result<bool, String> {
+    let sidecar = annotation_sidecar(&path);
+    let mut store: AnnotationStore = read_json_config(&sidecar)?;
+    let before = store.annotations.len();
+    store.annotations.retain(|annotation| annotation.id != id);
+    if store.annotations.len() == before {
+        return Ok(false);
+    }
+    if store.annotations.is_empty() {
+        fs::remove_file(&sidecar).map_err(|e| e.to_string())?;
+    } else {
+        write_json_config(&sidecar, &store)?;
+    }
+    Ok(true)
+}
+
+/// Copy the file with an extra column holding each row's annotations (bookmarks as
+/// `[bookmark]`, comments as their text, joined with `; `).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn export_with_annotations(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    target_path: UserPath,
+    dialect: CsvDialect,
+    column_name: Option<String>,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<SaveResult, String> {
+    ensure_can_write(&target_path, overwrite)?;
+    ensure_not_read_only(&state, &target_path)?;
+    if path == target_path {
+        return Err("output path must differ from the source file".to_string());
+    }
+    let store: AnnotationStore = read_json_config(&annotation_sidecar(&path))?;
+    let mut by_row: HashMap<usize, Vec<String>> = HashMap::new();
+    for annotation in &store.annotations {
+        let label = match (&annotation.text, annotation.kind.as_str()) {
+            (Some(text), _) if !text.is_empty() => text.clone(),
+            (_, kind) => format!("[{}]", kind),
+        };
+        by_row.entry(annotation.row).or_default().push(label);
+    }
+
+    let dry_run = dry_run.unwrap_or(false);
+    let mut reader = dialect.reader(&path)?;
+    let mut writer = dialect.output_writer(&target_path, dry_run)?;
+
+    let mut headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    headers.push_field(column_name.as_deref().unwrap_or("annotations"));
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+
+    let mut record = csv::StringRecord::new();
+    let mut row = 0usize;
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let labels = by_row.get(&row).map(|labels| labels.join("; "));
+        record.push_field(labels.as_deref().unwrap_or(""));
+        writer.write_record(&record).map_err(|e| e.to_string())?;
+        row += 1;
+    }
+    let hashes = dialect.finish_writer(writer, &target_path)?;
+    if dry_run {
+        return Ok(SaveResult {
+            hashes,
+            output_path: target_path.to_string(),
+            counts: None,
+            verification: None,
+            dry_run,
+        });
+    }
+
+    record_operation(
+        &app,
+        "export_with_annotations",
+        Some(&path),
+        Some(&target_path),
+        Some(store.annotations.len()),
+        serde_json::json!({ "column_name": column_name }),
+    );
+    Ok(SaveResult {
+        hashes,
+        output_path: target_path.to_string(),
+        counts: None,
+        verification: None,
+        dry_run: false,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ThreeWayMergeSpec {
+    pub base_path: UserPath,
+    pub mine_path: UserPath,
+    pub theirs_path: UserPath,
+    pub key_columns: Vec<String>,
+    /// Side that wins conflicts: `mine` (default) or `theirs`.
+    pub prefer: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MergeConflict {
+    pub key: String,
+    pub column: Option<String>,
+    /// `edit_edit`, `add_add` or `delete_edit`.
+    pub kind: String,
+    pub base: Option<String>,
+    pub mine: Option<String>,
+    pub theirs: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MergeResult {
+    pub output_path: String,
+    pub rows: usize,
+    pub conflict_count: usize,
+    pub conflicts: Vec<MergeConflict>,
+    pub hashes: FileHashes,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+/// Rows by key, with cells re-ordered to the requested columns. A cell is `None` when the
+/// file has no such column, which is not the same as an empty value.
+struct KeyedTable {
+    order: Vec<String>,
+    rows: HashMap<String, Vec<Option<String>>>,
+}
+
+fn read_csv_headers(path: &str, dialect: &CsvDialect) -> Result<Vec<String>, String> {
+    let mut reader = dialect.reader(path)?;
+    reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// Load a file keyed by `key_columns`, with cells re-ordered to `columns` by header name.
+fn load_keyed_table(
+    path: &str,
+    dialect: &CsvDialect,
+    key_columns: &[String],
+    columns: &[String],
+) -> Result<KeyedTable, String> {
+    let mut reader = dialect.reader(path)?;
+    let headers: Vec<String> = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect())
+        .map_err(|e| e.to_string())?;
+    let key_indexes = key_columns
+        .iter()
+        .map(|name| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| format!("key column '{}' missing in {}", name, path))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mapping: Vec<Option<usize>> = columns
+        .iter()
+        .map(|name| headers.iter().position(|h| h == name))
+        .collect();
+
+    let mut table = KeyedTable {
+        order: Vec::new(),
+        rows: HashMap::new(),
+    };
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let key = key_indexes
+            .iter()
+            .map(|idx| record.get(*idx).unwrap_or(""))
+            .collect::<Vec<_>>()
+            .join("\u{1f}");
+        let row = mapping
+            .iter()
+            .map(|idx| idx.map(|i| record.get(i).unwrap_or("").to_string()))
+            .collect();
+        if table.rows.insert(key.clone(), row).is_some() {
+            return Err(format!(
+                "duplicate key '{}' in {}",
+                key.replace('\u{1f}', ", "),
+                path
+            ));
+        }
+        table.order.push(key);
+    }
+    Ok(table)
+}
+
+/// Three-way merge of one keyed row given as `[base, mine, theirs]`; `None` drops the row.
+/// A column missing from one side (added or dropped by the other) takes the side that has
+/// it, so only columns present in both can conflict.
+fn merge_keyed_row(
+    key: &str,
+    columns: &[String],
+    [b, m, t]: [Option<&Vec<Option<String>>>; 3],
+    prefer_theirs: bool,
+    conflicts: &mut Vec<MergeConflict>,
+) -> Option<Vec<String>> {
+    let joined = |row: &Vec<Option<String>>| {
+        row.iter()
+            .map(|cell| cell.as_deref().unwrap_or(""))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let filled = |row: &Vec<Option<String>>| {
+        row.iter()
+            .map(|cell| cell.clone().unwrap_or_default())
+            .collect()
+    };
+    match (b, m, t) {
+        (_, Some(m), Some(t)) => {
+            let mut row = Vec::with_capacity(columns.len());
+            for (idx, name) in columns.iter().enumerate() {
+                let base_cell = b.and_then(|row| row[idx].as_deref());
+                let cell = match (m[idx].as_deref(), t[idx].as_deref()) {
+                    (Some(mine_cell), Some(theirs_cell)) => {
+                        if mine_cell == theirs_cell || Some(theirs_cell) == base_cell {
+                            mine_cell
+                        } else if Some(mine_cell) == base_cell {
+                            theirs_cell
+                        } else {
+                            conflicts.push(MergeConflict {
+                                key: key.to_string(),
+                                column: Some(name.clone()),
+                                kind: if b.is_some() { "edit_edit" } else { "add_add" }.to_string(),
+                                base: base_cell.map(|s| s.to_string()),
+                                mine: Some(mine_cell.to_string()),
+                                theirs: Some(theirs_cell.to_string()),
+                            });
+                            if prefer_theirs {
+                                theirs_cell
+                            } else {
+                                mine_cell
+                            }
+                        }
+                    }
+                    (Some(cell), None) | (None, Some(cell)) => cell,
+                    (None, None) => "",
+                };
+                row.push(cell.to_string());
+            }
+            Some(row)
+        }
+        // Present on one side only: an addition, or a deletion on the other side.
+        (Some(b), Some(kept), None) | (Some(b), None, Some(kept)) => {
+            // Only columns both files have can show an edit.
+            let edited = kept
+                .iter()
+                .zip(b)
+                .any(|(kept, base)| kept.is_some() && base.is_some() && kept != base);
+            if !edited {
+                return None;
+            }
+            let mine_side = m.is_some();
+            conflicts.push(MergeConflict {
+                key: key.to_string(),
+                column: None,
+                kind: "delete_edit".to_string(),
+                base: Some(joined(b)),
+                mine: m.map(joined),
+                theirs: t.map(joined),
+            });
+            (mine_side != prefer_theirs).then(|| filled(kept))
+        }
+        (None, Some(added), None) | (None, None, Some(added)) => Some(filled(added)),
+        (_, None, None) => None,
+    }
+}
+
+/// Merge local edits (`mine`) and a re-delivered file (`theirs`) against their common
+/// `base`, matching rows by key columns. Rows changed on one side take that side; rows
+/// changed on both sides are merged cell by cell. Cells edited differently on both sides,
+/// and rows deleted on one side but edited on the other, are reported as conflicts and
+/// resolved with `spec.prefer` so the output is always complete.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(target = %target_path))]
+#[allow(clippy::too_many_arguments)]
+fn merge_csv_three_way(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    spec: ThreeWayMergeSpec,
+    target_path: UserPath,
+    dialect: CsvDialect,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<MergeResult, String> {
+    ensure_can_write(&target_path, overwrite)?;
+    let ThreeWayMergeSpec {
+        base_path,
+        mine_path,
+        theirs_path,
+        key_columns,
+        prefer,
+    } = spec;
+    if key_columns.is_empty() {
+        return Err("at least one key column is required".to_string());
+    }
+    ensure_not_read_only(&state, &target_path)?;
+    for path in [&base_path, &mine_path, &theirs_path] {
+        check_file_size(&app, path, "merge_csv_three_way")?;
+    }
+    let prefer_theirs = prefer.as_deref() == Some("theirs");
+
+    // Output columns: theirs' order, then columns only added locally.
+    let mut columns = read_csv_headers(&theirs_path, &dialect)?;
+    for name in read_csv_headers(&mine_path, &dialect)? {
+        if !columns.contains(&name) {
+            columns.push(name);
+        }
+    }
+    let base = load_keyed_table(&base_path, &dialect, &key_columns, &columns)?;
+    let mine = load_keyed_table(&mine_path, &dialect, &key_columns, &columns)?;
+    let theirs = load_keyed_table(&theirs_path, &dialect, &key_columns, &columns)?;
+
+    let mut keys: Vec<&String> = theirs.order.iter().collect();
+    keys.extend(
+        mine.order
+            .iter()
+            .filter(|key| !theirs.rows.contains_key(*key)),
+    );
+
+    let dry_run = dry_run.unwrap_or(false);
+    let mut writer = dialect.output_writer(&target_path, dry_run)?;
+    writer.write_record(&columns).map_err(|e| e.to_string())?;
+
+    let mut conflicts = Vec::new();
+    let mut rows = 0usize;
+    for key in keys {
+        let merged = merge_keyed_row(
+            &key.replace('\u{1f}', ", "),
+            &columns,
+            [base.rows.get(key), mine.rows.get(key), theirs.rows.get(key)],
+            prefer_theirs,
+            &mut conflicts,
+        );
+        if let Some(row) = merged {
+            writer.write_record(&row).map_err(|e| e.to_string())?;
+            rows += 1;
+        }
+    }
+    let hashes = dialect.finish_writer(writer, &target_path)?;
+    if dry_run {
+        return Ok(MergeResult {
+            hashes,
+            output_path: target_path.to_string(),
+            rows,
+            conflict_count: conflicts.len(),
+            conflicts,
+            dry_run,
+        });
+    }
+
+    record_operation(
+        &app,
+        "merge_csv_three_way",
+        Some(&mine_path),
+        Some(&target_path),
+        Some(rows),
+        serde_json::json!({
+            "base": base_path,
+            "theirs": theirs_path,
+            "key_columns": key_columns,
+            "conflicts": conflicts.len(),
+        }),
+    );
+    Ok(MergeResult {
+        hashes,
+        output_path: target_path.to_string(),
+        rows,
+        conflict_count: conflicts.len(),
+        conflicts,
+        dry_run,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReconcileSpec {
+    pub left_path: UserPath,
+    pub right_path: UserPath,
+    pub key_columns: Vec<String>,
+    /// Columns, by header, whose values must agree for a key present in both files.
+    pub compare_columns: Vec<String>,
+    /// Numbers this close count as equal, so `10.00` against `10.001` can pass.
+    pub tolerance: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReconcileDifference {
+    pub key: String,
+    pub column: String,
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReconcileReport {
+    pub left_rows: usize,
+    pub right_rows: usize,
+    /// Keys found in both files with every compared value equal.
+    pub matched: usize,
+    pub only_in_left: Vec<String>,
+    pub only_in_right: Vec<String>,
+    pub differences: Vec<ReconcileDifference>,
+}
+
+/// Reconcile two files by key: keys only in one of them, and keys in both whose compare
+/// columns disagree. Keys are listed in file order, multi-column keys joined with ", ".
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+fn reconcile_files(
+    app: tauri::AppHandle,
+    spec: ReconcileSpec,
+    dialect: CsvDialect,
+) -> Result<ReconcileReport, String> {
+    if spec.key_columns.is_empty() {
+        return Err("at least one key column is required".to_string());
+    }
+    for path in [&spec.left_path, &spec.right_path] {
+        check_file_size(&app, path, "reconcile_files")?;
+        let headers = read_csv_headers(path, &dialect)?;
+        if let Some(missing) = spec.compare_columns.iter().find(|c| !headers.contains(c)) {
+            return Err(format!("compare column '{}' missing in {}", missing, path));
+        }
+    }
+    let left = load_keyed_table(
+        &spec.left_path,
+        &dialect,
+        &spec.key_columns,
+        &spec.compare_columns,
+    )?;
+    let right = load_keyed_table(
+        &spec.right_path,
+        &dialect,
+        &spec.key_columns,
+        &spec.compare_columns,
+    )?;
+    let tolerance = spec.tolerance.unwrap_or(0.0);
+    let same = |a: &str, b: &str| {
+        a == b
+            || match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+                (Ok(a), Ok(b)) => (a - b).abs() <= tolerance,
+                _ => false,
+            }
+    };
+    let display = |key: &String| key.replace('\u{1f}', ", ");
+
+    let mut report = ReconcileReport {
+        left_rows: left.order.len(),
+        right_rows: right.order.len(),
+        matched: 0,
+        only_in_left: Vec::new(),
+        only_in_right: Vec::new(),
+        differences: Vec::new(),
+    };
+    for key in &left.order {
+        let Some(right_row) = right.rows.get(key) else {
+            report.only_in_left.push(display(key));
+            continue;
+        };
+        let before = report.differences.len();
+        for (idx, column) in spec.compare_columns.iter().enumerate() {
+            let l = left.rows[key][idx].as_deref().unwrap_or("");
+            let r = right_row[idx].as_deref().unwrap_or("");
+            if !same(l, r) {
+                report.differences.push(ReconcileDifference {
+                    key: display(key),
+                    column: column.clone(),
+                    left: l.to_string(),
+                    right: r.to_string(),
+                });
+            }
+        }
+        if report.differences.len() == before {
+            report.matched += 1;
+        }
+    }
+    report.only_in_right = right
+        .order
+        .iter()
+        .filter(|key| !left.rows.contains_key(*key))
+        .map(display)
+        .collect();
+    Ok(report)
+}
+
+const VERSIONING_FILE: &str = "versioning.json";
+const VERSION_INDEX_FILE: &str = "index.json";
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct VersioningSettings {
+    pub enabled: bool,
+    /// Snapshots kept per document; older ones are pruned. `None` keeps all.
+    pub keep: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VersionEntry {
+    pub id: String,
+    pub path: String,
+    pub timestamp_ms: u64,
+    pub bytes: u64,
+    pub sha256: Option<String>,
+}
+
+#[tauri::command]
+fn get_versioning_settings(app: tauri::AppHandle) -> Result<VersioningSettings, String> {
+    read_json_config(&app_config_file(&app, VERSIONING_FILE)?)
+}
+
+#[tauri::command]
+fn set_versioning_settings(
+    app: tauri::AppHandle,
+    settings: VersioningSettings,
+) -> Result<VersioningSettings, String> {
+    write_json_config(&app_config_file(&app, VERSIONING_FILE)?, &settings)?;
+    Ok(settings)
+}
+
+/// Per-document snapshot dir under app data, named by a hash of the document path.
+fn versions_dir(app: &tauri::AppHandle, path: &str) -> Result<PathBuf, String> {
+    use tauri::Manager;
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("versions")
+        .join(&sha256_hex(path, "")[..16]);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+/// Store a gzip-compressed copy of a freshly saved file when versioning is enabled.
+fn snapshot_saved_file(
+    app: &tauri::AppHandle,
+    path: &str,
+    hashes: &FileHashes,
+) -> Result<Option<VersionEntry>, String> {
+    let settings: VersioningSettings = read_json_config(&app_config_file(app, VERSIONING_FILE)?)?;
+    if !settings.enabled {
+        return Ok(None);
+    }
+    let dir = versions_dir(app, path)?;
+    let timestamp_ms = now_millis();
+    let id = timestamp_ms.to_string();
+    let mut input = File::open(path).map_err(|e| e.to_string())?;
+    let output = File::create(dir.join(format!("{}.csv.gz", id))).map_err(|e| e.to_string())?;
+    let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+    std::io::copy(&mut input, &mut encoder).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())?;
+
+    let entry = VersionEntry {
+        id,
+        path: path.to_string(),
+        timestamp_ms,
+        bytes: hashes.bytes,
+        sha256: hashes.sha256.clone(),
+    };
+    let index_path = dir.join(VERSION_INDEX_FILE);
+    let mut entries: Vec<VersionEntry> = read_json_config(&index_path)?;
+    entries.push(entry.clone());
+    if let Some(keep) = settings.keep.filter(|keep| entries.len() > *keep) {
+        for old in entries.drain(..entries.len() - keep) {
+            let _ = fs::remove_file(dir.join(format!("{}.csv.gz", old.id)));
+        }
+    }
+    write_json_config(&index_path, &entries)?;
+    Ok(Some(entry))
+}
+
+#[tauri::command]
+fn list_versions(app: tauri::AppHandle, path: UserPath) -> Result<Vec<VersionEntry>, String> {
+    let mut entries: Vec<VersionEntry> =
+        read_json_config(&versions_dir(&app, &path)?.join(VERSION_INDEX_FILE))?;
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Write a snapshot back out, to `target_path` or over the original document.
+#[tauri::command]
+fn restore_version(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    version_id: String,
+    target_path: Option<UserPath>,
+    overwrite: Option<bool>,
+) -> Result<SaveResult, String> {
+    if let Some(target_path) = &target_path {
+        ensure_can_write(target_path, overwrite)?;
+    }
+    let target = target_path.unwrap_or_else(|| path.clone());
+    ensure_not_read_only(&state, &target)?;
+    let dir = versions_dir(&app, &path)?;
+    let entries: Vec<VersionEntry> = read_json_config(&dir.join(VERSION_INDEX_FILE))?;
+    if !entries.iter().any(|entry| entry.id == version_id) {
+        return Err("version not found".to_string());
+    }
+    let snapshot =
+        File::open(dir.join(format!("{}.csv.gz", version_id))).map_err(|e| e.to_string())?;
+    let mut decoder = flate2::read::GzDecoder::new(BufReader::new(snapshot));
+    let temp_target = format!("{}.restore", target);
+    let mut output = CommandOutput::File(
+        File::create(&temp_target).map_err(|e| e.to_string())?,
+        Box::default(),
+    );
+    std::io::copy(&mut decoder, &mut output).map_err(|e| e.to_string())?;
+    let hashes = output.into_hashes();
+    fs::rename(&temp_target, &target).map_err(|e| e.to_string())?;
+
+    record_operation(
+        &app,
+        "restore_version",
+        Some(&path),
+        Some(&target),
+        None,
+        serde_json::json!({ "version_id": version_id }),
+    );
+    Ok(SaveResult {
+        hashes,
+        output_path: target.to_string(),
+        counts: None,
+        verification: None,
+        dry_run: false,
+    })
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct PdfExportOptions {
+    /// First data row (0-based) and number of rows to render.
+    pub start_row: Option<usize>,
+    pub row_count: Option<usize>,
+    pub columns: Option<Vec<usize>>,
+    /// `A4` (default), `A3` or `Letter`.
+    pub page_size: Option<String>,
+    pub landscape: Option<bool>,
+    pub font_size: Option<f32>,
+    /// TrueType font to embed; the built-in Helvetica only covers Latin-1.
+    pub font_path: Option<UserPath>,
+    pub title: Option<String>,
+    pub max_lines_per_cell: Option<usize>,
+}
+
+const PDF_MARGIN_MM: f32 = 10.0;
+const PDF_MAX_ROWS: usize = 20_000;
+
+/// Greedy char-count wrap; widths are estimated, so this only approximates the layout.
+fn wrap_cell_text(text: &str, max_chars: usize, max_lines: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut current = String::new();
+        for word in paragraph.split(' ') {
+            let mut word = word.to_string();
+            while word.chars().count() > max_chars {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                let split: String = word.chars().take(max_chars).collect();
+                word = word.chars().skip(max_chars).collect();
+                lines.push(split);
+            }
+            let needed =
+                current.chars().count() + word.chars().count() + usize::from(!current.is_empty());
+            if needed > max_chars && !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&word);
+        }
+        lines.push(current);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            last.push('…');
+        }
+    }
+    lines
+}
+
+/// Render a row/column range as a paginated table with the header repeated on every page.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path, target = %target_path))]
+fn export_pdf(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    options: PdfExportOptions,
+    overwrite: Option<bool>,
+) -> Result<SaveResult, String> {
+    use printpdf::{BuiltinFont, Line, Mm, PdfDocument, Point};
+
+    ensure_can_write(&target_path, overwrite)?;
+    ensure_not_read_only(&state, &target_path)?;
+
+    let start_row = options.start_row.unwrap_or(0);
+    let row_count = options.row_count.unwrap_or(PDF_MAX_ROWS).min(PDF_MAX_ROWS);
+    let font_size = options.font_size.unwrap_or(8.0).clamp(4.0, 24.0);
+    let max_lines = options.max_lines_per_cell.unwrap_or(4).max(1);
+
+    let (read_path, dialect) = readable_source(&path, &dialect)?;
+    let mut reader = dialect
+        .reader_builder()
+        .flexible(true)
+        .from_reader(BufReader::new(
+            File::open(&read_path).map_err(|e| e.to_string())?,
+        ));
+    let headers: Vec<String> = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect())
+        .map_err(|e| e.to_string())?;
+    let columns: Vec<usize> = options
+        .columns
+        .clone()
+        .unwrap_or_else(|| (0..headers.len()).collect())
+        .into_iter()
+        .filter(|col| *col < headers.len())
+        .collect();
+    if columns.is_empty() {
+        return Err("no columns selected".to_string());
+    }
+    let pick = |record: &csv::StringRecord| -> Vec<String> {
+        columns
+            .iter()
+            .map(|col| record.get(*col).unwrap_or("").to_string())
+            .collect()
+    };
+    let mut rows = Vec::new();
+    for record in reader.records().skip(start_row).take(row_count) {
+        rows.push(pick(&record.map_err(|e| e.to_string())?));
+    }
+    let header_row: Vec<String> = columns.iter().map(|col| headers[*col].clone()).collect();
+
+    let (mut page_w, mut page_h) = match options.page_size.as_deref().unwrap_or("A4") {
+        "A3" => (297.0, 420.0),
+        "Letter" | "letter" => (215.9, 279.4),
+        _ => (210.0, 297.0),
+    };
+    if options.landscape.unwrap_or(false) {
+        std::mem::swap(&mut page_w, &mut page_h);
+    }
+
+    // Column widths follow content length (capped) so short codes don't get half the page.
+    let weights: Vec<f32> = (0..columns.len())
+        .map(|idx| {
+            let longest = std::iter::once(&header_row)
+                .chain(rows.iter().take(500))
+                .map(|row| row[idx].chars().count())
+                .max()
+                .unwrap_or(1);
+            longest.clamp(4, 40) as f32
+        })
+        .collect();
+    let usable_w = page_w - 2.0 * PDF_MARGIN_MM;
+    let total_weight: f32 = weights.iter().sum();
+    let widths: Vec<f32> = weights
+        .iter()
+        .map(|w| usable_w * w / total_weight)
+        .collect();
+    let pt_to_mm = 25.4 / 72.0;
+    let line_h = font_size * 1.25 * pt_to_mm;
+    let char_w = font_size * 0.5 * pt_to_mm;
+    let padding = 1.0;
+
+    let title = options.title.clone().unwrap_or_else(|| {
+        PathBuf::from(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let (doc, first_page, first_layer) =
+        PdfDocument::new(title.clone(), Mm(page_w), Mm(page_h), "Table");
+    let font = match &options.font_path {
+        Some(font_path) => doc
+            .add_external_font(File::open(font_path).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?,
+        None => doc
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|e| e.to_string())?,
+    };
+
+    let mut layer = doc.get_page(first_page).get_layer(first_layer);
+    let mut page_number = 1;
+    let top = page_h - PDF_MARGIN_MM;
+    let bottom = PDF_MARGIN_MM + line_h;
+    let draw_rule = |layer: &printpdf::PdfLayerReference, y: f32| {
+        layer.set_outline_thickness(0.3);
+        layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(PDF_MARGIN_MM), Mm(y)), false),
+                (Point::new(Mm(page_w - PDF_MARGIN_MM), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    };
+    let draw_row = |layer: &printpdf::PdfLayerReference, row: &[String], y: f32| -> f32 {
+        let cells: Vec<Vec<String>> = row
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| {
+                let max_chars = ((width - 2.0 * padding) / char_w).floor() as usize;
+                wrap_cell_text(value, max_chars, max_lines)
+            })
+            .collect();
+        let height = cells.iter().map(|lines| lines.len()).max().unwrap_or(1) as f32 * line_h;
+        let mut x = PDF_MARGIN_MM;
+        for (lines, width) in cells.iter().zip(&widths) {
+            for (line_idx, line) in lines.iter().enumerate() {
+                let baseline = y - (line_idx as f32 + 1.0) * line_h + line_h * 0.25;
+                layer.use_text(
+                    line.clone(),
+                    font_size,
+                    Mm(x + padding),
+                    Mm(baseline),
+                    &font,
+                );
+            }
+            x += width;
+        }
+        height
+    };
+    let row_height = |row: &[String]| -> f32 {
+        row.iter()
+            .zip(&widths)
+            .map(|(value, width)| {
+                let max_chars = ((width - 2.0 * padding) / char_w).floor() as usize;
+                wrap_cell_text(value, max_chars, max_lines).len()
+            })
+            .max()
+            .unwrap_or(1) as f32
+            * line_h
+    };
+    let start_page = |layer: &printpdf::PdfLayerReference, page_number: usize| -> f32 {
+        layer.use_text(
+            format!("{} — page {}", title, page_number),
+            font_size,
+            Mm(PDF_MARGIN_MM),
+            Mm(PDF_MARGIN_MM / 2.0),
+            &font,
+        );
+        let y = top;
+        let height = draw_row(layer, &header_row, y);
+        draw_rule(layer, y - height);
+        y - height - padding
+    };
+
+    let mut y = start_page(&layer, page_number);
+    for row in &rows {
+        if y - row_height(row) < bottom {
+            page_number += 1;
+            let (page, new_layer) = doc.add_page(Mm(page_w), Mm(page_h), "Table");
+            layer = doc.get_page(page).get_layer(new_layer);
+            y = start_page(&layer, page_number);
+        }
+        let height = draw_row(&layer, row, y);
+        y -= height + padding;
+    }
+
+    // Rendered next to the target and moved over it, so a failed save leaves it as it was.
+    let temp = TempFile::beside(&target_path, "pdf", "pdf")?;
+    let mut out = std::io::BufWriter::new(File::create(temp.path()).map_err(|e| e.to_string())?);
+    doc.save(&mut out).map_err(|e| e.to_string())?;
+    out.flush().map_err(|e| e.to_string())?;
+    drop(out);
+    fs::rename(temp.path(), &target_path).map_err(|e| e.to_string())?;
+
+    record_operation(
+        &app,
+        "export_pdf",
+        Some(&path),
+        Some(&target_path),
+        Some(rows.len()),
+        serde_json::json!({ "start_row": start_row, "pages": page_number }),
+    );
+    Ok(SaveResult {
+        hashes: output_hashes(&target_path)?,
+        output_path: target_path.to_string(),
+        counts: None,
+        verification: None,
+        dry_run: false,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChartSeriesSpec {
+    pub x_column: usize,
+    pub y_columns: Vec<usize>,
+    /// `sum`, `mean`, `min`, `max` or `count`; points sharing an x (or bucket) are combined.
+    pub aggregation: Option<String>,
+    /// Bucket width in x units (milliseconds for datetime x).
+    pub bucket: Option<f64>,
+    /// Calendar bucketing for datetime x: `hour`, `day`, `week`, `month`, `quarter`, `year`.
+    pub truncate: Option<String>,
+    pub x_format: Option<String>,
+    pub timezone: Option<String>,
+    pub max_points: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ChartPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChartSeries {
+    pub column: usize,
+    pub name: String,
+    pub source_points: usize,
+    pub points: Vec<ChartPoint>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChartSeriesResult {
+    /// `number`, `datetime` (epoch ms) or `index` (row number).
+    pub x_kind: String,
+    pub series: Vec<ChartSeries>,
+}
+
+/// Largest-Triangle-Three-Buckets downsampling; keeps the visual shape of a series sorted
+/// by x with far fewer points.
+fn lttb(points: &[ChartPoint], threshold: usize) -> Vec<ChartPoint> {
+    if threshold >= points.len() || threshold < 3 {
+        return points.to_vec();
+    }
+    let mut sampled = Vec::with_capacity(threshold);
+    let bucket_size = (points.len() - 2) as f64 / (threshold - 2) as f64;
+    let mut a = 0usize;
+    sampled.push(points[0]);
+    for i in 0..threshold - 2 {
+        let avg_start = ((i + 1) as f64 * bucket_size) as usize + 1;
+        let avg_end = (((i + 2) as f64 * bucket_size) as usize + 1).min(points.len());
+        let avg_len = (avg_end - avg_start).max(1) as f64;
+        let (avg_x, avg_y) = points[avg_start..avg_end]
+            .iter()
+            .fold((0.0, 0.0), |(sx, sy), p| (sx + p.x, sy + p.y));
+        let (avg_x, avg_y) = (avg_x / avg_len, avg_y / avg_len);
+
+        let range_start = (i as f64 * bucket_size) as usize + 1;
+        let range_end = ((i + 1) as f64 * bucket_size) as usize + 1;
+        let pa = points[a];
+        let mut best = range_start;
+        let mut best_area = -1.0;
+        for (idx, p) in points.iter().enumerate().take(range_end).skip(range_start) {
+            let area = ((pa.x - avg_x) * (p.y - pa.y) - (pa.x - p.x) * (avg_y - pa.y)).abs();
+            if area > best_area {
+                best_area = area;
+                best = idx;
+            }
+        }
+        sampled.push(points[best]);
+        a = best;
+    }
+    sampled.push(points[points.len() - 1]);
+    sampled
+}
+
+/// Build plot-ready series: x is parsed as a number, then a datetime, falling back to the
+/// row number. Datetime x can be truncated to calendar periods in `timezone`. With
+/// `aggregation`, points are combined per x value or `bucket`; the result is then
+/// LTTB-downsampled to `max_points`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn prepare_chart_series(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    spec: ChartSeriesSpec,
+) -> Result<ChartSeriesResult, String> {
+    check_file_size(&app, &path, "prepare_chart_series")?;
+    let max_points = spec.max_points.unwrap_or(2000).max(3);
+    let tz = parse_timezone(spec.timezone.as_deref())?;
+    let chart_datetime_ms = |text: &str| {
+        let dt = parse_datetime_value(text, spec.x_format.as_deref(), tz)?;
+        let dt = match spec.truncate.as_deref() {
+            Some(unit) => truncate_datetime(dt, unit, tz)?,
+            None => dt,
+        };
+        Some(dt.timestamp_millis() as f64)
+    };
+    let mut reader = dialect.reader(&path)?;
+    let headers: Vec<String> = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect())
+        .map_err(|e| e.to_string())?;
+    if spec.x_column >= headers.len() || spec.y_columns.iter().any(|c| *c >= headers.len()) {
+        return Err("column out of range".to_string());
+    }
+
+    // The x kind is decided by the first non-empty x value. Rows before it plot
+    // against their index, and are dropped again if the kind turns out otherwise.
+    let mut x_kind: Option<&str> = None;
+    let mut raw: Vec<Vec<ChartPoint>> = spec.y_columns.iter().map(|_| Vec::new()).collect();
+    let mut record = csv::StringRecord::new();
+    let mut row_index = 0usize;
+    let dated = spec.x_format.is_some() || spec.truncate.is_some();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let x_text = record.get(spec.x_column).unwrap_or("").trim();
+        let kind = match x_kind {
+            Some(kind) => kind,
+            None if x_text.is_empty() && !dated => "index",
+            None => {
+                let kind = if dated {
+                    "datetime"
+                } else if x_text.parse::<f64>().is_ok() {
+                    "number"
+                } else if chart_datetime_ms(x_text).is_some() {
+                    "datetime"
+                } else {
+                    "index"
+                };
+                if kind != "index" {
+                    raw.iter_mut().for_each(Vec::clear);
+                }
+                *x_kind.insert(kind)
+            }
+        };
+        let x = match kind {
+            "number" => x_text.parse::<f64>().ok(),
+            "datetime" => chart_datetime_ms(x_text),
+            _ => Some(row_index as f64),
+        };
+        row_index += 1;
+        let Some(x) = x.filter(|x| x.is_finite()) else {
+            continue;
+        };
+        for (slot, col) in spec.y_columns.iter().enumerate() {
+            let y_text = record.get(*col).unwrap_or("").trim();
+            let y = if spec.aggregation.as_deref() == Some("count") {
+                (!y_text.is_empty()).then_some(1.0)
+            } else {
+                y_text.parse::<f64>().ok().filter(|y| y.is_finite())
+            };
+            if let Some(y) = y {
+                raw[slot].push(ChartPoint { x, y });
+            }
+        }
+    }
+
+    let series = spec
+        .y_columns
+        .iter()
+        .zip(raw)
+        .map(|(col, mut points)| {
+            let source_points = points.len();
+            if !points.windows(2).all(|w| w[0].x <= w[1].x) {
+                points.sort_by(|a, b| a.x.total_cmp(&b.x));
+            }
+            if let Some(aggregation) = spec.aggregation.as_deref() {
+                let key = |x: f64| match spec.bucket.filter(|b| *b > 0.0) {
+                    Some(bucket) => (x / bucket).floor() * bucket,
+                    None => x,
+                };
+                let mut grouped: Vec<(f64, f64, f64, usize)> = Vec::new();
+                for point in &points {
+                    let x = key(point.x);
+                    match grouped.last_mut() {
+                        Some(group) if group.0 == x => {
+                            group.1 += point.y;
+                            group.2 = match aggregation {
+                                "min" => group.2.min(point.y),
+                                "max" => group.2.max(point.y),
+                                _ => group.2,
+                            };
+                            group.3 += 1;
+                        }
+                        _ => grouped.push((x, point.y, point.y, 1)),
+                    }
+                }
+                points = grouped
+                    .into_iter()
+                    .map(|(x, sum, extreme, count)| ChartPoint {
+                        x,
+                        y: match aggregation {
+                            "mean" => sum / count as f64,
+                            "min" | "max" => extreme,
+                            _ => sum,
+                        },
+                    })
+                    .collect();
+            }
+            ChartSeries {
+                column: *col,
+                name: headers[*col].clone(),
+                source_points,
+                points: lttb(&points, max_points),
+            }
+        })
+        .collect();
+
+    Ok(ChartSeriesResult {
+        x_kind: x_kind.unwrap_or("index").to_string(),
+        series,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExplodeSpec {
+    pub column: usize,
+    pub separator: String,
+    /// Trim whitespace around each part (default true).
+    pub trim: Option<bool>,
+    /// Emit rows for empty parts (default false); a blank cell always yields one row.
+    pub keep_empty: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImplodeSpec {
+    pub column: usize,
+    pub separator: String,
+    /// Rows with equal values here are joined; defaults to every other column. Other
+    /// columns take the group's first value.
+    pub key_columns: Option<Vec<usize>>,
+    /// Only join consecutive rows (default true), which streams; otherwise groups are
+    /// collected across the whole file in first-seen order.
+    pub adjacent_only: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReshapeResult {
+    pub output_path: String,
+    pub rows_read: usize,
+    pub rows_written: usize,
+    pub hashes: FileHashes,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+/// Checks shared by the reshape commands, run before anything is written. They read the
+/// source while writing, so the target must be another file.
+fn prepare_reshape(
+    state: &AppState,
+    path: &str,
+    target_path: &str,
+    dialect: &CsvDialect,
+    overwrite: Option<bool>,
+    operation: &str,
+) -> Result<(), String> {
+    let same_file = path == target_path
+        || matches!(
+            (fs::canonicalize(path), fs::canonicalize(target_path)),
+            (Ok(source), Ok(target)) if source == target
+        );
+    if same_file {
+        return Err(structured_error(
+            "same_file",
+            "the target is the source file",
+            serde_json::json!({ "path": target_path }),
+        ));
+    }
+    ensure_can_write(target_path, overwrite)?;
+    ensure_not_read_only(state, target_path)?;
+    ensure_space_for_rewrite(path, target_path, dialect, operation)
+}
+
+/// A reshape command's output: written to a temp file beside the target, which replaces the
+/// target only once the whole output is written.
+struct ReshapeOutput {
+    temp: TempFile,
+    writer: csv::Writer<CommandOutput>,
+}
+
+impl ReshapeOutput {
+    fn create(
+        target_path: &str,
+        dialect: &CsvDialect,
+        dry_run: Option<bool>,
+    ) -> Result<Self, String> {
+        let temp = TempFile::beside(target_path, "reshape", "csv")?;
+        let writer =
+            dialect.output_writer(&temp.path().to_string_lossy(), dry_run.unwrap_or(false))?;
+        Ok(Self { temp, writer })
+    }
+}
+
+/// Flush a reshape command's output, move it onto the target and report it. A dry run
+/// stops before the file is finished or the operation recorded.
+#[allow(clippy::too_many_arguments)]
+fn finish_reshape(
+    app: &tauri::AppHandle,
+    operation: &str,
+    path: &str,
+    target_path: &str,
+    dialect: &CsvDialect,
+    ReshapeOutput { temp, writer }: ReshapeOutput,
+    (rows_read, rows_written): (usize, usize),
+    params: serde_json::Value,
+) -> Result<ReshapeResult, String> {
+    let dry_run = matches!(writer.get_ref(), CommandOutput::DryRun(_));
+    let hashes = dialect.finish_writer(writer, &temp.path().to_string_lossy())?;
+    if !dry_run {
+        fs::rename(temp.path(), target_path).map_err(|e| e.to_string())?;
+        record_operation(
+            app,
+            operation,
+            Some(path),
+            Some(target_path),
+            Some(rows_written),
+            params,
+        );
+    }
+    Ok(ReshapeResult {
+        hashes,
+        output_path: target_path.to_string(),
+        rows_read,
+        rows_written,
+        dry_run,
+    })
+}
+
+/// Split a multi-value cell into one output row per part, copying the other columns.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn explode_column(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    spec: ExplodeSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<ReshapeResult, String> {
+    prepare_reshape(
+        &state,
+        &path,
+        &target_path,
+        &dialect,
+        overwrite,
+        "explode_column",
+    )?;
+    if spec.separator.is_empty() {
+        return Err("separator is required".to_string());
+    }
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    if spec.column >= headers.len() {
+        return Err("column out of range".to_string());
+    }
+    let mut output = ReshapeOutput::create(&target_path, &dialect, dry_run)?;
+    let writer = &mut output.writer;
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+    let trim = spec.trim.unwrap_or(true);
+    let keep_empty = spec.keep_empty.unwrap_or(false);
+
+    let mut rows_read = 0usize;
+    let mut rows_written = 0usize;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        rows_read += 1;
+        let mut row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
+        row.resize(headers.len(), String::new());
+        let cell = std::mem::take(&mut row[spec.column]);
+        let mut parts: Vec<&str> = cell
+            .split(spec.separator.as_str())
+            .map(|part| if trim { part.trim() } else { part })
+            .filter(|part| keep_empty || !part.is_empty())
+            .collect();
+        if parts.is_empty() {
+            parts.push("");
+        }
+        for part in parts {
+            row[spec.column] = part.to_string();
+            writer.write_record(&row).map_err(|e| e.to_string())?;
+            rows_written += 1;
+        }
+    }
+    finish_reshape(
+        &app,
+        "explode_column",
+        &path,
+        &target_path,
+        &dialect,
+        output,
+        (rows_read, rows_written),
+        serde_json::json!({ "column": spec.column, "separator": spec.separator }),
+    )
+}
+
+/// Inverse of `explode_column`: join the column's values across rows sharing a key.
+/// Empty values are dropped from the joined cell.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn implode_column(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    spec: ImplodeSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<ReshapeResult, String> {
+    prepare_reshape(
+        &state,
+        &path,
+        &target_path,
+        &dialect,
+        overwrite,
+        "implode_column",
+    )?;
+    if !spec.adjacent_only.unwrap_or(true) {
+        check_file_size(&app, &path, "implode_column")?;
+    }
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let key_columns = spec
+        .key_columns
+        .clone()
+        .unwrap_or_else(|| (0..headers.len()).filter(|c| *c != spec.column).collect());
+    if spec.column >= headers.len() || key_columns.iter().any(|c| *c >= headers.len()) {
+        return Err("column out of range".to_string());
+    }
+    let mut output = ReshapeOutput::create(&target_path, &dialect, dry_run)?;
+    let writer = &mut output.writer;
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+
+    let join = |row: &mut Vec<String>, values: &[String]| {
+        row[spec.column] = values
+            .iter()
+            .filter(|value| !value.is_empty())
+            .map(|value| value.as_str())
+            .collect::<Vec<_>>()
+            .join(&spec.separator);
+    };
+    let mut rows_read = 0usize;
+    let mut rows_written = 0usize;
+    // (key, first row, collected values)
+    let mut current: Option<(Vec<String>, Vec<String>, Vec<String>)> = None;
+    let mut groups: Vec<(Vec<String>, Vec<String>)> = Vec::new();
+    let mut group_index: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        rows_read += 1;
+        let mut row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
+        row.resize(headers.len(), String::new());
+        let key: Vec<String> = key_columns.iter().map(|c| row[*c].clone()).collect();
+        let value = row[spec.column].clone();
+        if spec.adjacent_only.unwrap_or(true) {
+            match current.as_mut() {
+                Some((current_key, _, values)) if *current_key == key => values.push(value),
+                _ => {
+                    if let Some((_, mut first, values)) = current.replace((key, row, vec![value])) {
+                        join(&mut first, &values);
+                        writer.write_record(&first).map_err(|e| e.to_string())?;
+                        rows_written += 1;
+                    }
+                }
+            }
+        } else if let Some(index) = group_index.get(&key) {
+            groups[*index].1.push(value);
+        } else {
+            group_index.insert(key, groups.len());
+            groups.push((row, vec![value]));
+        }
+    }
+    groups.extend(current.map(|(_, first, values)| (first, values)));
+    for (mut first, values) in groups {
+        join(&mut first, &values);
+        writer.write_record(&first).map_err(|e| e.to_string())?;
+        rows_written += 1;
+    }
+    finish_reshape(
+        &app,
+        "implode_column",
+        &path,
+        &target_path,
+        &dialect,
+        output,
+        (rows_read, rows_written),
+        serde_json::json!({ "column": spec.column, "key_columns": key_columns }),
+    )
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MeltSpec {
+    /// Columns copied onto every output row.
+    pub id_columns: Vec<usize>,
+    /// Columns turned into key/value rows; defaults to every non-id column.
+    pub value_columns: Option<Vec<usize>>,
+    pub variable_name: Option<String>,
+    pub value_name: Option<String>,
+    /// Skip pairs whose value is blank (default false).
+    pub skip_empty: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CastSpec {
+    /// Columns identifying an output row; defaults to every column except key and value.
+    pub id_columns: Option<Vec<usize>>,
+    /// Its distinct values become new columns, in first-seen order.
+    pub key_column: usize,
+    pub value_column: usize,
+    /// How repeated id/key pairs combine: `first` (default), `last`, `join`, `sum` or `count`.
+    pub aggregate: Option<String>,
+    /// Separator for `join` (default `;`).
+    pub separator: Option<String>,
+}
+
+/// Wide to long: every value column becomes a (variable, value) row alongside the ids.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn melt_csv(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    spec: MeltSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<ReshapeResult, String> {
+    prepare_reshape(&state, &path, &target_path, &dialect, overwrite, "melt_csv")?;
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let value_columns = spec.value_columns.clone().unwrap_or_else(|| {
+        (0..headers.len())
+            .filter(|c| !spec.id_columns.contains(c))
+            .collect()
+    });
+    if spec
+        .id_columns
+        .iter()
+        .chain(&value_columns)
+        .any(|c| *c >= headers.len())
+    {
+        return Err("column out of range".to_string());
+    }
+    if value_columns.is_empty() {
+        return Err("no columns to melt".to_string());
+    }
+    let skip_empty = spec.skip_empty.unwrap_or(false);
+
+    let mut output = ReshapeOutput::create(&target_path, &dialect, dry_run)?;
+    let writer = &mut output.writer;
+    let mut out_headers: Vec<String> = spec
+        .id_columns
+        .iter()
+        .map(|c| headers[*c].clone())
+        .collect();
+    out_headers.push(
+        spec.variable_name
+            .clone()
+            .unwrap_or_else(|| "variable".to_string()),
+    );
+    out_headers.push(
+        spec.value_name
+            .clone()
+            .unwrap_or_else(|| "value".to_string()),
+    );
+    writer
+        .write_record(&out_headers)
+        .map_err(|e| e.to_string())?;
+
+    let mut rows_read = 0usize;
+    let mut rows_written = 0usize;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        rows_read += 1;
+        let mut out: Vec<&str> = Vec::with_capacity(out_headers.len());
+        for col in &value_columns {
+            let value = record.get(*col).unwrap_or("");
+            if skip_empty && value.trim().is_empty() {
+                continue;
+            }
+            out.clear();
+            out.extend(spec.id_columns.iter().map(|c| record.get(*c).unwrap_or("")));
+            out.push(&headers[*col]);
+            out.push(value);
+            writer.write_record(&out).map_err(|e| e.to_string())?;
+            rows_written += 1;
+        }
+    }
+    finish_reshape(
+        &app,
+        "melt_csv",
+        &path,
+        &target_path,
+        &dialect,
+        output,
+        (rows_read, rows_written),
+        serde_json::json!({ "id_columns": spec.id_columns, "value_columns": value_columns }),
+    )
+}
+
+/// Long to wide: one row per distinct id, one column per distinct key value. Groups are
+/// held in memory, so the file size limits apply.
 #[tauri::command]
-fn open_csv_session(
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn cast_csv(
+    app: tauri::AppHandle,
     state: tauri::State<AppState>,
-    path: String,
-    delimiter: Option<String>,
-) -> Result<CsvSessionInfo, String> {
-    let path_buf = PathBuf::from(&path);
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    spec: CastSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<ReshapeResult, String> {
+    prepare_reshape(&state, &path, &target_path, &dialect, overwrite, "cast_csv")?;
+    check_file_size(&app, &path, "cast_csv")?;
+    let aggregate = spec.aggregate.as_deref().unwrap_or("first");
+    if !matches!(aggregate, "first" | "last" | "join" | "sum" | "count") {
+        return Err(format!("unknown aggregate '{}'", aggregate));
+    }
+    let separator = spec.separator.as_deref().unwrap_or(";");
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    let id_columns = spec.id_columns.clone().unwrap_or_else(|| {
+        (0..headers.len())
+            .filter(|c| *c != spec.key_column && *c != spec.value_column)
+            .collect()
+    });
+    if spec.key_column >= headers.len()
+        || spec.value_column >= headers.len()
+        || id_columns.iter().any(|c| *c >= headers.len())
+    {
+        return Err("column out of range".to_string());
+    }
 
-    let mut sample = String::new();
-    let sample_reader = BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?);
-    sample_reader
-        .take(64 * 1024)
-        .read_to_string(&mut sample)
+    let mut keys: Vec<String> = Vec::new();
+    let mut key_index: HashMap<String, usize> = HashMap::new();
+    let mut ids: Vec<Vec<String>> = Vec::new();
+    let mut id_index: HashMap<Vec<String>, usize> = HashMap::new();
+    // Per id row: cell text and number of values seen, by key slot.
+    let mut cells: Vec<Vec<(String, usize)>> = Vec::new();
+    let mut rows_read = 0usize;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        rows_read += 1;
+        let id: Vec<String> = id_columns
+            .iter()
+            .map(|c| record.get(*c).unwrap_or("").to_string())
+            .collect();
+        let key = record.get(spec.key_column).unwrap_or("");
+        let value = record.get(spec.value_column).unwrap_or("");
+        let slot = match key_index.get(key) {
+            Some(slot) => *slot,
+            None => {
+                key_index.insert(key.to_string(), keys.len());
+                keys.push(key.to_string());
+                keys.len() - 1
+            }
+        };
+        let row = match id_index.get(&id) {
+            Some(row) => *row,
+            None => {
+                id_index.insert(id.clone(), ids.len());
+                ids.push(id);
+                cells.push(Vec::new());
+                ids.len() - 1
+            }
+        };
+        if cells[row].len() <= slot {
+            cells[row].resize(slot + 1, (String::new(), 0));
+        }
+        let (cell, seen) = &mut cells[row][slot];
+        *cell = match (aggregate, *seen) {
+            ("count", _) => (*seen + 1).to_string(),
+            (_, 0) | ("last", _) => value.to_string(),
+            ("join", _) => format!("{}{}{}", cell, separator, value),
+            ("sum", _) => match (cell.trim().parse::<f64>(), value.trim().parse::<f64>()) {
+                (Ok(a), Ok(b)) => (a + b).to_string(),
+                _ => std::mem::take(cell),
+            },
+            _ => std::mem::take(cell),
+        };
+        *seen += 1;
+    }
+
+    let mut output = ReshapeOutput::create(&target_path, &dialect, dry_run)?;
+    let writer = &mut output.writer;
+    let mut out_headers: Vec<String> = id_columns.iter().map(|c| headers[*c].clone()).collect();
+    out_headers.extend(keys.iter().cloned());
+    writer
+        .write_record(&out_headers)
         .map_err(|e| e.to_string())?;
+    for (id, mut row_cells) in ids.into_iter().zip(cells) {
+        row_cells.resize(keys.len(), (String::new(), 0));
+        let row = id
+            .into_iter()
+            .chain(row_cells.into_iter().map(|(cell, _)| cell));
+        writer.write_record(row).map_err(|e| e.to_string())?;
+    }
+    let rows_written = id_index.len();
+    finish_reshape(
+        &app,
+        "cast_csv",
+        &path,
+        &target_path,
+        &dialect,
+        output,
+        (rows_read, rows_written),
+        serde_json::json!({
+            "key_column": spec.key_column,
+            "value_column": spec.value_column,
+            "aggregate": aggregate,
+        }),
+    )
+}
 
-    let delimiter_byte = delimiter
-        .as_deref()
-        .map(parse_delimiter)
-        .unwrap_or_else(|| detect_delimiter(&sample));
+#[derive(Serialize, Deserialize)]
+pub struct SampleSpec {
+    /// Rows to keep; per distinct value of `column` when stratified.
+    pub size: usize,
+    /// `uniform` (default), `stratified` or `weighted`.
+    pub method: Option<String>,
+    /// The stratum column for `stratified`, the weight column for `weighted`.
+    pub column: Option<usize>,
+    /// Fixed seed for a reproducible sample.
+    pub seed: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SampleStratum {
+    pub value: String,
+    /// Rows with this value in the source.
+    pub rows: usize,
+    pub sampled: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SampleResult {
+    pub output_path: String,
+    pub rows_read: usize,
+    pub rows_written: usize,
+    /// `weighted`: rows without a positive numeric weight, which are never picked.
+    pub skipped: usize,
+    /// `stratified`: every stratum, largest first.
+    pub strata: Vec<SampleStratum>,
+    pub hashes: FileHashes,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+/// A uniform sample of up to `size` of the rows offered so far, as (row, values).
+#[derive(Default)]
+struct Reservoir {
+    seen: usize,
+    rows: Vec<(usize, Vec<String>)>,
+}
 
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .from_reader(BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?));
+impl Reservoir {
+    fn offer(&mut self, size: usize, row: (usize, Vec<String>), rng: &mut rand::rngs::StdRng) {
+        use rand::Rng;
+        self.seen += 1;
+        if self.rows.len() < size {
+            self.rows.push(row);
+        } else {
+            let slot = rng.gen_range(0..self.seen);
+            if slot < size {
+                self.rows[slot] = row;
+            }
+        }
+    }
+}
 
+/// Write a random sample of rows without replacement, in file order: `uniform` picks `size`
+/// rows, `stratified` up to `size` per distinct value of `column`, and `weighted` `size`
+/// rows with odds proportional to the number in `column`. One pass, holding only the
+/// sample; stratified samples hold `size` rows per stratum.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn sample_csv(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    spec: SampleSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<SampleResult, String> {
+    use rand::{Rng, SeedableRng};
+    ensure_can_write(&target_path, overwrite)?;
+    ensure_space_for_rewrite(&path, &target_path, &dialect, "sample_csv")?;
+    ensure_not_read_only(&state, &target_path)?;
+    check_file_size(&app, &path, "sample_csv")?;
+    let method = spec.method.as_deref().unwrap_or("uniform");
+    if !matches!(method, "uniform" | "stratified" | "weighted") {
+        return Err(format!("unknown sampling method '{}'", method));
+    }
+    let mut reader = dialect.reader(&path)?;
     let headers = reader
         .headers()
         .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
         .map_err(|e| e.to_string())?;
+    let column = match (method, spec.column) {
+        ("uniform", _) => 0,
+        (_, Some(column)) if column < headers.len() => column,
+        (_, Some(_)) => return Err("column out of range".to_string()),
+        (_, None) => return Err(format!("{} sampling needs a column", method)),
+    };
+    let mut rng = match spec.seed {
+        Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+        None => rand::rngs::StdRng::from_entropy(),
+    };
 
-    let session_id = state.next_id.fetch_add(1, Ordering::Relaxed);
-    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
-    sessions.insert(
-        session_id,
-        CsvSession {
-            reader,
-            row_index: 0,
-            eof: false,
-        },
-    );
+    let mut rows_read = 0usize;
+    let mut skipped = 0usize;
+    let mut uniform = Reservoir::default();
+    let mut strata: HashMap<String, Reservoir> = HashMap::new();
+    // Weighted: each row draws the key u^(1/weight) and the `size` largest keys win
+    // (Efraimidis–Spirakis), kept with the same heap as `top_k_rows`.
+    let mut weighted: std::collections::BinaryHeap<TopKEntry> =
+        std::collections::BinaryHeap::with_capacity(spec.size + 1);
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        let row = rows_read;
+        rows_read += 1;
+        let values = || record.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        match method {
+            "stratified" => {
+                let value = record.get(column).unwrap_or("");
+                if !strata.contains_key(value) {
+                    strata.insert(value.to_string(), Reservoir::default());
+                }
+                if let Some(reservoir) = strata.get_mut(value) {
+                    reservoir.offer(spec.size, (row, values()), &mut rng);
+                }
+            }
+            "weighted" => {
+                let weight = record
+                    .get(column)
+                    .and_then(|cell| cell.trim().parse::<f64>().ok())
+                    .filter(|weight| weight.is_finite() && *weight > 0.0);
+                let Some(weight) = weight else {
+                    skipped += 1;
+                    continue;
+                };
+                let draw: f64 = rng.gen_range(f64::EPSILON..1.0);
+                let entry = TopKEntry {
+                    key: TypedValue::Number(draw.ln() / weight),
+                    descending: true,
+                    row,
+                    values: Vec::new(),
+                };
+                if spec.size == 0
+                    || (weighted.len() == spec.size
+                        && weighted.peek().is_some_and(|worst| entry >= *worst))
+                {
+                    continue;
+                }
+                weighted.push(TopKEntry {
+                    values: values(),
+                    ..entry
+                });
+                if weighted.len() > spec.size {
+                    weighted.pop();
+                }
+            }
+            _ => uniform.offer(spec.size, (row, values()), &mut rng),
+        }
+    }
 
-    let delimiter_str = match delimiter_byte {
-        b'\t' => "\\t".to_string(),
-        other => String::from_utf8_lossy(&[other]).to_string(),
+    let mut report: Vec<SampleStratum> = strata
+        .iter()
+        .map(|(value, reservoir)| SampleStratum {
+            value: value.clone(),
+            rows: reservoir.seen,
+            sampled: reservoir.rows.len(),
+        })
+        .collect();
+    report.sort_by(|a, b| b.rows.cmp(&a.rows).then_with(|| a.value.cmp(&b.value)));
+    let mut sample: Vec<(usize, Vec<String>)> = match method {
+        "stratified" => strata
+            .into_values()
+            .flat_map(|reservoir| reservoir.rows)
+            .collect(),
+        "weighted" => weighted
+            .into_iter()
+            .map(|entry| (entry.row, entry.values))
+            .collect(),
+        _ => uniform.rows,
     };
+    sample.sort_unstable_by_key(|(row, _)| *row);
 
-    Ok(CsvSessionInfo {
-        session_id,
-        headers,
-        delimiter: delimiter_str,
+    let dry_run = dry_run.unwrap_or(false);
+    let mut writer = dialect.output_writer(&target_path, dry_run)?;
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
+    for (_, row) in &sample {
+        writer.write_record(row).map_err(|e| e.to_string())?;
+    }
+    let hashes = dialect.finish_writer(writer, &target_path)?;
+    if !dry_run {
+        record_operation(
+            &app,
+            "sample_csv",
+            Some(&path),
+            Some(&target_path),
+            Some(sample.len()),
+            serde_json::json!({ "method": method, "size": spec.size, "column": spec.column }),
+        );
+    }
+    Ok(SampleResult {
+        hashes,
+        output_path: target_path.to_string(),
+        rows_read,
+        rows_written: sample.len(),
+        skipped,
+        strata: report,
+        dry_run,
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SplitTarget {
+    pub path: UserPath,
+    pub percent: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SplitSpec {
+    /// Two or more outputs; their percentages add up to 100.
+    pub outputs: Vec<SplitTarget>,
+    /// Split each distinct value of this column by the same percentages.
+    pub stratify_column: Option<usize>,
+    /// Fixed seed for a reproducible split.
+    pub seed: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SplitOutput {
+    pub output_path: String,
+    pub rows_written: usize,
+    pub hashes: FileHashes,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SplitResult {
+    pub rows_read: usize,
+    pub outputs: Vec<SplitOutput>,
+    /// Distinct values of the stratify column; 1 without one.
+    pub strata: usize,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+/// `total` rows shared out by `percents`, rounding so the counts add up to `total`: the
+/// largest remainders get the leftover rows, earlier outputs first on ties.
+fn split_counts(total: usize, percents: &[f64]) -> Vec<usize> {
+    let sum: f64 = percents.iter().sum();
+    let exact: Vec<f64> = percents
+        .iter()
+        .map(|percent| total as f64 * percent / sum)
+        .collect();
+    let mut counts: Vec<usize> = exact.iter().map(|share| share.floor() as usize).collect();
+    let mut order: Vec<usize> = (0..percents.len()).collect();
+    order.sort_by(|a, b| {
+        (exact[*b] - exact[*b].floor()).total_cmp(&(exact[*a] - exact[*a].floor()))
+    });
+    let short = total.saturating_sub(counts.iter().sum());
+    for slot in order.into_iter().cycle().take(short) {
+        counts[slot] += 1;
+    }
+    counts
+}
+
+/// Randomly partition the rows into `spec.outputs` by percentage, each output getting
+/// exactly its share (of every stratum, with `stratify_column`). Reads the file twice: once
+/// to count the rows, once to deal them out; only the counts are held in memory.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn split_csv(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: CsvDialect,
+    spec: SplitSpec,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<SplitResult, String> {
+    use rand::{Rng, SeedableRng};
+    if spec.outputs.len() < 2 {
+        return Err("a split needs at least two outputs".to_string());
+    }
+    if spec
+        .outputs
+        .iter()
+        .any(|output| !output.percent.is_finite() || output.percent <= 0.0)
+    {
+        return Err("every output needs a percentage above 0".to_string());
+    }
+    let total_percent: f64 = spec.outputs.iter().map(|output| output.percent).sum();
+    if (total_percent - 100.0).abs() > 0.01 {
+        return Err(format!("percentages add up to {}, not 100", total_percent));
+    }
+    let targets: HashSet<&str> = spec.outputs.iter().map(|o| o.path.as_str()).collect();
+    if targets.len() < spec.outputs.len() || targets.contains(path.as_str()) {
+        return Err("outputs must be distinct and differ from the source file".to_string());
+    }
+    for output in &spec.outputs {
+        ensure_can_write(&output.path, overwrite)?;
+        ensure_not_read_only(&state, &output.path)?;
+    }
+    ensure_space_for_rewrite(&path, &spec.outputs[0].path, &dialect, "split_csv")?;
+    check_file_size(&app, &path, "split_csv")?;
+
+    let mut reader = dialect.reader(&path)?;
+    let headers = reader
+        .headers()
+        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    if spec.stratify_column.is_some_and(|col| col >= headers.len()) {
+        return Err("column out of range".to_string());
+    }
+    let stratum = |record: &csv::StringRecord| {
+        spec.stratify_column
+            .and_then(|col| record.get(col))
+            .unwrap_or("")
+            .to_string()
+    };
+    let mut sizes: HashMap<String, usize> = HashMap::new();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        *sizes.entry(stratum(&record)).or_default() += 1;
+    }
+    let percents: Vec<f64> = spec.outputs.iter().map(|output| output.percent).collect();
+    let mut remaining: HashMap<String, Vec<usize>> = sizes
+        .iter()
+        .map(|(value, size)| (value.clone(), split_counts(*size, &percents)))
+        .collect();
+
+    let mut rng = match spec.seed {
+        Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+        None => rand::rngs::StdRng::from_entropy(),
+    };
+    let dry_run = dry_run.unwrap_or(false);
+    let mut writers = spec
+        .outputs
+        .iter()
+        .map(|output| dialect.output_writer(&output.path, dry_run))
+        .collect::<Result<Vec<_>, String>>()?;
+    for writer in &mut writers {
+        writer.write_record(&headers).map_err(|e| e.to_string())?;
+    }
+    let mut written = vec![0usize; writers.len()];
+    let mut rows_read = 0usize;
+    let mut reader = dialect.reader(&path)?;
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        rows_read += 1;
+        // Drawing each row's output in proportion to the rows each still needs deals out
+        // a uniformly random partition with exactly those counts.
+        let left = remaining
+            .get_mut(&stratum(&record))
+            .filter(|left| left.iter().sum::<usize>() > 0)
+            .ok_or("the file changed during the split")?;
+        let mut draw = rng.gen_range(0..left.iter().sum::<usize>());
+        let slot = left
+            .iter()
+            .position(|count| {
+                let hit = draw < *count;
+                draw = draw.saturating_sub(*count);
+                hit
+            })
+            .unwrap_or(0);
+        left[slot] -= 1;
+        writers[slot]
+            .write_record(&record)
+            .map_err(|e| e.to_string())?;
+        written[slot] += 1;
+    }
+
+    let mut outputs = Vec::with_capacity(writers.len());
+    for ((writer, target), rows_written) in writers.into_iter().zip(&spec.outputs).zip(written) {
+        outputs.push(SplitOutput {
+            hashes: dialect.finish_writer(writer, &target.path)?,
+            output_path: target.path.to_string(),
+            rows_written,
+        });
+    }
+    if !dry_run {
+        record_operation(
+            &app,
+            "split_csv",
+            Some(&path),
+            None,
+            Some(rows_read),
+            serde_json::json!({
+                "outputs": spec.outputs.iter().map(|o| (&o.path, o.percent)).collect::<Vec<_>>(),
+                "stratify_column": spec.stratify_column,
+            }),
+        );
+    }
+    Ok(SplitResult {
+        rows_read,
+        outputs,
+        strata: sizes.len(),
+        dry_run,
+    })
+}
+
+/// Per-column output formatting for `export_with_types`. Column types come from `schema`
+/// (matched by header name), falling back to the schema next to the file and then to types
+/// inferred from the data.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ExportCoercion {
+    pub schema: Option<TableSchema>,
+    /// Fixed precision for `number` columns; values are left as parsed otherwise.
+    pub decimals: Option<usize>,
+    /// chrono output formats (defaults `%Y-%m-%d` and `%Y-%m-%dT%H:%M:%S`).
+    pub date_format: Option<String>,
+    pub datetime_format: Option<String>,
+    /// `upper` (TRUE/FALSE, default), `lower` (true/false) or `numeric` (1/0).
+    pub boolean_style: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CoercedExportResult {
+    pub output_path: String,
+    pub rows: usize,
+    /// Type applied to each column: `integer`, `number`, `boolean`, `date`, `datetime` or
+    /// `string` (left as is).
+    pub column_types: Vec<String>,
+    /// Non-empty cells that did not parse as their column's type and were kept verbatim.
+    pub uncoerced: usize,
+    pub hashes: FileHashes,
+}
+
+fn parse_boolean_cell(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" => Some(true),
+        "false" | "f" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Types [`infer_column_types`] tries, narrowest first.
+const INFERRED_TYPES: [&str; 5] = ["integer", "number", "boolean", "date", "datetime"];
+
+/// Whether a trimmed, non-empty `value` parses as `INFERRED_TYPES[kind]`.
+fn value_fits_type(kind: usize, value: &str) -> bool {
+    match kind {
+        0 => value.parse::<i64>().is_ok(),
+        1 => value.parse::<f64>().is_ok_and(|n| n.is_finite()),
+        2 => parse_boolean_cell(value).is_some(),
+        3 => DATE_FORMATS
+            .iter()
+            .any(|format| chrono::NaiveDate::parse_from_str(value, format).is_ok()),
+        _ => parse_datetime_value(value, None, chrono_tz::UTC).is_some(),
+    }
+}
+
+/// One pass over the file: the narrowest type every non-empty value of a column fits.
+fn infer_column_types(
+    path: &str,
+    dialect: &CsvDialect,
+    columns: usize,
+) -> Result<Vec<String>, String> {
+    let mut reader = dialect.reader(path)?;
+    // All plausible until a value rules them out.
+    let mut fits = vec![[true; 5]; columns];
+    let mut seen = vec![false; columns];
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        for (col, value) in record.iter().enumerate().take(columns) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            seen[col] = true;
+            for (kind, fit) in fits[col].iter_mut().enumerate() {
+                if *fit {
+                    *fit = value_fits_type(kind, value);
+                }
+            }
+        }
+    }
+    Ok(fits
+        .iter()
+        .zip(seen)
+        .map(|(fit, seen)| {
+            fit.iter()
+                .position(|fit| *fit)
+                .filter(|_| seen)
+                .map_or("string", |kind| INFERRED_TYPES[kind])
+                .to_string()
+        })
+        .collect())
+}
+
+/// Format `value` as `kind`; `None` when it does not parse.
+fn coerce_cell(kind: &str, value: &str, options: &ExportCoercion) -> Option<String> {
+    let trimmed = value.trim();
+    match kind {
+        "integer" => {
+            let number = trimmed.parse::<f64>().ok().filter(|n| n.fract() == 0.0)?;
+            Some(format!("{:.0}", number))
+        }
+        "number" => {
+            let number = trimmed.parse::<f64>().ok().filter(|n| n.is_finite())?;
+            Some(match options.decimals {
+                Some(decimals) => format!("{:.*}", decimals, number),
+                None => number.to_string(),
+            })
+        }
+        "boolean" => {
+            let flag = parse_boolean_cell(trimmed)?;
+            Some(
+                match (options.boolean_style.as_deref(), flag) {
+                    (Some("numeric"), true) => "1",
+                    (Some("numeric"), false) => "0",
+                    (Some("lower"), true) => "true",
+                    (Some("lower"), false) => "false",
+                    (_, true) => "TRUE",
+                    (_, false) => "FALSE",
+                }
+                .to_string(),
+            )
+        }
+        "date" | "datetime" => {
+            let dt = parse_datetime_value(trimmed, None, chrono_tz::UTC)?;
+            let format = if kind == "date" {
+                options.date_format.as_deref().unwrap_or("%Y-%m-%d")
+            } else {
+                options
+                    .datetime_format
+                    .as_deref()
+                    .unwrap_or("%Y-%m-%dT%H:%M:%S")
+            };
+            format_or_none(dt.naive_utc().format(format))
+        }
+        _ => Some(value.to_string()),
+    }
+}
+
+/// Copy a file while normalizing each typed column's representation, so downstream
+/// loaders see one format per column. Empty cells stay empty. Runs off the main thread, so
+/// `cancel_export` can reach it.
+#[tauri::command]
+async fn export_with_types(
+    app: tauri::AppHandle,
+    path: UserPath,
+    dialect: CsvDialect,
+    target_path: UserPath,
+    output_dialect: Option<CsvDialect>,
+    coercion: ExportCoercion,
+    overwrite: Option<bool>,
+) -> Result<CoercedExportResult, String> {
+    ensure_can_write(&target_path, overwrite)?;
+    ensure_space_for_rewrite(
+        &path,
+        &target_path,
+        output_dialect.as_ref().unwrap_or(&dialect),
+        "export_with_types",
+    )?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let started = Instant::now();
+        let _taskbar = TaskbarProgress::start(&app);
+        let result = execute_typed_export(
+            app.clone(),
+            app.state(),
+            path.to_string(),
+            dialect,
+            target_path.to_string(),
+            output_dialect,
+            coercion,
+        );
+        notify_export_finished(&app, started, &result);
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn notify_export_finished(
+    app: &tauri::AppHandle,
+    started: Instant,
+    result: &Result<CoercedExportResult, String>,
+) {
+    let title = tr_app_with(app, "job_export", &[]);
+    notify_job_finished(app, started, &title, result, |done| {
+        tr_app_with(
+            app,
+            "summary_export",
+            &[
+                ("rows", &done.rows),
+                ("file", &path_display_name(&done.output_path)),
+            ],
+        )
+    });
+}
+
+#[tracing::instrument(skip_all, fields(path = %path))]
+fn execute_typed_export(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: String,
+    dialect: CsvDialect,
+    target_path: String,
+    output_dialect: Option<CsvDialect>,
+    coercion: ExportCoercion,
+) -> Result<CoercedExportResult, String> {
+    ensure_not_read_only(&state, &target_path)?;
+    check_file_size(&app, &path, "export_with_types")?;
+    for format in [&coercion.date_format, &coercion.datetime_format]
+        .into_iter()
+        .flatten()
+    {
+        check_strftime(format)?;
+    }
+    let output_dialect = output_dialect.unwrap_or_else(|| dialect.clone());
+    let headers = read_csv_headers(&path, &dialect)?;
+    let schema = match &coercion.schema {
+        Some(schema) => Some(schema.clone()),
+        None => find_table_schema(&path)?.map(|loaded| loaded.schema),
+    };
+    let settings = load_file_settings(&app, &path);
+    let declared = |header: &String| {
+        settings.column_types.get(header).cloned().or_else(|| {
+            schema
+                .as_ref()
+                .and_then(|schema| schema.fields.iter().find(|field| &field.name == header))
+                .map(|field| field.field_type.clone())
+        })
+    };
+    let inferred = if headers.iter().all(|header| declared(header).is_some()) {
+        Vec::new()
+    } else {
+        infer_column_types(&path, &dialect, headers.len())?
+    };
+    let column_types: Vec<String> = headers
+        .iter()
+        .enumerate()
+        .map(|(col, header)| {
+            declared(header)
+                .or_else(|| inferred.get(col).cloned())
+                .unwrap_or_else(|| "string".to_string())
+        })
+        .collect();
+
+    let (source_len, source_modified) = file_signature(&PathBuf::from(&path))?;
+    // Ids restart with the app, so ones still held by an interrupted export are skipped.
+    let job_id = loop {
+        let id = state.next_id.fetch_add(1, Ordering::Relaxed);
+        if !export_checkpoint_file(&app, id)?.exists() {
+            break id;
+        }
+    };
+    let job = ExportCheckpoint {
+        job_id,
         path,
+        dialect,
+        target_path,
+        output_dialect,
+        coercion,
+        headers,
+        column_types,
+        source_len,
+        source_modified,
+        rows_done: 0,
+        uncoerced: 0,
+        partial_bytes: 0,
+    };
+    run_typed_export(&app, &state, job)
+}
+
+/// Rows between checkpoints of a typed export.
+const EXPORT_CHECKPOINT_ROWS: usize = 100_000;
+
+/// Where a typed export stopped. Saved under app data once the export passes its first
+/// checkpoint and removed when it completes, so a crash or `cancel_export` leaves a record
+/// `resume_export` can continue from.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExportCheckpoint {
+    pub job_id: u64,
+    pub path: String,
+    pub dialect: CsvDialect,
+    pub target_path: String,
+    pub output_dialect: CsvDialect,
+    pub coercion: ExportCoercion,
+    pub headers: Vec<String>,
+    pub column_types: Vec<String>,
+    /// Source size and mtime when the export started; a changed source can't be resumed.
+    pub source_len: u64,
+    pub source_modified: u64,
+    pub rows_done: usize,
+    pub uncoerced: usize,
+    /// Bytes of `<target>.partial` that hold `rows_done` rows.
+    pub partial_bytes: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ExportProgress {
+    pub job_id: u64,
+    pub rows: usize,
+}
+
+fn export_checkpoints_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("exports");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+fn export_checkpoint_file(app: &tauri::AppHandle, job_id: u64) -> Result<PathBuf, String> {
+    Ok(export_checkpoints_dir(app)?.join(format!("{}.json", job_id)))
+}
+
+fn partial_export_path(target: &str) -> String {
+    format!("{}.partial", target)
+}
+
+/// Write the rows of `job` after `rows_done` to `<target>.partial`, checkpointing every
+/// `EXPORT_CHECKPOINT_ROWS` rows and on cancel, then move it into place.
+fn run_typed_export(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    job: ExportCheckpoint,
+) -> Result<CoercedExportResult, String> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    state
+        .export_cancels
+        .lock()
+        .map_err(|_| "lock poisoned")?
+        .insert(job.job_id, cancel.clone());
+    let result = write_typed_export(app, job, &cancel);
+    if let Ok(mut cancels) = state.export_cancels.lock() {
+        cancels.retain(|_, flag| !Arc::ptr_eq(flag, &cancel));
+    }
+    result
+}
+
+#[tracing::instrument(skip_all, fields(job_id = job.job_id, resume_at = job.rows_done))]
+fn write_typed_export(
+    app: &tauri::AppHandle,
+    mut job: ExportCheckpoint,
+    cancel: &AtomicBool,
+) -> Result<CoercedExportResult, String> {
+    let partial = partial_export_path(&job.target_path);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&partial)
+        .map_err(|e| e.to_string())?;
+    // Drop whatever was written after the last checkpoint; what is kept is hashed once
+    // here so the finished file needn't be read again.
+    file.set_len(job.partial_bytes).map_err(|e| e.to_string())?;
+    let mut digest: Box<OutputDigest> = Box::default();
+    std::io::copy(&mut (&file).take(job.partial_bytes), &mut digest).map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+    let mut writer = job
+        .output_dialect
+        .writer_to(CommandOutput::File(file, digest));
+    if job.rows_done == 0 {
+        writer
+            .write_record(&job.headers)
+            .map_err(|e| e.to_string())?;
+    }
+    let _ = app.emit(
+        "export-progress",
+        ExportProgress {
+            job_id: job.job_id,
+            rows: job.rows_done,
+        },
+    );
+
+    let mut reader = job.dialect.reader(&job.path)?;
+    let mut record = csv::StringRecord::new();
+    let mut rows = 0usize;
+    let mut uncoerced = job.uncoerced;
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        rows += 1;
+        if rows <= job.rows_done {
+            continue;
+        }
+        let row: Vec<String> = record
+            .iter()
+            .enumerate()
+            .map(|(col, value)| {
+                let kind = job
+                    .column_types
+                    .get(col)
+                    .map(|s| s.as_str())
+                    .unwrap_or("string");
+                if value.trim().is_empty() {
+                    return value.to_string();
+                }
+                coerce_cell(kind, value, &job.coercion).unwrap_or_else(|| {
+                    uncoerced += 1;
+                    value.to_string()
+                })
+            })
+            .collect();
+        writer.write_record(&row).map_err(|e| e.to_string())?;
+
+        let cancelled = cancel.load(Ordering::Relaxed);
+        if rows.is_multiple_of(EXPORT_CHECKPOINT_ROWS) || cancelled {
+            writer.flush().map_err(|e| e.to_string())?;
+            job.rows_done = rows;
+            job.uncoerced = uncoerced;
+            job.partial_bytes = writer.get_ref().bytes();
+            write_json_config(&export_checkpoint_file(app, job.job_id)?, &job)?;
+            let _ = app.emit(
+                "export-progress",
+                ExportProgress {
+                    job_id: job.job_id,
+                    rows,
+                },
+            );
+        }
+        if cancelled {
+            return Err(structured_error(
+                "export_interrupted",
+                "export cancelled; resume_export continues it",
+                serde_json::json!({ "job_id": job.job_id, "rows_done": rows }),
+            ));
+        }
+    }
+    let hashes = job.output_dialect.finish_writer(writer, &partial)?;
+    if std::path::Path::new(&job.target_path).exists() {
+        fs::remove_file(&job.target_path).map_err(|e| e.to_string())?;
+    }
+    fs::rename(&partial, &job.target_path).map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(export_checkpoint_file(app, job.job_id)?);
+
+    record_operation(
+        app,
+        "export_with_types",
+        Some(&job.path),
+        Some(&job.target_path),
+        Some(rows),
+        serde_json::json!({
+            "column_types": job.column_types,
+            "uncoerced": uncoerced,
+            "resumed_at": job.rows_done,
+        }),
+    );
+    Ok(CoercedExportResult {
+        hashes,
+        output_path: job.target_path,
+        rows,
+        column_types: job.column_types,
+        uncoerced,
+    })
+}
+
+/// Typed exports that stopped after a checkpoint and can be resumed.
+#[tauri::command]
+fn list_interrupted_exports(app: tauri::AppHandle) -> Result<Vec<ExportCheckpoint>, String> {
+    let mut jobs: Vec<ExportCheckpoint> = fs::read_dir(export_checkpoints_dir(&app)?)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .filter_map(|entry| read_json_config::<Option<ExportCheckpoint>>(&entry.path()).ok()?)
+        .collect();
+    jobs.sort_by_key(|job| job.job_id);
+    Ok(jobs)
+}
+
+/// Continue a crashed or cancelled typed export from its last checkpoint, off the main
+/// thread like `export_with_types`.
+#[tauri::command]
+async fn resume_export(app: tauri::AppHandle, job_id: u64) -> Result<CoercedExportResult, String> {
+    let job: Option<ExportCheckpoint> = read_json_config(&export_checkpoint_file(&app, job_id)?)?;
+    let job = job.ok_or_else(|| format!("no interrupted export {}", job_id))?;
+    authorize_path(&job.path)?;
+    authorize_path(&job.target_path)?;
+    if file_signature(&PathBuf::from(&job.path))? != (job.source_len, job.source_modified) {
+        return Err(structured_error(
+            "export_source_changed",
+            "the source changed since the export started; run it again",
+            serde_json::json!({ "job_id": job_id, "path": job.path }),
+        ));
+    }
+    let partial_len = fs::metadata(partial_export_path(&job.target_path))
+        .map(|m| m.len())
+        .unwrap_or(0);
+    if partial_len < job.partial_bytes {
+        return Err(format!(
+            "the partial output of export {} is missing",
+            job_id
+        ));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let started = Instant::now();
+        let _taskbar = TaskbarProgress::start(&app);
+        let result = run_typed_export(&app, &app.state(), job);
+        notify_export_finished(&app, started, &result);
+        result
     })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
+/// Ask a running typed export to stop at the next row, keeping a checkpoint.
 #[tauri::command]
-fn read_csv_rows(
-    state: tauri::State<AppState>,
-    session_id: u64,
-    limit: usize,
-) -> Result<CsvSlice, String> {
-    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
-    let session = sessions
-        .get_mut(&session_id)
-        .ok_or_else(|| "session not found".to_string())?;
+fn cancel_export(state: tauri::State<AppState>, job_id: u64) -> Result<bool, String> {
+    let cancels = state.export_cancels.lock().map_err(|_| "lock poisoned")?;
+    Ok(cancels
+        .get(&job_id)
+        .map(|flag| flag.store(true, Ordering::Relaxed))
+        .is_some())
+}
 
-    if session.eof {
-        return Ok(CsvSlice {
-            rows: Vec::new(),
-            start: session.row_index,
-            end: session.row_index,
-            eof: true,
-        });
+/// Forget an interrupted export and delete its partial output.
+#[tauri::command]
+fn discard_export(app: tauri::AppHandle, job_id: u64) -> Result<(), String> {
+    let file = export_checkpoint_file(&app, job_id)?;
+    let job: Option<ExportCheckpoint> = read_json_config(&file)?;
+    if let Some(job) = job {
+        let _ = fs::remove_file(partial_export_path(&job.target_path));
     }
+    let _ = fs::remove_file(file);
+    Ok(())
+}
 
-    let start = session.row_index;
-    let mut rows = Vec::new();
-    for rec in session.reader.records() {
-        let record = rec.map_err(|e| e.to_string())?;
-        rows.push(record.iter().map(|s| s.to_string()).collect());
-        session.row_index += 1;
-        if rows.len() >= limit {
-            break;
+#[derive(Serialize, Deserialize, Default)]
+pub struct HeaderNormalization {
+    /// `snake_case`, `kebab_case`, `camel_case`, `pascal_case`, `lowercase`, `uppercase`,
+    /// or unset to keep the casing.
+    pub convention: Option<String>,
+    /// Strip accents and drop any remaining non-ASCII characters.
+    pub ascii_only: Option<bool>,
+    /// Trim surrounding whitespace (default true).
+    pub trim: Option<bool>,
+    /// Suffix repeated names with `_2`, `_3`, ... (default true).
+    pub dedupe: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HeaderNormalizationResult {
+    pub headers: Vec<String>,
+    /// Rename ops for the changed headers, ready for `save_csv_with_patches`.
+    pub ops: Vec<ColumnOp>,
+}
+
+/// Propose normalized header names without applying them; the returned rename ops are
+/// meant to be confirmed by the user first. Blank names become `column_<n>`.
+#[tauri::command]
+fn normalize_headers(
+    headers: Vec<String>,
+    options: HeaderNormalization,
+) -> Result<HeaderNormalizationResult, String> {
+    let convention = options.convention.as_deref();
+    let separator = if convention == Some("kebab_case") {
+        "-"
+    } else {
+        "_"
+    };
+    let mut proposed = Vec::with_capacity(headers.len());
+    for (index, header) in headers.iter().enumerate() {
+        let mut name = if options.trim.unwrap_or(true) {
+            header.trim().to_string()
+        } else {
+            header.clone()
+        };
+        if options.ascii_only.unwrap_or(false) {
+            name = strip_accents(&name)
+                .chars()
+                .filter(|c| c.is_ascii())
+                .collect();
         }
+        name = match convention {
+            None => name,
+            Some("snake_case") => identifier_words(&name).join("_"),
+            Some("kebab_case") => identifier_words(&name).join("-"),
+            Some("camel_case") => camel_case(&name, false),
+            Some("pascal_case") => camel_case(&name, true),
+            Some("lowercase") => name.to_lowercase(),
+            Some("uppercase") => name.to_uppercase(),
+            Some(other) => return Err(format!("unknown convention '{}'", other)),
+        };
+        if name.trim().is_empty() {
+            name = format!("column{}{}", separator, index + 1);
+        }
+        proposed.push(name);
     }
 
-    if rows.len() < limit {
-        session.eof = true;
+    if options.dedupe.unwrap_or(true) {
+        dedupe_names(&mut proposed, separator);
     }
 
-    let end = start + rows.len();
-
-    Ok(CsvSlice {
-        rows,
-        start,
-        end,
-        eof: session.eof,
+    let ops = headers
+        .iter()
+        .zip(&proposed)
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(index, (_, after))| ColumnOp::Rename {
+            index,
+            name: after.clone(),
+        })
+        .collect();
+    Ok(HeaderNormalizationResult {
+        headers: proposed,
+        ops,
     })
 }
 
-#[tauri::command]
-fn read_csv_rows_window(
-    state: tauri::State<AppState>,
-    path: String,
-    delimiter: Option<String>,
-    start: usize,
-    limit: usize,
-) -> Result<CsvSlice, String> {
-    let path_buf = PathBuf::from(&path);
+#[derive(Serialize, Deserialize)]
+pub struct ColumnMatch {
+    pub source: String,
+    pub source_index: usize,
+    /// Best unclaimed target column, or `None` when nothing scored high enough.
+    pub target: Option<String>,
+    pub target_index: Option<usize>,
+    pub score: f64,
+    pub name_similarity: f64,
+    pub value_overlap: f64,
+}
 
-    let delimiter_byte = if let Some(value) = delimiter.as_deref() {
-        parse_delimiter(value)
-    } else {
-        let mut sample = String::new();
-        let sample_reader = BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?);
-        sample_reader
-            .take(64 * 1024)
-            .read_to_string(&mut sample)
-            .map_err(|e| e.to_string())?;
-        detect_delimiter(&sample)
-    };
+#[derive(Serialize, Deserialize)]
+pub struct ColumnMappingSuggestion {
+    /// One entry per source column, in source order.
+    pub mappings: Vec<ColumnMatch>,
+    pub unmatched_targets: Vec<String>,
+}
 
-    let signature = file_signature(&path_buf)?;
-    let key = index_key(&path, delimiter_byte);
-    let index = {
-        let mut indexes = state.indexes.lock().map_err(|_| "lock poisoned")?;
-        if let Some(candidate) = indexes.get(&key) {
-            if candidate.file_len == signature.0 && candidate.modified == signature.1 {
-                Some(candidate.clone())
-            } else {
-                indexes.remove(&key);
-                None
+/// Header reduced to lowercase letters and digits, so `E-Mail` and `email` agree.
+fn mapping_name(header: &str) -> String {
+    strip_accents(header)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Distinct trimmed, lowercased values of each column over the first `rows` records.
+fn sample_column_values(
+    path: &str,
+    dialect: &CsvDialect,
+    rows: usize,
+) -> Result<(Vec<String>, Vec<HashSet<String>>), String> {
+    let headers = read_csv_headers(path, dialect)?;
+    let mut values = vec![HashSet::new(); headers.len()];
+    let mut reader = dialect.reader(path)?;
+    for record in reader.records().take(rows) {
+        let record = record.map_err(|e| e.to_string())?;
+        for (set, value) in values.iter_mut().zip(record.iter()) {
+            let value = value.trim();
+            if !value.is_empty() {
+                set.insert(value.to_lowercase());
             }
-        } else {
-            None
         }
-    };
+    }
+    Ok((headers, values))
+}
 
-    if let Some(index) = index {
-        let (base_row, base_offset) = find_index_base(&index, start);
-        let mut file = File::open(&path_buf).map_err(|e| e.to_string())?;
-        file.seek(SeekFrom::Start(base_offset)).map_err(|e| e.to_string())?;
-        let mut reader = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .delimiter(delimiter_byte)
-            .from_reader(BufReader::new(file));
+/// Propose which `target_path` column each `source_path` column feeds when concatenating or
+/// importing, from normalized header similarity and the overlap of sampled values. Pairs
+/// are claimed best score first; nothing is applied, the caller confirms the mapping.
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+fn suggest_column_mapping(
+    source_path: UserPath,
+    target_path: UserPath,
+    dialect: CsvDialect,
+    sample_rows: Option<usize>,
+    min_score: Option<f64>,
+) -> Result<ColumnMappingSuggestion, String> {
+    let sample_rows = sample_rows.unwrap_or(1000);
+    let min_score = min_score.unwrap_or(0.5);
+    let (sources, source_values) = sample_column_values(&source_path, &dialect, sample_rows)?;
+    let (targets, target_values) = sample_column_values(&target_path, &dialect, sample_rows)?;
 
-        let mut record = csv::StringRecord::new();
-        let mut current = base_row;
-        while current < start {
-            if !reader.read_record(&mut record).map_err(|e| e.to_string())? {
-                break;
-            }
-            current += 1;
+    let mut pairs = Vec::new();
+    for (s, source) in sources.iter().enumerate() {
+        let source_name = mapping_name(source);
+        for (t, target) in targets.iter().enumerate() {
+            let target_name = mapping_name(target);
+            let longest = source_name.chars().count().max(target_name.chars().count());
+            let name_similarity = if source_name == target_name {
+                1.0
+            } else {
+                1.0 - edit_distance(&source_name, &target_name, longest) as f64 / longest as f64
+            };
+            let shared = source_values[s].intersection(&target_values[t]).count();
+            let union = source_values[s].len() + target_values[t].len() - shared;
+            let value_overlap = if union == 0 {
+                0.0
+            } else {
+                shared as f64 / union as f64
+            };
+            let score = if name_similarity == 1.0 {
+                1.0
+            } else {
+                0.6 * name_similarity + 0.4 * value_overlap
+            };
+            pairs.push((score, s, t, name_similarity, value_overlap));
         }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
 
-        let mut rows = Vec::new();
-        while rows.len() < limit {
-            if !reader.read_record(&mut record).map_err(|e| e.to_string())? {
-                break;
-            }
-            rows.push(record.iter().map(|s| s.to_string()).collect());
-            current += 1;
+    let mut mappings: Vec<ColumnMatch> = sources
+        .iter()
+        .enumerate()
+        .map(|(source_index, source)| ColumnMatch {
+            source: source.clone(),
+            source_index,
+            target: None,
+            target_index: None,
+            score: 0.0,
+            name_similarity: 0.0,
+            value_overlap: 0.0,
+        })
+        .collect();
+    let mut claimed = vec![false; targets.len()];
+    for (score, s, t, name_similarity, value_overlap) in pairs {
+        if score < min_score || mappings[s].target_index.is_some() || claimed[t] {
+            continue;
         }
+        claimed[t] = true;
+        let mapping = &mut mappings[s];
+        mapping.target = Some(targets[t].clone());
+        mapping.target_index = Some(t);
+        mapping.score = score;
+        mapping.name_similarity = name_similarity;
+        mapping.value_overlap = value_overlap;
+    }
+    Ok(ColumnMappingSuggestion {
+        mappings,
+        unmatched_targets: targets
+            .into_iter()
+            .zip(claimed)
+            .filter_map(|(target, claimed)| (!claimed).then_some(target))
+            .collect(),
+    })
+}
 
-        let eof = rows.len() < limit;
-        let end = start + rows.len();
+/// Entry points for `benches/`: the streaming cores without the app handle, path check,
+/// audit log and output hashing. Not part of the app's API.
+#[doc(hidden)]
+pub mod bench {
+    use super::*;
 
-        return Ok(CsvSlice {
-            rows,
-            start,
-            end,
-            eof,
-        });
+    pub fn preview(path: &str, dialect: &CsvDialect) -> Result<CsvPreview, String> {
+        preview_csv(UserPath(path.to_string()), Some(dialect.clone()))
     }
 
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .from_reader(BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?));
+    pub fn count(path: &str, dialect: &CsvDialect) -> Result<usize, String> {
+        count_csv_rows(UserPath(path.to_string()), Some(dialect.clone()))
+    }
 
-    let _ = reader.headers().map_err(|e| e.to_string())?;
+    /// Unindexed window read, i.e. a scan from the first row.
+    pub fn window(
+        path: &str,
+        dialect: &CsvDialect,
+        start: usize,
+        limit: usize,
+    ) -> Result<CsvSlice, String> {
+        read_rows_window(&Mutex::default(), path, Some(dialect.clone()), start, limit)
+    }
 
-    let mut rows = Vec::new();
-    let mut current = 0usize;
-    for rec in reader.records() {
-        let record = rec.map_err(|e| e.to_string())?;
-        if current >= start {
-            rows.push(record.iter().map(|s| s.to_string()).collect());
-            if rows.len() >= limit {
-                break;
-            }
-        }
-        current += 1;
+    pub fn stats(path: &str, dialect: &CsvDialect) -> Result<Vec<ColumnStat>, String> {
+        collect_column_stats(path, dialect, 5000, &FileSettings::default())
     }
 
-    let eof = rows.len() < limit;
-    let end = start + rows.len();
+    pub fn macro_pass(
+        path: &str,
+        target_path: &str,
+        dialect: &CsvDialect,
+        spec: &CsvMacroSpec,
+    ) -> Result<usize, String> {
+        let mut reader = dialect.reader(path)?;
+        let headers = reader
+            .headers()
+            .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+            .map_err(|e| e.to_string())?;
+        let mut run_state = MacroRunState::new(spec, &headers, None)?;
+        let mut writer = dialect.writer(target_path)?;
+        writer.write_record(&headers).map_err(|e| e.to_string())?;
+        let (applied, _) = macro_file_pass(&mut reader, &mut writer, spec, &mut run_state)?;
+        writer.flush().map_err(|e| e.to_string())?;
+        Ok(applied)
+    }
 
-    Ok(CsvSlice {
-        rows,
-        start,
-        end,
-        eof,
-    })
+    pub fn find_replace(
+        path: &str,
+        target_path: &str,
+        dialect: &CsvDialect,
+        spec: &FindReplaceSpec,
+    ) -> Result<usize, String> {
+        let mut reader = dialect.reader(path)?;
+        let mut writer = dialect.writer(target_path)?;
+        writer
+            .write_record(reader.headers().map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        let counts = find_replace_file_pass(&mut reader, &mut writer, &FindReplacer::new(spec)?)?;
+        writer.flush().map_err(|e| e.to_string())?;
+        Ok(counts.cells_modified)
+    }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HttpServerInfo {
+    pub url: String,
+    pub port: u16,
+    /// Bearer token every request must send in `Authorization`.
+    pub token: String,
+}
 
-#[tauri::command]
-fn count_csv_rows(path: String, delimiter: Option<String>) -> Result<usize, String> {
-    let path_buf = PathBuf::from(&path);
+/// Requests served at once; more wait in the server's queue.
+const HTTP_WORKERS: usize = 4;
+/// Largest request body read, in bytes.
+const HTTP_BODY_LIMIT: u64 = 1024 * 1024;
 
-    let mut sample = String::new();
-    let sample_reader = BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?);
-    sample_reader
-        .take(64 * 1024)
-        .read_to_string(&mut sample)
-        .map_err(|e| e.to_string())?;
+struct HttpServer {
+    server: Arc<tiny_http::Server>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+    info: HttpServerInfo,
+}
 
-    let delimiter_byte = delimiter
-        .as_deref()
-        .map(parse_delimiter)
-        .unwrap_or_else(|| detect_delimiter(&sample));
+/// Deserialize one field of a JSON request body; a missing field reads as `null`.
+fn body_field<T: serde::de::DeserializeOwned>(
+    body: &serde_json::Value,
+    name: &str,
+) -> Result<T, String> {
+    serde_json::from_value(body.get(name).cloned().unwrap_or_default())
+        .map_err(|e| format!("{}: {}", name, e))
+}
 
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .from_reader(BufReader::new(File::open(&path_buf).map_err(|e| e.to_string())?));
+fn to_json<T: Serialize>(result: Result<T, String>) -> Result<serde_json::Value, String> {
+    result.and_then(|value| serde_json::to_value(value).map_err(|e| e.to_string()))
+}
 
-    let _ = reader.headers().map_err(|e| e.to_string())?;
+/// Route one API request to the command it mirrors. Bodies carry the command's
+/// arguments in snake_case; `Err` becomes a 400 with the message.
+fn route_http_request(
+    app: &tauri::AppHandle,
+    method: &tiny_http::Method,
+    url: &str,
+    body: &serde_json::Value,
+) -> Option<Result<serde_json::Value, String>> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let result = match (method, segments.as_slice()) {
+        (tiny_http::Method::Post, ["sessions"]) => (|| {
+            to_json(start_csv_session(
+                &app.state(),
+                None,
+                body_field(body, "path")?,
+                body_field(body, "dialect")?,
+                body_field(body, "read_only")?,
+                body_field(body, "disambiguate_headers")?,
+            ))
+        })(),
+        (tiny_http::Method::Get, ["sessions", id, "rows"]) => (|| {
+            let id: u64 = id.parse().map_err(|_| "invalid session id".to_string())?;
+            let mut start = 0usize;
+            let mut limit = 200usize;
+            for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+                match key {
+                    "start" => start = value.parse().map_err(|_| "invalid start".to_string())?,
+                    "limit" => limit = value.parse().map_err(|_| "invalid limit".to_string())?,
+                    _ => {}
+                }
+            }
+            let state = app.state::<AppState>();
+            let (path, dialect) = {
+                let sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+                sessions
+                    .get(&id)
+                    .map(|session| (session.path.clone(), session.dialect.clone()))
+                    .ok_or_else(|| "session not found".to_string())?
+            };
+            to_json(read_rows_window(
+                &state.indexes,
+                &path,
+                Some(dialect),
+                start,
+                limit,
+            ))
+        })(),
+        (tiny_http::Method::Post, ["stats"]) => (|| {
+            to_json(compute_column_stats(
+                app.clone(),
+                body_field(body, "path")?,
+                body_field::<Option<CsvDialect>>(body, "dialect")?.unwrap_or_default(),
+                body_field(body, "max_distinct")?,
+            ))
+        })(),
+        (tiny_http::Method::Post, ["macro"]) => (|| {
+            to_json(apply_macro_to_file(
+                app.clone(),
+                app.state(),
+                body_field(body, "path")?,
+                body_field(body, "target_path")?,
+                body_field::<Option<CsvDialect>>(body, "dialect")?.unwrap_or_default(),
+                body_field(body, "spec")?,
+                body_field(body, "overwrite")?,
+                body_field(body, "dry_run")?,
+            ))
+        })(),
+        (tiny_http::Method::Post, ["export"]) => (|| {
+            to_json(tauri::async_runtime::block_on(export_with_types(
+                app.clone(),
+                body_field(body, "path")?,
+                body_field::<Option<CsvDialect>>(body, "dialect")?.unwrap_or_default(),
+                body_field(body, "target_path")?,
+                body_field(body, "output_dialect")?,
+                body_field::<Option<ExportCoercion>>(body, "coercion")?.unwrap_or_default(),
+                body_field(body, "overwrite")?,
+            )))
+        })(),
+        _ => return None,
+    };
+    Some(result)
+}
 
-    let mut count = 0usize;
-    for rec in reader.records() {
-        rec.map_err(|e| e.to_string())?;
-        count += 1;
+/// The request body as JSON, or `None` when it is over `HTTP_BODY_LIMIT`.
+fn read_http_body(request: &mut tiny_http::Request) -> Option<Result<serde_json::Value, String>> {
+    if request
+        .body_length()
+        .is_some_and(|length| length as u64 > HTTP_BODY_LIMIT)
+    {
+        return None;
     }
+    let mut raw = String::new();
+    match request
+        .as_reader()
+        .take(HTTP_BODY_LIMIT + 1)
+        .read_to_string(&mut raw)
+    {
+        Ok(read) if read as u64 > HTTP_BODY_LIMIT => None,
+        Ok(_) if raw.trim().is_empty() => Some(Ok(serde_json::Value::Null)),
+        Ok(_) => Some(serde_json::from_str(&raw).map_err(|e| e.to_string())),
+        Err(err) => Some(Err(err.to_string())),
+    }
+}
 
-    Ok(count)
+fn serve_http_request(app: &tauri::AppHandle, token: &str, mut request: tiny_http::Request) {
+    use subtle::ConstantTimeEq;
+    let expected = format!("Bearer {}", token);
+    let authorized = request.headers().iter().any(|h| {
+        h.field.equiv("Authorization")
+            && bool::from(h.value.as_str().as_bytes().ct_eq(expected.as_bytes()))
+    });
+    let (status, payload) = if !authorized {
+        (401, serde_json::json!({ "error": "unauthorized" }))
+    } else if let Some(body) = read_http_body(&mut request) {
+        let method = request.method().clone();
+        let url = request.url().to_string();
+        match body.map(|body| route_http_request(app, &method, &url, &body)) {
+            Ok(Some(Ok(value))) => (200, value),
+            Ok(Some(Err(err))) | Err(err) => (400, serde_json::json!({ "error": err })),
+            Ok(None) => (404, serde_json::json!({ "error": "not found" })),
+        }
+    } else {
+        (413, serde_json::json!({ "error": "body too large" }))
+    };
+    tracing::debug!(method = %request.method(), url = %request.url(), status, "http api request");
+    let response = tiny_http::Response::from_string(payload.to_string())
+        .with_status_code(status)
+        .with_header(
+            tiny_http::Header::from_bytes("Content-Type", "application/json")
+                .expect("static header"),
+        );
+    if let Err(err) = request.respond(response) {
+        tracing::warn!(error = %err, "http api response failed");
+    }
 }
 
+/// Serve open, rows, stats, macro and export as a JSON API on 127.0.0.1 while the app
+/// runs. Port 0 picks a free port; without `token` a random one is generated.
 #[tauri::command]
-fn close_csv_session(state: tauri::State<AppState>, session_id: u64) -> Result<bool, String> {
-    let mut sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
-    Ok(sessions.remove(&session_id).is_some())
+fn start_http_server(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    port: Option<u16>,
+    token: Option<String>,
+) -> Result<HttpServerInfo, String> {
+    let mut slot = state.http_server.lock().map_err(|_| "lock poisoned")?;
+    if let Some(running) = slot.as_ref() {
+        return Ok(running.info.clone());
+    }
+    let token = match token.filter(|t| !t.is_empty()) {
+        Some(token) => token,
+        None => hex_digest(&rand::random::<[u8; 16]>()),
+    };
+    let server =
+        tiny_http::Server::http(("127.0.0.1", port.unwrap_or(0))).map_err(|e| e.to_string())?;
+    let port = server
+        .server_addr()
+        .to_ip()
+        .map(|addr| addr.port())
+        .ok_or_else(|| "server is not bound to a TCP port".to_string())?;
+    let server = Arc::new(server);
+    let info = HttpServerInfo {
+        url: format!("http://127.0.0.1:{}", port),
+        port,
+        token: token.clone(),
+    };
+    // Long scans (stats, macros) run off the main thread, at most `HTTP_WORKERS` at once.
+    let workers = (0..HTTP_WORKERS)
+        .map(|_| {
+            let (server, app, token) = (server.clone(), app.clone(), token.clone());
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    serve_http_request(&app, &token, request);
+                }
+            })
+        })
+        .collect();
+    tracing::info!(port, "http api started");
+    *slot = Some(HttpServer {
+        server,
+        workers,
+        info: info.clone(),
+    });
+    Ok(info)
 }
 
-fn normalize_row_ops(ops: &[RowOp]) -> Vec<NormalizedRowOp> {
-    let mut normalized = Vec::new();
-    let mut offset: isize = 0;
-    for op in ops {
-        match op {
-            RowOp::Insert { index, .. } => {
-                let input_index = (*index as isize - offset).max(0);
-                normalized.push(NormalizedRowOp {
-                    input_index,
-                    op: op.clone(),
-                });
-                offset += 1;
-            }
-            RowOp::Delete { index } => {
-                let input_index = (*index as isize - offset).max(0);
-                normalized.push(NormalizedRowOp {
-                    input_index,
-                    op: op.clone(),
-                });
-                offset -= 1;
-            }
-        }
+/// Stop the API server once requests in progress finish; returns whether one was running.
+#[tauri::command]
+fn stop_http_server(state: tauri::State<AppState>) -> Result<bool, String> {
+    let running = state
+        .http_server
+        .lock()
+        .map_err(|_| "lock poisoned")?
+        .take();
+    let Some(running) = running else {
+        return Ok(false);
+    };
+    // Each unblock releases one waiting worker.
+    for _ in &running.workers {
+        running.server.unblock();
     }
-    normalized
+    for worker in running.workers {
+        worker
+            .join()
+            .map_err(|_| "http api thread panicked".to_string())?;
+    }
+    tracing::info!(port = running.info.port, "http api stopped");
+    Ok(true)
 }
 
-fn apply_column_ops_to_headers(headers: &mut Vec<String>, column_ops: &[ColumnOp]) {
-    for op in column_ops {
-        match op {
-            ColumnOp::Insert { index, name } => {
-                let idx = (*index).min(headers.len());
-                headers.insert(idx, name.clone());
-            }
-            ColumnOp::Delete { index } => {
-                if *index < headers.len() {
-                    headers.remove(*index);
-                }
-            }
-            ColumnOp::Rename { index, name } => {
-                if *index < headers.len() {
-                    headers[*index] = name.clone();
-                }
-            }
-        }
+#[derive(Serialize, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    #[serde(alias = "verification_url")]
+    pub verification_uri: String,
+    pub expires_in: u64,
+    #[serde(default = "default_device_interval")]
+    pub interval: u64,
+}
+
+fn default_device_interval() -> u64 {
+    5
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CloudAuthStatus {
+    pub authorized: bool,
+    /// `authorization_pending` or `slow_down` while the user has not finished signing in.
+    pub pending: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CloudExportResult {
+    pub provider: String,
+    pub id: String,
+    pub url: String,
+    pub rows: usize,
+}
+
+/// Device-flow endpoints and scope for `google` (Drive, converted to a Sheet) or
+/// `microsoft` (OneDrive, uploaded as .xlsx).
+#[cfg(feature = "cloud-export")]
+fn cloud_endpoints(provider: &str) -> Result<(&'static str, &'static str, &'static str), String> {
+    match provider {
+        "google" => Ok((
+            "https://oauth2.googleapis.com/device/code",
+            "https://oauth2.googleapis.com/token",
+            "https://www.googleapis.com/auth/drive.file",
+        )),
+        "microsoft" => Ok((
+            "https://login.microsoftonline.com/common/oauth2/v2.0/devicecode",
+            "https://login.microsoftonline.com/common/oauth2/v2.0/token",
+            "Files.ReadWrite offline_access",
+        )),
+        other => Err(format!("unknown cloud provider: {}", other)),
+    }
+}
+
+/// JSON body of a response; OAuth and the storage APIs report errors as JSON on 4xx.
+#[cfg(feature = "cloud-export")]
+fn cloud_json(response: Result<ureq::Response, ureq::Error>) -> Result<serde_json::Value, String> {
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => return Err(err.to_string()),
+    };
+    response.into_json().map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "cloud-export")]
+fn cloud_error(body: &serde_json::Value) -> Option<String> {
+    let error = body.get("error")?;
+    let message = body
+        .get("error_description")
+        .or_else(|| error.get("message"))
+        .unwrap_or(error);
+    Some(
+        message
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| message.to_string()),
+    )
+}
+
+/// Start an OAuth device flow. The user enters `user_code` at `verification_uri` while the
+/// frontend polls `poll_cloud_auth` every `interval` seconds.
+#[cfg(feature = "cloud-export")]
+#[tauri::command]
+fn start_cloud_auth(provider: String, client_id: String) -> Result<DeviceAuthorization, String> {
+    let (device_url, _, scope) = cloud_endpoints(&provider)?;
+    let body = cloud_json(
+        ureq::post(device_url).send_form(&[("client_id", client_id.as_str()), ("scope", scope)]),
+    )?;
+    if let Some(error) = cloud_error(&body) {
+        return Err(error);
+    }
+    serde_json::from_value(body).map_err(|e| e.to_string())
+}
+
+/// Exchange the device code for an access token, kept in memory for `export_to_cloud`.
+/// Google also needs the secret of its "TVs and limited input devices" client.
+#[cfg(feature = "cloud-export")]
+#[tauri::command]
+fn poll_cloud_auth(
+    state: tauri::State<AppState>,
+    provider: String,
+    client_id: String,
+    client_secret: Option<String>,
+    device_code: String,
+) -> Result<CloudAuthStatus, String> {
+    let (_, token_url, _) = cloud_endpoints(&provider)?;
+    let mut form = vec![
+        ("client_id", client_id.as_str()),
+        ("device_code", device_code.as_str()),
+        ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+    ];
+    if let Some(secret) = client_secret.as_deref() {
+        form.push(("client_secret", secret));
+    }
+    let body = cloud_json(ureq::post(token_url).send_form(&form))?;
+    if let Some(token) = body.get("access_token").and_then(|t| t.as_str()) {
+        let mut tokens = state.cloud_tokens.lock().map_err(|_| "lock poisoned")?;
+        tokens.insert(provider, token.to_string());
+        return Ok(CloudAuthStatus {
+            authorized: true,
+            pending: None,
+        });
+    }
+    match body.get("error").and_then(|e| e.as_str()) {
+        Some(pending @ ("authorization_pending" | "slow_down")) => Ok(CloudAuthStatus {
+            authorized: false,
+            pending: Some(pending.to_string()),
+        }),
+        _ => Err(cloud_error(&body).unwrap_or_else(|| "token request failed".to_string())),
     }
 }
 
-fn apply_column_ops_to_row(row: &mut Vec<String>, column_ops: &[ColumnOp]) {
-    for op in column_ops {
-        match op {
-            ColumnOp::Insert { index, .. } => {
-                let idx = (*index).min(row.len());
-                row.insert(idx, String::new());
-            }
-            ColumnOp::Delete { index } => {
-                if *index < row.len() {
-                    row.remove(*index);
-                }
+/// Minimal single-sheet workbook: finite numbers become numeric cells, everything else
+/// (including codes with leading zeros) an inline string.
+#[cfg(feature = "cloud-export")]
+fn build_xlsx(reader: &mut csv::Reader<BufReader<File>>) -> Result<(Vec<u8>, usize), String> {
+    use std::fmt::Write as _;
+    fn push_row(sheet: &mut String, record: &csv::StringRecord) {
+        sheet.push_str("<row>");
+        for value in record.iter() {
+            let trimmed = value.trim();
+            let leading_zero =
+                trimmed.len() > 1 && trimmed.starts_with('0') && !trimmed.starts_with("0.");
+            if !leading_zero && trimmed.parse::<f64>().is_ok_and(|n| n.is_finite()) {
+                let _ = write!(sheet, "<c><v>{}</v></c>", trimmed);
+            } else {
+                let text: String = value
+                    .chars()
+                    .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+                    .collect();
+                let _ = write!(
+                    sheet,
+                    "<c t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+                    html_encode(&text)
+                );
             }
-            ColumnOp::Rename { .. } => {}
         }
+        sheet.push_str("</row>");
     }
-}
 
-#[tauri::command]
-fn save_csv_with_patches(
-    path: String,
-    target_path: String,
-    delimiter: String,
-    patches: Vec<CsvPatch>,
-    row_ops: Vec<RowOp>,
-    column_ops: Vec<ColumnOp>,
-    eol: Option<String>,
-    bom: Option<bool>,
-    encoding: Option<String>,
-    quote: Option<String>,
-    escape: Option<String>,
-) -> Result<String, String> {
-    let delimiter_byte = parse_delimiter(&delimiter);
-    let eol_bytes = normalize_terminator(eol);
-    let quote_byte = quote
-        .as_deref()
-        .and_then(|q| q.as_bytes().first().copied())
-        .unwrap_or(b'"');
-    let escape_byte = escape
-        .as_deref()
-        .and_then(|q| q.as_bytes().first().copied())
-        .unwrap_or(b'"');
+    const XML_HEAD: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>";
+    const MAIN_NS: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+    const REL_NS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+    const DOC_REL: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+    const CT_PREFIX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml";
 
-    let encoding = encoding.unwrap_or_else(|| "UTF-8".to_string());
-    let use_utf16 = encoding.eq_ignore_ascii_case("UTF-16LE");
-    let mut patch_map: HashMap<usize, HashMap<usize, String>> = HashMap::new();
-    for patch in patches {
-        patch_map
-            .entry(patch.row)
-            .or_default()
-            .insert(patch.col, patch.value);
+    let mut sheet = format!("{}<worksheet xmlns=\"{}\"><sheetData>", XML_HEAD, MAIN_NS);
+    push_row(&mut sheet, reader.headers().map_err(|e| e.to_string())?);
+    let mut rows = 0usize;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        push_row(&mut sheet, &record);
+        rows += 1;
     }
+    sheet.push_str("</sheetData></worksheet>");
 
-    let needs_replace = target_path == path;
-    let write_target = if needs_replace {
-        format!("{}.tmp", path)
+    let parts = [
+        (
+            "[Content_Types].xml",
+            format!(
+                "{}<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+                 <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+                 <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+                 <Override PartName=\"/xl/workbook.xml\" ContentType=\"{}.sheet.main+xml\"/>\
+                 <Override PartName=\"/xl/worksheets/sheet1.xml\" ContentType=\"{}.worksheet+xml\"/>\
+                 </Types>",
+                XML_HEAD, CT_PREFIX, CT_PREFIX
+            ),
+        ),
+        (
+            "_rels/.rels",
+            format!(
+                "{}<Relationships xmlns=\"{}\"><Relationship Id=\"rId1\" \
+                 Type=\"{}/officeDocument\" Target=\"xl/workbook.xml\"/></Relationships>",
+                XML_HEAD, REL_NS, DOC_REL
+            ),
+        ),
+        (
+            "xl/workbook.xml",
+            format!(
+                "{}<workbook xmlns=\"{}\" xmlns:r=\"{}\"><sheets>\
+                 <sheet name=\"Sheet1\" sheetId=\"1\" r:id=\"rId1\"/></sheets></workbook>",
+                XML_HEAD, MAIN_NS, DOC_REL
+            ),
+        ),
+        (
+            "xl/_rels/workbook.xml.rels",
+            format!(
+                "{}<Relationships xmlns=\"{}\"><Relationship Id=\"rId1\" \
+                 Type=\"{}/worksheet\" Target=\"worksheets/sheet1.xml\"/></Relationships>",
+                XML_HEAD, REL_NS, DOC_REL
+            ),
+        ),
+        ("xl/worksheets/sheet1.xml", sheet),
+    ];
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    for (name, content) in parts {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(content.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    let workbook = zip.finish().map_err(|e| e.to_string())?.into_inner();
+    Ok((workbook, rows))
+}
+
+/// Google upload: a resumable session (no 5 MB multipart cap) whose metadata asks Drive
+/// to convert the CSV into a Sheet. Returns `(response, url, rows)`.
+#[cfg(feature = "cloud-export")]
+fn upload_google_sheet(
+    bearer: &str,
+    title: &str,
+    reader: &mut csv::Reader<BufReader<File>>,
+) -> Result<(serde_json::Value, String, usize), String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(reader.headers().map_err(|e| e.to_string())?)
+        .map_err(|e| e.to_string())?;
+    let mut rows = 0usize;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+        writer.write_record(&record).map_err(|e| e.to_string())?;
+        rows += 1;
+    }
+    let content = writer.into_inner().map_err(|e| e.to_string())?;
+
+    let session = ureq::post(
+        "https://www.googleapis.com/upload/drive/v3/files?uploadType=resumable&fields=id,webViewLink",
+    )
+    .set("Authorization", bearer)
+    .set("X-Upload-Content-Type", "text/csv")
+    .send_json(serde_json::json!({
+        "name": title,
+        "mimeType": "application/vnd.google-apps.spreadsheet",
+    }));
+    let location = match session {
+        Ok(response) => response
+            .header("Location")
+            .map(str::to_string)
+            .ok_or_else(|| "upload session has no location".to_string())?,
+        Err(err) => {
+            let body = cloud_json(Err(err))?;
+            return Err(cloud_error(&body).unwrap_or_else(|| "upload session failed".to_string()));
+        }
+    };
+    let body = cloud_json(
+        ureq::put(&location)
+            .set("Authorization", bearer)
+            .set("Content-Type", "text/csv")
+            .send_bytes(&content),
+    )?;
+    let url = match body["webViewLink"].as_str() {
+        Some(link) => link.to_string(),
+        None => format!(
+            "https://docs.google.com/spreadsheets/d/{}",
+            body["id"].as_str().unwrap_or_default()
+        ),
+    };
+    Ok((body, url, rows))
+}
+
+/// Upload the file as a new Google Sheet or a new OneDrive workbook (renamed on conflict)
+/// with the token from `poll_cloud_auth`, returning the document URL.
+#[cfg(feature = "cloud-export")]
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path, provider = %provider))]
+fn export_to_cloud(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: UserPath,
+    dialect: CsvDialect,
+    provider: String,
+    title: String,
+) -> Result<CloudExportResult, String> {
+    check_file_size(&app, &path, "export_to_cloud")?;
+    cloud_endpoints(&provider)?;
+    let token = {
+        let tokens = state.cloud_tokens.lock().map_err(|_| "lock poisoned")?;
+        tokens
+            .get(&provider)
+            .cloned()
+            .ok_or_else(|| format!("not signed in to {}", provider))?
+    };
+    let bearer = format!("Bearer {}", token);
+    let mut reader = dialect.reader(&path)?;
+
+    let (body, url, rows) = if provider == "google" {
+        upload_google_sheet(&bearer, &title, &mut reader)?
     } else {
-        target_path.clone()
+        let (workbook, rows) = build_xlsx(&mut reader)?;
+        let name = percent_encoding::utf8_percent_encode(
+            &format!("{}.xlsx", title),
+            percent_encoding::NON_ALPHANUMERIC,
+        )
+        .to_string();
+        let body = cloud_json(
+            ureq::put(&format!(
+                "https://graph.microsoft.com/v1.0/me/drive/root:/{}:/content?@microsoft.graph.conflictBehavior=rename",
+                name
+            ))
+            .set("Authorization", &bearer)
+            .set(
+                "Content-Type",
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            )
+            .send_bytes(&workbook),
+        )?;
+        let url = body["webUrl"].as_str().unwrap_or_default().to_string();
+        (body, url, rows)
     };
+    if let Some(error) = cloud_error(&body) {
+        return Err(error);
+    }
+    let id = body["id"].as_str().unwrap_or_default().to_string();
 
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .from_reader(BufReader::new(File::open(&path).map_err(|e| e.to_string())?));
+    record_operation(
+        &app,
+        "export_to_cloud",
+        Some(&path),
+        Some(&url),
+        Some(rows),
+        serde_json::json!({ "provider": provider, "id": id }),
+    );
+    Ok(CloudExportResult {
+        provider,
+        id,
+        url,
+        rows,
+    })
+}
 
-    let mut headers = reader
-        .headers()
-        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
-        .map_err(|e| e.to_string())?;
+/// Temp dirs of earlier runs untouched for this long are removed at startup.
+const TEMP_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
 
-    apply_column_ops_to_headers(&mut headers, &column_ops);
+/// Scratch space for this process, `<app cache>/tmp/<pid>`: sort spills and captured
+/// streams. Removed on exit.
+static TEMP_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Files held by a live [`TempFile`], with what they are for.
+static TEMP_IN_USE: Mutex<std::collections::BTreeMap<PathBuf, String>> =
+    Mutex::new(std::collections::BTreeMap::new());
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
 
-    let mut writer = csv::WriterBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .terminator(eol_bytes)
-        .quote(quote_byte)
-        .escape(escape_byte)
-        .from_path(&write_target)
-        .map_err(|e| e.to_string())?;
+fn temp_dir() -> PathBuf {
+    TEMP_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| std::env::temp_dir().join(format!("nmeditor-{}", std::process::id())))
+}
 
-    writer.write_record(&headers).map_err(|e| e.to_string())?;
+/// A fresh path in the temp dir, e.g. `stream-<millis>-<n>.csv`. Not tracked: callers that
+/// keep the file past their own scope (stream captures) rely on exit cleanup.
+fn temp_path(purpose: &str, extension: &str) -> Result<PathBuf, String> {
+    temp_path_in(&temp_dir(), purpose, extension)
+}
 
-    let normalized_ops = normalize_row_ops(&row_ops);
-    let mut op_index = 0usize;
-    let mut output_index = 0usize;
-    let mut input_index = 0usize;
+fn temp_path_in(dir: &std::path::Path, purpose: &str, extension: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(format!(
+        "{}-{}-{}.{}",
+        purpose,
+        now_millis(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed),
+        extension
+    )))
+}
 
-    for record in reader.records() {
-        let record = record.map_err(|e| e.to_string())?;
-        let mut skip_current = false;
+/// A temp file deleted when dropped, so failed or cancelled jobs don't leave it behind.
+/// `clean_temp_files` leaves it alone while it lives.
+struct TempFile {
+    path: PathBuf,
+}
 
-        while op_index < normalized_ops.len()
-            && normalized_ops[op_index].input_index == input_index as isize
-        {
-            match &normalized_ops[op_index].op {
-                RowOp::Insert { values, .. } => {
-                    let mut row = values.clone();
-                    apply_column_ops_to_row(&mut row, &column_ops);
-                    if let Some(row_patches) = patch_map.get(&output_index) {
-                        for (col_idx, value) in row_patches {
-                            if *col_idx >= row.len() {
-                                row.resize(col_idx + 1, String::new());
-                            }
-                            row[*col_idx] = value.clone();
-                        }
-                    }
-                    writer.write_record(&row).map_err(|e| e.to_string())?;
-                    output_index += 1;
-                }
-                RowOp::Delete { .. } => {
-                    skip_current = true;
-                }
-            }
-            op_index += 1;
+impl TempFile {
+    fn new_in(dir: &std::path::Path, purpose: &str, extension: &str) -> Result<Self, String> {
+        let path = temp_path_in(dir, purpose, extension)?;
+        if let Ok(mut in_use) = TEMP_IN_USE.lock() {
+            in_use.insert(path.clone(), purpose.to_string());
         }
+        Ok(Self { path })
+    }
 
-        if skip_current {
-            input_index += 1;
-            continue;
-        }
+    /// A temp file in the directory of `target`, so it can be renamed onto it.
+    fn beside(target: &str, purpose: &str, extension: &str) -> Result<Self, String> {
+        let dir = std::path::Path::new(target)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(std::path::Path::new("."));
+        Self::new_in(dir, purpose, extension)
+    }
 
-        let mut row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
-        apply_column_ops_to_row(&mut row, &column_ops);
-        if let Some(row_patches) = patch_map.get(&output_index) {
-            for (col_idx, value) in row_patches {
-                if *col_idx >= row.len() {
-                    row.resize(col_idx + 1, String::new());
-                }
-                row[*col_idx] = value.clone();
-            }
+    fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        if let Ok(mut in_use) = TEMP_IN_USE.lock() {
+            in_use.remove(&self.path);
         }
-        writer.write_record(&row).map_err(|e| e.to_string())?;
-        output_index += 1;
-        input_index += 1;
     }
+}
 
-    while op_index < normalized_ops.len() {
-        if let RowOp::Insert { values, .. } = &normalized_ops[op_index].op {
-            let mut row = values.clone();
-            apply_column_ops_to_row(&mut row, &column_ops);
-            if let Some(row_patches) = patch_map.get(&output_index) {
-                for (col_idx, value) in row_patches {
-                    if *col_idx >= row.len() {
-                        row.resize(col_idx + 1, String::new());
-                    }
-                    row[*col_idx] = value.clone();
-                }
-            }
-            writer.write_record(&row).map_err(|e| e.to_string())?;
-            output_index += 1;
+/// Point the temp dir at the app cache and drop what crashed runs left behind.
+fn init_temp_dir(app: &tauri::AppHandle) -> Result<(), String> {
+    let cache = app.path().app_cache_dir().map_err(|e| e.to_string())?;
+    let root = cache.join("tmp");
+    fs::create_dir_all(&root).map_err(|e| e.to_string())?;
+    for entry in fs::read_dir(&root).map_err(|e| e.to_string())?.flatten() {
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > TEMP_MAX_AGE);
+        if stale {
+            let _ = fs::remove_dir_all(entry.path());
         }
-        op_index += 1;
     }
+    // Stream captures used to live here.
+    let _ = fs::remove_dir_all(cache.join("streams"));
+    let _ = TEMP_DIR.set(root.join(std::process::id().to_string()));
+    Ok(())
+}
 
-    writer.flush().map_err(|e| e.to_string())?;
+#[derive(Serialize, Deserialize, Default)]
+pub struct TempCleanup {
+    pub files: usize,
+    pub bytes: u64,
+}
 
-    if use_utf16 {
-        rewrite_as_utf16le(&write_target, bom.unwrap_or(false))?;
-    } else {
-        rewrite_with_utf8_bom(&write_target, bom.unwrap_or(false))?;
+/// Delete temp files older than `older_than_secs` (all by default), except those a running
+/// job holds or an open session reads.
+#[tauri::command]
+fn clean_temp_files(
+    state: tauri::State<AppState>,
+    older_than_secs: Option<u64>,
+) -> Result<TempCleanup, String> {
+    let mut keep: HashSet<PathBuf> = TEMP_IN_USE
+        .lock()
+        .map_err(|_| "lock poisoned")?
+        .keys()
+        .cloned()
+        .collect();
+    keep.extend(
+        state
+            .sessions
+            .lock()
+            .map_err(|_| "lock poisoned")?
+            .values()
+            .map(|session| PathBuf::from(&session.path)),
+    );
+    let min_age = Duration::from_secs(older_than_secs.unwrap_or(0));
+    let mut cleanup = TempCleanup::default();
+    let Ok(entries) = fs::read_dir(temp_dir()) else {
+        return Ok(cleanup);
+    };
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let old_enough = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age >= min_age);
+        if !metadata.is_file() || !old_enough || keep.contains(&entry.path()) {
+            continue;
+        }
+        if fs::remove_file(entry.path()).is_ok() {
+            cleanup.files += 1;
+            cleanup.bytes += metadata.len();
+        }
     }
+    tracing::info!(
+        files = cleanup.files,
+        bytes = cleanup.bytes,
+        "cleaned temp files"
+    );
+    Ok(cleanup)
+}
 
-    if needs_replace {
-        let final_path = PathBuf::from(&path);
-        if final_path.exists() {
-            fs::remove_file(&final_path).map_err(|e| e.to_string())?;
+/// `-` (stdin), a FIFO, or a Windows named pipe.
+fn is_stream_source(arg: &str) -> bool {
+    if arg == "-" || arg.starts_with(r"\\.\pipe\") {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if let Ok(metadata) = fs::metadata(arg) {
+            return metadata.file_type().is_fifo();
         }
-        fs::rename(&write_target, &final_path).map_err(|e| e.to_string())?;
-        return Ok(path);
     }
-
-    Ok(write_target)
+    false
 }
 
+/// The stream named on the command line (`nmeditor -` or `nmeditor /path/to/fifo`).
+/// Handed out once, since a stream can only be read once.
 #[tauri::command]
-fn apply_macro_to_file(
-    path: String,
-    target_path: String,
-    delimiter: String,
-    spec: CsvMacroSpec,
-    eol: Option<String>,
-    bom: Option<bool>,
-    encoding: Option<String>,
-    quote: Option<String>,
-    escape: Option<String>,
-) -> Result<CsvMacroResult, String> {
-    let delimiter_byte = parse_delimiter(&delimiter);
-    let eol_bytes = normalize_terminator(eol);
-    let quote_byte = quote
-        .as_deref()
-        .and_then(|q| q.as_bytes().first().copied())
-        .unwrap_or(b'"');
-    let escape_byte = escape
-        .as_deref()
-        .and_then(|q| q.as_bytes().first().copied())
-        .unwrap_or(b'"');
-
-    let encoding = encoding.unwrap_or_else(|| "UTF-8".to_string());
-    let use_utf16 = encoding.eq_ignore_ascii_case("UTF-16LE");
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .from_reader(BufReader::new(File::open(&path).map_err(|e| e.to_string())?));
+fn startup_stream_source(state: tauri::State<AppState>) -> Result<Option<String>, String> {
+    let mut source = state.startup_stream.lock().map_err(|_| "lock poisoned")?;
+    Ok(source.take())
+}
 
-    let headers = reader
-        .headers()
-        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+/// Copy stdin (`-`) or a named pipe to the temp dir and open it as a read-only session;
+/// edits are kept with Save As. The copy runs off the main thread, since the producer may
+/// keep the stream open for a long time.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(source = %source, bytes = tracing::field::Empty))]
+async fn open_stream_session(
+    window: tauri::Window,
+    source: String,
+    dialect: Option<CsvDialect>,
+) -> Result<CsvSessionInfo, String> {
+    if source != "-" {
+        authorize_path(&source)?;
+    }
+    let span = tracing::Span::current();
+    tauri::async_runtime::spawn_blocking(move || {
+        let target = temp_path("stream", "csv")?;
+        let mut output = File::create(&target).map_err(|e| e.to_string())?;
+        let bytes = if source == "-" {
+            std::io::copy(&mut std::io::stdin().lock(), &mut output)
+        } else {
+            std::io::copy(
+                &mut File::open(&source).map_err(|e| e.to_string())?,
+                &mut output,
+            )
+        }
         .map_err(|e| e.to_string())?;
+        output.flush().map_err(|e| e.to_string())?;
+        span.record("bytes", bytes);
+        if bytes == 0 {
+            let _ = fs::remove_file(&target);
+            return Err("the stream was empty".to_string());
+        }
 
-    let mut writer = csv::WriterBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .terminator(eol_bytes)
-        .quote(quote_byte)
-        .escape(escape_byte)
-        .from_path(&target_path)
+        let mut info = start_csv_session(
+            &window.state::<AppState>(),
+            Some(window.label().to_string()),
+            UserPath::checked(target.to_string_lossy().to_string())?,
+            dialect,
+            Some(true),
+            None,
+        )?;
+        info.read_only_reason = Some("stream".to_string());
+        Ok(info)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Open another editor window, optionally with `path` loaded. Returns the window label.
+#[tauri::command]
+fn new_window(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: Option<UserPath>,
+) -> Result<String, String> {
+    let label = format!(
+        "editor-{}",
+        state.next_window.fetch_add(1, Ordering::Relaxed)
+    );
+    // The frontend opens `__NMEDITOR_OPEN_PATH__` on mount.
+    let script = format!(
+        "window.__NMEDITOR_OPEN_PATH__ = {};",
+        serde_json::to_string(&path).map_err(|e| e.to_string())?
+    );
+    tauri::WebviewWindowBuilder::new(&app, &label, tauri::WebviewUrl::default())
+        .title("nmeditor")
+        .inner_size(800.0, 600.0)
+        .initialization_script(&script)
+        .build()
         .map_err(|e| e.to_string())?;
+    Ok(label)
+}
 
-    writer.write_record(&headers).map_err(|e| e.to_string())?;
+/// Drop the sessions a closed window owned.
+fn close_window_sessions(app: &tauri::AppHandle, label: &str) {
+    let state = app.state::<AppState>();
+    if let Ok(mut sessions) = state.sessions.lock() {
+        sessions.retain(|_, session| session.owner.as_deref() != Some(label));
+    };
+    if let Ok(mut sessions) = state.replace_sessions.lock() {
+        sessions.retain(|_, session| session.owner.as_deref() != Some(label));
+    };
+}
 
-    let mut applied = 0usize;
-    for record in reader.records() {
-        let record = record.map_err(|e| e.to_string())?;
-        let mut row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
-        let col = spec.column;
-        if col >= row.len() {
-            row.resize(col + 1, String::new());
-        }
-        let current = row[col].clone();
-        let next = match spec.op.as_str() {
-            "replace" => {
-                let find = spec.find.clone().unwrap_or_default();
-                let replace = spec.replace.clone().unwrap_or_default();
-                if find.is_empty() {
-                    current.clone()
-                } else {
-                    current.replace(&find, &replace)
-                }
-            }
-            "uppercase" => current.to_uppercase(),
-            "lowercase" => current.to_lowercase(),
-            "trim" => current.trim().to_string(),
-            "prefix" => format!("{}{}", spec.text.clone().unwrap_or_default(), current),
-            "suffix" => format!("{}{}", current, spec.text.clone().unwrap_or_default()),
-            _ => current.clone(),
-        };
-        if next != current {
-            row[col] = next;
-            applied += 1;
-        }
-        writer.write_record(&row).map_err(|e| e.to_string())?;
+/// Send an app-level event (menu choices) to the focused window only, so each window
+/// handles its own menu actions. With no window focused it goes to the first one by label,
+/// never to all of them.
+fn emit_to_focused<S: Serialize + Clone>(app: &tauri::AppHandle, event: &str, payload: S) {
+    let windows = app.webview_windows();
+    let target = windows
+        .iter()
+        .find(|(_, window)| window.is_focused().unwrap_or(false))
+        .map(|(label, _)| label)
+        .or_else(|| windows.keys().min());
+    if let Some(label) = target {
+        let _ = app.emit_to(label.as_str(), event, payload);
     }
+}
 
-    writer.flush().map_err(|e| e.to_string())?;
-    if use_utf16 {
-        rewrite_as_utf16le(&target_path, bom.unwrap_or(false))?;
-        return Ok(CsvMacroResult {
-            output_path: target_path,
-            applied,
+/// Background jobs currently showing progress on the dock/taskbar icon.
+static TASKBAR_JOBS: AtomicU64 = AtomicU64::new(0);
+
+fn set_taskbar_progress(
+    app: &tauri::AppHandle,
+    status: tauri::window::ProgressBarStatus,
+    progress: Option<u64>,
+) {
+    for window in app.webview_windows().values() {
+        let _ = window.set_progress_bar(tauri::window::ProgressBarState {
+            status: Some(status),
+            progress,
         });
     }
-
-    rewrite_with_utf8_bom(&target_path, bom.unwrap_or(false))?;
-    Ok(CsvMacroResult {
-        output_path: target_path,
-        applied,
-    })
 }
 
-#[tauri::command]
-fn compute_column_stats(
-    path: String,
-    delimiter: String,
-    max_distinct: Option<usize>,
-) -> Result<Vec<ColumnStat>, String> {
-    let delimiter_byte = parse_delimiter(&delimiter);
-    let max_distinct = max_distinct.unwrap_or(5000);
+/// Shows a long job on the dock/taskbar icon while alive: indeterminate until `set` is
+/// called, cleared when the last running job drops its guard.
+struct TaskbarProgress {
+    app: tauri::AppHandle,
+}
 
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .from_reader(BufReader::new(File::open(&path).map_err(|e| e.to_string())?));
+impl TaskbarProgress {
+    fn start(app: &tauri::AppHandle) -> Self {
+        TASKBAR_JOBS.fetch_add(1, Ordering::Relaxed);
+        set_taskbar_progress(app, tauri::window::ProgressBarStatus::Indeterminate, None);
+        Self { app: app.clone() }
+    }
 
-    let headers = reader
-        .headers()
-        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
-        .map_err(|e| e.to_string())?;
+    /// Report `percent` (0-100); with several jobs running the latest report wins.
+    fn set(&self, percent: u64) {
+        set_taskbar_progress(
+            &self.app,
+            tauri::window::ProgressBarStatus::Normal,
+            Some(percent.min(100)),
+        );
+    }
+}
 
-    struct StatInternal {
-        non_empty: usize,
-        number_count: usize,
-        distinct: HashSet<String>,
-        distinct_truncated: bool,
+impl Drop for TaskbarProgress {
+    fn drop(&mut self) {
+        if TASKBAR_JOBS.fetch_sub(1, Ordering::Relaxed) == 1 {
+            set_taskbar_progress(&self.app, tauri::window::ProgressBarStatus::None, None);
+        }
     }
+}
 
-    let mut stats: Vec<StatInternal> = headers
-        .iter()
-        .map(|_| StatInternal {
-            non_empty: 0,
-            number_count: 0,
-            distinct: HashSet::new(),
-            distinct_truncated: false,
-        })
-        .collect();
+/// Show the open file and whether it has unsaved edits in the window title
+/// (`• data.csv — nmeditor`), so the taskbar and window switcher reflect it.
+#[tauri::command]
+fn set_document_edited(
+    window: tauri::Window,
+    path: Option<String>,
+    edited: bool,
+) -> Result<(), String> {
+    let name = path.as_deref().map(path_display_name);
+    let title = match (name, edited) {
+        (Some(name), true) => format!("\u{2022} {} \u{2014} nmeditor", name),
+        (Some(name), false) => format!("{} \u{2014} nmeditor", name),
+        (None, _) => "nmeditor".to_string(),
+    };
+    window.set_title(&title).map_err(|e| e.to_string())
+}
 
-    for record in reader.records() {
-        let record = record.map_err(|e| e.to_string())?;
-        for (idx, value) in record.iter().enumerate() {
-            if idx >= stats.len() {
-                continue;
-            }
-            let value = value.trim();
-            if value.is_empty() {
-                continue;
-            }
-            let stat = &mut stats[idx];
-            stat.non_empty += 1;
-            if value.parse::<f64>().is_ok() {
-                stat.number_count += 1;
-            }
-            if !stat.distinct_truncated {
-                if stat.distinct.len() < max_distinct {
-                    stat.distinct.insert(value.to_string());
-                } else {
-                    stat.distinct_truncated = true;
-                }
-            }
+/// The file an OS hand-off refers to: a `file://` URL (Open With, file associations) or
+/// `nmeditor://open?path=<percent-encoded path>`.
+fn path_from_open_url(url: &url::Url) -> Option<String> {
+    match (url.scheme(), url.host_str()) {
+        ("file", _) => url
+            .to_file_path()
+            .ok()
+            .map(|path| path.to_string_lossy().to_string()),
+        ("nmeditor", Some("open")) => url
+            .query_pairs()
+            .find(|(key, _)| key == "path")
+            .map(|(_, value)| value.into_owned()),
+        _ => None,
+    }
+}
+
+/// Queue files the OS asked us to open and tell the focused window to collect them. Files
+/// handed over by the OS count as chosen by the user; an `nmeditor://open` link can come
+/// from any web page, so its file is only granted once the user confirms it.
+fn queue_open_urls(app: &tauri::AppHandle, urls: impl IntoIterator<Item = url::Url>) {
+    let mut files = Vec::new();
+    for url in urls {
+        match path_from_open_url(&url) {
+            Some(path) if url.scheme() == "file" => files.push(path),
+            Some(path) => confirm_link_open(app, path),
+            None => tracing::warn!(url = %url, "ignored unsupported open url"),
         }
     }
+    queue_opens(app, files);
+}
 
-    let results = headers
-        .into_iter()
-        .enumerate()
-        .map(|(idx, name)| {
-            let stat = &stats[idx];
-            let inferred = if stat.non_empty > 0 && stat.number_count == stat.non_empty {
-                "number"
-            } else {
-                "text"
-            };
-            ColumnStat {
-                name,
-                non_empty: stat.non_empty,
-                distinct: stat.distinct.len(),
-                distinct_truncated: stat.distinct_truncated,
-                inferred: inferred.to_string(),
-            }
-        })
-        .collect();
+fn queue_opens(app: &tauri::AppHandle, paths: Vec<String>) {
+    if paths.is_empty() {
+        return;
+    }
+    paths.iter().for_each(|path| grant_file(path));
+    if let Ok(mut pending) = app.state::<AppState>().pending_opens.lock() {
+        pending.extend(paths);
+    }
+    emit_to_focused(app, "open-requested", ());
+}
 
-    Ok(results)
+/// Ask before opening (and granting) the file a deep link names.
+fn confirm_link_open(app: &tauri::AppHandle, path: String) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+    let handle = app.clone();
+    app.dialog()
+        .message(tr_app(app, "open_link_message", &path))
+        .title(tr_app(app, "open_link_title", ""))
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            tr_app(app, "open_link_confirm", ""),
+            tr_app(app, "open_link_cancel", ""),
+        ))
+        .show(move |confirmed| {
+            if confirmed {
+                queue_opens(&handle, vec![path]);
+            } else {
+                tracing::info!(path = %path, "declined link open");
+            }
+        });
 }
 
+/// Files handed over by the OS or the command line since the last call, oldest first.
+/// Drained, so only one window opens each.
 #[tauri::command]
-fn apply_find_replace_to_file(
-    path: String,
-    target_path: String,
-    delimiter: String,
-    spec: FindReplaceSpec,
-    eol: Option<String>,
-    bom: Option<bool>,
-    encoding: Option<String>,
-    quote: Option<String>,
-    escape: Option<String>,
-) -> Result<FindReplaceResult, String> {
-    let delimiter_byte = parse_delimiter(&delimiter);
-    let eol_bytes = normalize_terminator(eol);
-    let quote_byte = quote
-        .as_deref()
-        .and_then(|q| q.as_bytes().first().copied())
-        .unwrap_or(b'"');
-    let escape_byte = escape
-        .as_deref()
-        .and_then(|q| q.as_bytes().first().copied())
-        .unwrap_or(b'"');
-
-    let encoding = encoding.unwrap_or_else(|| "UTF-8".to_string());
-    let use_utf16 = encoding.eq_ignore_ascii_case("UTF-16LE");
-
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .from_reader(BufReader::new(File::open(&path).map_err(|e| e.to_string())?));
-
-    let headers = reader
-        .headers()
-        .map(|h| h.iter().map(|s| s.to_string()).collect::<Vec<_>>())
-        .map_err(|e| e.to_string())?;
+fn take_pending_opens(state: tauri::State<AppState>) -> Result<Vec<String>, String> {
+    let mut pending = state.pending_opens.lock().map_err(|_| "lock poisoned")?;
+    Ok(std::mem::take(&mut *pending))
+}
 
-    let mut writer = csv::WriterBuilder::new()
-        .has_headers(true)
-        .delimiter(delimiter_byte)
-        .terminator(eol_bytes)
-        .quote(quote_byte)
-        .escape(escape_byte)
-        .from_path(&target_path)
-        .map_err(|e| e.to_string())?;
+/// One matching cell; `row` counts data rows from 0, as in the grid.
+#[derive(Serialize, Deserialize)]
+pub struct SearchHit {
+    pub row: usize,
+    pub col: usize,
+    pub value: String,
+}
 
-    writer.write_record(&headers).map_err(|e| e.to_string())?;
+#[derive(Serialize, Deserialize)]
+pub struct DocumentSearchHits {
+    pub session_id: u64,
+    pub path: String,
+    /// All matching cells, even past the returned `hits`.
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+}
 
-    let mut applied = 0usize;
-    let regex = if spec.regex {
-        let flags = if spec.match_case { "g" } else { "gi" };
-        let pattern = format!("(?{}){}", flags, spec.find);
-        regex::Regex::new(&pattern).map_err(|e| e.to_string())?
-    } else {
-        regex::Regex::new("$")
-            .map_err(|e| e.to_string())?
+/// Find `find` in every open session, tracked edits included, and return the hits grouped by
+/// document in opening order. Documents without a match are left out; `limit` caps the hits
+/// returned per document (default 100).
+#[tauri::command]
+fn search_all_documents(
+    state: tauri::State<AppState>,
+    find: String,
+    column: Option<usize>,
+    regex: bool,
+    match_case: bool,
+    limit: Option<usize>,
+) -> Result<Vec<DocumentSearchHits>, String> {
+    if find.is_empty() {
+        return Err("search text is empty".to_string());
+    }
+    let spec = FindReplaceSpec {
+        find,
+        replace: String::new(),
+        column,
+        regex,
+        match_case,
+    };
+    let matcher = FindReplacer::new(&spec)?;
+    let limit = limit.unwrap_or(100);
+    let mut documents: Vec<_> = {
+        let sessions = state.sessions.lock().map_err(|_| "lock poisoned")?;
+        sessions
+            .iter()
+            .map(|(id, session)| {
+                let edits: HashMap<(usize, usize), String> = session
+                    .changes
+                    .iter()
+                    .map(|(key, change)| (*key, change.value.clone()))
+                    .collect();
+                (*id, session.path.clone(), session.dialect.clone(), edits)
+            })
+            .collect()
     };
+    documents.sort_by_key(|(id, ..)| *id);
 
-    for record in reader.records() {
-        let record = record.map_err(|e| e.to_string())?;
-        let mut row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
-        let columns: Vec<usize> = match spec.column {
-            Some(col) => vec![col],
-            None => (0..row.len()).collect(),
+    let mut results = Vec::new();
+    for (session_id, path, dialect, edits) in documents {
+        let mut reader = dialect.reader(&path)?;
+        let mut found = DocumentSearchHits {
+            session_id,
+            path,
+            total: 0,
+            hits: Vec::new(),
         };
-        for col in columns {
-            if col >= row.len() {
-                continue;
-            }
-            let current = row[col].clone();
-            let next = if spec.regex {
-                regex.replace_all(&current, spec.replace.as_str()).to_string()
-            } else if spec.match_case {
-                current.replace(&spec.find, &spec.replace)
-            } else {
-                let escaped = regex::escape(&spec.find);
-                let ci = regex::RegexBuilder::new(&escaped)
-                    .case_insensitive(true)
-                    .build()
-                    .map_err(|e| e.to_string())?;
-                ci.replace_all(&current, spec.replace.as_str()).to_string()
-            };
-            if next != current {
-                row[col] = next;
-                applied += 1;
+        let mut record = csv::StringRecord::new();
+        let mut row = 0usize;
+        while reader.read_record(&mut record).map_err(|e| e.to_string())? {
+            for col in matcher.columns(record.len()) {
+                let value = match edits.get(&(row, col)) {
+                    Some(edited) => edited.as_str(),
+                    None => record.get(col).unwrap_or(""),
+                };
+                if !matcher.matches(value) {
+                    continue;
+                }
+                found.total += 1;
+                if found.hits.len() < limit {
+                    found.hits.push(SearchHit {
+                        row,
+                        col,
+                        value: value.to_string(),
+                    });
+                }
             }
+            row += 1;
+        }
+        if found.total > 0 {
+            results.push(found);
         }
-        writer.write_record(&row).map_err(|e| e.to_string())?;
-    }
-
-    writer.flush().map_err(|e| e.to_string())?;
-    if use_utf16 {
-        rewrite_as_utf16le(&target_path, bom.unwrap_or(false))?;
-        return Ok(FindReplaceResult {
-            output_path: target_path,
-            applied,
-        });
     }
-
-    rewrite_with_utf8_bom(&target_path, bom.unwrap_or(false))?;
-    Ok(FindReplaceResult {
-        output_path: target_path,
-        applied,
-    })
+    Ok(results)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -1418,15 +16390,70 @@ pub fn run() {
             indexes: Arc::new(Mutex::new(HashMap::new())),
             index_jobs: Arc::new(Mutex::new(HashMap::new())),
             next_index_job: AtomicU64::new(1),
+            export_cancels: Mutex::new(HashMap::new()),
+            replace_sessions: Mutex::new(HashMap::new()),
+            column_indexes: Mutex::new(HashMap::new()),
+            next_window: AtomicU64::new(1),
+            http_server: Mutex::new(None),
+            startup_stream: Mutex::new(std::env::args().skip(1).find(|arg| is_stream_source(arg))),
+            // `nmeditor data.csv` and "Open With" on Windows/Linux pass the file as an argument.
+            pending_opens: Mutex::new(
+                std::env::args()
+                    .skip(1)
+                    .filter(|arg| !is_stream_source(arg) && PathBuf::from(arg).is_file())
+                    .collect(),
+            ),
+            watch_folders: Mutex::new(HashMap::new()),
+            job_history: Mutex::new(Vec::new()),
+            menu_locale: Mutex::new("en".to_string()),
+            #[cfg(feature = "cloud-export")]
+            cloud_tokens: Mutex::new(HashMap::new()),
         })
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_fs::init())
         .setup(|app| {
+            if let Err(err) = init_logging(app.handle()) {
+                eprintln!("failed to initialize logging: {}", err);
+            }
+            if let Err(err) = init_temp_dir(app.handle()) {
+                tracing::warn!(error = %err, "failed to set up the temp dir");
+            }
+            init_path_grants(app.handle());
+            {
+                // Files named on the command line count as chosen by the user.
+                let state = app.state::<AppState>();
+                if let Ok(pending) = state.pending_opens.lock() {
+                    pending.iter().for_each(|path| grant_file(path));
+                }
+                let stream = state.startup_stream.lock().map(|source| source.clone());
+                if let Ok(Some(source)) = stream {
+                    grant_file(&source);
+                }
+            }
+            if let Err(err) = start_saved_watch_folders(app.handle()) {
+                tracing::warn!(error = %err, "failed to start watch folders");
+            }
+            start_job_scheduler(app.handle());
             #[cfg(desktop)]
             {
-                let menu = build_app_menu(app, "en")?;
-                app.set_menu(menu)?;
+                use tauri_plugin_deep_link::DeepLinkExt;
+                let handle = app.handle().clone();
+                app.deep_link()
+                    .on_open_url(move |event| queue_open_urls(&handle, event.urls()));
+                // Linux and Windows pass the link that launched us as an argument.
+                if let Ok(Some(urls)) = app.deep_link().get_current() {
+                    queue_open_urls(app.handle(), urls);
+                }
+            }
+            // Restores each window's size, position and maximized state by label.
+            #[cfg(desktop)]
+            app.handle()
+                .plugin(tauri_plugin_window_state::Builder::default().build())?;
+            if let Err(err) = refresh_app_menu(app.handle()) {
+                tracing::warn!(error = %err, "failed to build menu");
             }
             Ok(())
         })
@@ -1444,13 +16471,147 @@ pub fn run() {
             apply_macro_to_file,
             compute_column_stats,
             apply_find_replace_to_file,
-            set_menu_locale
+            set_menu_locale,
+            find_similar_rows,
+            detect_outliers,
+            profile_column_patterns,
+            generate_csv,
+            list_script_presets,
+            save_script_preset,
+            delete_script_preset,
+            list_plugins,
+            save_project,
+            load_project,
+            run_project,
+            get_operation_history,
+            export_diagnostics,
+            check_path_writable,
+            set_session_read_only,
+            get_file_size_limits,
+            set_file_size_limits,
+            analyze_column_relationships,
+            suggest_key_columns,
+            hash_file,
+            generate_data_dictionary,
+            load_table_schema,
+            validate_against_schema,
+            write_table_schema,
+            add_annotation,
+            list_annotations,
+            remove_annotation,
+            export_with_annotations,
+            track_cell_changes,
+            get_changed_cells,
+            clear_cell_changes,
+            merge_csv_three_way,
+            get_versioning_settings,
+            set_versioning_settings,
+            list_versions,
+            restore_version,
+            export_pdf,
+            prepare_chart_series,
+            validate_coordinates,
+            detect_column_languages,
+            explode_column,
+            implode_column,
+            melt_csv,
+            cast_csv,
+            apply_macro_to_rows,
+            apply_find_replace_to_rows,
+            export_with_types,
+            normalize_headers,
+            #[cfg(feature = "cloud-export")]
+            start_cloud_auth,
+            #[cfg(feature = "cloud-export")]
+            poll_cloud_auth,
+            #[cfg(feature = "cloud-export")]
+            export_to_cloud,
+            start_http_server,
+            stop_http_server,
+            startup_stream_source,
+            open_stream_session,
+            list_watch_folders,
+            save_watch_folder,
+            delete_watch_folder,
+            list_scheduled_jobs,
+            save_scheduled_job,
+            delete_scheduled_job,
+            run_scheduled_job_now,
+            list_job_history,
+            list_recent_files,
+            add_recent_file,
+            clear_recent_files,
+            get_keybindings,
+            set_keybindings,
+            list_locales,
+            show_cell_context_menu,
+            new_window,
+            set_document_edited,
+            take_pending_opens,
+            list_granted_directories,
+            #[cfg(desktop)]
+            grant_directory,
+            revoke_directory,
+            clean_temp_files,
+            list_interrupted_exports,
+            resume_export,
+            cancel_export,
+            discard_export,
+            get_spill_settings,
+            set_spill_settings,
+            get_file_settings,
+            set_column_type,
+            search_all_documents,
+            reconcile_files,
+            suggest_column_mapping,
+            detect_mojibake,
+            find_invisible_characters,
+            find_quote_anomalies,
+            get_raw_record,
+            measure_column_widths,
+            get_export_profile,
+            list_export_profiles,
+            save_export_profile,
+            delete_export_profile,
+            count_formula_cells,
+            start_replace_session,
+            confirm_replace,
+            skip_match,
+            finish_replace_session,
+            cancel_replace_session,
+            list_search_history,
+            add_search_history,
+            clear_search_history,
+            list_saved_searches,
+            save_search,
+            delete_saved_search,
+            goto_row,
+            read_csv_rows_before,
+            preview_csv_sampled,
+            preview_with_encodings,
+            analyze_file_for_import,
+            filter_by_column_values,
+            list_distinct_values,
+            clear_column_indexes,
+            top_k_rows,
+            sample_csv,
+            split_csv
         ])
         .on_menu_event(|app, event| {
             if event.id() == "app_quit" {
                 app.exit(0);
                 return;
             }
+            if let Some(cell_event) = parse_cell_menu_id(event.id().as_ref()) {
+                emit_to_focused(app, "cell-context-menu", cell_event);
+                return;
+            }
+            if event.id() == "recent_clear" {
+                if let Err(err) = store_recent_files(app, &Vec::new()) {
+                    tracing::warn!(error = %err, "failed to clear recent files");
+                }
+                return;
+            }
             let guard = MENU_EVENT_GUARD.get_or_init(|| Mutex::new(HashMap::new()));
             let now = Instant::now();
             let should_emit = {
@@ -1469,9 +16630,983 @@ pub fn run() {
                 }
             };
             if should_emit {
-                let _ = app.emit("menu-event", event.id().as_ref());
+                emit_to_focused(app, "menu-event", event.id().as_ref());
+            }
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                close_window_sessions(window.app_handle(), window.label());
+            }
+        })
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| match event {
+            tauri::RunEvent::Exit => {
+                let _ = fs::remove_dir_all(temp_dir());
+                let _ = fs::remove_dir_all(spill_dir(&load_spill_settings(app)));
+            }
+            // macOS delivers Finder "Open With" and file associations as an event.
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            tauri::RunEvent::Opened { urls } => queue_open_urls(app, urls),
+            _ => {}
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    fn to_csv(headers: &[String], rows: &[Vec<String>]) -> Vec<u8> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(headers).unwrap();
+        for row in rows {
+            writer.write_record(row).unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    /// Run the save core over an in-memory file; returns its counts and every output
+    /// record, headers first.
+    fn save(
+        input: &[u8],
+        patch_map: &HashMap<usize, HashMap<usize, String>>,
+        row_ops: &[RowOp],
+        column_ops: &[ColumnOp],
+    ) -> (RowCounts, Vec<Vec<String>>) {
+        let mut reader = csv::Reader::from_reader(input);
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let counts =
+            write_patched_csv(&mut reader, &mut writer, patch_map, row_ops, column_ops).unwrap();
+        let output = writer.into_inner().unwrap();
+        let records = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(output.as_slice())
+            .records()
+            .map(|record| record.unwrap().iter().map(|s| s.to_string()).collect())
+            .collect();
+        (counts, records)
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn cron_expressions_match_local_minutes() {
+        let at =
+            |text: &str| chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        let weekdays = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert!(weekdays.matches(&at("2024-03-04 09:45")));
+        assert!(!weekdays.matches(&at("2024-03-04 09:50")));
+        assert!(!weekdays.matches(&at("2024-03-03 09:45")));
+
+        // Day and weekday both restricted: either one matches. 7 is Sunday.
+        let either = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert!(either.matches(&at("2024-03-01 00:00")));
+        assert!(either.matches(&at("2024-03-03 00:00")));
+        assert!(!either.matches(&at("2024-03-04 00:00")));
+
+        // A stepped `*` still counts as unrestricted: odd days that are Mondays.
+        let both = CronSchedule::parse("0 0 */2 * 1").unwrap();
+        assert!(both.matches(&at("2024-03-11 00:00")));
+        assert!(!both.matches(&at("2024-03-04 00:00")));
+        assert!(!both.matches(&at("2024-03-01 00:00")));
+
+        let daily = CronSchedule::parse("@daily").unwrap();
+        assert!(daily.matches(&at("2024-03-04 00:00")));
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* * *").is_err());
+    }
+
+    #[test]
+    fn row_ops_address_the_table_as_edited_so_far() {
+        let headers = strings(&["id"]);
+        let rows: Vec<Vec<String>> = (0..5).map(|i| vec![i.to_string()]).collect();
+        let row_ops = vec![
+            RowOp::Insert {
+                index: 4,
+                values: strings(&["a"]),
+            },
+            RowOp::Delete { index: 0 },
+            RowOp::Insert {
+                index: 1,
+                values: strings(&["b"]),
+            },
+            RowOp::Delete { index: 4 },
+        ];
+        let patches = HashMap::from([(1, HashMap::from([(0, "B".to_string())]))]);
+        let (counts, records) = save(&to_csv(&headers, &rows), &patches, &row_ops, &[]);
+        assert_eq!((counts.rows_read, counts.rows_written), (5, 5));
+        // "a" is inserted and deleted again, so it counts as neither.
+        assert_eq!((counts.rows_inserted, counts.rows_deleted), (1, 1));
+        assert_eq!(counts.cells_modified, 1);
+        let expected: Vec<Vec<String>> = ["id", "1", "B", "2", "3", "4"]
+            .iter()
+            .map(|v| strings(&[v]))
+            .collect();
+        assert_eq!(records, expected);
+    }
+
+    #[test]
+    fn mojibake_is_repaired_and_clean_text_left_alone() {
+        assert_eq!(repair_mojibake("CafÃ©").as_deref(), Some("Café"));
+        assert_eq!(repair_mojibake("itâ€™s").as_deref(), Some("it’s"));
+        assert_eq!(repair_mojibake("CafÃƒÂ©").as_deref(), Some("Café"));
+        assert_eq!(repair_mojibake("Café"), None);
+        assert_eq!(repair_mojibake("plain"), None);
+    }
+
+    #[test]
+    fn typed_sort_keys_order_values_and_group_unparsed() {
+        let sorted = |cells: &[&str], key: ProjectSortKey| {
+            let mut rows: Vec<Vec<String>> = cells.iter().map(|v| strings(&[v])).collect();
+            let keys = [key];
+            rows.sort_by(|a, b| compare_rows_by_keys(a, b, &keys, &[None]));
+            rows.concat()
+        };
+        let key = |kind: &str, descending: bool, unparsed: Option<&str>| ProjectSortKey {
+            column: 0,
+            descending,
+            value_type: Some(kind.to_string()),
+            unparsed: unparsed.map(str::to_string),
+            nulls: None,
+        };
+        assert_eq!(
+            sorted(&["10", "n/a", "9", "-1.5"], key("number", false, None)),
+            strings(&["-1.5", "9", "10", "n/a"])
+        );
+        assert_eq!(
+            sorted(&["10", "n/a", "9"], key("number", true, Some("first"))),
+            strings(&["n/a", "10", "9"])
+        );
+        assert_eq!(
+            sorted(
+                &["2024-04-01", "5 Mar 2024", "01.02.2024"],
+                key("date", false, None)
+            ),
+            strings(&["01.02.2024", "5 Mar 2024", "2024-04-01"])
+        );
+    }
+
+    #[test]
+    fn multi_key_sort_places_nulls_per_key_and_keeps_ties_in_file_order() {
+        let rows: Vec<Vec<String>> = [
+            ["b", "", "1"],
+            ["a", "2", "2"],
+            ["b", "x", "3"],
+            ["a", "", "4"],
+            ["b", "10", "5"],
+            ["a", "2", "6"],
+        ]
+        .iter()
+        .map(|row| strings(row))
+        .collect();
+        let key = |column: usize, descending: bool, nulls: Option<&str>| ProjectSortKey {
+            column,
+            descending,
+            value_type: None,
+            unparsed: None,
+            nulls: nulls.map(str::to_string),
+        };
+        let order = |keys: &[ProjectSortKey]| {
+            let mut sorted = rows.clone();
+            sorted.sort_by(|a, b| compare_rows_by_keys(a, b, keys, &[]));
+            sorted.iter().map(|row| row[2].clone()).collect::<Vec<_>>()
+        };
+        // Untyped: empty, then numbers, then text; equal rows keep their order.
+        assert_eq!(
+            order(&[key(0, false, None), key(1, false, None)]),
+            strings(&["4", "2", "6", "1", "5", "3"])
+        );
+        assert_eq!(
+            order(&[key(0, true, None), key(1, true, Some("first"))]),
+            strings(&["1", "3", "5", "4", "2", "6"])
+        );
+        assert_eq!(
+            order(&[key(1, false, Some("last"))]),
+            strings(&["2", "6", "5", "3", "1", "4"])
+        );
+    }
+
+    #[test]
+    fn export_profile_selects_columns_and_writes_null_token() {
+        let path = std::env::temp_dir().join(format!("profile-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        fs::write(&path, "name,age,city\nann,,x\nbob,7,\n").unwrap();
+        let document = CsvDialect {
+            eol: Some("LF".to_string()),
+            ..Default::default()
+        };
+        let profile = CsvDialect {
+            quoting: Some("always".to_string()),
+            null_token: Some("NULL".to_string()),
+            columns: Some(strings(&["age", "name"])),
+            ..Default::default()
+        };
+        document
+            .with_profile(&profile)
+            .finish_output(&path)
+            .unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            written,
+            "\"age\",\"name\"\n\"NULL\",\"ann\"\n\"7\",\"bob\"\n"
+        );
+    }
+
+    #[test]
+    fn raw_records_keep_their_line_endings_with_and_without_an_index() {
+        let path = std::env::temp_dir().join(format!("raw-record-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let dialect = CsvDialect::default();
+        // (file, data start, byte the indexer records for row 2, rows as written)
+        let cases = [
+            (
+                "a,b\r\n1,x\r\n2,\"y\r\nz\"\r\n3,w\r\n",
+                4,
+                19,
+                ["1,x\r\n", "2,\"y\r\nz\"\r\n", "3,w\r\n"],
+            ),
+            (
+                "a,b\n1,x\n2,\"y\nz\"\n3,w\n",
+                4,
+                16,
+                ["1,x\n", "2,\"y\nz\"\n", "3,w\n"],
+            ),
+            (
+                "a,b\n1,x\r\n2,\"y\nz\"\n3,w",
+                4,
+                17,
+                ["1,x\r\n", "2,\"y\nz\"\n", "3,w"],
+            ),
+        ];
+        for (content, data_start, third, expected) in cases {
+            fs::write(&path, content).unwrap();
+            let index = CsvIndex {
+                dialect: dialect.clone(),
+                stride: 2,
+                data_start,
+                offsets: vec![CsvIndexEntry {
+                    row: 2,
+                    byte: third,
+                    line: 0,
+                    hash: 0,
+                }],
+                file_len: content.len() as u64,
+                modified: 0,
+                total_rows: 3,
+            };
+            for (row, text) in expected.iter().enumerate() {
+                for index in [None, Some(&index)] {
+                    let raw = read_raw_record(&path, row, &dialect, index).unwrap();
+                    assert_eq!(raw.text, *text, "{:?} row {}", content, row);
+                    assert_eq!(raw.byte as usize, content.find(text).unwrap());
+                    assert_eq!(raw.length as usize, text.len());
+                }
+            }
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn row_positions_follow_each_line_ending() {
+        let path = std::env::temp_dir().join(format!("positions-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        fs::write(&path, "a,b\n1,x\r\n2,\"y\nz\"\r\n3,w\r\n4,v\n").unwrap();
+        let dialect = CsvDialect {
+            delimiter: Some(",".to_string()),
+            ..Default::default()
+        };
+        // (byte, line) of each data row.
+        let expected = [(4, 2), (9, 3), (18, 5), (23, 6)];
+        let window = |indexes: &Mutex<HashMap<String, CsvIndex>>, start, limit| {
+            read_rows_window(indexes, &path, Some(dialect.clone()), start, limit)
+                .unwrap()
+                .positions
+                .iter()
+                .map(|position| (position.byte, position.line))
+                .collect::<Vec<_>>()
+        };
+        let indexes = Mutex::new(HashMap::new());
+        let scanned = window(&indexes, 1, 3);
+        let (file_len, modified) = file_signature(&PathBuf::from(&path)).unwrap();
+        // Where the indexer puts rows 0 and 2: row 2 follows a CRLF, so it sits on the LF.
+        let entry = |row, byte, line| CsvIndexEntry {
+            row,
+            byte,
+            line,
+            hash: 0,
+        };
+        let index = CsvIndex {
+            dialect: dialect.clone(),
+            stride: 2,
+            data_start: 4,
+            offsets: vec![entry(0, 4, 2), entry(2, 17, 4)],
+            file_len,
+            modified,
+            total_rows: 4,
+        };
+        indexes
+            .lock()
+            .unwrap()
+            .insert(index_key(&path, &dialect), index);
+        let from_top = window(&indexes, 1, 3);
+        let from_entry = window(&indexes, 2, 2);
+        let _ = fs::remove_file(&path);
+        assert_eq!(scanned, expected[1..]);
+        assert_eq!(from_top, expected[1..]);
+        assert_eq!(from_entry, expected[2..]);
+    }
+
+    #[test]
+    fn indexed_windows_read_with_the_dialect_quote() {
+        let path = std::env::temp_dir().join(format!("quote-index-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        fs::write(&path, "a,b\n'x,1',2\n'y\nz',3\n").unwrap();
+        let dialect = CsvDialect {
+            delimiter: Some(",".to_string()),
+            quote: Some("'".to_string()),
+            ..Default::default()
+        };
+        let indexes = Mutex::new(HashMap::new());
+        let scanned = read_rows_window(&indexes, &path, Some(dialect.clone()), 0, 2).unwrap();
+        let (file_len, modified) = file_signature(&PathBuf::from(&path)).unwrap();
+        let index = CsvIndex {
+            dialect: dialect.clone(),
+            stride: 1,
+            data_start: 4,
+            offsets: vec![CsvIndexEntry {
+                row: 1,
+                byte: 12,
+                line: 3,
+                hash: 0,
+            }],
+            file_len,
+            modified,
+            total_rows: 2,
+        };
+        indexes
+            .lock()
+            .unwrap()
+            .insert(index_key(&path, &dialect), index);
+        let indexed = read_rows_window(&indexes, &path, Some(dialect.clone()), 1, 1).unwrap();
+        let unquoted = index_key(&path, &CsvDialect::default().with_delimiter(b','));
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            scanned.rows,
+            [strings(&["x,1", "2"]), strings(&["y\nz", "3"])]
+        );
+        assert_eq!(indexed.rows, scanned.rows[1..]);
+        assert_ne!(unquoted, index_key(&path, &dialect));
+    }
+
+    #[test]
+    fn rows_at_are_read_from_the_nearest_entry() {
+        let path = std::env::temp_dir().join(format!("rows-at-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        fs::write(&path, "a\n0\n1\n\"2\n2\"\n3\n4\n").unwrap();
+        let dialect = CsvDialect {
+            delimiter: Some(",".to_string()),
+            ..Default::default()
+        };
+        let wanted = [1, 2, 4, 9].into_iter().collect();
+        let indexes = Mutex::new(HashMap::new());
+        let scanned = read_rows_at(&indexes, &path, &dialect, &wanted).unwrap();
+        let (file_len, modified) = file_signature(&PathBuf::from(&path)).unwrap();
+        let entry = |row, byte| CsvIndexEntry {
+            row,
+            byte,
+            line: 0,
+            hash: 0,
+        };
+        let index = CsvIndex {
+            dialect: dialect.clone(),
+            stride: 2,
+            data_start: 2,
+            offsets: vec![entry(0, 2), entry(2, 6), entry(4, 14)],
+            file_len,
+            modified,
+            total_rows: 5,
+        };
+        indexes
+            .lock()
+            .unwrap()
+            .insert(index_key(&path, &dialect), index);
+        let indexed = read_rows_at(&indexes, &path, &dialect, &wanted).unwrap();
+        let _ = fs::remove_file(&path);
+        let expected = HashMap::from([
+            (1, strings(&["1"])),
+            (2, strings(&["2\n2"])),
+            (4, strings(&["4"])),
+        ]);
+        assert_eq!(scanned, expected);
+        assert_eq!(indexed, expected);
+    }
+
+    #[test]
+    fn verification_catches_truncation_and_changed_cells() {
+        let path = std::env::temp_dir().join(format!("verify-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        fs::write(&path, "a,b\r\n1,'=x\r\n2,\r\n").unwrap();
+        let dialect = CsvDialect {
+            formula_guard: Some("quote".to_string()),
+            ..Default::default()
+        };
+        let headers = strings(&["a", "b"]);
+        let cells = [(0, 1, "=x".to_string()), (1, 1, String::new())];
+        let written = verify_output(&path, &dialect, &headers, 2, &cells);
+        let truncated = verify_output(&path, &dialect, &headers, 3, &cells);
+        let changed = verify_output(&path, &dialect, &headers, 2, &[(1, 0, "3".to_string())]);
+        let _ = fs::remove_file(&path);
+        let missing = verify_output(&path, &dialect, &headers, 2, &cells);
+        assert!(written.ok);
+        assert_eq!(written.cells_checked, 2);
+        assert_eq!((truncated.rows, truncated.ok), (2, false));
+        assert_eq!(
+            changed.mismatches,
+            [(1, 0, "3".to_string(), "2".to_string())]
+        );
+        assert!(!missing.ok && missing.error.is_some());
+    }
+
+    #[test]
+    fn samples_decode_per_candidate_encoding() {
+        let latin = b"caf\xe9,\x80 price\n";
+        let decode = |encoding| decode_sample(latin, encoding).unwrap();
+        assert_eq!(decode("windows-1252"), "café,€ price\n");
+        assert_eq!(decode("ISO-8859-1"), "café,\u{80} price\n");
+        assert_eq!(decode("UTF-8"), "caf\u{FFFD},\u{FFFD} price\n");
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("é,b".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_sample(&utf16, "UTF-16LE").unwrap(), "é,b");
+        assert!(decode_sample(latin, "EBCDIC").is_err());
+    }
+
+    #[test]
+    fn transcoding_carries_code_units_split_across_reads() {
+        /// Hands out three bytes per read, so units and surrogate pairs get split.
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.0.len().min(buf.len()).min(3);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        let text = "a,\u{1F600}\nb,é\n";
+        let utf16: Vec<u8> = [0xFE, 0xFF]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        let mut out = Vec::new();
+        transcode_to_utf8(&mut Trickle(&utf16), "UTF-16BE", &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), text);
+        let mut out = Vec::new();
+        transcode_to_utf8(&mut Trickle(b"\xEF\xBB\xBFx,\xC3\xA9"), "UTF-8", &mut out).unwrap();
+        assert_eq!(out, "x,é".as_bytes());
+    }
+
+    #[test]
+    fn import_proposal_detects_encoding_delimiter_header_and_types() {
+        let sample = b"id;name;when\r\n1;caf\xe9;2024-01-02\r\n2;b\xe4r;2024-01-03\r\n";
+        let proposal = propose_import(sample, false, CsvDialect::default()).unwrap();
+        assert_eq!(proposal.encoding.value, "windows-1252");
+        assert_eq!(proposal.encoding_alternatives[0].value, "UTF-8");
+        assert_eq!(proposal.delimiter.value, ";");
+        assert!(proposal.header.has_header);
+        let kinds: Vec<_> = proposal
+            .columns
+            .iter()
+            .map(|c| c.kind.value.as_str())
+            .collect();
+        assert_eq!(kinds, ["integer", "string", "date"]);
+        assert_eq!(proposal.rows[0], strings(&["1", "café", "2024-01-02"]));
+
+        let headerless = propose_import(b"1,2\n3,4\n", false, CsvDialect::default()).unwrap();
+        assert!(!headerless.header.has_header);
+        assert_eq!(headerless.header.names, strings(&["column_1", "column_2"]));
+        assert_eq!(headerless.dialect.has_header, Some(false));
+        assert_eq!(headerless.sampled_rows, 2);
+    }
+
+    #[test]
+    fn column_index_spills_and_reads_rows_back() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("column-index-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let csv = "k,v\r\na,1\r\nb,2\r\n\"a\",\"3\r\n3\"\r\nc,4\r\na,5\r\n";
+        fs::write(&path, csv).unwrap();
+        let dialect = CsvDialect::default();
+        let too_many = ColumnIndex::build(&path, &dialect, 0, &dir, 0, 2);
+        assert!(too_many.is_err_and(|err| err.contains("too_many_values")));
+        let index = ColumnIndex::build(&path, &dialect, 0, &dir, 0, 3).unwrap();
+        let (rows, starts): (Vec<u64>, Vec<u64>) =
+            index.rows_with("a").unwrap().into_iter().unzip();
+        let records = read_records_at(&path, &dialect, &starts);
+        drop(index);
+        let _ = fs::remove_file(&path);
+        assert_eq!(rows, [0, 2, 4]);
+        let expected = [["a", "1"], ["a", "3\r\n3"], ["a", "5"]].map(|row| strings(&row));
+        assert_eq!(records.unwrap(), expected);
+    }
+
+    #[test]
+    fn top_k_keeps_the_extremes_and_skips_unparsed_cells() {
+        let csv = "id,amount,day\n1,5,2024-03-01\n2,x,\n3,12,2024-01-15\n4,12,2023-12-31\n5,-1,\n";
+        let reader = || csv::Reader::from_reader(csv.as_bytes());
+        let largest = top_k_from(&mut reader(), 1, 2, true, "number".to_string()).unwrap();
+        assert_eq!(largest.row_indexes, [2, 3]);
+        assert_eq!((largest.rows_read, largest.skipped), (5, 1));
+        let smallest = top_k_from(&mut reader(), 1, 2, false, "number".to_string()).unwrap();
+        assert_eq!(smallest.row_indexes, [4, 0]);
+        let earliest = top_k_from(&mut reader(), 2, 5, false, "date".to_string()).unwrap();
+        assert_eq!(earliest.row_indexes, [3, 2, 0]);
+        assert_eq!(earliest.rows[0], strings(&["4", "12", "2023-12-31"]));
+    }
+
+    #[test]
+    fn window_columns_total_average_and_rank_per_partition() {
+        let window = |name: &str, function: &str, window: Option<usize>| WindowColumn {
+            name: name.to_string(),
+            function: function.to_string(),
+            column: 1,
+            partition: Some(0),
+            window,
+            descending: true,
+            decimals: None,
+        };
+        let specs = [
+            window("total", "running_total", None),
+            window("avg", "moving_average", Some(2)),
+            window("rank", "rank", None),
+        ];
+        let unsorted = WindowRunner::new(
+            &specs,
+            &mut strings(&["region", "amount"]),
+            vec![None, None],
+            &[],
+            std::env::temp_dir(),
+        );
+        assert!(unsorted.is_err());
+
+        // Held in memory, then spilled every two rows: the output is the same.
+        for chunk_rows in [SORT_CHUNK_ROWS, 2] {
+            let mut headers = strings(&["region", "amount"]);
+            let mut runner = WindowRunner::new(
+                &specs,
+                &mut headers,
+                vec![None, None],
+                &[ProjectSortKey::default()],
+                std::env::temp_dir(),
+            )
+            .unwrap();
+            runner.chunk_rows = chunk_rows;
+            assert_eq!(headers[2..], strings(&["total", "avg", "rank"]));
+            let mut out = Vec::new();
+            let mut emit = |row: &[String]| {
+                out.push(row.join(","));
+                Ok(())
+            };
+            for row in [["a", "4"], ["a", "x"], ["a", "8"], ["a", "8"], ["b", "1"]] {
+                runner.push(strings(&row), &mut emit).unwrap();
             }
+            runner.flush(&mut emit).unwrap();
+            let regrouped = runner.push(strings(&["a", "1"]), &mut emit);
+            assert_eq!(
+                out,
+                [
+                    "a,4,4,4,3",
+                    "a,x,4,4,",
+                    "a,8,12,8,1",
+                    "a,8,20,8,1",
+                    "b,1,1,1,1"
+                ]
+            );
+            assert!(regrouped.is_err());
+        }
+    }
+
+    #[test]
+    fn reservoir_keeps_a_fixed_size_sample_of_distinct_rows() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut reservoir = Reservoir::default();
+        for row in 0..1000 {
+            reservoir.offer(10, (row, Vec::new()), &mut rng);
+        }
+        let rows: HashSet<usize> = reservoir.rows.iter().map(|(row, _)| *row).collect();
+        assert_eq!((reservoir.seen, rows.len()), (1000, 10));
+        assert!(rows.iter().any(|row| *row >= 10));
+    }
+
+    #[test]
+    fn split_counts_add_up_with_the_largest_remainders_rounded_up() {
+        assert_eq!(split_counts(10, &[70.0, 30.0]), vec![7, 3]);
+        assert_eq!(split_counts(7, &[80.0, 10.0, 10.0]), vec![5, 1, 1]);
+        assert_eq!(split_counts(2, &[34.0, 33.0, 33.0]), vec![1, 1, 0]);
+        assert_eq!(split_counts(0, &[50.0, 50.0]), vec![0, 0]);
+    }
+
+    #[test]
+    fn display_width_counts_wide_graphemes_twice() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("日本"), 4);
+        assert_eq!(display_width("e\u{301}"), 1);
+        assert_eq!(display_width("👨\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(display_width("ｶﾀｶﾅ"), 4);
+        assert_eq!(display_width("a\tb"), 2);
+        assert_eq!(display_width("ab\n日本語"), 6);
+    }
+
+    #[test]
+    fn plugin_names_stay_inside_the_plugins_dir() {
+        assert!(check_plugin_name("upper_case").is_ok());
+        assert!(check_plugin_name("v1.2").is_ok());
+        for name in ["", "../evil", "..", "a/b", "a\\b", "C:evil", "/etc/evil"] {
+            assert!(check_plugin_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn digit_grouping_is_only_stripped_between_groups_of_three() {
+        let point = |value| strip_digit_grouping(value, ".", ",");
+        let comma = |value| strip_digit_grouping(value, ",", ".");
+        assert_eq!(point("1.234.567,89").as_deref(), Some("1234567,89"));
+        assert_eq!(point("-12.345").as_deref(), Some("-12345"));
+        assert_eq!(point("3,14").as_deref(), Some("3,14"));
+        assert_eq!(point("3.14"), None);
+        assert_eq!(point("1234.567"), None);
+        assert_eq!(comma("1,234.5").as_deref(), Some("1234.5"));
+        assert_eq!(comma("1,5"), None);
+        assert_eq!(comma("1.5,0"), None);
+    }
+
+    #[test]
+    fn bad_date_formats_are_rejected_instead_of_panicking() {
+        assert!(check_strftime("%Y-%m-%d %H:%M").is_ok());
+        assert!(check_strftime("%Q").is_err());
+        let naive = chrono::NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let formatted = format_or_none(naive.format("%d.%m.%Y"));
+        assert_eq!(formatted.as_deref(), Some("01.03.2024"));
+        assert_eq!(format_or_none(naive.format("%z")), None);
+        assert_eq!(format_or_none(naive.format("%Q")), None);
+    }
+
+    #[test]
+    fn csvw_date_formats_parse_dates_instead_of_matching_strings() {
+        let schema = csvw_table_schema(&serde_json::json!({
+            "columns": [
+                {"name": "day", "datatype": {"base": "date", "format": "dd.MM.yyyy"}},
+                {
+                    "name": "at",
+                    "datatype": {"base": "dateTime", "format": "yyyy-MM-dd'T'HH:mm:ssXXX"}
+                },
+                {"name": "code", "datatype": {"base": "string", "format": "[A-Z]{3}"}}
+            ]
+        }));
+        let [day, at, code] = &schema.fields[..] else {
+            panic!("expected three fields");
+        };
+        assert_eq!(day.format.as_deref(), Some("%d.%m.%Y"));
+        assert_eq!(day.constraints.pattern, None);
+        assert_eq!(schema_type_error(day, "01.03.2024"), None);
+        assert!(schema_type_error(day, "2024-03-01").is_some());
+        assert_eq!(schema_type_error(at, "2024-03-01T12:00:00+01:00"), None);
+        assert!(schema_type_error(at, "01.03.2024 12:00").is_some());
+        assert_eq!(code.format, None);
+        assert_eq!(code.constraints.pattern.as_deref(), Some("[A-Z]{3}"));
+        assert_eq!(uts35_to_strftime("h:mm a").as_deref(), Some("%I:%M %p"));
+        assert_eq!(uts35_to_strftime("yyyy-QQ"), None);
+    }
+
+    #[test]
+    fn accelerators_must_end_in_a_menu_key() {
+        assert_eq!(normalize_accelerator("Ctrl+Alt+s").unwrap(), "Ctrl+Alt+S");
+        assert_eq!(normalize_accelerator("Ctrl+KeyA").unwrap(), "Ctrl+A");
+        assert_eq!(
+            normalize_accelerator("Alt+Esc").unwrap(),
+            normalize_accelerator("alt+escape").unwrap()
+        );
+        assert_eq!(normalize_accelerator("Ctrl+/").unwrap(), "Ctrl+SLASH");
+        assert_eq!(normalize_accelerator("Shift+F12").unwrap(), "Shift+F12");
+        assert_eq!(normalize_accelerator("Ctrl+Num5").unwrap(), "Ctrl+NUMPAD5");
+        for invalid in ["Ctrl+Foo", "Ctrl+F25", "Ctrl+F01", "Ctrl+", "Hyper+A"] {
+            assert!(normalize_accelerator(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn tracked_changes_map_back_to_file_cells() {
+        let segments = plan_row_segments(&[
+            RowOp::Insert {
+                index: 1,
+                values: Vec::new(),
+            },
+            RowOp::Delete { index: 3 },
+        ]);
+        let rows: Vec<_> = (0..5).map(|row| source_row(&segments, row)).collect();
+        assert_eq!(rows, [Some(0), None, Some(1), Some(3), Some(4)]);
+
+        let mut columns: Vec<Option<usize>> = (0..3).map(Some).collect();
+        apply_column_ops_to_row(
+            &mut columns,
+            &[
+                ColumnOp::Insert {
+                    index: 0,
+                    name: "new".to_string(),
+                },
+                ColumnOp::Delete { index: 2 },
+            ],
+        );
+        assert_eq!(columns, [None, Some(0), Some(2)]);
+
+        let before = strings(&["a", "b"]);
+        let mut patches = Vec::new();
+        let after = strings(&["A", "x", "b"]);
+        row_patches(7, &before, &after, Some(1), &mut patches);
+        let changes = engine_changes(&before, &patches, Some(1), "macro");
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].row, changes[0].col), (7, 0));
+        assert_eq!(changes[0].original, "a");
+        assert_eq!(changes[0].value, "A");
+    }
+
+    #[test]
+    fn replace_session_confirms_each_match() {
+        let path = std::env::temp_dir().join(format!("replace-{}.csv", std::process::id()));
+        let input = "name,note\nfoo foo,x\nbar,Foo\n";
+        fs::write(&path, input).unwrap();
+        let spec = FindReplaceSpec {
+            find: "foo".to_string(),
+            replace: "baz".to_string(),
+            column: None,
+            regex: false,
+            match_case: false,
+        };
+        let mut session =
+            ReplaceSession::open(path.to_str().unwrap(), CsvDialect::default(), &spec, None)
+                .unwrap();
+        let mut seen = Vec::new();
+        for confirm in [true, false, true] {
+            let current = session.current.clone().unwrap();
+            seen.push((current.row, current.col, current.start, current.end));
+            session.answer(confirm).unwrap();
+        }
+        assert!(session.current.is_none());
+        assert!(session.answer(true).is_err());
+        assert_eq!(seen, [(0, 0, 0, 3), (0, 0, 4, 7), (1, 1, 0, 3)]);
+        assert_eq!((session.confirmed_count, session.skipped), (2, 1));
+
+        let (counts, records) = save(input.as_bytes(), &session.confirmed, &[], &[]);
+        assert_eq!(counts.cells_modified, 2);
+        assert_eq!(
+            records,
+            [
+                strings(&["name", "note"]),
+                strings(&["baz foo", "x"]),
+                strings(&["bar", "baz"]),
+            ]
+        );
+        fs::remove_file(&path).unwrap();
+
+        let swap = FindReplacer::new(&FindReplaceSpec {
+            find: r"(\d+)-(\d+)".to_string(),
+            replace: "$2-$1".to_string(),
+            column: None,
+            regex: true,
+            match_case: true,
+        })
+        .unwrap();
+        assert_eq!(swap.cell_matches("1-2 x 3-3"), [(0..3, "2-1".to_string())]);
+    }
+
+    #[test]
+    fn script_time_limit_restarts_for_every_row() {
+        let script = MacroScript::compile("row_index + 1", 20).unwrap();
+        for row in 0..3i64 {
+            std::thread::sleep(Duration::from_millis(30));
+            let mut scope = rhai::Scope::new();
+            scope.push("row_index", row);
+            assert_eq!(script.run(&mut scope).unwrap().as_int(), Ok(row + 1));
+        }
+    }
+
+    #[test]
+    fn audit_params_hide_salts_and_scripts() {
+        let mut params = serde_json::json!({
+            "op": "hash",
+            "salt": "pepper",
+            "script": null,
+            "steps": [{ "op": "script", "script": "value.upper()" }],
+        });
+        redact_params(&mut params);
+        assert_eq!(
+            params,
+            serde_json::json!({
+                "op": "hash",
+                "salt": "[redacted]",
+                "script": null,
+                "steps": [{ "op": "script", "script": "[redacted]" }],
+            })
+        );
+    }
+
+    #[test]
+    fn merge_keeps_columns_only_one_side_has() {
+        // Columns: id, name, region (added upstream), note (added locally).
+        let columns: Vec<String> = ["id", "name", "region", "note"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let row = |cells: [Option<&str>; 4]| -> Vec<Option<String>> {
+            cells.iter().map(|c| c.map(|s| s.to_string())).collect()
+        };
+        let base = row([Some("1"), Some("Ann"), None, None]);
+        let mine = row([Some("1"), Some("Ann"), None, Some("vip")]);
+        let theirs = row([Some("1"), Some("Anne"), Some("EU"), None]);
+        for prefer_theirs in [false, true] {
+            let mut conflicts = Vec::new();
+            let merged = merge_keyed_row(
+                "1",
+                &columns,
+                [Some(&base), Some(&mine), Some(&theirs)],
+                prefer_theirs,
+                &mut conflicts,
+            );
+            assert_eq!(merged.unwrap(), vec!["1", "Anne", "EU", "vip"]);
+            assert!(conflicts.is_empty());
+        }
+
+        // Deleted upstream, untouched locally apart from a local-only column.
+        let mut conflicts = Vec::new();
+        let merged = merge_keyed_row(
+            "1",
+            &columns,
+            [Some(&base), Some(&mine), None],
+            false,
+            &mut conflicts,
+        );
+        assert_eq!((merged, conflicts.len()), (None, 0));
+
+        // An empty value is an edit, not a missing column.
+        let blanked = row([Some("1"), Some(""), None, None]);
+        let merged = merge_keyed_row(
+            "1",
+            &columns,
+            [Some(&base), Some(&blanked), None],
+            false,
+            &mut conflicts,
+        );
+        assert_eq!(merged.unwrap(), vec!["1", "", "", ""]);
+        assert_eq!(conflicts[0].kind, "delete_edit");
+    }
+
+    fn cell() -> impl Strategy<Value = String> {
+        "[ab,\"\n ]{0,3}"
+    }
+
+    /// Headers, rows, then raw row ops, column ops and patches. Raw indexes are reduced
+    /// against the table as edited so far, the way the grid only offers existing positions.
+    type Case = (
+        Vec<String>,
+        Vec<Vec<String>>,
+        Vec<(bool, usize, Vec<String>)>,
+        Vec<(u8, usize, String)>,
+        Vec<(usize, usize, String)>,
+    );
+
+    fn case() -> impl Strategy<Value = Case> {
+        (1usize..4, 0usize..8).prop_flat_map(|(width, rows)| {
+            (
+                vec("[a-c]{1,2}", width),
+                vec(vec(cell(), width), rows),
+                vec((any::<bool>(), any::<usize>(), vec(cell(), width)), 0..8),
+                vec((0u8..3, any::<usize>(), "[x-z]{1,2}"), 0..4),
+                vec((any::<usize>(), any::<usize>(), cell()), 0..6),
+            )
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+    }
+
+    proptest! {
+        #[test]
+        fn save_matches_in_memory_model(
+            (headers, rows, raw_row_ops, raw_column_ops, raw_patches) in case()
+        ) {
+            // Row ops replay in order over rows in the file's column layout.
+            let mut model = rows.clone();
+            let mut row_ops = Vec::new();
+            for (insert, raw, values) in raw_row_ops {
+                if insert || model.is_empty() {
+                    let index = raw % (model.len() + 1);
+                    model.insert(index, values.clone());
+                    row_ops.push(RowOp::Insert { index, values });
+                } else {
+                    let index = raw % model.len();
+                    model.remove(index);
+                    row_ops.push(RowOp::Delete { index });
+                }
+            }
+
+            // Column ops then apply to every output row, inserted rows included.
+            let mut model_headers = headers.clone();
+            let mut column_ops = Vec::new();
+            for (kind, raw, name) in raw_column_ops {
+                let width = model_headers.len();
+                let op = match kind {
+                    0 => {
+                        let index = raw % (width + 1);
+                        model_headers.insert(index, name.clone());
+                        for row in &mut model {
+                            row.insert(index, String::new());
+                        }
+                        ColumnOp::Insert { index, name }
+                    }
+                    1 if width > 1 => {
+                        let index = raw % width;
+                        model_headers.remove(index);
+                        for row in &mut model {
+                            row.remove(index);
+                        }
+                        ColumnOp::Delete { index }
+                    }
+                    _ => {
+                        let index = raw % width;
+                        model_headers[index] = name.clone();
+                        ColumnOp::Rename { index, name }
+                    }
+                };
+                column_ops.push(op);
+            }
+
+            // Patches address the final layout; a later patch of the same cell wins.
+            let mut patch_map: HashMap<usize, HashMap<usize, String>> = HashMap::new();
+            if !model.is_empty() {
+                for (raw_row, raw_col, value) in raw_patches {
+                    let (row, col) = (raw_row % model.len(), raw_col % model_headers.len());
+                    model[row][col] = value.clone();
+                    patch_map.entry(row).or_default().insert(col, value);
+                }
+            }
+
+            let (counts, records) =
+                save(&to_csv(&headers, &rows), &patch_map, &row_ops, &column_ops);
+            prop_assert_eq!(counts.rows_written, model.len());
+            prop_assert_eq!(
+                counts.rows_written + counts.rows_deleted,
+                rows.len() + counts.rows_inserted
+            );
+            let mut expected = vec![model_headers];
+            expected.extend(model);
+            prop_assert_eq!(records, expected);
+        }
+    }
 }
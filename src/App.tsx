@@ -1,6 +1,6 @@
 import { useCallback, useEffect, useMemo, useRef, useState } from "react";
-import { listen } from "@tauri-apps/api/event";
 import { invoke } from "@tauri-apps/api/core";
+import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow";
 import {
   confirm,
   message,
@@ -29,6 +29,12 @@ type PatchOp = {
   next: string | null;
 };
 
+type CellMenuEvent = {
+  action: string;
+  row: number;
+  col: number;
+};
+
 type TabFileData = {
   fileType: "csv" | "text";
   csvData?: {
@@ -151,7 +157,10 @@ function App() {
     setRows,
     setHeaders,
     setEof,
+    sessionId,
+    readOnly,
     openCsvPath,
+    openStreamSource,
     closeSession,
     applyDelimiter,
   } = useCsvSession({ setError });
@@ -377,7 +386,9 @@ function App() {
     setColumnOps,
     resetOps,
     insertRow,
+    insertRowAt,
     deleteRow,
+    deleteRowAt,
     insertColumn,
     deleteColumn,
     renameColumn,
@@ -652,6 +663,26 @@ function App() {
     }
   }, [activeTabId, tabs, patches, textDirty, rowOps, columnOps]);
 
+  // Mirror the active tab in the window title (`• name — nmeditor` while unsaved).
+  const activeTab = tabs.find((tab) => tab.id === activeTabId);
+  useEffect(() => {
+    void invoke("set_document_edited", {
+      path: activeTab?.path ?? null,
+      edited: activeTab?.isDirty ?? false,
+    });
+  }, [activeTab?.path, activeTab?.isDirty]);
+
+  // Keep the backend's tracked changes in step with the pending edits, for the change review
+  // and the search across open documents.
+  useEffect(() => {
+    if (!sessionId) return;
+    const patchList = Object.entries(patches).map(([key, value]) => {
+      const [row, col] = key.split(":").map(Number);
+      return { row, col, value };
+    });
+    void invoke("track_cell_changes", { sessionId, patches: patchList, rowOps, columnOps });
+  }, [sessionId, patches, rowOps, columnOps]);
+
   const indexPollRef = useRef<number | null>(null);
 
   const rowVirtualizer = useVirtualizer({
@@ -879,7 +910,7 @@ function App() {
           total_rows?: number;
         }>("start_prepare_csv_index", {
           path,
-          delimiter: delimiterValue ?? delimiter,
+          dialect: { delimiter: delimiterValue ?? delimiter },
         });
         if (response.done) {
           setIndexRunning(false);
@@ -979,7 +1010,7 @@ function App() {
           eof: boolean;
         }>("read_csv_rows_window", {
           path,
-          delimiter: resolvedDelimiter,
+          dialect: { delimiter: resolvedDelimiter },
           start,
           limit: windowSize,
         });
@@ -1040,7 +1071,7 @@ function App() {
           eof: boolean;
         }>("read_csv_rows_window", {
           path,
-          delimiter: resolvedDelimiter,
+          dialect: { delimiter: resolvedDelimiter },
           start,
           limit: windowSize,
         });
@@ -1530,7 +1561,8 @@ function App() {
     void handleOpen();
   }, []);
 
-  const handleOpen = async () => {
+  // `recentPath` comes from the File > Recent Files menu and skips the dialog.
+  const handleOpen = async (recentPath?: string) => {
     if (activeTabId) {
       const currentTab = tabs.find((tab) => tab.id === activeTabId);
       if (currentTab) {
@@ -1541,13 +1573,15 @@ function App() {
     if (openDialogActiveRef.current) return;
     openDialogActiveRef.current = true;
     try {
-      const selected = await openDialog({
-        multiple: false,
-        filters: [
-          { name: "CSV", extensions: ["csv"] },
-          { name: "Text", extensions: ["txt", "log", "md"] },
-        ],
-      });
+      const selected =
+        recentPath ??
+        (await openDialog({
+          multiple: false,
+          filters: [
+            { name: "CSV", extensions: ["csv"] },
+            { name: "Text", extensions: ["txt", "log", "md"] },
+          ],
+        }));
 
       if (!selected || Array.isArray(selected)) return;
 
@@ -1567,6 +1601,7 @@ function App() {
         await closeSession();
         const info = await openCsvPath(path);
         if (!info) return;
+        void invoke("add_recent_file", { path });
         setFileMode("csv");
         resetSessionState();
         await requestWindow(0, path, info.delimiter);
@@ -1585,6 +1620,7 @@ function App() {
       await closeSession();
       const opened = await openText(path);
       if (!opened) return;
+      void invoke("add_recent_file", { path });
       setFileMode("text");
       const tabId = createTab(path, "text");
 
@@ -1597,12 +1633,96 @@ function App() {
     }
   };
 
+  // Windows opened by `new_window` may be handed a file to load.
+  useEffect(() => {
+    const path = (window as { __NMEDITOR_OPEN_PATH__?: string | null }).__NMEDITOR_OPEN_PATH__;
+    if (path) {
+      void handleOpen(path);
+    }
+  }, []);
+
+  // Files from "Open With", file associations, `nmeditor://open?path=...` links and the command line.
+  const handleOpenRef = useRef(handleOpen);
+  handleOpenRef.current = handleOpen;
+  useEffect(() => {
+    const openPending = async () => {
+      const paths = await invoke<string[]>("take_pending_opens");
+      for (const path of paths) {
+        await handleOpenRef.current(path);
+      }
+    };
+    void openPending();
+    // Sent to one window only; a global `listen` would also fire in every other window.
+    const unlisten = getCurrentWebviewWindow().listen("open-requested", () => {
+      void openPending();
+    });
+    return () => {
+      void unlisten.then((dispose) => dispose());
+    };
+  }, []);
+
+  // `nmeditor -` or `nmeditor <fifo>`: open the piped data once on startup.
+  useEffect(() => {
+    void (async () => {
+      const source = await invoke<string | null>("startup_stream_source");
+      if (!source) return;
+      const info = await openStreamSource(source);
+      if (!info) return;
+      setFileMode("csv");
+      resetSessionState();
+      await requestWindow(0, info.path, info.delimiter);
+      void refreshTotalRows(info.path, info.delimiter);
+      const tabId = createTab(info.path, "csv");
+      setTimeout(() => {
+        saveCurrentTabData(tabId, "csv");
+      }, 100);
+    })();
+  }, []);
+
 
   useEffect(() => {
     window.localStorage.setItem("nmeditor.locale", locale);
     void invoke("set_menu_locale", { locale });
   }, [locale]);
 
+  // Choices from the native cell context menu built by `show_cell_context_menu`.
+  const cellMenuHandlerRef = useRef<(event: CellMenuEvent) => void>(() => {});
+  cellMenuHandlerRef.current = ({ action, row, col }) => {
+    switch (action) {
+      case "copy_cell":
+        void navigator.clipboard.writeText(getCellValue(row, col));
+        break;
+      case "copy_row": {
+        const values = Array.from({ length: getColumnCount() }, (_, idx) => getCellValue(row, idx));
+        void navigator.clipboard.writeText(values.join(getCurrentDelimiter()));
+        break;
+      }
+      case "insert_above":
+        insertRowAt(row);
+        break;
+      case "insert_below":
+        insertRowAt(row + 1);
+        break;
+      case "delete_row":
+        deleteRowAt(row);
+        break;
+      case "filter_value":
+        setFilterRules((current) => [...current, { column: String(col), value: getCellValue(row, col) }]);
+        break;
+      default:
+        break;
+    }
+  };
+
+  useEffect(() => {
+    const unlisten = getCurrentWebviewWindow().listen<CellMenuEvent>("cell-context-menu", (event) => {
+      cellMenuHandlerRef.current(event.payload);
+    });
+    return () => {
+      void unlisten.then((dispose) => dispose());
+    };
+  }, []);
+
   const showAboutDialog = async () => {
     await message(t("nmeditor — Streamed CSV editor.", "nmeditor — 流式CSV编辑器。"), {
       title: t("About", "关于"),
@@ -1697,12 +1817,15 @@ function App() {
     if (menuListenerRef.current) return;
     let disposed = false;
     const setup = async () => {
-      const unlisten = await listen<string>("menu-event", (event) => {
+      const unlisten = await getCurrentWebviewWindow().listen<string>("menu-event", (event) => {
         const handlers = menuHandlersRef.current;
         switch (event.payload) {
           case "file_open":
             void handlers.handleOpen();
             break;
+          case "file_new_window":
+            void invoke("new_window");
+            break;
           case "file_save_as":
             void handlers.saveAsCurrent();
             break;
@@ -1779,6 +1902,9 @@ function App() {
             void handlers.showAboutDialog();
             break;
           default:
+            if (event.payload.startsWith("recent_file:")) {
+              void handlers.handleOpen(event.payload.slice("recent_file:".length));
+            }
             break;
         }
       });
@@ -2000,6 +2126,9 @@ function App() {
             setIsDraggingSelection={setIsDraggingSelection}
             isDraggingSelection={isDraggingSelection}
             selectionMode={selectionMode}
+            onCellContextMenu={(row, col, x, y) => {
+              void invoke("show_cell_context_menu", { row, col, x, y, readOnly });
+            }}
             t={t}
           />
         </section>
@@ -32,6 +32,7 @@ export default function GridView({
   setIsDraggingSelection,
   isDraggingSelection,
   selectionMode,
+  onCellContextMenu,
   t,
 }: GridViewProps) {
   const headerRef = useRef<HTMLDivElement | null>(null);
@@ -203,6 +204,11 @@ export default function GridView({
                         if (!isDraggingSelection || selectionMode !== "cell") return;
                         updateSelection({ row: rowIndex, col: colIdx }, "cell", { shift: true, ctrl: false });
                       }}
+                      onContextMenu={(event) => {
+                        if (!rowLoaded || !onCellContextMenu) return;
+                        event.preventDefault();
+                        onCellContextMenu(rowIndex, colIdx, event.clientX, event.clientY);
+                      }}
                     >
                       <span
                         className="resize-handle"
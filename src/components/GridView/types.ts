@@ -41,5 +41,6 @@ export type GridViewProps = {
   setIsDraggingSelection: (value: boolean) => void;
   isDraggingSelection: boolean;
   selectionMode: SelectionMode;
+  onCellContextMenu?: (row: number, col: number, clientX: number, clientY: number) => void;
   t: (en: string, zh: string) => string;
 };
@@ -13,6 +13,7 @@ export type CsvSessionInfo = {
   headers: string[];
   delimiter: string;
   path: string;
+  read_only: boolean;
 };
 
 type CsvSlice = {
@@ -20,6 +21,8 @@ type CsvSlice = {
   start: number;
   end: number;
   eof: boolean;
+  /** Byte offset and 1-based line where each row starts in the file. */
+  positions?: { byte: number; line: number }[];
 };
 
 type UseCsvSessionParams = {
@@ -32,6 +35,7 @@ export default function useCsvSession({ setError }: UseCsvSessionParams) {
   const [loading, setLoading] = useState(false);
   const [loadingRows, setLoadingRows] = useState(false);
   const [sessionId, setSessionId] = useState<number | null>(null);
+  const [readOnly, setReadOnly] = useState(false);
   const [rows, setRows] = useState<string[][]>([]);
   const [headers, setHeaders] = useState<string[]>([]);
   const [eof, setEof] = useState(false);
@@ -54,9 +58,10 @@ export default function useCsvSession({ setError }: UseCsvSessionParams) {
 
       const info = await invoke<CsvSessionInfo>("open_csv_session", {
         path,
-        delimiter: delimiterOverride ?? delimiter,
+        dialect: { delimiter: delimiterOverride ?? delimiter },
       });
       setSessionId(info.session_id);
+      setReadOnly(info.read_only);
       setHeaders(info.headers);
       setRows([]);
       setEof(false);
@@ -78,6 +83,38 @@ export default function useCsvSession({ setError }: UseCsvSessionParams) {
     }
   };
 
+  // stdin (`-`) or a named pipe; the backend captures it to a read-only temp file.
+  const openStreamSource = async (source: string): Promise<CsvSessionInfo | null> => {
+    setError(null);
+    setLoading(true);
+    try {
+      if (sessionId) {
+        await invoke("close_csv_session", { sessionId });
+      }
+
+      const info = await invoke<CsvSessionInfo>("open_stream_session", { source });
+      setSessionId(info.session_id);
+      setReadOnly(info.read_only);
+      setHeaders(info.headers);
+      setRows([]);
+      setEof(false);
+      setActivePath(info.path);
+      setDelimiterApplied(info.delimiter);
+      setPreview({
+        headers: info.headers,
+        rows: [],
+        delimiter: info.delimiter,
+        path: info.path,
+      });
+      return info;
+    } catch (err) {
+      setError(String(err));
+      return null;
+    } finally {
+      setLoading(false);
+    }
+  };
+
   const closeSession = async () => {
     if (sessionId) {
       try {
@@ -87,6 +124,7 @@ export default function useCsvSession({ setError }: UseCsvSessionParams) {
       }
     }
     setSessionId(null);
+    setReadOnly(false);
     setRows([]);
     setHeaders([]);
     setEof(false);
@@ -106,9 +144,10 @@ export default function useCsvSession({ setError }: UseCsvSessionParams) {
 
       const info = await invoke<CsvSessionInfo>("open_csv_session", {
         path: activePath,
-        delimiter,
+        dialect: { delimiter },
       });
       setSessionId(info.session_id);
+      setReadOnly(info.read_only);
       setHeaders(info.headers);
       setRows([]);
       setEof(false);
@@ -153,6 +192,7 @@ export default function useCsvSession({ setError }: UseCsvSessionParams) {
     loading,
     loadingRows,
     sessionId,
+    readOnly,
     rows,
     headers,
     eof,
@@ -166,6 +206,7 @@ export default function useCsvSession({ setError }: UseCsvSessionParams) {
     setPreview,
     setSessionId,
     openCsvPath,
+    openStreamSource,
     closeSession,
     applyDelimiter,
     loadMore,
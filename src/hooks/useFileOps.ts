@@ -1,6 +1,6 @@
 import { useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { save as saveDialog } from "@tauri-apps/plugin-dialog";
+import { confirm, save as saveDialog } from "@tauri-apps/plugin-dialog";
 import type { RowOp, ColumnOp } from "./useRowColumnOps";
 
 type MacroOp = "replace" | "uppercase" | "lowercase" | "trim" | "prefix" | "suffix";
@@ -31,6 +31,15 @@ type FindReplaceResult = {
   applied: number;
 };
 
+type CsvDialect = {
+  delimiter?: string;
+  eol?: string;
+  bom?: boolean;
+  encoding?: string;
+  quote?: string;
+  escape?: string;
+};
+
 type FullColumnStat = {
   name: string;
   non_empty: number;
@@ -91,6 +100,15 @@ export default function useFileOps({
   const [fullStats, setFullStats] = useState<FullColumnStat[] | null>(null);
   const [fullStatsLoading, setFullStatsLoading] = useState(false);
 
+  const outputDialect = (): CsvDialect => ({
+    delimiter: dialectDelimiter || preview?.delimiter,
+    eol: eolMode,
+    bom: includeBom,
+    encoding: encodingMode,
+    quote: dialectQuote,
+    escape: dialectEscape,
+  });
+
   const resetFileOps = () => {
     setMacroAppliedCount(0);
     setMacroOutputPath(null);
@@ -99,6 +117,32 @@ export default function useFileOps({
     setFullStats(null);
   };
 
+  // Writes without replacing an existing target; on `would_overwrite`, asks first and
+  // retries with `overwrite`. Resolves to null when the user keeps the existing file.
+  const invokeWrite = async <T>(
+    command: string,
+    args: Record<string, unknown>,
+  ): Promise<T | null> => {
+    try {
+      return await invoke<T>(command, { ...args, overwrite: false });
+    } catch (err) {
+      let code: string | undefined;
+      try {
+        code = JSON.parse(String(err))?.code;
+      } catch {
+        code = undefined;
+      }
+      if (code !== "would_overwrite") throw err;
+      const target = String(args.targetPath);
+      const replace = await confirm(
+        t(`${target} already exists. Replace it?`, `${target} 已存在。要替换吗？`),
+        { title: t("Replace file", "替换文件"), kind: "warning" },
+      );
+      if (!replace) return null;
+      return invoke<T>(command, { ...args, overwrite: true });
+    }
+  };
+
   const parseOptionalIndex = (value: string) => {
     if (value.trim() === "") return null;
     const parsed = Number.parseInt(value, 10);
@@ -113,7 +157,7 @@ export default function useFileOps({
     try {
       const result = await invoke<FullColumnStat[]>("compute_column_stats", {
         path: preview.path,
-        delimiter: preview.delimiter,
+        dialect: { delimiter: preview.delimiter },
         maxDistinct: 5000,
       });
       setFullStats(result);
@@ -259,17 +303,13 @@ export default function useFileOps({
         regex: useRegex,
         match_case: matchCase,
       };
-      const result = await invoke<FindReplaceResult>("apply_find_replace_to_file", {
+      const result = await invokeWrite<FindReplaceResult>("apply_find_replace_to_file", {
         path: preview.path,
         targetPath: target,
-        delimiter: dialectDelimiter || preview.delimiter,
+        dialect: outputDialect(),
         spec,
-        eol: eolMode,
-        bom: includeBom,
-        encoding: encodingMode,
-        quote: dialectQuote,
-        escape: dialectEscape,
       });
+      if (!result) return;
       setFindAppliedCount(result.applied);
       setFindOutputPath(result.output_path);
     } catch (err) {
@@ -308,17 +348,13 @@ export default function useFileOps({
         replace: macroReplace || undefined,
         text: macroText || undefined,
       };
-      const result = await invoke<CsvMacroResult>("apply_macro_to_file", {
+      const result = await invokeWrite<CsvMacroResult>("apply_macro_to_file", {
         path: preview.path,
         targetPath: target,
-        delimiter: dialectDelimiter || preview.delimiter,
+        dialect: outputDialect(),
         spec,
-        eol: eolMode,
-        bom: includeBom,
-        encoding: encodingMode,
-        quote: dialectQuote,
-        escape: dialectEscape,
       });
+      if (!result) return;
       setMacroAppliedCount(result.applied);
       setMacroOutputPath(result.output_path);
     } catch (err) {
@@ -338,19 +374,16 @@ export default function useFileOps({
         return { row, col, value };
       });
 
-      await invoke("save_csv_with_patches", {
+      // Saving over the open file itself is not an overwrite, so only "save as" asks.
+      const saved = await invokeWrite<unknown>("save_csv_with_patches", {
         path: preview.path,
         targetPath: target,
-        delimiter: dialectDelimiter || preview.delimiter,
+        dialect: outputDialect(),
         patches: patchList,
         rowOps,
         columnOps,
-        eol: eolMode,
-        bom: includeBom,
-        encoding: encodingMode,
-        quote: dialectQuote,
-        escape: dialectEscape,
       });
+      if (saved === null) return false;
     } catch (err) {
       setError(String(err));
       return false;
@@ -186,6 +186,10 @@ export default function useRowColumnOps({
       setError(t("Select a row to delete.", "请选择要删除的行。"));
       return;
     }
+    deleteRowAt(target);
+  };
+
+  const deleteRowAt = (target: number) => {
     setRowOps((current) => [...current, { type: "delete", index: target }]);
     setRows((current) => (target < current.length ? current.filter((_, idx) => idx !== target) : current));
     shiftPatchesForRowDelete(target);
@@ -317,7 +321,9 @@ export default function useRowColumnOps({
     setColumnOps,
     resetOps,
     insertRow,
+    insertRowAt,
     deleteRow,
+    deleteRowAt,
     insertColumn,
     deleteColumn,
     renameColumn,